@@ -1,17 +1,128 @@
+use crate::field::{Field, NullOrder};
+use crate::index::{HashIndex, Index};
 use crate::schema::{RecordId, Schema};
+use crate::serde::{put_str, put_u32, take_str, take_u32};
 use crate::tuple::Tuple;
-use rustdb_error::Result;
+use rustdb_error::{errinput, Result};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::io::{Read, Write};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, RwLock};
 
 pub type TableId = u32;
 pub type IndexId = u32;
 
 /// Stores metadata about a given table in a DBMS.
+#[derive(Debug, Clone)]
 pub struct TableInfo {
     id: TableId,
     name: String,
     schema: Schema,
+    /// The statistics last computed by [`Catalog::analyze`], if any.
+    stats: Option<TableStats>,
+}
+
+impl TableInfo {
+    /// Returns the id of this table.
+    pub fn id(&self) -> TableId {
+        self.id
+    }
+
+    /// Returns the name of this table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the schema of this table's tuples.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Returns the statistics last computed for this table by [`Catalog::analyze`], if any. They
+    /// may be stale if the table has changed since.
+    pub fn stats(&self) -> Option<&TableStats> {
+        self.stats.as_ref()
+    }
+}
+
+/// Stores metadata about an index over a table, along with the index itself.
+pub struct IndexInfo {
+    id: IndexId,
+    name: String,
+    table_id: TableId,
+    key_columns: Vec<usize>,
+    index: Box<dyn Index>,
+}
+
+impl IndexInfo {
+    /// Returns the id of this index.
+    pub fn id(&self) -> IndexId {
+        self.id
+    }
+
+    /// Returns the name of this index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the id of the table this index is over.
+    pub fn table_id(&self) -> TableId {
+        self.table_id
+    }
+
+    /// Returns the indexes of the table's columns that make up this index's keys, in key order.
+    pub fn key_columns(&self) -> &[usize] {
+        &self.key_columns
+    }
+
+    /// Returns the index itself.
+    pub fn index(&self) -> &dyn Index {
+        self.index.as_ref()
+    }
+
+    /// Returns the index itself, to keep it up to date as the table changes.
+    pub fn index_mut(&mut self) -> &mut dyn Index {
+        self.index.as_mut()
+    }
+}
+
+/// Statistics about a table's contents, computed by [`StorageApi::analyze`] for use in estimating
+/// the cost of query plans.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStats {
+    /// The number of tuples in the table.
+    pub row_count: usize,
+    /// The statistics of each column, in schema order.
+    pub columns: Vec<ColumnStats>,
+}
+
+/// The size of a table, computed by [`StorageApi::table_size`] from its pages' headers, without
+/// reading its tuples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSize {
+    /// The number of pages in the table.
+    pub page_count: usize,
+    /// The number of tuples that aren't deleted.
+    pub live_tuples: usize,
+    /// The number of deleted tuples still taking up space in the table's pages.
+    pub dead_tuples: usize,
+    /// The number of bytes the table's pages take up on disk. Pages that haven't been written to
+    /// disk yet aren't counted.
+    pub bytes_on_disk: u64,
+}
+
+/// Statistics about the values of a single column of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    /// The smallest non-NULL value, or NULL if there are none.
+    pub min: Field,
+    /// The largest non-NULL value, or NULL if there are none.
+    pub max: Field,
+    /// The number of NULL values.
+    pub null_count: usize,
+    /// An estimate of the number of distinct non-NULL values. Values are told apart by their
+    /// hashes, so this may undercount if two values collide.
+    pub approx_distinct: usize,
 }
 
 /// A catalog of relevant information and references to objects relevant to the query execution.
@@ -26,6 +137,12 @@ pub struct Catalog<S: StorageApi> {
     table_names: HashMap<String, TableId>,
     /// The next `TableId` to be used.
     next_table_id: std::sync::atomic::AtomicU32,
+    /// Maps index id -> index metadata.
+    indexes: HashMap<IndexId, IndexInfo>,
+    /// Maps index name -> index id.
+    index_names: HashMap<String, IndexId>,
+    /// The next `IndexId` to be used.
+    next_index_id: std::sync::atomic::AtomicU32,
 }
 
 impl<S: StorageApi> Catalog<S> {
@@ -36,31 +153,190 @@ impl<S: StorageApi> Catalog<S> {
             tables: HashMap::new(),
             table_names: HashMap::new(),
             next_table_id: std::sync::atomic::AtomicU32::new(0),
+            indexes: HashMap::new(),
+            index_names: HashMap::new(),
+            next_index_id: std::sync::atomic::AtomicU32::new(0),
         }
     }
 
-    /// Creates a new table with the given name and schema.
+    /// Creates a new table with the given name and schema, in both the catalog and the storage
+    /// engine.
     ///
     /// NOTE: We do not allow more than one table to share the same table name!
-    pub fn create_table(&mut self, name: String, schema: Schema) -> &TableInfo {
-        assert!(
-            !self.table_names.contains_key(&name),
-            "Table names must be unique."
-        );
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if a table with the given name already
+    /// exists, and any error from creating the table in the storage engine, in which case the
+    /// catalog is left unchanged.
+    pub fn create_table(&mut self, name: String, schema: Schema) -> Result<&TableInfo> {
+        if self.table_names.contains_key(&name) {
+            return errinput!("Table {name} already exists");
+        }
 
         let new_table_id = {
-            // Generate the id for the new table, and map the table name to this id.
+            // Generate the id for the new table, and map the table name to this id once the
+            // storage engine has created it.
             let id = self
                 .next_table_id
                 .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.storage.create_table(id, &name, &schema)?;
             self.table_names.insert(name.clone(), id);
 
             // Update the table metadata map.
-            let info = TableInfo { id, name, schema };
+            let info = TableInfo {
+                id,
+                name,
+                schema,
+                stats: None,
+            };
             self.tables.insert(id, info);
             id
         };
-        self.tables.get(&new_table_id).unwrap()
+        Ok(self.tables.get(&new_table_id).unwrap())
+    }
+
+    /// Creates a hash index with the given name over the table with id `table_id`, keyed on the
+    /// columns at `key_columns`, and fills it with the table's current tuples.
+    ///
+    /// Indexes aren't persisted by [`Catalog::save`], and the catalog doesn't update them as the
+    /// table changes; use [`IndexInfo::index_mut`] to do so.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if there's no table with id `table_id`, if an
+    /// index with the given name already exists, or if `key_columns` is empty, and
+    /// [`rustdb_error::Error::OutOfBounds`] if a key column isn't a column of the table.
+    pub fn create_index(
+        &mut self,
+        name: String,
+        table_id: TableId,
+        key_columns: &[usize],
+    ) -> Result<IndexId> {
+        let Some(table) = self.tables.get(&table_id) else {
+            return errinput!("No table with id {table_id}");
+        };
+        if self.index_names.contains_key(&name) {
+            return errinput!("Index {name} already exists");
+        }
+        if key_columns.is_empty() {
+            return errinput!("Index {name} has no key columns");
+        }
+        for &i in key_columns {
+            table.schema.column_at(i)?;
+        }
+
+        let mut index = HashIndex::with_key_columns(key_columns);
+        index.rebuild_from_scan(self.storage.scan_dyn(table_id)?, &table.schema)?;
+
+        let id = self
+            .next_index_id
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.index_names.insert(name.clone(), id);
+        self.indexes.insert(
+            id,
+            IndexInfo {
+                id,
+                name,
+                table_id,
+                key_columns: key_columns.to_vec(),
+                index: Box::new(index),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Fetches the metadata for the index with given id, if one exists.
+    pub fn index_with_id(&self, id: IndexId) -> Option<&IndexInfo> {
+        self.indexes.get(&id)
+    }
+
+    /// Fetches the metadata for the index with given id mutably, if one exists.
+    pub fn index_with_id_mut(&mut self, id: IndexId) -> Option<&mut IndexInfo> {
+        self.indexes.get_mut(&id)
+    }
+
+    /// Fetches the metadata for the index with given name, if one exists.
+    pub fn index_with_name(&self, name: &str) -> Option<&IndexInfo> {
+        let id = self.index_names.get(name)?;
+        self.indexes.get(id)
+    }
+
+    /// Returns the indexes over the table with the given id, sorted by id.
+    pub fn table_indexes(&self, table_id: TableId) -> Vec<&IndexInfo> {
+        let mut indexes = self
+            .indexes
+            .values()
+            .filter(|info| info.table_id == table_id)
+            .collect::<Vec<_>>();
+        indexes.sort_by_key(|info| info.id);
+        indexes
+    }
+
+    /// Writes the metadata of every table in the catalog to `store`, so that it can be restored by
+    /// [`Catalog::load`].
+    ///
+    /// Each table is encoded as its id, the id of its first page in the storage engine, its name,
+    /// and its schema (see [`Schema::to_bytes`]). Integers are little-endian `u32`s, and strings
+    /// are prefixed by their length in bytes.
+    pub fn save(&self, store: &mut impl CatalogStore) -> Result<()> {
+        let mut bytes = Vec::new();
+        put_u32(&mut bytes, self.tables.len() as u32);
+        for (id, _) in self.list_tables() {
+            let info = &self.tables[&id];
+            put_u32(&mut bytes, info.id);
+            put_u32(&mut bytes, self.storage.first_page_id(info.id)?);
+            put_str(&mut bytes, &info.name);
+            bytes.extend_from_slice(&info.schema.to_bytes());
+        }
+        store.write_catalog(&bytes)
+    }
+
+    /// Restores a catalog written to `store` by [`Catalog::save`], reopening each of its tables in
+    /// `storage`. If nothing has been saved to `store`, the catalog is empty.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidData`] if the saved catalog is malformed.
+    pub fn load(storage: Arc<S>, store: &mut impl CatalogStore) -> Result<Self> {
+        let mut catalog = Self::new(storage);
+        let Some(data) = store.read_catalog()? else {
+            return Ok(catalog);
+        };
+
+        let mut bytes = data.as_slice();
+        let num_tables = take_u32(&mut bytes)?;
+        for _ in 0..num_tables {
+            let id = take_u32(&mut bytes)?;
+            let first_page_id = take_u32(&mut bytes)?;
+            let name = take_str(&mut bytes)?;
+            let schema = Schema::read_from(&mut bytes)?;
+
+            catalog
+                .storage
+                .open_table(id, &name, first_page_id, &schema)?;
+            catalog.table_names.insert(name.clone(), id);
+            catalog.tables.insert(
+                id,
+                TableInfo {
+                    id,
+                    name,
+                    schema,
+                    stats: None,
+                },
+            );
+        }
+
+        // New tables must not reuse the ids of the restored ones.
+        let next_table_id = catalog.tables.keys().max().map_or(0, |id| id + 1);
+        catalog.next_table_id = std::sync::atomic::AtomicU32::new(next_table_id);
+        Ok(catalog)
+    }
+
+    /// Computes statistics for the table with the given id by scanning it, and stores them in its
+    /// metadata (see [`TableInfo::stats`]) so that they can be read without rescanning.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if there's no table with the given id.
+    pub fn analyze(&mut self, id: TableId) -> Result<&TableStats> {
+        let Some(info) = self.tables.get_mut(&id) else {
+            return errinput!("No table with id {id}");
+        };
+        let stats = self.storage.analyze(id, &info.schema)?;
+        Ok(info.stats.insert(stats))
     }
 
     /// Fetches the metadata for the table with given id, if one exists.
@@ -74,12 +350,142 @@ impl<S: StorageApi> Catalog<S> {
         self.tables.get(id)
     }
 
+    /// Returns the id and name of every table in the catalog, sorted by id.
+    pub fn list_tables(&self) -> Vec<(TableId, &str)> {
+        let mut tables = self
+            .tables
+            .values()
+            .map(|info| (info.id, info.name.as_str()))
+            .collect::<Vec<_>>();
+        tables.sort_by_key(|(id, _)| *id);
+        tables
+    }
+
+    /// Returns the number of tables in the catalog.
+    pub fn num_tables(&self) -> usize {
+        self.tables.len()
+    }
+
     /// Fetches an iterator over table with the given id, if one exists.
     pub fn table_iter(&self, id: TableId) -> Option<S::ScanIterator> {
         self.storage.scan(id).map_or(None, |iter| Some(iter))
     }
 }
 
+/// A [`Catalog`] that can be shared between threads, e.g. to run DDL and DML concurrently. Every
+/// method takes `&self`, taking the catalog's write lock to modify it and its read lock to look
+/// things up, and returns owned copies of the metadata so that no reference outlives the lock.
+pub struct SharedCatalog<S: StorageApi> {
+    catalog: RwLock<Catalog<S>>,
+}
+
+impl<S: StorageApi> SharedCatalog<S> {
+    /// Instantiates an empty shared catalog, given a reference to the storage engine that we'll
+    /// use.
+    pub fn new(storage: Arc<S>) -> Self {
+        Self::from(Catalog::new(storage))
+    }
+
+    /// See [`Catalog::create_table`].
+    pub fn create_table(&self, name: String, schema: Schema) -> Result<TableInfo> {
+        Ok(self.catalog.write()?.create_table(name, schema)?.clone())
+    }
+
+    /// See [`Catalog::create_index`].
+    pub fn create_index(
+        &self,
+        name: String,
+        table_id: TableId,
+        key_columns: &[usize],
+    ) -> Result<IndexId> {
+        self.catalog
+            .write()?
+            .create_index(name, table_id, key_columns)
+    }
+
+    /// See [`Catalog::analyze`].
+    pub fn analyze(&self, id: TableId) -> Result<TableStats> {
+        Ok(self.catalog.write()?.analyze(id)?.clone())
+    }
+
+    /// See [`Catalog::save`].
+    pub fn save(&self, store: &mut impl CatalogStore) -> Result<()> {
+        self.catalog.read()?.save(store)
+    }
+
+    /// See [`Catalog::table_with_id`].
+    pub fn table_with_id(&self, id: TableId) -> Result<Option<TableInfo>> {
+        Ok(self.catalog.read()?.table_with_id(id).cloned())
+    }
+
+    /// See [`Catalog::table_with_name`].
+    pub fn table_with_name(&self, name: &str) -> Result<Option<TableInfo>> {
+        Ok(self.catalog.read()?.table_with_name(name).cloned())
+    }
+
+    /// See [`Catalog::list_tables`].
+    pub fn list_tables(&self) -> Result<Vec<(TableId, String)>> {
+        let catalog = self.catalog.read()?;
+        Ok(catalog
+            .list_tables()
+            .into_iter()
+            .map(|(id, name)| (id, name.to_string()))
+            .collect())
+    }
+
+    /// See [`Catalog::num_tables`].
+    pub fn num_tables(&self) -> Result<usize> {
+        Ok(self.catalog.read()?.num_tables())
+    }
+
+    /// See [`Catalog::table_iter`].
+    pub fn table_iter(&self, id: TableId) -> Result<Option<S::ScanIterator>> {
+        Ok(self.catalog.read()?.table_iter(id))
+    }
+
+    /// Runs `f` with the index with the given id, e.g. to update it as its table changes, while
+    /// holding the catalog's write lock. Returns `None` if there's no such index.
+    pub fn with_index_mut<T>(
+        &self,
+        id: IndexId,
+        f: impl FnOnce(&mut IndexInfo) -> T,
+    ) -> Result<Option<T>> {
+        Ok(self.catalog.write()?.index_with_id_mut(id).map(f))
+    }
+}
+
+impl<S: StorageApi> From<Catalog<S>> for SharedCatalog<S> {
+    fn from(catalog: Catalog<S>) -> Self {
+        Self {
+            catalog: RwLock::new(catalog),
+        }
+    }
+}
+
+/// A place to persist the catalog across restarts, e.g. a page of the database file reserved
+/// for the catalog.
+pub trait CatalogStore {
+    /// Reads back the bytes last written by [`CatalogStore::write_catalog`], if any. The bytes may
+    /// be followed by padding.
+    fn read_catalog(&mut self) -> Result<Option<Vec<u8>>>;
+
+    /// Persists the given catalog bytes, replacing any previously written ones.
+    fn write_catalog(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+/// A store shared behind a mutex, e.g. a disk manager that the buffer pool also uses, is only
+/// locked while its catalog is read or written. Loading a catalog reopens its tables, which may
+/// read their pages from the same store.
+impl<T: CatalogStore + ?Sized> CatalogStore for &Mutex<T> {
+    fn read_catalog(&mut self) -> Result<Option<Vec<u8>>> {
+        self.lock()?.read_catalog()
+    }
+
+    fn write_catalog(&mut self, bytes: &[u8]) -> Result<()> {
+        self.lock()?.write_catalog(bytes)
+    }
+}
+
 /// An iterator that emits tuples sequentially scanned from a table.
 ///
 /// NOTE: This iterator returns items that are owned values instead of references. This design
@@ -90,6 +496,24 @@ pub trait ScanIterator: Iterator<Item = Result<(RecordId, Tuple)>> {}
 /// Blanket implementation of ScanIterator for any `T` satisfying the trait bound.
 impl<T: Iterator<Item = Result<(RecordId, Tuple)>>> ScanIterator for T {}
 
+/// An aggregate function over the values of a column, computed by [`StorageApi::aggregate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggOp {
+    /// The number of non-NULL values.
+    Count,
+    /// The number of rows, including those whose value is NULL.
+    CountAll,
+    /// The sum of the non-NULL values of a numeric column, or NULL if there are none. Integer
+    /// sums that overflow are an error.
+    Sum,
+    /// The smallest non-NULL value, or NULL if there are none.
+    Min,
+    /// The largest non-NULL value, or NULL if there are none.
+    Max,
+    /// The mean of the non-NULL values of a numeric column as a float, or NULL if there are none.
+    Avg,
+}
+
 /// Should be implemented by the storage engine we're using to enable an access interface between
 /// the execution engine using this Catalog and its corresponding storage engine.
 pub trait StorageApi {
@@ -102,8 +526,21 @@ pub trait StorageApi {
     where
         Self: Sized;
 
-    /// Creates a table with the given name and id.
-    fn create_table(&self, table_id: TableId, name: &str) -> Result<&TableInfo>;
+    /// Creates a table with the given name, id, and schema.
+    fn create_table(&self, table_id: TableId, name: &str, schema: &Schema) -> Result<()>;
+
+    /// Reopens an existing table with the given name, id, and schema, whose data begins at the
+    /// page with id `first_page_id`. Used when restoring a saved catalog.
+    fn open_table(
+        &self,
+        table_id: TableId,
+        name: &str,
+        first_page_id: u32,
+        schema: &Schema,
+    ) -> Result<()>;
+
+    /// Returns the id of the first page of the table with corresponding id `table_id`.
+    fn first_page_id(&self, table_id: TableId) -> Result<u32>;
 
     /// Retrieves a tuple, with record id `rid`, from the table with corresponding id `table_id`.
     fn get_tuple(&self, table_id: TableId, rid: RecordId) -> Result<Tuple>;
@@ -115,6 +552,158 @@ pub trait StorageApi {
     /// newly inserted tuple's record id.
     fn insert_tuple(&self, table_id: TableId, tuple: &Tuple) -> Result<RecordId>;
 
+    /// Inserts the given tuples into the table with corresponding id `table_id`, returning the
+    /// newly inserted tuples' record ids in the same order as `tuples`.
+    ///
+    /// NOTE: If an insert fails partway through, the error is returned but the tuples inserted
+    /// before it are not rolled back.
+    fn insert_tuples(&self, table_id: TableId, tuples: &[Tuple]) -> Result<Vec<RecordId>>;
+
+    /// Inserts a tuple into the table with corresponding id `table_id`, whose tuples have the
+    /// given `schema`, unless the table already has a tuple with the same primary key.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if the key is a duplicate, or if `schema` has
+    /// no primary key.
+    fn insert_unique(&self, table_id: TableId, tuple: &Tuple, schema: &Schema) -> Result<RecordId>;
+
+    /// Replaces the tuple with record id `rid` in the table with corresponding id `table_id`,
+    /// returning the updated tuple's record id. This is `rid` itself if the tuple could be
+    /// updated in place, or a new record id if it had to be moved.
+    fn update_tuple(&self, table_id: TableId, rid: RecordId, tuple: &Tuple) -> Result<RecordId>;
+
+    /// Checks whether the table with corresponding id `table_id` might contain a tuple with the
+    /// primary key `key`. Returns `false` if it definitely doesn't, and `true` if it might, so a
+    /// lookup that misses can often skip scanning the table.
+    fn might_contain_key(&self, table_id: TableId, key: &[Field]) -> Result<bool>;
+
+    /// Scans the table with corresponding id `table_id`, whose tuples have the given `schema`,
+    /// returning the tuples whose column at `column_index` equals `value`, along with their record
+    /// ids, in scan order.
+    fn scan_filter(
+        &self,
+        table_id: TableId,
+        column_index: usize,
+        value: Field,
+        schema: &Schema,
+    ) -> Result<Vec<(RecordId, Tuple)>>;
+
+    /// Scans the table with corresponding id `table_id`, whose tuples have the given `schema`,
+    /// returning its tuples and their record ids sorted by the column at `column_index`, in
+    /// ascending or descending order. NULLs go first or last as `nulls` says, whatever the
+    /// direction.
+    fn scan_sorted(
+        &self,
+        table_id: TableId,
+        column_index: usize,
+        ascending: bool,
+        nulls: NullOrder,
+        schema: &Schema,
+    ) -> Result<Vec<(RecordId, Tuple)>>;
+
+    /// [`StorageApi::scan_sorted`], but holding at most `run_size` tuples in memory while scanning:
+    /// each `run_size` tuples are sorted and spilled to disk, and the returned iterator merges the
+    /// sorted runs as it's read, holding one page of each run at a time. NULLs go first or last as
+    /// `nulls` says, whatever the direction.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if `run_size` is 0.
+    fn scan_sorted_external(
+        &self,
+        table_id: TableId,
+        column_index: usize,
+        ascending: bool,
+        nulls: NullOrder,
+        schema: &Schema,
+        run_size: usize,
+    ) -> Result<Box<dyn ScanIterator>>;
+
+    /// Scans the table with corresponding id `table_id`, returning the first occurrence of each
+    /// distinct tuple, along with its record id, in scan order. Tuples are distinct if their bytes
+    /// differ.
+    fn scan_distinct(&self, table_id: TableId) -> Result<Vec<(RecordId, Tuple)>>;
+
+    /// Computes the aggregate `op` over the column at `column_index` of the table with
+    /// corresponding id `table_id`, whose tuples have the given `schema`.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidData`] if `op` is [`AggOp::Sum`] or [`AggOp::Avg`]
+    /// and the column isn't numeric, and [`rustdb_error::Error::ArithmeticOverflow`] if the sum
+    /// of an integer column overflows.
+    fn aggregate(
+        &self,
+        table_id: TableId,
+        column_index: usize,
+        op: AggOp,
+        schema: &Schema,
+    ) -> Result<Field>;
+
+    /// Computes statistics over every column of the table with corresponding id `table_id`, whose
+    /// tuples have the given `schema`, in a single scan.
+    fn analyze(&self, table_id: TableId, schema: &Schema) -> Result<TableStats>;
+
+    /// Returns the size of the table with corresponding id `table_id`.
+    fn table_size(&self, table_id: TableId) -> Result<TableSize>;
+
+    /// Scans the table with corresponding id `table_id`, whose tuples have the given `schema`,
+    /// returning every tuple deserialized into its fields, along with its record id, in scan
+    /// order.
+    fn scan_rows(&self, table_id: TableId, schema: &Schema) -> Result<Vec<(RecordId, Vec<Field>)>>;
+
+    /// Scans the table with corresponding id `table_id`, whose tuples have the given `schema`,
+    /// lazily emitting the value of the column at `column_index` of each live tuple in scan
+    /// order. Only that column is deserialized (see [`Tuple::field_at`]).
+    ///
+    /// Returns [`rustdb_error::Error::OutOfBounds`] if `column_index` isn't a column of `schema`.
+    fn scan_column(
+        &self,
+        table_id: TableId,
+        column_index: usize,
+        schema: &Schema,
+    ) -> Result<impl Iterator<Item = Result<Field>>>
+    where
+        Self: Sized;
+
+    /// Creates a B+-tree index with id `index_id` over the column at `column_index` of the table
+    /// with corresponding id `table_id`, whose tuples have the given `schema`, for
+    /// [`StorageApi::range_lookup`]. The index covers the table's current tuples and those
+    /// inserted later.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if an index with id `index_id` already
+    /// exists, or if the column's values can't be indexed.
+    fn create_range_index(
+        &self,
+        index_id: IndexId,
+        table_id: TableId,
+        column_index: usize,
+        schema: &Schema,
+    ) -> Result<()>;
+
+    /// Returns the live tuples whose key in the index with id `index_id` (see
+    /// [`StorageApi::create_range_index`]) is within the bounds `low` and `high`, along with their
+    /// record ids, sorted by key. Each bound is a key of one field, or unbounded.
+    fn range_lookup(
+        &self,
+        index_id: IndexId,
+        low: Bound<&[Field]>,
+        high: Bound<&[Field]>,
+    ) -> Result<Vec<(RecordId, Tuple)>>;
+
+    /// Writes the live tuples of the table with corresponding id `table_id`, whose tuples have the
+    /// given `schema`, to `writer` as a stream of length-prefixed tuples, returning the number of
+    /// tuples written. The stream doesn't depend on the page layout, so it can be imported (see
+    /// [`StorageApi::import_table`]) into a database with a different page size.
+    fn export_table(
+        &self,
+        table_id: TableId,
+        schema: &Schema,
+        writer: &mut dyn Write,
+    ) -> Result<usize>;
+
+    /// Inserts every tuple of a stream written by [`StorageApi::export_table`] into the table
+    /// with corresponding id `table_id`, returning the number of tuples inserted. Tuples inserted
+    /// before an error are not rolled back.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidData`] if the stream is malformed or truncated.
+    fn import_table(&self, table_id: TableId, reader: &mut dyn Read) -> Result<usize>;
+
     /// Retrieves an iterator that emits tuples from a table via sequential scan.
     fn scan(&self, table_id: TableId) -> Result<Self::ScanIterator>
     where
@@ -124,3 +713,375 @@ pub trait StorageApi {
     /// dispatch, which incurs a runtime performance penalty.
     fn scan_dyn(&self, table_id: TableId) -> Result<Box<dyn ScanIterator>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::catalog::{
+        AggOp, Catalog, ColumnStats, IndexId, ScanIterator, SharedCatalog, StorageApi, TableId,
+        TableSize, TableStats,
+    };
+    use crate::column::Column;
+    use crate::field::{Field, NullOrder};
+    use crate::schema::{RecordId, Schema};
+    use crate::tuple::Tuple;
+    use crate::types::Type;
+    use rustdb_error::{Error, Result};
+    use std::io::{Read, Write};
+    use std::ops::Bound;
+    use std::sync::Arc;
+
+    /// A storage engine stub; the catalog tests only exercise the catalog's own bookkeeping.
+    struct MockStorage {}
+
+    impl StorageApi for MockStorage {
+        type ScanIterator = std::vec::IntoIter<Result<(RecordId, Tuple)>>;
+
+        fn create_table(&self, _table_id: TableId, _name: &str, _schema: &Schema) -> Result<()> {
+            Ok(())
+        }
+
+        fn open_table(
+            &self,
+            _table_id: TableId,
+            _name: &str,
+            _first_page_id: u32,
+            _schema: &Schema,
+        ) -> Result<()> {
+            unimplemented!()
+        }
+
+        fn first_page_id(&self, _table_id: TableId) -> Result<u32> {
+            unimplemented!()
+        }
+
+        fn get_tuple(&self, _table_id: TableId, _rid: RecordId) -> Result<Tuple> {
+            unimplemented!()
+        }
+
+        fn delete_tuple(&self, _table_id: TableId, _rid: RecordId) -> Result<()> {
+            unimplemented!()
+        }
+
+        fn insert_tuple(&self, _table_id: TableId, _tuple: &Tuple) -> Result<RecordId> {
+            unimplemented!()
+        }
+
+        fn insert_tuples(&self, _table_id: TableId, _tuples: &[Tuple]) -> Result<Vec<RecordId>> {
+            unimplemented!()
+        }
+
+        fn insert_unique(
+            &self,
+            _table_id: TableId,
+            _tuple: &Tuple,
+            _schema: &Schema,
+        ) -> Result<RecordId> {
+            unimplemented!()
+        }
+
+        fn update_tuple(
+            &self,
+            _table_id: TableId,
+            _rid: RecordId,
+            _tuple: &Tuple,
+        ) -> Result<RecordId> {
+            unimplemented!()
+        }
+
+        fn might_contain_key(&self, _table_id: TableId, _key: &[Field]) -> Result<bool> {
+            unimplemented!()
+        }
+
+        fn scan_filter(
+            &self,
+            _table_id: TableId,
+            _column_index: usize,
+            _value: Field,
+            _schema: &Schema,
+        ) -> Result<Vec<(RecordId, Tuple)>> {
+            unimplemented!()
+        }
+
+        fn scan_sorted(
+            &self,
+            _table_id: TableId,
+            _column_index: usize,
+            _ascending: bool,
+            _nulls: NullOrder,
+            _schema: &Schema,
+        ) -> Result<Vec<(RecordId, Tuple)>> {
+            unimplemented!()
+        }
+
+        fn scan_sorted_external(
+            &self,
+            _table_id: TableId,
+            _column_index: usize,
+            _ascending: bool,
+            _nulls: NullOrder,
+            _schema: &Schema,
+            _run_size: usize,
+        ) -> Result<Box<dyn ScanIterator>> {
+            unimplemented!()
+        }
+
+        fn scan_distinct(&self, _table_id: TableId) -> Result<Vec<(RecordId, Tuple)>> {
+            unimplemented!()
+        }
+
+        fn aggregate(
+            &self,
+            _table_id: TableId,
+            _column_index: usize,
+            _op: AggOp,
+            _schema: &Schema,
+        ) -> Result<Field> {
+            unimplemented!()
+        }
+
+        fn analyze(&self, _table_id: TableId, schema: &Schema) -> Result<TableStats> {
+            let columns = schema
+                .columns()
+                .iter()
+                .map(|_| ColumnStats {
+                    min: Field::Null,
+                    max: Field::Null,
+                    null_count: 0,
+                    approx_distinct: 0,
+                })
+                .collect();
+            Ok(TableStats {
+                row_count: 0,
+                columns,
+            })
+        }
+
+        fn table_size(&self, _table_id: TableId) -> Result<TableSize> {
+            unimplemented!()
+        }
+
+        fn scan_rows(
+            &self,
+            _table_id: TableId,
+            _schema: &Schema,
+        ) -> Result<Vec<(RecordId, Vec<Field>)>> {
+            unimplemented!()
+        }
+
+        fn scan_column(
+            &self,
+            _table_id: TableId,
+            _column_index: usize,
+            _schema: &Schema,
+        ) -> Result<impl Iterator<Item = Result<Field>>> {
+            Ok(std::iter::empty())
+        }
+
+        fn create_range_index(
+            &self,
+            _index_id: IndexId,
+            _table_id: TableId,
+            _column_index: usize,
+            _schema: &Schema,
+        ) -> Result<()> {
+            unimplemented!()
+        }
+
+        fn range_lookup(
+            &self,
+            _index_id: IndexId,
+            _low: Bound<&[Field]>,
+            _high: Bound<&[Field]>,
+        ) -> Result<Vec<(RecordId, Tuple)>> {
+            unimplemented!()
+        }
+
+        fn export_table(
+            &self,
+            _table_id: TableId,
+            _schema: &Schema,
+            _writer: &mut dyn Write,
+        ) -> Result<usize> {
+            unimplemented!()
+        }
+
+        fn import_table(&self, _table_id: TableId, _reader: &mut dyn Read) -> Result<usize> {
+            unimplemented!()
+        }
+
+        fn scan(&self, _table_id: TableId) -> Result<Self::ScanIterator> {
+            Ok(Vec::new().into_iter())
+        }
+
+        fn scan_dyn(&self, table_id: TableId) -> Result<Box<dyn ScanIterator>> {
+            Ok(Box::new(self.scan(table_id)?))
+        }
+    }
+
+    fn create_catalog() -> Catalog<MockStorage> {
+        Catalog::new(Arc::new(MockStorage {}))
+    }
+
+    #[test]
+    fn test_table_info_accessors() -> Result<()> {
+        let mut catalog = create_catalog();
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+
+        let info = catalog.create_table("users".to_string(), schema.clone())?;
+        let id = info.id();
+        assert_eq!(info.name(), "users");
+        assert_eq!(info.schema(), &schema);
+
+        // The same metadata is reachable through the lookup methods.
+        let by_name = catalog.table_with_name("users").unwrap();
+        assert_eq!(by_name.id(), id);
+        assert_eq!(by_name.schema(), &schema);
+        let by_id = catalog.table_with_id(id).unwrap();
+        assert_eq!(by_id.name(), "users");
+
+        // Table names are unique.
+        assert!(matches!(
+            catalog.create_table("users".to_string(), schema),
+            Err(Error::InvalidInput(_))
+        ));
+        assert_eq!(catalog.num_tables(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_tables() -> Result<()> {
+        let mut catalog = create_catalog();
+        assert_eq!(catalog.num_tables(), 0);
+        assert!(catalog.list_tables().is_empty());
+
+        let schema = Schema::new(&[Column::new("a".to_string(), Type::Integer)]);
+        let ids = ["c", "a", "b"]
+            .iter()
+            .map(|name| Ok(catalog.create_table(name.to_string(), schema.clone())?.id()))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Tables are listed in ascending id order, i.e. creation order, not name order.
+        let tables = catalog.list_tables();
+        assert_eq!(catalog.num_tables(), 3);
+        assert_eq!(tables, vec![(ids[0], "c"), (ids[1], "a"), (ids[2], "b")]);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        // The listing is stable when the catalog hasn't changed.
+        assert_eq!(catalog.list_tables(), tables);
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_stores_stats() -> Result<()> {
+        let mut catalog = create_catalog();
+        let schema = Schema::new(&[Column::new("a".to_string(), Type::Integer)]);
+        let id = catalog.create_table("t".to_string(), schema)?.id();
+        assert_eq!(catalog.table_with_id(id).unwrap().stats(), None);
+
+        let stats = catalog.analyze(id)?.clone();
+        assert_eq!(stats.row_count, 0);
+        assert_eq!(stats.columns.len(), 1);
+        assert_eq!(catalog.table_with_name("t").unwrap().stats(), Some(&stats));
+
+        assert!(matches!(
+            catalog.analyze(id + 1),
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_index() -> Result<()> {
+        let mut catalog = create_catalog();
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        let table_id = catalog.create_table("users".to_string(), schema)?.id();
+
+        let by_id = catalog.create_index("by_id".to_string(), table_id, &[0])?;
+        let by_name = catalog.create_index("by_name".to_string(), table_id, &[1, 0])?;
+        assert_ne!(by_id, by_name);
+        let info = catalog.index_with_name("by_name").unwrap();
+        assert_eq!(info.id(), by_name);
+        assert_eq!(info.table_id(), table_id);
+        assert_eq!(info.key_columns(), &[1, 0]);
+        let ids = catalog
+            .table_indexes(table_id)
+            .iter()
+            .map(|info| info.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![by_id, by_name]);
+
+        // The index is maintained through its metadata.
+        let index = catalog.index_with_id_mut(by_id).unwrap().index_mut();
+        index.insert(&[Field::Integer(1)], 7);
+        let info = catalog.index_with_id(by_id).unwrap();
+        assert_eq!(info.index().lookup(&[Field::Integer(1)]), vec![7]);
+
+        let create = |catalog: &mut Catalog<MockStorage>, name: &str, table, keys: &[usize]| {
+            catalog.create_index(name.to_string(), table, keys)
+        };
+        assert!(matches!(
+            create(&mut catalog, "by_id", table_id, &[0]),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            create(&mut catalog, "x", table_id + 1, &[0]),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            create(&mut catalog, "x", table_id, &[]),
+            Err(Error::InvalidInput(_))
+        ));
+        assert_eq!(
+            create(&mut catalog, "x", table_id, &[2]).err(),
+            Some(Error::OutOfBounds)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_catalog_concurrent_create() -> Result<()> {
+        let catalog = SharedCatalog::new(Arc::new(MockStorage {}));
+        let schema = Schema::new(&[Column::new("a".to_string(), Type::Integer)]);
+
+        let ids = std::thread::scope(|s| {
+            let handles = (0..8)
+                .map(|thread| {
+                    let (catalog, schema) = (&catalog, &schema);
+                    s.spawn(move || {
+                        (0..10)
+                            .map(|i| {
+                                let name = format!("t{thread}_{i}");
+                                Ok(catalog.create_table(name, schema.clone())?.id())
+                            })
+                            .collect::<Result<Vec<_>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        // Every table got its own id, and can be found by name and id.
+        let mut all_ids = ids.iter().flatten().copied().collect::<Vec<_>>();
+        all_ids.sort();
+        all_ids.dedup();
+        assert_eq!(all_ids.len(), 80);
+        assert_eq!(catalog.num_tables()?, 80);
+        for (thread, ids) in ids.iter().enumerate() {
+            for (i, &id) in ids.iter().enumerate() {
+                let info = catalog.table_with_name(&format!("t{thread}_{i}"))?.unwrap();
+                assert_eq!(info.id(), id);
+                assert_eq!(catalog.table_with_id(id)?.unwrap().name(), info.name());
+            }
+        }
+        Ok(())
+    }
+}
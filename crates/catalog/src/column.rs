@@ -1,17 +1,89 @@
+use crate::field::Field;
+use crate::serde::{put_str, put_u32, take, take_str, take_u32};
 use crate::types::Type;
+use rustdb_error::{errdata, errinput, Error, Result};
 use std::fmt::Debug;
 
+/// How the values of a varchar column are ordered (see
+/// [`crate::schema::Schema::compare_fields`]).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Collation {
+    /// Compares strings byte by byte.
+    #[default]
+    Binary,
+    /// Compares strings ignoring case, by comparing their lowercase forms.
+    CaseInsensitive,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Column {
     /// The name of this column.
     name: String,
     /// This column value's type.
     field_type: Type,
+    /// Whether this column's value may be NULL.
+    nullable: bool,
+    /// The value used for this column when none is provided.
+    default: Option<Field>,
+    /// The name of the table this column belongs to, used to tell apart columns with the same
+    /// name from different tables (e.g. in the output of a join).
+    table_qualifier: Option<String>,
+    /// The maximum length, in bytes, of this varchar column's values, if they're limited.
+    max_len: Option<u16>,
+    /// How this varchar column's values are ordered.
+    collation: Collation,
 }
 
 impl Column {
+    /// Creates a nullable column with no default value.
     pub fn new(name: String, field_type: Type) -> Self {
-        Column { name, field_type }
+        Column {
+            name,
+            field_type,
+            nullable: true,
+            default: None,
+            table_qualifier: None,
+            max_len: None,
+            collation: Collation::Binary,
+        }
+    }
+
+    /// Creates a column with the given nullability and default value.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if the default's type doesn't match
+    /// `field_type`, if a char default is wider than the column, or if the default is NULL for a
+    /// non-nullable column.
+    pub fn new_with(
+        name: String,
+        field_type: Type,
+        nullable: bool,
+        default: Option<Field>,
+    ) -> Result<Self> {
+        match &default {
+            Some(Field::Null) if !nullable => {
+                return errinput!("Column {name} is NOT NULL but has a NULL default");
+            }
+            Some(Field::Null) | None => {}
+            Some(field) if !field.matches_type(field_type) => {
+                return errinput!(
+                    "Default for column {name} has type {}, expected {field_type}",
+                    field.get_type()
+                );
+            }
+            // Serializing the default checks that it fits in the column.
+            Some(field) => {
+                field.to_bytes_as(field_type)?;
+            }
+        }
+        Ok(Column {
+            name,
+            field_type,
+            nullable,
+            default,
+            table_qualifier: None,
+            max_len: None,
+            collation: Collation::Binary,
+        })
     }
 
     /// Returns the name of this column.
@@ -19,11 +91,102 @@ impl Column {
         &self.name
     }
 
+    /// Returns this column with its table qualifier set to `table`.
+    pub fn with_table_qualifier(mut self, table: String) -> Self {
+        self.table_qualifier = Some(table);
+        self
+    }
+
+    /// Returns this varchar column with its values limited to `max_len` bytes, as in
+    /// `VARCHAR(max_len)`. The limit counts bytes rather than chars, since that's the space values
+    /// take up in storage.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if this isn't a varchar column, or if its
+    /// default is longer than `max_len`.
+    pub fn with_max_len(mut self, max_len: u16) -> Result<Self> {
+        if self.field_type != Type::Varchar {
+            return errinput!(
+                "Column {} of type {} can't have a max length",
+                self.name,
+                self.field_type
+            );
+        }
+        self.max_len = Some(max_len);
+        if let Some(default) = &self.default {
+            self.check_len(default)?;
+        }
+        Ok(self)
+    }
+
+    /// Returns the maximum length, in bytes, of this column's values, if they're limited.
+    pub fn max_len(&self) -> Option<u16> {
+        self.max_len
+    }
+
+    /// Returns this varchar column with its values ordered by `collation`.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if this isn't a varchar column.
+    pub fn with_collation(mut self, collation: Collation) -> Result<Self> {
+        if self.field_type != Type::Varchar {
+            return errinput!(
+                "Column {} of type {} can't have a collation",
+                self.name,
+                self.field_type
+            );
+        }
+        self.collation = collation;
+        Ok(self)
+    }
+
+    /// Returns how this column's values are ordered.
+    pub fn collation(&self) -> Collation {
+        self.collation
+    }
+
+    /// Returns [`rustdb_error::Error::InvalidInput`] if `field` is a varchar longer than this
+    /// column's max length.
+    pub(crate) fn check_len(&self, field: &Field) -> Result<()> {
+        match (field, self.max_len) {
+            (Field::Varchar(string), Some(max_len)) if string.len() > max_len as usize => {
+                errinput!(
+                    "Value {field} is {} bytes long, but column {} holds at most {max_len}",
+                    string.len(),
+                    self.name
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the name of the table this column is qualified with, if any.
+    pub fn table_qualifier(&self) -> Option<&str> {
+        self.table_qualifier.as_deref()
+    }
+
+    /// Returns the name of this column prefixed with its table qualifier, e.g. `"users.id"`, or
+    /// just its name if it has no qualifier.
+    pub fn qualified_name(&self) -> String {
+        match &self.table_qualifier {
+            Some(table) => format!("{table}.{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
     /// Returns the `field_type` of the column.
     pub fn field_type(&self) -> Type {
         self.field_type
     }
 
+    /// Returns whether this column's value may be NULL.
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    /// Returns this column's default value, if it has one.
+    pub fn default(&self) -> Option<&Field> {
+        self.default.as_ref()
+    }
+
     /// Returns the fixed byte size of this column's field data. In the case of variable-length
     /// fields, returns `None`.
     pub fn size(&self) -> Option<usize> {
@@ -32,6 +195,98 @@ impl Column {
             fixed_size_type @ _ => Some(fixed_size_type.size()),
         }
     }
+
+    /// Serializes this column: its name and type (see [`Type::write_to`]), then a byte for
+    /// whether it's nullable, then its default, max length, collation, and table qualifier. The
+    /// default and the other optional attributes start with a tag byte that is 0 if they're
+    /// absent; a default is NULL if its tag is 1, and a value of the column's type prefixed by its
+    /// length in bytes if its tag is 2. The max length is a little-endian `u16`, and names are
+    /// UTF-8 prefixed by their length in bytes.
+    pub(crate) fn write_to(&self, bytes: &mut Vec<u8>) {
+        put_str(bytes, &self.name);
+        self.field_type.write_to(bytes);
+        bytes.push(self.nullable as u8);
+        match &self.default {
+            None => bytes.push(0),
+            Some(Field::Null) => bytes.push(1),
+            Some(default) => {
+                bytes.push(2);
+                // The default was checked to fit the column when it was set.
+                let value = default.to_bytes_as(self.field_type).unwrap();
+                put_u32(bytes, value.len() as u32);
+                bytes.extend_from_slice(&value);
+            }
+        }
+        match self.max_len {
+            None => bytes.push(0),
+            Some(max_len) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&max_len.to_le_bytes());
+            }
+        }
+        bytes.push(match self.collation {
+            Collation::Binary => 0,
+            Collation::CaseInsensitive => 1,
+        });
+        match &self.table_qualifier {
+            None => bytes.push(0),
+            Some(table) => {
+                bytes.push(1);
+                put_str(bytes, table);
+            }
+        }
+    }
+
+    /// Removes and deserializes a column written by [`Column::write_to`] from the front of
+    /// `bytes`.
+    ///
+    /// Returns [`Error::InvalidData`] if the bytes are truncated or contain an unknown tag, or if
+    /// the column's attributes don't fit together, e.g. a max length for a non-varchar column.
+    pub(crate) fn read_from(bytes: &mut &[u8]) -> Result<Column> {
+        let name = take_str(bytes)?;
+        let field_type = Type::read_from(bytes)?;
+        let nullable = match take(bytes, 1)?[0] {
+            0 => false,
+            1 => true,
+            byte => return errdata!("Invalid nullability {byte} for column {name}"),
+        };
+        let default = match take(bytes, 1)?[0] {
+            0 => None,
+            1 => Some(Field::Null),
+            2 => {
+                let len = take_u32(bytes)? as usize;
+                Some(Field::try_from_bytes(take(bytes, len)?, field_type)?)
+            }
+            tag => return errdata!("Unknown default tag {tag} for column {name}"),
+        };
+        let max_len = match take(bytes, 1)?[0] {
+            0 => None,
+            1 => Some(u16::from_le_bytes(take(bytes, 2)?.try_into()?)),
+            tag => return errdata!("Unknown max length tag {tag} for column {name}"),
+        };
+        let collation = match take(bytes, 1)?[0] {
+            0 => Collation::Binary,
+            1 => Collation::CaseInsensitive,
+            tag => return errdata!("Unknown collation tag {tag} for column {name}"),
+        };
+        let table_qualifier = match take(bytes, 1)?[0] {
+            0 => None,
+            1 => Some(take_str(bytes)?),
+            tag => return errdata!("Unknown table qualifier tag {tag} for column {name}"),
+        };
+
+        // Build the column through its constructors, so that they check its attributes.
+        let invalid = |e: Error| Error::InvalidData(e.to_string());
+        let mut column = Column::new_with(name, field_type, nullable, default).map_err(invalid)?;
+        if let Some(max_len) = max_len {
+            column = column.with_max_len(max_len).map_err(invalid)?;
+        }
+        if collation != Collation::Binary {
+            column = column.with_collation(collation).map_err(invalid)?;
+        }
+        column.table_qualifier = table_qualifier;
+        Ok(column)
+    }
 }
 
 impl std::fmt::Display for Column {
@@ -43,7 +298,9 @@ impl std::fmt::Display for Column {
         write!(
             f,
             "Column[ {}, {}, Length: {} bytes ]",
-            self.name, self.field_type, length
+            self.qualified_name(),
+            self.field_type,
+            length
         )
     }
 }
@@ -51,7 +308,9 @@ impl std::fmt::Display for Column {
 #[cfg(test)]
 mod tests {
     use crate::column::Column;
+    use crate::field::Field;
     use crate::types::Type;
+    use rustdb_error::Error;
 
     #[test]
     fn test_column_size() {
@@ -87,10 +346,91 @@ mod tests {
         assert_ne!(type_is_different, column);
     }
 
+    #[test]
+    fn test_not_null_column() {
+        let column =
+            Column::new_with("TestColumn".to_string(), Type::Integer, false, None).unwrap();
+        assert!(!column.is_nullable());
+        assert!(column.default().is_none());
+
+        // Columns are nullable without a default unless otherwise specified.
+        let column = with_type(Type::Integer);
+        assert!(column.is_nullable());
+        assert!(column.default().is_none());
+
+        // A NOT NULL column can't default to NULL...
+        assert!(matches!(
+            Column::new_with("c".to_string(), Type::Integer, false, Some(Field::Null)),
+            Err(Error::InvalidInput(_))
+        ));
+        // ...but a nullable one can.
+        assert!(Column::new_with("c".to_string(), Type::Integer, true, Some(Field::Null)).is_ok());
+    }
+
+    #[test]
+    fn test_default_value() {
+        let column = Column::new_with(
+            "TestColumn".to_string(),
+            Type::Integer,
+            true,
+            Some(Field::Integer(339)),
+        )
+        .unwrap();
+        assert_eq!(column.default(), Some(&Field::Integer(339)));
+
+        // The default must match the column's type.
+        assert!(matches!(
+            Column::new_with(
+                "c".to_string(),
+                Type::Integer,
+                true,
+                Some(Field::Float(3.39))
+            ),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        let column = with_type(Type::Integer);
+        assert_eq!(column.table_qualifier(), None);
+        assert_eq!(column.qualified_name(), "TestColumn");
+
+        let qualified = column.clone().with_table_qualifier("users".to_string());
+        assert_eq!(qualified.table_qualifier(), Some("users"));
+        assert_eq!(qualified.name(), "TestColumn");
+        assert_eq!(qualified.qualified_name(), "users.TestColumn");
+        assert_ne!(qualified, column);
+    }
+
+    #[test]
+    fn test_max_len() {
+        let column = with_type(Type::Varchar);
+        assert_eq!(column.max_len(), None);
+        assert!(column.check_len(&"a very long value".into()).is_ok());
+
+        let column = column.with_max_len(5).unwrap();
+        assert_eq!(column.max_len(), Some(5));
+        assert!(column.check_len(&"hello".into()).is_ok());
+        assert!(matches!(
+            column.check_len(&"hello!".into()),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // Only varchar columns can have a max length, and their default must fit.
+        assert!(matches!(
+            with_type(Type::Integer).with_max_len(5),
+            Err(Error::InvalidInput(_))
+        ));
+        let with_default =
+            Column::new_with("c".to_string(), Type::Varchar, true, Some("hello!".into())).unwrap();
+        assert!(matches!(
+            with_default.with_max_len(5),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
     fn with_type(field_type: Type) -> Column {
-        Column {
-            name: "TestColumn".to_string(),
-            field_type,
-        }
+        Column::new("TestColumn".to_string(), field_type)
     }
 }
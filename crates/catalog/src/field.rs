@@ -1,4 +1,6 @@
 use crate::types::Type;
+use ::serde::{Deserialize, Serialize};
+use rustdb_error::{errdata, errinput, Error, Result};
 
 /// Represents a view over a SQL value data stored in some materialized state. Normally, tuple data
 /// is passed around as a byte slice (e.g. data: Vec<u8>); you can think of this `Field` class as
@@ -23,27 +25,149 @@ use crate::types::Type;
 /// to run queries over the actual field values of the tuple, e.g. false, 1, 2. When we do that,
 /// we'll need to materialize the field values from the tuple data into some sort of value object;
 /// this `Field` object provides a way to do so.
-#[derive(Debug, Clone)]
+///
+/// Fields also implement serde's `Serialize` and `Deserialize`, tagged by their variant, to ship
+/// rows over the network or persist them in any serde format. That's independent of the on-page
+/// format, which is [`crate::serde::Serde`]'s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Field {
     Null,
     Boolean(bool),
     Integer(i32),
     Float(f64),
     Varchar(String),
+    /// A value of a [`Type::Char`] column, without its padding.
+    Char(String),
+}
+
+/// A [`Field`] that borrows its string value from the buffer it was deserialized from, rather than
+/// copying it, for reads that only look at a tuple's values (see
+/// [`crate::serde::Serde::deserialize_borrowed`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldRef<'a> {
+    Null,
+    Boolean(bool),
+    Integer(i32),
+    Float(f64),
+    Varchar(&'a str),
+    /// A value of a [`Type::Char`] column, without its padding.
+    Char(&'a str),
+}
+
+impl<'a> FieldRef<'a> {
+    /// Deserializes a byte slice into a field like [`Field::try_from_bytes`], borrowing the
+    /// value of a string type from `bytes`.
+    pub fn try_from_bytes(bytes: &'a [u8], field_type: Type) -> Result<Self> {
+        let as_str = |bytes| match std::str::from_utf8(bytes) {
+            Ok(string) => Ok(string),
+            Err(e) => errdata!("{e}"),
+        };
+        match field_type {
+            Type::Varchar => Ok(FieldRef::Varchar(as_str(bytes)?)),
+            Type::Char(width) if width as usize != bytes.len() => errdata!(
+                "Expected {width} bytes for a {field_type} field, found {}",
+                bytes.len()
+            ),
+            Type::Char(_) => Ok(FieldRef::Char(as_str(bytes)?.trim_end_matches(' '))),
+            _ => Ok(match Field::try_from_bytes(bytes, field_type)? {
+                Field::Boolean(b) => FieldRef::Boolean(b),
+                Field::Integer(i) => FieldRef::Integer(i),
+                Field::Float(f) => FieldRef::Float(f),
+                _ => FieldRef::Null,
+            }),
+        }
+    }
+
+    /// Copies this field into an owned [`Field`].
+    pub fn to_owned(&self) -> Field {
+        match *self {
+            FieldRef::Null => Field::Null,
+            FieldRef::Boolean(b) => Field::Boolean(b),
+            FieldRef::Integer(i) => Field::Integer(i),
+            FieldRef::Float(f) => Field::Float(f),
+            FieldRef::Varchar(string) => Field::Varchar(string.to_string()),
+            FieldRef::Char(string) => Field::Char(string.to_string()),
+        }
+    }
+}
+
+/// The byte order in which [`Field::Integer`] and [`Field::Float`] values are serialized. Tuples
+/// are always stored in little-endian form; big-endian is for interop with other formats, such as
+/// network byte order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Where NULLs go when sorting, as in SQL's `ORDER BY a NULLS FIRST` and `NULLS LAST`. It's
+/// independent of the sort direction: NULLs go first or last whether the other values are sorted
+/// in ascending or descending order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NullOrder {
+    /// NULLs sort before every other value, like [`Field`]'s `Ord`.
+    #[default]
+    First,
+    /// NULLs sort after every other value.
+    Last,
+}
+
+/// How [`Field::to_integer`] rounds a float with a fractional part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundMode {
+    /// Rounds to the nearest integer, and halfway cases away from zero, like [`Field::round`].
+    Nearest,
+    /// Rounds towards zero, dropping the fractional part, like [`Field::trunc`].
+    Trunc,
 }
 
 impl Field {
     /// Serializes a field into an owned byte slice.
     ///
     /// Note that [`Field::Float`] and [`Field::Integer`] values get serialized into their byte
-    /// representation in **little-endian** form!
+    /// representation in **little-endian** form! See [`Field::to_bytes_with`] for big-endian.
+    ///
+    /// A field doesn't know the width of the column it's stored in, so [`Field::Char`] values are
+    /// serialized without padding; use [`Field::to_bytes_as`] to pad them.
     pub fn to_bytes(&self) -> Vec<u8> {
-        match self {
-            Field::Null => vec![],
-            Field::Boolean(value) => vec![u8::from(*value)],
-            Field::Integer(value) => Vec::from(i32::to_le_bytes(*value)),
-            Field::Float(value) => Vec::from(f64::to_le_bytes(*value)),
-            Field::Varchar(string) => string.as_bytes().to_vec(),
+        self.to_bytes_with(Endianness::Little)
+    }
+
+    /// Serializes a field like [`Field::to_bytes`], with [`Field::Float`] and [`Field::Integer`]
+    /// values in the given byte order. Other values are the same in either byte order.
+    pub fn to_bytes_with(&self, endian: Endianness) -> Vec<u8> {
+        match (self, endian) {
+            (Field::Null, _) => vec![],
+            (Field::Boolean(value), _) => vec![u8::from(*value)],
+            (Field::Integer(value), Endianness::Little) => Vec::from(value.to_le_bytes()),
+            (Field::Integer(value), Endianness::Big) => Vec::from(value.to_be_bytes()),
+            (Field::Float(value), Endianness::Little) => Vec::from(value.to_le_bytes()),
+            (Field::Float(value), Endianness::Big) => Vec::from(value.to_be_bytes()),
+            (Field::Varchar(string) | Field::Char(string), _) => string.as_bytes().to_vec(),
+        }
+    }
+
+    /// Serializes a field stored in a column of type `field_type`. This is the same as
+    /// [`Field::to_bytes`], except that a [`Field::Char`] is padded with spaces to exactly the
+    /// width of its [`Type::Char`] column.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if a char value is wider than its column.
+    pub fn to_bytes_as(&self, field_type: Type) -> Result<Vec<u8>> {
+        match (self, field_type) {
+            (Field::Char(string), Type::Char(width)) => {
+                let width = width as usize;
+                if string.len() > width {
+                    return errinput!(
+                        "Value {} is {} bytes long, which doesn't fit in {field_type}",
+                        self,
+                        string.len()
+                    );
+                }
+                let mut bytes = string.as_bytes().to_vec();
+                bytes.resize(width, b' ');
+                Ok(bytes)
+            }
+            _ => Ok(self.to_bytes()),
         }
     }
 
@@ -51,21 +175,103 @@ impl Field {
     /// represented by a [`crate::catalog::types::Type`].
     ///
     /// Remember that [`Field::Float`] and [`Field::Integer`] are represented as **little-endian**
-    /// byte slices!
+    /// byte slices! The trailing padding of a [`Field::Char`] is trimmed.
+    ///
+    /// Panics if the bytes aren't a valid `field_type` value; use [`Field::try_from_bytes`] unless
+    /// they're known to be.
     pub fn from_bytes(bytes: &[u8], field_type: Type) -> Self {
-        if field_type != Type::Varchar {
-            assert_eq!(field_type.size(), bytes.len());
-        }
-        match field_type {
-            Type::Null => Field::Null,
-            Type::Boolean => Field::Boolean(bytes[0] == 1),
-            Type::Integer => Field::Integer(i32::from_le_bytes(bytes.try_into().unwrap())),
-            Type::Float => Field::Float(f64::from_le_bytes(bytes.try_into().unwrap())),
-            Type::Varchar => Field::Varchar(String::from_utf8(bytes.to_vec()).unwrap()),
+        Self::from_bytes_with(bytes, field_type, Endianness::Little)
+    }
+
+    /// Deserializes a byte slice written by [`Field::to_bytes_with`] in the given byte order.
+    /// Panics like [`Field::from_bytes`].
+    pub fn from_bytes_with(bytes: &[u8], field_type: Type, endian: Endianness) -> Self {
+        Self::decode(bytes, field_type, endian).unwrap()
+    }
+
+    /// Deserializes a byte slice into a field like [`Field::from_bytes`], but returns
+    /// [`rustdb_error::Error::InvalidData`] instead of panicking if `bytes` isn't the size of a
+    /// fixed-size `field_type`, or isn't valid UTF-8 for a string type.
+    pub fn try_from_bytes(bytes: &[u8], field_type: Type) -> Result<Self> {
+        Self::decode(bytes, field_type, Endianness::Little)
+    }
+
+    fn decode(bytes: &[u8], field_type: Type, endian: Endianness) -> Result<Self> {
+        if field_type != Type::Varchar && field_type.size() != bytes.len() {
+            return errdata!(
+                "Expected {} bytes for a {field_type} field, found {}",
+                field_type.size(),
+                bytes.len()
+            );
         }
+        let field = match (field_type, endian) {
+            (Type::Null, _) => Field::Null,
+            (Type::Boolean, _) => Field::Boolean(bytes[0] == 1),
+            (Type::Integer, Endianness::Little) => {
+                Field::Integer(i32::from_le_bytes(bytes.try_into()?))
+            }
+            (Type::Integer, Endianness::Big) => {
+                Field::Integer(i32::from_be_bytes(bytes.try_into()?))
+            }
+            (Type::Float, Endianness::Little) => {
+                Field::Float(f64::from_le_bytes(bytes.try_into()?))
+            }
+            (Type::Float, Endianness::Big) => Field::Float(f64::from_be_bytes(bytes.try_into()?)),
+            (Type::Varchar, _) => Field::Varchar(String::from_utf8(bytes.to_vec())?),
+            (Type::Char(_), _) => {
+                let string = String::from_utf8(bytes.to_vec())?;
+                Field::Char(string.trim_end_matches(' ').to_string())
+            }
+        };
+        Ok(field)
     }
 
-    /// Returns the corresponding [`crate::types::Type`] for the given field.
+    /// Converts a JSON value into a field of type `field_type`, the per-field counterpart of
+    /// [`crate::serde::Serde::from_json`]. JSON `null` is [`Field::Null`] whatever the type, and
+    /// a float column also accepts the strings `"NaN"`, `"Infinity"`, and `"-Infinity"`.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if the value can't be stored as `field_type`,
+    /// e.g. a number that isn't an integer in the range of `i32` for an integer column.
+    pub fn from_json_value(value: &serde_json::Value, field_type: Type) -> Result<Self> {
+        use serde_json::Value;
+        let field = match (field_type, value) {
+            (_, Value::Null) => Field::Null,
+            (Type::Boolean, Value::Bool(b)) => Field::Boolean(*b),
+            (Type::Integer, Value::Number(number)) => {
+                let integer = match number.as_i64() {
+                    Some(i) => i32::try_from(i).ok(),
+                    // Integral floats like `2.0` are fine, as long as they fit.
+                    None => number
+                        .as_f64()
+                        .filter(|f| f.fract() == 0.0)
+                        .filter(|f| (i32::MIN as f64..=i32::MAX as f64).contains(f))
+                        .map(|f| f as i32),
+                };
+                match integer {
+                    Some(i) => Field::Integer(i),
+                    None => return errinput!("JSON number {number} doesn't fit an {field_type}"),
+                }
+            }
+            (Type::Float, Value::Number(number)) => match number.as_f64() {
+                Some(f) => Field::Float(f),
+                None => return errinput!("JSON number {number} doesn't fit a {field_type}"),
+            },
+            (Type::Float, Value::String(string)) => match string.as_str() {
+                "NaN" => Field::Float(f64::NAN),
+                "Infinity" => Field::Float(f64::INFINITY),
+                "-Infinity" => Field::Float(f64::NEG_INFINITY),
+                _ => return errinput!("Invalid {field_type} value {string:?}"),
+            },
+            (Type::Varchar, Value::String(string)) => Field::Varchar(string.clone()),
+            (Type::Char(_), Value::String(string)) => Field::Char(string.clone()),
+            (field_type, value) => return errinput!("Invalid {field_type} value {value}"),
+        };
+        Ok(field)
+    }
+
+    /// Returns the corresponding [`crate::types::Type`] for the given field. For a
+    /// [`Field::Char`], that's the narrowest char type holding the value; see
+    /// [`Field::matches_type`].
     pub fn get_type(&self) -> Type {
         match self {
             Field::Null => Type::Null,
@@ -73,6 +279,182 @@ impl Field {
             Field::Integer(_) => Type::Integer,
             Field::Float(_) => Type::Float,
             Field::Varchar(_) => Type::Varchar,
+            Field::Char(string) => Type::Char(u16::try_from(string.len()).unwrap_or(u16::MAX)),
+        }
+    }
+
+    /// Returns the absolute value of a numeric field. Like the arithmetic operators, returns
+    /// [`Field::Null`] for non-numeric fields and on overflow, i.e. for `i32::MIN`.
+    pub fn abs(&self) -> Field {
+        match self {
+            Field::Integer(i) => i.checked_abs().map_or(Field::Null, Field::Integer),
+            Field::Float(f) => Field::Float(f.abs()),
+            _ => Field::Null,
+        }
+    }
+
+    /// Rounds a float to the nearest integer, and halfway cases away from zero, keeping it a
+    /// float. Integers are returned as is and, like [`Field::abs`], non-numeric fields give
+    /// [`Field::Null`].
+    pub fn round(&self) -> Field {
+        match self {
+            Field::Integer(i) => Field::Integer(*i),
+            Field::Float(f) => Field::Float(f.round()),
+            _ => Field::Null,
+        }
+    }
+
+    /// Like [`Field::round`], but rounds towards zero, dropping the fractional part.
+    pub fn trunc(&self) -> Field {
+        match self {
+            Field::Integer(i) => Field::Integer(*i),
+            Field::Float(f) => Field::Float(f.trunc()),
+            _ => Field::Null,
+        }
+    }
+
+    /// Converts a numeric field to a [`Field::Integer`], rounding floats as given by `mode`.
+    /// [`Field::Null`] stays NULL.
+    ///
+    /// Returns [`Error::ArithmeticOverflow`] if the rounded value is out of the range of an `i32`
+    /// or NaN, and [`Error::InvalidInput`] for non-numeric fields.
+    pub fn to_integer(&self, mode: RoundMode) -> Result<Field> {
+        let rounded = match (self, mode) {
+            (Field::Null, _) => return Ok(Field::Null),
+            (Field::Integer(i), _) => return Ok(Field::Integer(*i)),
+            (Field::Float(f), RoundMode::Nearest) => f.round(),
+            (Field::Float(f), RoundMode::Trunc) => f.trunc(),
+            _ => return errinput!("Can't convert {} {self} to an integer", self.get_type()),
+        };
+        if !(i32::MIN as f64..=i32::MAX as f64).contains(&rounded) {
+            return Err(Error::ArithmeticOverflow);
+        }
+        Ok(Field::Integer(rounded as i32))
+    }
+
+    /// Concatenates two string fields into a [`Field::Varchar`], like SQL's `a || b`. A
+    /// [`Field::Char`] operand contributes its value without padding. Per SQL, concatenating
+    /// [`Field::Null`] gives NULL.
+    ///
+    /// Returns [`Error::InvalidData`] if either operand is neither a string nor NULL.
+    pub fn concat(&self, other: &Field) -> Result<Field> {
+        match (self, other) {
+            (Field::Varchar(l) | Field::Char(l), Field::Varchar(r) | Field::Char(r)) => {
+                Ok(Field::Varchar(format!("{l}{r}")))
+            }
+            (
+                Field::Null | Field::Varchar(_) | Field::Char(_),
+                Field::Null | Field::Varchar(_) | Field::Char(_),
+            ) => Ok(Field::Null),
+            _ => errdata!(
+                "Can't concatenate {} and {}",
+                self.get_type(),
+                other.get_type()
+            ),
+        }
+    }
+
+    /// Returns the number of characters (Unicode scalar values, not bytes) of a string field as a
+    /// [`Field::Integer`], like SQL's `LENGTH`. [`Field::Null`] gives NULL.
+    ///
+    /// Returns [`Error::InvalidData`] for non-string fields, and [`Error::ArithmeticOverflow`] if
+    /// the length doesn't fit in an `i32`.
+    pub fn char_length(&self) -> Result<Field> {
+        let Some(string) = self.as_string("LENGTH")? else {
+            return Ok(Field::Null);
+        };
+        let length =
+            i32::try_from(string.chars().count()).map_err(|_| Error::ArithmeticOverflow)?;
+        Ok(Field::Integer(length))
+    }
+
+    /// Returns the `len` characters of a string field starting at the 1-based character position
+    /// `start`, or the rest of the string if `len` is `None`, like SQL's `SUBSTR`. Positions
+    /// outside the string are clamped to it, so e.g. `substr(0, Some(2))` only takes the first
+    /// character, and a negative `len` gives an empty string. [`Field::Null`] gives NULL.
+    ///
+    /// Returns [`Error::InvalidData`] for non-string fields.
+    pub fn substr(&self, start: i32, len: Option<i32>) -> Result<Field> {
+        let Some(string) = self.as_string("SUBSTR")? else {
+            return Ok(Field::Null);
+        };
+        // Computed in i64 so that `start + len` can't overflow.
+        let end = len.map(|len| i64::from(start) + i64::from(len));
+        let start = i64::from(start).max(1);
+        let count = end.map_or(usize::MAX, |end| (end - start).max(0) as usize);
+        let substring = string
+            .chars()
+            .skip((start - 1) as usize)
+            .take(count)
+            .collect();
+        Ok(Field::Varchar(substring))
+    }
+
+    /// Converts a string field to uppercase, like SQL's `UPPER`. [`Field::Null`] gives NULL.
+    ///
+    /// Returns [`Error::InvalidData`] for non-string fields.
+    pub fn to_upper(&self) -> Result<Field> {
+        self.map_string("UPPER", str::to_uppercase)
+    }
+
+    /// Converts a string field to lowercase, like SQL's `LOWER`. [`Field::Null`] gives NULL.
+    ///
+    /// Returns [`Error::InvalidData`] for non-string fields.
+    pub fn to_lower(&self) -> Result<Field> {
+        self.map_string("LOWER", str::to_lowercase)
+    }
+
+    /// Returns the value of a string field, or `None` for [`Field::Null`]. Other fields give
+    /// [`Error::InvalidData`], naming the function `op` they were passed to.
+    fn as_string(&self, op: &str) -> Result<Option<&str>> {
+        match self {
+            Field::Varchar(string) | Field::Char(string) => Ok(Some(string)),
+            Field::Null => Ok(None),
+            _ => errdata!("{op} expects a string, got {} {self}", self.get_type()),
+        }
+    }
+
+    /// Applies `f` to the value of a string field, keeping its variant (see
+    /// [`Field::as_string`]).
+    fn map_string(&self, op: &str, f: impl Fn(&str) -> String) -> Result<Field> {
+        match self {
+            Field::Varchar(string) => Ok(Field::Varchar(f(string))),
+            Field::Char(string) => Ok(Field::Char(f(string))),
+            _ => self.as_string(op).map(|_| Field::Null),
+        }
+    }
+
+    /// Returns whether this field has the type `field_type`. A [`Field::Char`] matches a char type
+    /// of any width, since whether it fits is only checked when it's serialized (see
+    /// [`Field::to_bytes_as`]).
+    pub fn matches_type(&self, field_type: Type) -> bool {
+        match (self, field_type) {
+            (Field::Char(_), Type::Char(_)) => true,
+            _ => self.get_type() == field_type,
+        }
+    }
+
+    /// Interprets this field as the result of a boolean condition, e.g. a `WHERE` predicate:
+    /// `Some(b)` for [`Field::Boolean`], and `None` (SQL's unknown) for [`Field::Null`] and, since
+    /// they aren't truth values, fields of other types. Filters should treat `None` as not
+    /// matching; use [`Field::try_is_truthy`] to reject non-boolean fields instead.
+    pub fn is_truthy(&self) -> Option<bool> {
+        match self {
+            Field::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Like [`Field::is_truthy`], but returns [`rustdb_error::Error::InvalidInput`] for fields
+    /// that are neither boolean nor NULL.
+    pub fn try_is_truthy(&self) -> Result<Option<bool>> {
+        match self {
+            Field::Boolean(b) => Ok(Some(*b)),
+            Field::Null => Ok(None),
+            _ => errinput!(
+                "Expected a boolean condition, got {} {self}",
+                self.get_type()
+            ),
         }
     }
 }
@@ -114,6 +496,7 @@ impl PartialEq for Field {
             (Field::Boolean(a), Field::Boolean(b)) => a.eq(b),
             (Field::Integer(a), Field::Integer(b)) => a.eq(b),
             (Field::Varchar(a), Field::Varchar(b)) => a.eq(b),
+            (Field::Char(a), Field::Char(b)) => a.eq(b),
             (Field::Float(a), Field::Float(b)) => {
                 // Match on NaN, in addition to equality, for floats.
                 a.eq(b) || (a.is_nan() && b.is_nan())
@@ -124,6 +507,23 @@ impl PartialEq for Field {
 }
 impl Eq for Field {}
 
+impl std::hash::Hash for Field {
+    /// Hashes the field consistently with its [`PartialEq`] implementation: all NaNs hash alike,
+    /// as do `0.0` and `-0.0`.
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Field::Null => {}
+            Field::Boolean(b) => b.hash(state),
+            Field::Integer(i) => i.hash(state),
+            Field::Float(f) if f.is_nan() => f64::NAN.to_bits().hash(state),
+            // Adding 0.0 turns -0.0 into 0.0, and leaves every other value unchanged.
+            Field::Float(f) => (f + 0.0).to_bits().hash(state),
+            Field::Varchar(s) | Field::Char(s) => s.hash(state),
+        }
+    }
+}
+
 impl Ord for Field {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         match (self, other) {
@@ -136,6 +536,7 @@ impl Ord for Field {
             (Field::Boolean(a), Field::Boolean(b)) => a.cmp(b),
             (Field::Integer(a), Field::Integer(b)) => a.cmp(b),
             (Field::Varchar(a), Field::Varchar(b)) => a.cmp(b),
+            (Field::Char(a), Field::Char(b)) => a.cmp(b),
             // Per IEEE standard, NaN should not be comparable to anything (including itself).
             // But we still might need to query for it, so we're going to pretend it's comparable.
             (Field::Float(a), Field::Float(b)) => match (a.is_nan(), b.is_nan()) {
@@ -156,6 +557,56 @@ impl PartialOrd for Field {
     }
 }
 
+impl Field {
+    /// Compares two fields like [`Field`]'s `Ord`, but with NULL as the smallest value for
+    /// [`NullOrder::First`], like `Ord`, and as the largest for [`NullOrder::Last`].
+    pub fn cmp_with_nulls(&self, other: &Self, nulls: NullOrder) -> std::cmp::Ordering {
+        match (self, other, nulls) {
+            (Field::Null, Field::Null, _) => std::cmp::Ordering::Equal,
+            (Field::Null, _, NullOrder::Last) => std::cmp::Ordering::Greater,
+            (_, Field::Null, NullOrder::Last) => std::cmp::Ordering::Less,
+            _ => self.cmp(other),
+        }
+    }
+
+    /// Compares two fields for a sort in ascending or descending order, with NULLs placed as
+    /// `nulls` says whatever the direction.
+    pub fn cmp_for_sort(
+        &self,
+        other: &Self,
+        ascending: bool,
+        nulls: NullOrder,
+    ) -> std::cmp::Ordering {
+        match (self, other) {
+            (Field::Null, _) | (_, Field::Null) => self.cmp_with_nulls(other, nulls),
+            _ if ascending => self.cmp(other),
+            _ => other.cmp(self),
+        }
+    }
+}
+
+/// Compares two rows by each `(column_index, ascending)` sort key in turn, as in
+/// `ORDER BY a, b DESC`, returning `Equal` only if the rows tie on every key. NULLs are placed
+/// as `nulls` says (see [`Field::cmp_for_sort`]), and a column index past the end of a row
+/// compares as [`Field::Null`].
+pub fn compare_rows(
+    a: &[Field],
+    b: &[Field],
+    keys: &[(usize, bool)],
+    nulls: NullOrder,
+) -> std::cmp::Ordering {
+    keys.iter()
+        .map(|&(index, ascending)| {
+            let (a, b) = (
+                a.get(index).unwrap_or(&Field::Null),
+                b.get(index).unwrap_or(&Field::Null),
+            );
+            a.cmp_for_sort(b, ascending, nulls)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
 impl std::ops::Add for Field {
     type Output = Self;
     fn add(self, other: Self) -> Self {
@@ -236,6 +687,19 @@ impl std::ops::Rem for Field {
     }
 }
 
+impl std::ops::Neg for Field {
+    type Output = Self;
+    fn neg(self) -> Self {
+        match self {
+            // -i32::MIN overflows, like the other integer operations.
+            Field::Integer(i) => i.checked_neg().map_or(Field::Null, Field::Integer),
+            Field::Float(f) => Field::Float(-f),
+            // We shouldn't be able to negate non-numerical types.
+            _ => Field::Null,
+        }
+    }
+}
+
 impl std::fmt::Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -244,16 +708,19 @@ impl std::fmt::Display for Field {
             Self::Boolean(false) => write!(f, "FALSE"),
             Self::Integer(i) => i.fmt(f),
             Self::Float(float) => float.fmt(f),
-            Self::Varchar(varchar) => write!(f, "{}", varchar.escape_default()),
+            Self::Varchar(string) | Self::Char(string) => write!(f, "{}", string.escape_default()),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::field::Field;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use crate::field::{compare_rows, Endianness, Field, NullOrder, RoundMode};
     use crate::types::Type;
-    use rustdb_error::assert_errors;
+    use rustdb_error::{assert_errors, Error, Result};
 
     #[test]
     fn test_creation() {
@@ -359,6 +826,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hash() {
+        let hash = |field: &Field| {
+            let mut hasher = DefaultHasher::new();
+            field.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // Equal fields must hash alike, including the special float values.
+        let equal = [
+            (Field::Null, Field::Null),
+            (Field::Integer(7), Field::Integer(7)),
+            (Field::Float(0.0), Field::Float(-0.0)),
+            (Field::Float(f64::NAN), Field::Float(-f64::NAN)),
+            (Field::Varchar("a".into()), Field::Varchar("a".into())),
+        ];
+        for (a, b) in equal {
+            assert_eq!(a, b);
+            assert_eq!(hash(&a), hash(&b));
+        }
+
+        // Fields of different types with the same payload are distinguished.
+        assert_ne!(hash(&Field::Boolean(true)), hash(&Field::Integer(1)));
+        assert_ne!(hash(&Field::Integer(1)), hash(&Field::Integer(2)));
+    }
+
     #[test]
     fn test_comparison() {
         // NULL is less than any non-null field.
@@ -440,4 +933,407 @@ mod tests {
                 )
             });
     }
+
+    #[test]
+    fn test_compare_rows() {
+        let row = |a: i32, b: &str| vec![Field::Integer(a), b.into()];
+        let mut rows = vec![
+            row(2, "x"),
+            row(1, "a"),
+            row(2, "z"),
+            row(1, "c"),
+            vec![Field::Null, "m".into()],
+            row(1, "a"),
+        ];
+
+        // Sort by the first column ascending, then break ties by the second column descending.
+        rows.sort_by(|a, b| compare_rows(a, b, &[(0, true), (1, false)], NullOrder::First));
+        assert_eq!(
+            rows,
+            vec![
+                vec![Field::Null, "m".into()],
+                row(1, "c"),
+                row(1, "a"),
+                row(1, "a"),
+                row(2, "z"),
+                row(2, "x"),
+            ]
+        );
+
+        // Rows are only equal if they tie on every key, and no keys means every row ties.
+        assert_eq!(
+            compare_rows(
+                &row(1, "a"),
+                &row(1, "a"),
+                &[(0, true), (1, false)],
+                NullOrder::First
+            ),
+            std::cmp::Ordering::Equal
+        );
+        assert_eq!(
+            compare_rows(&row(1, "a"), &row(2, "b"), &[], NullOrder::First),
+            std::cmp::Ordering::Equal
+        );
+
+        // Missing columns compare as NULL.
+        assert_eq!(
+            compare_rows(
+                &row(1, "a"),
+                &[Field::Integer(1)],
+                &[(0, true), (1, true)],
+                NullOrder::First
+            ),
+            std::cmp::Ordering::Greater
+        );
+
+        // NULLs go first or last whatever the direction.
+        let mut rows = [Field::Integer(1), Field::Null, Field::Integer(2)].map(|f| vec![f]);
+        for (ascending, nulls, expected) in [
+            (true, NullOrder::First, [None, Some(1), Some(2)]),
+            (true, NullOrder::Last, [Some(1), Some(2), None]),
+            (false, NullOrder::First, [None, Some(2), Some(1)]),
+            (false, NullOrder::Last, [Some(2), Some(1), None]),
+        ] {
+            rows.sort_by(|a, b| compare_rows(a, b, &[(0, ascending)], nulls));
+            let expected = expected.map(|i| vec![i.map_or(Field::Null, Field::Integer)]);
+            assert_eq!(rows, expected);
+        }
+    }
+
+    #[test]
+    fn test_cmp_with_nulls() {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        let (null, one) = (Field::Null, Field::Integer(1));
+        assert_eq!(null.cmp_with_nulls(&one, NullOrder::First), null.cmp(&one));
+        assert_eq!(null.cmp_with_nulls(&one, NullOrder::First), Less);
+        assert_eq!(null.cmp_with_nulls(&one, NullOrder::Last), Greater);
+        assert_eq!(one.cmp_with_nulls(&null, NullOrder::Last), Less);
+        assert_eq!(null.cmp_with_nulls(&null, NullOrder::Last), Equal);
+        assert_eq!(
+            one.cmp_with_nulls(&Field::Integer(2), NullOrder::Last),
+            Less
+        );
+        assert_eq!(NullOrder::default(), NullOrder::First);
+    }
+
+    #[test]
+    fn test_char_serialization() {
+        let char_type = Type::Char(5);
+
+        // Shorter values are padded with spaces, and the padding is trimmed on the way back.
+        let padded = Field::Char("ab".into()).to_bytes_as(char_type).unwrap();
+        assert_eq!(padded, b"ab   ");
+        assert_eq!(
+            Field::from_bytes(&padded, char_type),
+            Field::Char("ab".into())
+        );
+
+        // Values that fill the column exactly aren't padded.
+        let exact = Field::Char("hello".into()).to_bytes_as(char_type).unwrap();
+        assert_eq!(exact, b"hello");
+        assert_eq!(
+            Field::from_bytes(&exact, char_type),
+            Field::Char("hello".into())
+        );
+
+        // Values wider than the column are rejected. The width is in bytes, not chars.
+        for too_long in ["hello!", "🛸🛸"] {
+            assert!(matches!(
+                Field::Char(too_long.into()).to_bytes_as(char_type),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+
+        // A char field matches char types of any width, but nothing else.
+        let field = Field::Char("ab".into());
+        assert!(field.matches_type(Type::Char(1)));
+        assert!(field.matches_type(Type::Char(100)));
+        assert!(!field.matches_type(Type::Varchar));
+        assert_ne!(field, Field::Varchar("ab".into()));
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        // Mis-sized slices of fixed-size types are invalid data, not a panic.
+        assert!(matches!(
+            Field::try_from_bytes(&[1, 2, 3], Type::Integer),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(
+            Field::try_from_bytes(&[], Type::Boolean),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(
+            Field::try_from_bytes(b"abc", Type::Char(4)),
+            Err(Error::InvalidData(_))
+        ));
+
+        // So are strings that aren't valid UTF-8.
+        for field_type in [Type::Varchar, Type::Char(2)] {
+            assert!(matches!(
+                Field::try_from_bytes(&[0xff, 0xfe], field_type),
+                Err(Error::InvalidData(_))
+            ));
+        }
+
+        // Well-formed bytes deserialize like with `from_bytes`.
+        assert_eq!(
+            Field::try_from_bytes(&Field::Integer(-7).to_bytes(), Type::Integer),
+            Ok(Field::Integer(-7))
+        );
+        assert_eq!(
+            Field::try_from_bytes("🦀".as_bytes(), Type::Varchar),
+            Ok(Field::Varchar("🦀".into()))
+        );
+    }
+
+    #[test]
+    fn test_from_json_value() {
+        use serde_json::json;
+
+        let cases = [
+            (json!(true), Type::Boolean, Field::Boolean(true)),
+            (json!(-7), Type::Integer, Field::Integer(-7)),
+            (json!(i32::MAX), Type::Integer, Field::Integer(i32::MAX)),
+            (json!(2.0), Type::Integer, Field::Integer(2)),
+            (json!(1.5), Type::Float, Field::Float(1.5)),
+            (json!(3), Type::Float, Field::Float(3.0)),
+            (json!("Infinity"), Type::Float, Field::Float(f64::INFINITY)),
+            (json!("🦀"), Type::Varchar, Field::Varchar("🦀".into())),
+            (json!("ab"), Type::Char(4), Field::Char("ab".into())),
+        ];
+        for (value, field_type, expected) in cases {
+            assert_eq!(Field::from_json_value(&value, field_type), Ok(expected));
+        }
+
+        // NULL fits every type.
+        for field_type in [Type::Null, Type::Integer, Type::Varchar] {
+            assert_eq!(
+                Field::from_json_value(&json!(null), field_type),
+                Ok(Field::Null)
+            );
+        }
+
+        // Numbers for integer columns must be integers that fit in an i32.
+        for value in [json!(3e10), json!(i64::from(i32::MAX) + 1), json!(1.5)] {
+            assert!(matches!(
+                Field::from_json_value(&value, Type::Integer),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+
+        // So must values of the wrong kind.
+        for (value, field_type) in [
+            (json!("1"), Type::Integer),
+            (json!(1), Type::Varchar),
+            (json!("yes"), Type::Boolean),
+            (json!("one"), Type::Float),
+            (json!([1]), Type::Integer),
+        ] {
+            assert!(matches!(
+                Field::from_json_value(&value, field_type),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        assert_eq!(Field::Boolean(true).is_truthy(), Some(true));
+        assert_eq!(Field::Boolean(false).is_truthy(), Some(false));
+        assert_eq!(Field::Null.is_truthy(), None);
+        assert_eq!(Field::Integer(1).is_truthy(), None);
+
+        assert_eq!(Field::Boolean(true).try_is_truthy(), Ok(Some(true)));
+        assert_eq!(Field::Null.try_is_truthy(), Ok(None));
+        for field in [Field::Integer(1), Field::Float(0.0), "true".into()] {
+            assert!(matches!(field.try_is_truthy(), Err(Error::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn test_negation_and_abs() {
+        assert_eq!(-Field::Integer(5), Field::Integer(-5));
+        assert_eq!(-Field::Float(-2.0), Field::Float(2.0));
+        assert_eq!(Field::Integer(-3).abs(), Field::Integer(3));
+        assert_eq!(Field::Float(-0.5).abs(), Field::Float(0.5));
+
+        // i32::MIN has no positive counterpart, so negating it overflows to NULL.
+        assert_eq!(-Field::Integer(i32::MIN), Field::Null);
+        assert_eq!(Field::Integer(i32::MIN).abs(), Field::Null);
+        assert_eq!(-Field::Integer(i32::MAX), Field::Integer(-i32::MAX));
+
+        // Non-numeric fields have no sign.
+        for field in [Field::Null, Field::Boolean(true), "-1".into()] {
+            assert_eq!(field.abs(), Field::Null);
+            assert_eq!(-field, Field::Null);
+        }
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(Field::Float(2.7).round(), Field::Float(3.0));
+        assert_eq!(Field::Float(2.7).trunc(), Field::Float(2.0));
+        assert_eq!(Field::Float(-2.5).round(), Field::Float(-3.0));
+        assert_eq!(Field::Float(-2.7).trunc(), Field::Float(-2.0));
+        assert_eq!(Field::Integer(7).round(), Field::Integer(7));
+        assert_eq!(Field::from("2.7").trunc(), Field::Null);
+
+        let float = Field::Float(2.7);
+        assert_eq!(float.to_integer(RoundMode::Nearest), Ok(Field::Integer(3)));
+        assert_eq!(float.to_integer(RoundMode::Trunc), Ok(Field::Integer(2)));
+        assert_eq!(
+            Field::Integer(-4).to_integer(RoundMode::Nearest),
+            Ok(Field::Integer(-4))
+        );
+        assert_eq!(Field::Null.to_integer(RoundMode::Trunc), Ok(Field::Null));
+        assert_eq!(
+            Field::Float(i32::MAX as f64 + 0.4).to_integer(RoundMode::Nearest),
+            Ok(Field::Integer(i32::MAX))
+        );
+
+        // Values that don't fit in an i32 overflow.
+        for f in [1e20, -1e20, i32::MAX as f64 + 1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                Field::Float(f).to_integer(RoundMode::Nearest),
+                Err(Error::ArithmeticOverflow)
+            );
+        }
+        assert!(matches!(
+            Field::Boolean(true).to_integer(RoundMode::Trunc),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_concat() -> Result<()> {
+        let foo = Field::from("foo");
+        assert_eq!(foo.concat(&"bar".into())?, "foobar".into());
+        assert_eq!(foo.concat(&Field::Char("ba".to_string()))?, "fooba".into());
+        assert_eq!(Field::from("").concat(&foo)?, foo);
+        assert_eq!(
+            Field::from("🦀 ").concat(&"naïve 🚀".into())?,
+            "🦀 naïve 🚀".into()
+        );
+
+        // Concatenating NULL gives NULL.
+        assert_eq!(foo.concat(&Field::Null)?, Field::Null);
+        assert_eq!(Field::Null.concat(&foo)?, Field::Null);
+        assert_eq!(Field::Null.concat(&Field::Null)?, Field::Null);
+
+        for (l, r) in [
+            (Field::Integer(1), foo.clone()),
+            (foo.clone(), Field::Boolean(true)),
+            (Field::Null, Field::Float(1.0)),
+        ] {
+            assert!(matches!(l.concat(&r), Err(Error::InvalidData(_))));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_functions() -> Result<()> {
+        // "héllo wörld" takes 13 bytes but is 11 characters long.
+        assert_eq!("héllo wörld".len(), 13);
+        let field = Field::from("héllo wörld");
+        assert_eq!(field.char_length()?, Field::Integer(11));
+        assert_eq!(Field::from("🦀🦀").char_length()?, Field::Integer(2));
+        assert_eq!(Field::from("").char_length()?, Field::Integer(0));
+
+        assert_eq!(field.substr(1, Some(5))?, "héllo".into());
+        assert_eq!(field.substr(7, None)?, "wörld".into());
+        assert_eq!(field.substr(2, Some(1))?, "é".into());
+
+        // Out-of-range positions are clamped to the string.
+        assert_eq!(field.substr(0, Some(3))?, "hé".into());
+        assert_eq!(field.substr(-5, Some(7))?, "h".into());
+        assert_eq!(field.substr(-5, None)?, field.clone());
+        assert_eq!(field.substr(7, Some(100))?, "wörld".into());
+        assert_eq!(field.substr(20, Some(2))?, "".into());
+        assert_eq!(field.substr(3, Some(-1))?, "".into());
+        assert_eq!(field.substr(i32::MAX, Some(i32::MAX))?, "".into());
+        assert_eq!(field.substr(i32::MIN, Some(i32::MIN))?, "".into());
+
+        assert_eq!(field.to_upper()?, "HÉLLO WÖRLD".into());
+        assert_eq!(Field::from("ÀbC").to_lower()?, "àbc".into());
+        assert_eq!(
+            Field::Char("ab".to_string()).to_upper()?,
+            Field::Char("AB".to_string())
+        );
+
+        // NULL propagates, and other types are rejected.
+        assert_eq!(Field::Null.char_length()?, Field::Null);
+        assert_eq!(Field::Null.substr(1, Some(1))?, Field::Null);
+        assert_eq!(Field::Null.to_upper()?, Field::Null);
+        assert_eq!(Field::Null.to_lower()?, Field::Null);
+        let integer = Field::Integer(1);
+        assert!(matches!(integer.char_length(), Err(Error::InvalidData(_))));
+        assert!(matches!(
+            integer.substr(1, None),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(integer.to_upper(), Err(Error::InvalidData(_))));
+        assert!(matches!(integer.to_lower(), Err(Error::InvalidData(_))));
+        Ok(())
+    }
+
+    /// Test that rows round-trip through serde formats.
+    #[test]
+    fn test_serde_formats() {
+        let row = vec![
+            Field::Null,
+            Field::Boolean(true),
+            Field::Integer(-7),
+            Field::Float(2.5),
+            "héllo 🦀".into(),
+            Field::Char("ab".to_string()),
+        ];
+
+        let json = serde_json::to_string(&row).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Field>>(&json).unwrap(), row);
+        assert_eq!(
+            serde_json::to_value(Field::Integer(1)).unwrap(),
+            serde_json::json!({ "Integer": 1 })
+        );
+
+        let bytes = bincode::serialize(&row).unwrap();
+        assert_eq!(bincode::deserialize::<Vec<Field>>(&bytes).unwrap(), row);
+    }
+
+    #[test]
+    fn test_endianness() {
+        let fields = [
+            (Field::Null, Type::Null),
+            (Field::Boolean(true), Type::Boolean),
+            (Field::Integer(-123456789), Type::Integer),
+            (Field::Float(339.339), Type::Float),
+            (Field::Float(f64::NAN), Type::Float),
+            ("All love 🛸💕🕺".into(), Type::Varchar),
+        ];
+        for endian in [Endianness::Little, Endianness::Big] {
+            for (field, field_type) in &fields {
+                let bytes = field.to_bytes_with(endian);
+                assert_eq!(Field::from_bytes_with(&bytes, *field_type, endian), *field);
+            }
+        }
+
+        // Numbers are reversed in big-endian form, and the default is little-endian.
+        assert_eq!(
+            Field::Integer(1).to_bytes_with(Endianness::Big),
+            [0, 0, 0, 1]
+        );
+        assert_eq!(
+            Field::Integer(1).to_bytes_with(Endianness::Little),
+            [1, 0, 0, 0]
+        );
+        assert_eq!(Field::Integer(1).to_bytes(), [1, 0, 0, 0]);
+        assert_eq!(
+            Field::Float(1.0).to_bytes_with(Endianness::Big),
+            1.0f64.to_be_bytes()
+        );
+
+        // Everything else is the same in either byte order.
+        let text = Field::from("hi");
+        assert_eq!(text.to_bytes_with(Endianness::Big), text.to_bytes());
+    }
 }
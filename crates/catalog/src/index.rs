@@ -0,0 +1,175 @@
+use crate::field::Field;
+use crate::schema::{RecordId, Schema};
+use crate::serde::Serde;
+use crate::tuple::Tuple;
+use rustdb_error::Result;
+use std::collections::HashMap;
+
+/// A secondary index, mapping the key values of a table's tuples to their record ids. A key is
+/// the values of the index's key columns, in key column order, and may be shared by any number
+/// of tuples.
+pub trait Index: Send + Sync {
+    /// Adds an entry mapping `key` to `rid`.
+    fn insert(&mut self, key: &[Field], rid: RecordId);
+
+    /// Removes the entry mapping `key` to `rid`, if there is one.
+    fn delete(&mut self, key: &[Field], rid: RecordId);
+
+    /// Returns the record ids of every entry with the given key, in insertion order.
+    fn lookup(&self, key: &[Field]) -> Vec<RecordId>;
+}
+
+/// An index that hashes keys, supporting only point lookups. Keys are stored serialized (see
+/// [`Serde::serialize`]), so two keys match exactly when their fields are equal and of the same
+/// types.
+#[derive(Debug, Default)]
+pub struct HashIndex {
+    entries: HashMap<Vec<u8>, Vec<RecordId>>,
+    /// The indexes of the key columns in the indexed table's schema, in key order, used to
+    /// extract keys from tuples when rebuilding the index.
+    key_columns: Vec<usize>,
+}
+
+impl HashIndex {
+    /// Creates an empty hash index, without key columns to rebuild it from.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty hash index whose keys are the values of the columns at `key_columns` of
+    /// the indexed table, in order.
+    pub fn with_key_columns(key_columns: &[usize]) -> Self {
+        Self {
+            entries: HashMap::new(),
+            key_columns: key_columns.to_vec(),
+        }
+    }
+
+    /// Returns the indexes of the key columns, in key order.
+    pub fn key_columns(&self) -> &[usize] {
+        &self.key_columns
+    }
+
+    /// Replaces the index's entries with one for each tuple emitted by `iter`, e.g. a scan of
+    /// the table's live tuples after a crash or a vacuum moved them. Keys are extracted from the
+    /// tuples, which have the given `schema`, at the index's key columns.
+    ///
+    /// Returns the first error from `iter` or from deserializing a key (see
+    /// [`Serde::deserialize_columns`]), in which case the index is left unchanged.
+    pub fn rebuild_from_scan(
+        &mut self,
+        iter: impl Iterator<Item = Result<(RecordId, Tuple)>>,
+        schema: &Schema,
+    ) -> Result<()> {
+        let mut rebuilt = Self::with_key_columns(&self.key_columns);
+        for item in iter {
+            let (rid, tuple) = item?;
+            let key = Serde::deserialize_columns(tuple.as_slice(), schema, &self.key_columns)?;
+            rebuilt.insert(&key, rid);
+        }
+        *self = rebuilt;
+        Ok(())
+    }
+}
+
+impl Index for HashIndex {
+    fn insert(&mut self, key: &[Field], rid: RecordId) {
+        self.entries
+            .entry(Serde::serialize(key))
+            .or_default()
+            .push(rid);
+    }
+
+    fn delete(&mut self, key: &[Field], rid: RecordId) {
+        let key = Serde::serialize(key);
+        if let Some(rids) = self.entries.get_mut(&key) {
+            rids.retain(|&r| r != rid);
+            if rids.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+
+    fn lookup(&self, key: &[Field]) -> Vec<RecordId> {
+        self.entries
+            .get(&Serde::serialize(key))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::column::Column;
+    use crate::field::Field;
+    use crate::index::{HashIndex, Index};
+    use crate::schema::Schema;
+    use crate::serde::Serde;
+    use crate::tuple::Tuple;
+    use crate::types::Type;
+    use rustdb_error::{Error, Result};
+
+    #[test]
+    fn test_hash_index() {
+        let mut index = HashIndex::new();
+        let keys = [3, 1, 3, 2, 3];
+        for (rid, &key) in keys.iter().enumerate() {
+            index.insert(&[Field::Integer(key)], rid as u64);
+        }
+
+        // Lookups return exactly the matching record ids, including for duplicate keys.
+        assert_eq!(index.lookup(&[Field::Integer(3)]), vec![0, 2, 4]);
+        assert_eq!(index.lookup(&[Field::Integer(1)]), vec![1]);
+        assert_eq!(index.lookup(&[Field::Integer(2)]), vec![3]);
+        assert!(index.lookup(&[Field::Integer(4)]).is_empty());
+
+        // Deleting an entry leaves the others with the same key, and deleting a missing one is a
+        // no-op.
+        index.delete(&[Field::Integer(3)], 2);
+        index.delete(&[Field::Integer(3)], 1);
+        index.delete(&[Field::Integer(4)], 0);
+        assert_eq!(index.lookup(&[Field::Integer(3)]), vec![0, 4]);
+        index.delete(&[Field::Integer(1)], 1);
+        assert!(index.lookup(&[Field::Integer(1)]).is_empty());
+
+        // Composite keys match on every field.
+        index.insert(&[Field::Integer(1), "a".into()], 10);
+        index.insert(&[Field::Integer(1), "b".into()], 11);
+        assert_eq!(index.lookup(&[Field::Integer(1), "a".into()]), vec![10]);
+        assert!(index.lookup(&[Field::Integer(1)]).is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_from_scan() -> Result<()> {
+        let schema = Schema::new(&[
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("id".to_string(), Type::Integer),
+        ]);
+        let tuple = |name: &str, id| {
+            Tuple::new(Serde::serialize(&[name.into(), Field::Integer(id)]).into())
+        };
+        let mut index = HashIndex::with_key_columns(&[1]);
+        assert_eq!(index.key_columns(), &[1]);
+        index.rebuild_from_scan(
+            [Ok((0, tuple("a", 1))), Ok((1, tuple("b", 2)))].into_iter(),
+            &schema,
+        )?;
+        assert_eq!(index.lookup(&[Field::Integer(1)]), vec![0]);
+
+        // Simulate a vacuum that moved the tuples and deleted one of them.
+        let scan = [Ok((10, tuple("b", 2))), Ok((11, tuple("c", 2)))];
+        index.rebuild_from_scan(scan.into_iter(), &schema)?;
+        assert!(index.lookup(&[Field::Integer(1)]).is_empty());
+        assert_eq!(index.lookup(&[Field::Integer(2)]), vec![10, 11]);
+
+        // A failed scan leaves the index as it was.
+        let scan = [
+            Ok((20, tuple("d", 3))),
+            Err(Error::InvalidData("corrupt".to_string())),
+        ];
+        assert!(index.rebuild_from_scan(scan.into_iter(), &schema).is_err());
+        assert!(index.lookup(&[Field::Integer(3)]).is_empty());
+        assert_eq!(index.lookup(&[Field::Integer(2)]), vec![10, 11]);
+        Ok(())
+    }
+}
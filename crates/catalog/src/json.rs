@@ -0,0 +1,127 @@
+//! JSON helpers for exporting and importing tuples (see [`crate::serde::Serde::to_json`] and
+//! [`crate::serde::Serde::from_json`]): a writer for strings, and a reader for the members of an
+//! object, built on `serde_json`.
+use ::serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use rustdb_error::{errinput, Result};
+
+/// Appends `value` to `out` as a quoted JSON string, escaping it as needed.
+pub(crate) fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Parses a JSON object, returning its members in order. Unlike a [`serde_json::Map`], duplicate
+/// members are all kept, so that the caller can reject them.
+///
+/// Returns [`rustdb_error::Error::InvalidInput`] if `json` isn't an object.
+pub(crate) fn parse_object(json: &str) -> Result<Vec<(String, serde_json::Value)>> {
+    match serde_json::from_str::<Members>(json) {
+        Ok(Members(members)) => Ok(members),
+        Err(e) => errinput!("Invalid JSON object: {e}"),
+    }
+}
+
+/// The members of a JSON object, in order.
+struct Members(Vec<(String, serde_json::Value)>);
+
+impl<'de> Deserialize<'de> for Members {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct MembersVisitor;
+
+        impl<'de> Visitor<'de> for MembersVisitor {
+            type Value = Members;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Members, A::Error> {
+                let mut members = Vec::new();
+                while let Some(member) = map.next_entry()? {
+                    members.push(member);
+                }
+                Ok(Members(members))
+            }
+        }
+
+        deserializer.deserialize_map(MembersVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_object, write_string};
+    use rustdb_error::Error;
+    use serde_json::json;
+
+    #[test]
+    fn test_strings() {
+        for value in [
+            "",
+            "plain",
+            "quote \" and \\ slash",
+            "tab\tnew\nline\u{1}",
+            "🛸💕",
+        ] {
+            let mut json = "{\"k\": ".to_string();
+            write_string(&mut json, value);
+            json.push('}');
+            assert_eq!(
+                parse_object(&json),
+                Ok(vec![("k".to_string(), json!(value))])
+            );
+        }
+
+        // Escaped surrogate pairs are combined.
+        assert_eq!(
+            parse_object(r#"{"k":"\ud83d\udef8"}"#),
+            Ok(vec![("k".to_string(), json!("🛸"))])
+        );
+    }
+
+    #[test]
+    fn test_members() {
+        // Members are kept in order, duplicates included.
+        assert_eq!(
+            parse_object(r#"{"b": 1, "a": null, "b": [true]}"#),
+            Ok(vec![
+                ("b".to_string(), json!(1)),
+                ("a".to_string(), json!(null)),
+                ("b".to_string(), json!([true])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        for json in [
+            "",
+            "[]",
+            "{",
+            "{\"a\" 1}",
+            "{\"a\": 1,}",
+            "{\"a\": nul}",
+            "{\"a\": \"open}",
+            "{} {}",
+        ] {
+            assert!(
+                matches!(parse_object(json), Err(Error::InvalidInput(_))),
+                "{json}"
+            );
+        }
+    }
+}
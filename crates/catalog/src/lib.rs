@@ -5,6 +5,8 @@
 pub mod catalog;
 pub mod column;
 pub mod field;
+pub mod index;
+mod json;
 pub mod schema;
 pub mod serde;
 pub mod tuple;
@@ -1,5 +1,10 @@
-use crate::column::Column;
-use rustdb_error::{Error, Result};
+use crate::column::{Collation, Column};
+use crate::field::Field;
+use crate::serde::{put_u32, take_u32};
+use crate::types::Type;
+use rustdb_error::{errdata, errinput, Error, Result};
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::sync::Arc;
 /// Can be converted to and from a [`rustdb_storage::record_id::RecordId`] via From/Into trait.
 pub type RecordId = u64;
@@ -15,16 +20,41 @@ pub struct Schema {
     /// variable-length fields in the schema, this number excludes their field sizes, but _does_
     /// include the size of their offsets into the data payload.
     size: usize,
+    /// The indexes of the columns making up the primary key, in key order. Empty if the schema
+    /// has no primary key.
+    primary_key: Vec<usize>,
 }
 
 impl Schema {
+    /// Returns a [`SchemaBuilder`] for building a schema column by column.
+    pub fn builder() -> SchemaBuilder {
+        SchemaBuilder::default()
+    }
+
     pub fn new(columns: &[Column]) -> Self {
         let columns = columns.to_vec();
         let size = columns.iter().filter_map(|c| c.size()).sum();
-        Schema { columns, size }
+        Schema {
+            columns,
+            size,
+            primary_key: Vec::new(),
+        }
+    }
+
+    /// Creates a schema whose primary key consists of the columns at the given indexes, in order.
+    /// Returns [`Error::OutOfBounds`] if any index isn't a column of the schema.
+    pub fn with_primary_key(columns: &[Column], primary_key: &[usize]) -> Result<Self> {
+        if primary_key.iter().any(|&i| i >= columns.len()) {
+            return Err(Error::OutOfBounds);
+        }
+        let mut schema = Schema::new(columns);
+        schema.primary_key = primary_key.to_vec();
+        Ok(schema)
     }
 
-    /// Moves all the columns of `other` into `self`, consuming `other`.
+    /// Moves all the columns of `other` into `self`, consuming `other`. The primary key of `self`
+    /// is kept, while that of `other` is discarded. Column names aren't checked for duplicates;
+    /// see [`Schema::try_append`] for that.
     ///
     /// Because `Schema` stores its columns in a `Vec`, this method will panic if the new column
     /// vector capacity exceeds `isize::MAX` _bytes_ (see [`Vec::append`]).
@@ -33,6 +63,39 @@ impl Schema {
         self.columns.append(&mut other.columns);
     }
 
+    /// Like [`Schema::append`], but returns [`Error::InvalidInput`] listing the conflicting names
+    /// if any column of `other` has the same qualified name (see [`Column::qualified_name`]) as a
+    /// column of `self` or an earlier column of `other`. On error, `self` is left unchanged.
+    pub fn try_append(&mut self, other: Self) -> Result<()> {
+        let mut names = self
+            .columns
+            .iter()
+            .map(|c| c.qualified_name())
+            .collect::<HashSet<_>>();
+        let mut conflicts = Vec::new();
+        for column in &other.columns {
+            let name = column.qualified_name();
+            if !names.insert(name.clone()) && !conflicts.contains(&name) {
+                conflicts.push(name);
+            }
+        }
+        if !conflicts.is_empty() {
+            return errinput!("Duplicate column names: {}", conflicts.join(", "));
+        }
+        self.append(other);
+        Ok(())
+    }
+
+    /// Builds a new schema from the columns at the given `indices`, in the given order. Indices
+    /// may be reordered or repeated. Returns [`Error::OutOfBounds`] if any index is invalid.
+    pub fn project(&self, indices: &[usize]) -> Result<Schema> {
+        let columns = indices
+            .iter()
+            .map(|&i| self.column_at(i).cloned())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Schema::new(&columns))
+    }
+
     /// Returns an immutable view of the columns.
     pub fn columns(&self) -> &Vec<Column> {
         &self.columns
@@ -54,6 +117,15 @@ impl Schema {
             .map(|(i, _)| i)
     }
 
+    /// Returns the index of the column in the schema with the given name and table qualifier, if
+    /// one exists. Unlike [`Schema::column_index_of`], this tells apart columns with the same name
+    /// from different tables.
+    pub fn column_index_of_qualified(&self, table: &str, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|col| col.table_qualifier() == Some(table) && col.name() == name)
+    }
+
     /// Returns the number of columns in the schema.
     pub fn num_columns(&self) -> usize {
         self.columns.len()
@@ -63,6 +135,123 @@ impl Schema {
     pub fn size(&self) -> usize {
         self.size
     }
+
+    /// Returns the indexes of the primary key columns, in key order.
+    pub fn primary_key(&self) -> &[usize] {
+        &self.primary_key
+    }
+
+    /// Compares two values of the column at index `col` under the column's collation. Values of
+    /// other types than varchar, and of columns past the end of the schema, compare like
+    /// [`Field::cmp`].
+    pub fn compare_fields(&self, col: usize, a: &Field, b: &Field) -> Ordering {
+        let collation = self.columns.get(col).map(Column::collation);
+        match (collation, a, b) {
+            (Some(Collation::CaseInsensitive), Field::Varchar(a), Field::Varchar(b)) => {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            }
+            _ => a.cmp(b),
+        }
+    }
+
+    /// Checks that `fields` is a valid row of this schema: that there's exactly one field per
+    /// column, that no NOT NULL column is NULL, and that every other field has its column's type.
+    /// Nullable columns of any type may be NULL.
+    pub fn check_fields(&self, fields: &[Field]) -> std::result::Result<(), SchemaError> {
+        if fields.len() != self.num_columns() {
+            return Err(SchemaError::ColumnCountMismatch {
+                expected: self.num_columns(),
+                got: fields.len(),
+            });
+        }
+        for (index, (field, column)) in fields.iter().zip(&self.columns).enumerate() {
+            if *field == Field::Null {
+                if !column.is_nullable() {
+                    return Err(SchemaError::NotNullViolation { index });
+                }
+                continue;
+            }
+            if !field.matches_type(column.field_type()) {
+                return Err(SchemaError::TypeMismatch {
+                    index,
+                    expected: column.field_type(),
+                    got: field.get_type(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the primary key values of a deserialized tuple, in key order.
+    ///
+    /// Returns [`Error::InvalidInput`] if the schema has no primary key, or if `fields` doesn't
+    /// have exactly one value per column of the schema.
+    pub fn extract_key(&self, fields: &[Field]) -> Result<Vec<Field>> {
+        if self.primary_key.is_empty() {
+            return errinput!("Schema has no primary key");
+        }
+        if fields.len() != self.num_columns() {
+            return errinput!(
+                "Expected {} fields, got {}",
+                self.num_columns(),
+                fields.len()
+            );
+        }
+        Ok(self
+            .primary_key
+            .iter()
+            .map(|&i| fields[i].clone())
+            .collect())
+    }
+
+    /// Serializes this schema. The encoding is the number of columns, then each column with all
+    /// of its attributes (see [`Column::write_to`]), then the number of primary key columns
+    /// followed by their indexes. Counts and indexes are little-endian `u32`s.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_u32(&mut bytes, self.columns.len() as u32);
+        for column in &self.columns {
+            column.write_to(&mut bytes);
+        }
+        put_u32(&mut bytes, self.primary_key.len() as u32);
+        for &index in &self.primary_key {
+            put_u32(&mut bytes, index as u32);
+        }
+        bytes
+    }
+
+    /// Deserializes a schema written by [`Schema::to_bytes`].
+    ///
+    /// Returns [`Error::InvalidData`] if the bytes are truncated, have trailing data, or contain an
+    /// invalid column (see [`Column::read_from`]) or primary key index.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Schema> {
+        let schema = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return errdata!("Found {} trailing bytes after schema", bytes.len());
+        }
+        Ok(schema)
+    }
+
+    /// Removes and deserializes a schema written by [`Schema::to_bytes`] from the front of
+    /// `bytes`.
+    pub(crate) fn read_from(bytes: &mut &[u8]) -> Result<Schema> {
+        let num_columns = take_u32(bytes)?;
+        let columns = (0..num_columns)
+            .map(|_| Column::read_from(bytes))
+            .collect::<Result<Vec<_>>>()?;
+
+        let num_keys = take_u32(bytes)?;
+        let primary_key = (0..num_keys)
+            .map(|_| Ok(take_u32(bytes)? as usize))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(index) = primary_key.iter().find(|&&i| i >= columns.len()) {
+            return errdata!("Primary key index {index} is out of bounds");
+        }
+
+        let mut schema = Schema::new(&columns);
+        schema.primary_key = primary_key;
+        Ok(schema)
+    }
 }
 
 impl std::fmt::Display for Schema {
@@ -83,13 +272,129 @@ impl std::fmt::Display for Schema {
     }
 }
 
+/// A way in which a row doesn't fit a schema (see [`Schema::check_fields`]). Converts into
+/// [`Error::InvalidInput`] for NOT NULL violations, and [`Error::InvalidData`] otherwise.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SchemaError {
+    /// The row doesn't have exactly one field per column.
+    ColumnCountMismatch { expected: usize, got: usize },
+    /// The field at `index` doesn't have its column's type.
+    TypeMismatch {
+        index: usize,
+        expected: Type,
+        got: Type,
+    },
+    /// The field at `index` is NULL, but its column is NOT NULL.
+    NotNullViolation { index: usize },
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SchemaError::ColumnCountMismatch { expected, got } => {
+                write!(f, "Expected {expected} fields, got {got}")
+            }
+            SchemaError::TypeMismatch {
+                index,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Field {index} has type {got}, but its column has type {expected}"
+            ),
+            SchemaError::NotNullViolation { index } => {
+                write!(f, "Field {index} is NULL, but its column is NOT NULL")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<SchemaError> for Error {
+    fn from(e: SchemaError) -> Self {
+        match e {
+            SchemaError::NotNullViolation { .. } => Error::InvalidInput(e.to_string()),
+            _ => Error::InvalidData(e.to_string()),
+        }
+    }
+}
+
+/// Builds a [`Schema`] one column at a time, referring to primary key columns by name. Created by
+/// [`Schema::builder`].
+#[derive(Debug, Default)]
+pub struct SchemaBuilder {
+    /// The name, type, and nullability of each column, in order.
+    columns: Vec<(String, Type, bool)>,
+    /// The names of the primary key columns, in key order.
+    primary_key: Vec<String>,
+}
+
+impl SchemaBuilder {
+    /// Adds a `NOT NULL` column.
+    pub fn column(mut self, name: &str, field_type: Type) -> Self {
+        self.columns.push((name.to_string(), field_type, false));
+        self
+    }
+
+    /// Adds a nullable column.
+    pub fn nullable_column(mut self, name: &str, field_type: Type) -> Self {
+        self.columns.push((name.to_string(), field_type, true));
+        self
+    }
+
+    /// Sets the primary key to the columns with the given names, in key order.
+    pub fn primary_key(mut self, names: &[&str]) -> Self {
+        self.primary_key = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Builds the schema.
+    ///
+    /// Returns [`Error::InvalidInput`] if two columns have the same name, or if a primary key
+    /// column isn't one of the columns.
+    pub fn build(self) -> Result<Schema> {
+        let mut names = HashSet::new();
+        let mut columns = Vec::with_capacity(self.columns.len());
+        for (name, field_type, nullable) in self.columns {
+            if !names.insert(name.clone()) {
+                return errinput!("Duplicate column name {name}");
+            }
+            columns.push(Column::new_with(name, field_type, nullable, None)?);
+        }
+        let primary_key = self
+            .primary_key
+            .iter()
+            .map(|name| match columns.iter().position(|c| c.name() == name) {
+                Some(index) => Ok(index),
+                None => errinput!("Primary key column {name} does not exist"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Schema::with_primary_key(&columns, &primary_key)
+    }
+}
+
+/// Appends a computed field `value` to `row`, and a column named `name` for it to the row's
+/// `schema`, e.g. for a projection that evaluates an expression. The column's type is the type
+/// of `value` (see [`Field::get_type`]), and the schema's size grows accordingly.
+pub fn append_field(row: &mut Vec<Field>, schema: &mut Schema, name: &str, value: Field) {
+    schema.append(Schema::new(&[Column::new(
+        name.to_string(),
+        value.get_type(),
+    )]));
+    row.push(value);
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::column::Column;
-    use crate::schema::Schema;
+    use crate::column::{Collation, Column};
+    use crate::field::Field;
+    use crate::schema::{append_field, Schema};
+    use crate::serde::{put_str, put_u32, Serde};
     use crate::types::Type;
     use rand::Rng;
     use rustdb_error::Error;
+    use std::cmp::Ordering;
 
     #[test]
     fn test_append() {
@@ -109,6 +414,65 @@ mod tests {
         assert_eq!(first.columns(), &all_columns);
     }
 
+    #[test]
+    fn test_append_field() {
+        let mut schema = Schema::new(&[
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("price".to_string(), Type::Integer),
+        ]);
+        let mut row = vec!["apple".into(), Field::Integer(3)];
+        let size = schema.size();
+
+        // The computed column doubles the price.
+        append_field(&mut row, &mut schema, "doubled", Field::Integer(6));
+        assert_eq!(schema.num_columns(), 3);
+        assert_eq!(schema.column_index_of("doubled"), Some(2));
+        assert_eq!(schema.column_at(2).unwrap().field_type(), Type::Integer);
+        assert_eq!(schema.size(), size + Type::Integer.size());
+
+        let bytes = Serde::serialize(&row);
+        assert_eq!(Serde::deserialize(&bytes, &schema), row);
+        assert_eq!(
+            Serde::deserialize_columns(&bytes, &schema, &[2]),
+            Ok(vec![Field::Integer(6)])
+        );
+    }
+
+    #[test]
+    fn test_try_append() {
+        let columns = vec![
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ];
+        let mut schema = Schema::new(&columns);
+
+        // Columns with distinct names are appended just like with `append`.
+        let extra = Column::new("total".to_string(), Type::Float);
+        schema.try_append(Schema::new(&[extra.clone()])).unwrap();
+        let mut expected = Schema::new(&columns);
+        expected.append(Schema::new(&[extra]));
+        assert_eq!(schema, expected);
+
+        // Conflicting names are all reported, and the schema is left as it was.
+        let conflicting = Schema::new(&[
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("other".to_string(), Type::Integer),
+            Column::new("id".to_string(), Type::Integer),
+        ]);
+        match schema.try_append(conflicting) {
+            Err(Error::InvalidInput(message)) => {
+                assert!(message.contains("name") && message.contains("id"));
+                assert!(!message.contains("other"));
+            }
+            result => panic!("expected a conflict, got {result:?}"),
+        }
+        assert_eq!(schema, expected);
+
+        // Names repeated within the appended schema conflict too.
+        let repeated = Schema::new(&create_n_columns(1)).project(&[0, 0]).unwrap();
+        assert!(Schema::new(&[]).try_append(repeated).is_err());
+    }
+
     #[test]
     fn test_equality() {
         let names = vec!["a", "b", "c"];
@@ -126,7 +490,10 @@ mod tests {
 
         // Schemas with matching column vectors (even if they're different objects) are equal.
         let more_integer_columns = integer_columns.clone();
-        assert_eq!(Schema::new(&integer_columns), Schema::new(&integer_columns));
+        assert_eq!(
+            Schema::new(&integer_columns),
+            Schema::new(&more_integer_columns)
+        );
     }
 
     #[test]
@@ -181,9 +548,307 @@ mod tests {
         assert!(schema.column_index_of("All love 🛸💕🕺").is_none());
     }
 
+    #[test]
+    fn test_column_index_of_qualified() {
+        let column = |table: &str, name: &str| {
+            Column::new(name.to_string(), Type::Integer).with_table_qualifier(table.to_string())
+        };
+        let mut schema = Schema::new(&[column("users", "id"), column("users", "name")]);
+        schema
+            .try_append(Schema::new(&[
+                column("orders", "id"),
+                column("orders", "user_id"),
+            ]))
+            .unwrap();
+
+        // Qualified lookups resolve columns with the same name to their own table's column.
+        assert_eq!(schema.column_index_of_qualified("users", "id"), Some(0));
+        assert_eq!(schema.column_index_of_qualified("orders", "id"), Some(2));
+        assert_eq!(
+            schema.column_index_of_qualified("orders", "user_id"),
+            Some(3)
+        );
+        assert_eq!(schema.column_index_of_qualified("users", "user_id"), None);
+        assert_eq!(schema.column_index_of_qualified("items", "id"), None);
+
+        // Unqualified lookups still return the first match.
+        assert_eq!(schema.column_index_of("id"), Some(0));
+
+        // Unqualified columns are never found by a qualified lookup.
+        let unqualified = Schema::new(&[Column::new("id".to_string(), Type::Integer)]);
+        assert_eq!(unqualified.column_index_of_qualified("users", "id"), None);
+    }
+
+    #[test]
+    fn test_project() {
+        let columns = vec![
+            Column::new("a".to_string(), Type::Integer),
+            Column::new("b".to_string(), Type::Varchar),
+            Column::new("c".to_string(), Type::Float),
+        ];
+        let schema = Schema::new(&columns);
+
+        // Projected columns come back in the requested order, with the size recomputed.
+        let projected = schema.project(&[2, 0]).unwrap();
+        assert_eq!(
+            projected.columns(),
+            &vec![columns[2].clone(), columns[0].clone()]
+        );
+        assert_eq!(projected.size(), Type::Float.size() + Type::Integer.size());
+
+        // Columns may be repeated.
+        let repeated = schema.project(&[1, 1]).unwrap();
+        assert_eq!(repeated.num_columns(), 2);
+        assert_eq!(repeated.column_index_of("b"), Some(0));
+
+        // An empty projection is an empty schema.
+        assert_eq!(schema.project(&[]).unwrap(), Schema::new(&[]));
+    }
+
+    #[test]
+    fn test_project_out_of_bounds() {
+        let schema = Schema::new(&create_n_columns(3));
+        assert_eq!(schema.project(&[0, 3]).err(), Some(Error::OutOfBounds));
+    }
+
+    #[test]
+    fn test_builder() {
+        let schema = Schema::builder()
+            .column("tenant", Type::Integer)
+            .nullable_column("name", Type::Varchar)
+            .column("id", Type::Integer)
+            .primary_key(&["id", "tenant"])
+            .build()
+            .unwrap();
+
+        // Columns keep the order they were added in, and key columns are resolved to indexes.
+        let names = schema
+            .columns()
+            .iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["tenant", "name", "id"]);
+        assert_eq!(schema.primary_key(), &[2, 0]);
+        let nullable = schema
+            .columns()
+            .iter()
+            .map(|c| c.is_nullable())
+            .collect::<Vec<_>>();
+        assert_eq!(nullable, vec![false, true, false]);
+        assert_eq!(
+            schema.size(),
+            Schema::new(&[
+                Column::new("tenant".to_string(), Type::Integer),
+                Column::new("name".to_string(), Type::Varchar),
+                Column::new("id".to_string(), Type::Integer),
+            ])
+            .size()
+        );
+
+        // Unknown key columns and duplicate column names are rejected.
+        assert!(matches!(
+            Schema::builder()
+                .column("id", Type::Integer)
+                .primary_key(&["missing"])
+                .build(),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Schema::builder()
+                .column("id", Type::Integer)
+                .nullable_column("id", Type::Varchar)
+                .build(),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare_fields() {
+        let schema = Schema::new(&[
+            Column::new("binary".to_string(), Type::Varchar),
+            Column::new("ci".to_string(), Type::Varchar)
+                .with_collation(Collation::CaseInsensitive)
+                .unwrap(),
+            Column::new("id".to_string(), Type::Integer),
+        ]);
+        let (upper, lower): (Field, Field) = ("Apple".into(), "apple".into());
+
+        // Case only matters under the binary collation.
+        assert_eq!(schema.compare_fields(0, &upper, &lower), Ordering::Less);
+        assert_eq!(schema.compare_fields(1, &upper, &lower), Ordering::Equal);
+        assert_eq!(
+            schema.compare_fields(1, &"BANANA".into(), &lower),
+            Ordering::Greater
+        );
+        assert_eq!(
+            schema.compare_fields(1, &Field::Null, &lower),
+            Ordering::Less
+        );
+
+        // Other columns compare as usual.
+        assert_eq!(
+            schema.compare_fields(2, &Field::Integer(2), &Field::Integer(10)),
+            Ordering::Less
+        );
+
+        // Only varchar columns have a collation.
+        assert!(matches!(
+            Column::new("id".to_string(), Type::Integer).with_collation(Collation::Binary),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_extract_key() {
+        let columns = vec![
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("version".to_string(), Type::Integer),
+        ];
+        let schema = Schema::with_primary_key(&columns, &[0, 2]).unwrap();
+        assert_eq!(schema.primary_key(), &[0, 2]);
+
+        let row = vec![Field::Integer(7), "seven".into(), Field::Integer(3)];
+        assert_eq!(
+            schema.extract_key(&row),
+            Ok(vec![Field::Integer(7), Field::Integer(3)])
+        );
+
+        // The row must match the schema's arity.
+        assert!(matches!(
+            schema.extract_key(&row[..2]),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // A schema without a primary key has no key to extract.
+        assert!(matches!(
+            Schema::new(&columns).extract_key(&row),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // Primary key columns must exist.
+        assert_eq!(
+            Schema::with_primary_key(&columns, &[3]).err(),
+            Some(Error::OutOfBounds)
+        );
+    }
+
     fn create_n_columns(n: usize) -> Vec<Column> {
         (0..n)
             .map(|i| Column::new(i.to_string(), Type::Null))
             .collect::<Vec<_>>()
     }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let columns = [
+            Column::new("null".to_string(), Type::Null),
+            Column::new("flag".to_string(), Type::Boolean),
+            Column::new("count".to_string(), Type::Integer),
+            Column::new("ratio".to_string(), Type::Float),
+            Column::new("名前 🦀".to_string(), Type::Varchar),
+            Column::new("code".to_string(), Type::Char(300)),
+        ];
+        let schema = Schema::with_primary_key(&columns, &[2, 4]).unwrap();
+        assert_eq!(Schema::from_bytes(&schema.to_bytes()).unwrap(), schema);
+
+        let schema = Schema::new(&columns);
+        assert_eq!(Schema::from_bytes(&schema.to_bytes()).unwrap(), schema);
+        assert_eq!(
+            Schema::from_bytes(&Schema::new(&[]).to_bytes()).unwrap(),
+            Schema::new(&[])
+        );
+
+        // Every column attribute survives the round trip.
+        let columns = [
+            Column::new_with("id".to_string(), Type::Integer, false, None).unwrap(),
+            Column::new_with(
+                "name".to_string(),
+                Type::Varchar,
+                true,
+                Some(Field::Varchar("anon".to_string())),
+            )
+            .unwrap()
+            .with_max_len(20)
+            .unwrap()
+            .with_collation(Collation::CaseInsensitive)
+            .unwrap()
+            .with_table_qualifier("users".to_string()),
+            Column::new_with("note".to_string(), Type::Varchar, true, Some(Field::Null)).unwrap(),
+            Column::new_with(
+                "code".to_string(),
+                Type::Char(4),
+                false,
+                Some(Field::Char("ab".to_string())),
+            )
+            .unwrap(),
+            Column::new_with(
+                "ratio".to_string(),
+                Type::Float,
+                false,
+                Some(Field::Float(0.5)),
+            )
+            .unwrap(),
+        ];
+        let schema = Schema::with_primary_key(&columns, &[0]).unwrap();
+        let restored = Schema::from_bytes(&schema.to_bytes()).unwrap();
+        assert_eq!(restored, schema);
+        let name = restored.column_at(1).unwrap();
+        assert!(!restored.column_at(0).unwrap().is_nullable());
+        assert_eq!(name.max_len(), Some(20));
+        assert_eq!(name.collation(), Collation::CaseInsensitive);
+        assert_eq!(name.qualified_name(), "users.name");
+        assert_eq!(restored.column_at(2).unwrap().default(), Some(&Field::Null));
+    }
+
+    #[test]
+    fn test_from_bytes_errors() {
+        let schema = Schema::with_primary_key(&create_n_columns(3), &[0]).unwrap();
+        let bytes = schema.to_bytes();
+
+        // Truncated at every possible length.
+        for len in 0..bytes.len() {
+            assert!(matches!(
+                Schema::from_bytes(&bytes[..len]),
+                Err(Error::InvalidData(_))
+            ));
+        }
+
+        // Trailing data.
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Schema::from_bytes(&trailing),
+            Err(Error::InvalidData(_))
+        ));
+
+        // Unknown type tag: the first column's tag follows its count, name length and name.
+        let mut unknown_type = bytes.clone();
+        let tag_position = 4 + 4 + schema.column_at(0).unwrap().name().len();
+        unknown_type[tag_position] = 42;
+        assert!(matches!(
+            Schema::from_bytes(&unknown_type),
+            Err(Error::InvalidData(_))
+        ));
+
+        // Attributes that don't fit the column, like a max length for an integer column.
+        let mut bad_max_len = Vec::new();
+        put_u32(&mut bad_max_len, 1);
+        put_str(&mut bad_max_len, "id");
+        bad_max_len.extend_from_slice(&[2, 1, 0, 1, 10, 0, 0, 0]);
+        put_u32(&mut bad_max_len, 0);
+        assert!(matches!(
+            Schema::from_bytes(&bad_max_len),
+            Err(Error::InvalidData(_))
+        ));
+
+        // Primary key index past the last column.
+        let mut bad_key = bytes.clone();
+        let key_position = bad_key.len() - 4;
+        bad_key[key_position] = 3;
+        assert!(matches!(
+            Schema::from_bytes(&bad_key),
+            Err(Error::InvalidData(_))
+        ));
+    }
 }
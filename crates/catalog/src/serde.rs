@@ -1,59 +1,182 @@
-use crate::field::Field;
+use crate::field::{Field, FieldRef};
+use crate::json;
 use crate::schema::Schema;
 use crate::types::Type;
+use rustdb_error::{errdata, errinput, Error, Result};
+use std::hash::{DefaultHasher, Hash, Hasher};
 
 /// A utility struct that provides a mapping between serialized tuple data (e.g. &[u8]) and its
 /// deserialized, semantically meaningful counterpart: `Vec<Field>`. Deserialization requires a
 /// schema, which itself is an instruction set for how to interpret the bytes of a given payload.
 ///
 /// A tuple, when represented as a list of fields, is serialized into `data: Vec<u8>` as follows:
-///     ----------------------------------------------------------------------
-///     | FIXED-SIZE or VARIED-SIZED OFFSET | PAYLOAD OF VARIED-SIZED FIELDS |
-///     ----------------------------------------------------------------------
-/// where all the fixed size fields are serialized and placed in the front, and any variable-length
-/// field is placed _after_ the fixed sized field section, with the offset to that location in the
-/// payload stored (as a serialized integer) with the fixed-size fields in order.
+/// ```text
+///     ----------------------------------------------------------------------------------
+///     | NULL BITMAP | FIXED-SIZE or VARIED-SIZED OFFSET | PAYLOAD OF VARIED-SIZED FIELDS |
+///     ----------------------------------------------------------------------------------
+/// ```
+/// where the null bitmap has one bit per field, rounded up to whole bytes, with bit `i % 8` of
+/// byte `i / 8` set if field `i` is NULL. NULL fields take up no other space. Then all the fixed
+/// size fields are serialized and placed in the front, and any variable-length field is placed
+/// _after_ the fixed sized field section, with the offset to that location in the payload stored
+/// (as a serialized `usize`) with the fixed-size fields in order.
 ///
 /// For example, a tuple [1, "hello", 3] with schema (INTEGER, VARCHAR, INTEGER) would be
 /// serialized as follows:
 ///
+/// ```text
 ///     1_i32   -> [1, 0, 0, 0] (in little endian)
 ///     "hello" -> [104, 101, 108, 108, 111]
 ///     3_i32   -> [3, 0, 0, 0] (in little endian)
 ///
-/// ==> [1, 0, 0, 0, 12, 0, 0, 0, 3, 0, 0, 0, 104, 101, 108, 108, 111]
-///          ^            ^           ^                  ^
-///          |            |           |                  |
-///        1_i32   the offset of     3_i32             "hello"
-///                "hello" (12)
+/// ==> [0, 1, 0, 0, 0, 17, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 104, 101, 108, 108, 111]
+///      ^     ^              ^                       ^                  ^
+///      |     |              |                       |                  |
+///   no NULLs 1_i32    the offset of "hello" (17)   3_i32             "hello"
+/// ```
+///
+/// while [NULL, "hello", 3] would be `[1, 13, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 104, ...]`.
+///
+/// Char fields are fixed-size, padded to the width of their column. Since only the schema knows
+/// that width, they're only padded by [`Serde::serialize_with_schema`].
 pub struct Serde {}
 impl Serde {
     pub fn serialize(row: &[Field]) -> Vec<u8> {
-        let fixed_payload_size = row
+        Self::serialize_fields(row.iter().map(|field| (field, field.to_bytes())).collect())
+    }
+
+    /// Serializes `row` as a tuple of the given schema, padding char fields to the width of their
+    /// columns.
+    ///
+    /// Returns [`Error::InvalidInput`] if a char field is wider than its column or a varchar field
+    /// is longer than its column's max length, and [`Error::InvalidData`] if there isn't exactly
+    /// one field per column.
+    pub fn serialize_with_schema(row: &[Field], schema: &Schema) -> Result<Vec<u8>> {
+        if row.len() != schema.num_columns() {
+            return errdata!(
+                "Expected {} fields, got {}",
+                schema.num_columns(),
+                row.len()
+            );
+        }
+        let fields = row
+            .iter()
+            .zip(schema.columns())
+            .map(|(field, column)| {
+                column.check_len(field)?;
+                Ok((field, field.to_bytes_as(column.field_type())?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::serialize_fields(fields))
+    }
+
+    /// Serializes `row` as a constant-width record of the given schema, exactly
+    /// [`Schema::size`] bytes long: each field at a fixed offset, with char fields padded to the
+    /// width of their columns and no varchar offsets. Useful for array-style heap files.
+    ///
+    /// Returns [`Error::InvalidInput`] if the schema has a varchar column, a char field is wider
+    /// than its column, or a column that isn't of type NULL is NULL, since fixed-width records
+    /// have no null bitmap, and the errors of [`Schema::check_fields`] if `row` doesn't fit the
+    /// schema.
+    pub fn serialize_fixed(row: &[Field], schema: &Schema) -> Result<Vec<u8>> {
+        Self::check_fixed(schema)?;
+        schema.check_fields(row)?;
+        // Fixed-width records have no null bitmap, so only NULL columns can hold NULL.
+        if let Some(column) = row
+            .iter()
+            .zip(schema.columns())
+            .find(|(field, column)| **field == Field::Null && column.field_type() != Type::Null)
+            .map(|(_, column)| column)
+        {
+            return errinput!(
+                "Fixed-width records can't hold NULL in column {}",
+                column.name()
+            );
+        }
+        let mut bytes = Vec::with_capacity(schema.size());
+        for (field, column) in row.iter().zip(schema.columns()) {
+            bytes.extend(field.to_bytes_as(column.field_type())?);
+        }
+        Ok(bytes)
+    }
+
+    /// Deserializes a record written by [`Serde::serialize_fixed`], slicing each field at its
+    /// fixed offset.
+    ///
+    /// Returns [`Error::InvalidInput`] if the schema has a varchar column, and
+    /// [`Error::InvalidData`] if `bytes` isn't [`Schema::size`] bytes long or holds an invalid
+    /// field.
+    pub fn deserialize_fixed(bytes: &[u8], schema: &Schema) -> Result<Vec<Field>> {
+        Self::check_fixed(schema)?;
+        if bytes.len() != schema.size() {
+            return errdata!(
+                "Expected a {}-byte fixed-width record, got {} bytes",
+                schema.size(),
+                bytes.len()
+            );
+        }
+        let mut position = 0;
+        schema
+            .columns()
+            .iter()
+            .map(|column| {
+                let ty = column.field_type();
+                position += ty.size();
+                Field::try_from_bytes(&bytes[position - ty.size()..position], ty)
+            })
+            .collect()
+    }
+
+    /// Checks that every column of `schema` is fixed-size, as fixed-width records require.
+    fn check_fixed(schema: &Schema) -> Result<()> {
+        match schema
+            .columns()
             .iter()
-            .map(|field| field.get_type().size())
-            .sum::<usize>();
+            .find(|column| column.field_type() == Type::Varchar)
+        {
+            Some(column) => errinput!(
+                "Fixed-width records can't hold varchar column {}",
+                column.name()
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Lays out the serialized `fields` of a row as described above.
+    fn serialize_fields(fields: Vec<(&Field, Vec<u8>)>) -> Vec<u8> {
+        let bitmap_size = Self::null_bitmap_size(fields.len());
+        let fixed_payload_size = bitmap_size
+            + fields
+                .iter()
+                .map(|(field, serialized_field)| match field {
+                    Field::Null => 0,
+                    Field::Varchar(_) => size_of::<usize>(),
+                    _ => serialized_field.len(),
+                })
+                .sum::<usize>();
 
         let bytes = {
             let mut bytes = Vec::with_capacity(fixed_payload_size);
+            bytes.resize(bitmap_size, 0);
             let mut var_len_offset = fixed_payload_size;
             let mut var_len_fields = Vec::new();
 
             // Build the fixed payload:
-            for field in row {
+            for (i, (field, mut serialized_field)) in fields.into_iter().enumerate() {
                 match field {
+                    // NULL fields are only flagged in the null bitmap.
+                    Field::Null => bytes[i / 8] |= 1 << (i % 8),
                     // For variable-length fields, add the offset to the payload now and the
                     // serialized field later.
                     Field::Varchar(_) => {
                         bytes.extend(var_len_offset.to_le_bytes());
 
-                        let serialized_field = field.to_bytes();
                         var_len_offset += serialized_field.len();
                         var_len_fields.extend(serialized_field);
                     }
                     // For fixed-size fields, just add its serialized form to the payload.
                     _ => {
-                        bytes.append(&mut field.to_bytes());
+                        bytes.append(&mut serialized_field);
                     }
                 }
             }
@@ -65,58 +188,457 @@ impl Serde {
         bytes
     }
 
+    /// Deserializes the serialized tuple `bytes` according to `schema`.
+    ///
+    /// Panics if `bytes` doesn't fit the schema; use [`Serde::deserialize_columns`] unless it's
+    /// known to.
     pub fn deserialize(bytes: &[u8], schema: &Schema) -> Vec<Field> {
+        Self::deserialize_borrowed(bytes, schema)
+            .unwrap()
+            .iter()
+            .map(FieldRef::to_owned)
+            .collect()
+    }
+
+    /// Deserializes only the columns at `indices` of the serialized tuple `bytes`, returning
+    /// the fields in the same order as `indices`. Columns that aren't requested are skipped over
+    /// rather than decoded.
+    ///
+    /// Returns [`Error::OutOfBounds`] if an index isn't a column of `schema`, and
+    /// [`Error::InvalidData`] if `bytes` is too short to hold a requested column.
+    pub fn deserialize_columns(
+        bytes: &[u8],
+        schema: &Schema,
+        indices: &[usize],
+    ) -> Result<Vec<Field>> {
+        indices
+            .iter()
+            .map(|&i| Self::deserialize_column(bytes, schema, i))
+            .collect()
+    }
+
+    /// Hashes the values of the columns at `group_columns` of the serialized tuple `bytes`, in
+    /// order, e.g. to bucket tuples by their group for a hash aggregation. Tuples whose group
+    /// columns hold equal fields (see [`Field`]'s `Hash` implementation) hash alike, whatever
+    /// their other columns hold. Only the group columns are deserialized.
+    ///
+    /// Returns the same errors as [`Serde::deserialize_columns`].
+    pub fn hash_key(bytes: &[u8], schema: &Schema, group_columns: &[usize]) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        for &i in group_columns {
+            Self::deserialize_column(bytes, schema, i)?.hash(&mut hasher);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Compares two serialized tuples of the same `schema`, e.g. the old and new versions of an
+    /// updated tuple, returning the index, old value and new value of every column that changed,
+    /// in column order. Columns are compared like [`Field`]'s `PartialEq`.
+    ///
+    /// Returns [`Error::InvalidData`] if either tuple doesn't fit the schema.
+    pub fn diff(old: &[u8], new: &[u8], schema: &Schema) -> Result<Vec<(usize, Field, Field)>> {
+        let old = Self::deserialize_borrowed(old, schema)?;
+        let new = Self::deserialize_borrowed(new, schema)?;
+        Ok(old
+            .iter()
+            .zip(&new)
+            .enumerate()
+            // Borrowed fields differ for NaN floats that are equal as fields, so check both.
+            .filter(|(_, (old, new))| old != new)
+            .map(|(i, (old, new))| (i, old.to_owned(), new.to_owned()))
+            .filter(|(_, old, new)| old != new)
+            .collect())
+    }
+
+    /// Deserializes the single column at `index` of the serialized tuple `bytes`.
+    pub(crate) fn deserialize_column(bytes: &[u8], schema: &Schema, index: usize) -> Result<Field> {
+        let field_type = schema.column_at(index)?.field_type();
+        let positions = Self::positions(bytes, schema)?;
+        let Some(position) = positions[index] else {
+            return Ok(Field::Null);
+        };
+
+        match field_type {
+            Type::Varchar => {
+                let start = Self::read_offset(bytes, position)?;
+                // A varchar's payload runs until the start of the next non-NULL varchar's
+                // payload, or to the end of the tuple if it is the last one.
+                let mut end = bytes.len();
+                let rest = schema.columns()[index + 1..]
+                    .iter()
+                    .zip(&positions[index + 1..]);
+                for (column, next_position) in rest {
+                    if let (Type::Varchar, Some(next_position)) =
+                        (column.field_type(), next_position)
+                    {
+                        end = Self::read_offset(bytes, *next_position)?;
+                        break;
+                    }
+                }
+                let data = bytes.get(start..end).ok_or_else(|| {
+                    Error::InvalidData(format!("Varchar column {index} out of range"))
+                })?;
+                Field::try_from_bytes(data, Type::Varchar)
+            }
+            ty => match bytes.get(position..position + ty.size()) {
+                Some(data) => Field::try_from_bytes(data, ty),
+                None => errdata!("Column {index} out of range"),
+            },
+        }
+    }
+
+    /// Deserializes the serialized tuple `bytes` like [`Serde::deserialize`], but without copying
+    /// string values: varchar and char fields borrow their values from `bytes`, and only
+    /// fixed-size values are copied out.
+    ///
+    /// Returns [`Error::InvalidData`] if `bytes` doesn't fit the schema.
+    pub fn deserialize_borrowed<'a>(bytes: &'a [u8], schema: &Schema) -> Result<Vec<FieldRef<'a>>> {
         let mut fields = Vec::with_capacity(schema.num_columns());
-        // List of (index, offset) pairs, where an index `i` is the i-th field of the row, and
-        // its corresponding offset is the serialized field's offset into the `bytes` payload.
-        let mut varchar_offsets: Vec<(usize, usize)> = Vec::new();
-        let mut i = 0;
+        // The (column index, payload offset) of each non-NULL varchar, in column order.
+        let mut varchar_offsets = Vec::new();
 
-        for column in schema.columns() {
-            match column.field_type() {
-                Type::Null => {
-                    fields.push(Field::Null);
+        let positions = Self::positions(bytes, schema)?;
+        for (index, (column, position)) in schema.columns().iter().zip(positions).enumerate() {
+            let ty = column.field_type();
+            match position {
+                None => fields.push(FieldRef::Null),
+                Some(position) if ty == Type::Varchar => {
+                    varchar_offsets.push((index, Self::read_offset(bytes, position)?));
+                    // Push a placeholder for now to maintain the ordering.
+                    fields.push(FieldRef::Null);
                 }
-                Type::Varchar => {
-                    let size = size_of::<usize>();
-                    let offset = usize::from_le_bytes(bytes[i..i + size].try_into().unwrap());
+                Some(position) => match bytes.get(position..position + ty.size()) {
+                    Some(data) => fields.push(FieldRef::try_from_bytes(data, ty)?),
+                    None => return errdata!("Column {index} out of range"),
+                },
+            }
+        }
 
-                    varchar_offsets.push((fields.len(), offset));
-                    // Push a dummy field into the fields vec for now to maintain the ordering.
-                    fields.push(Field::Varchar("".to_string()));
+        // A varchar's payload runs until the start of the next one's, or to the end of the tuple.
+        for (n, &(index, start)) in varchar_offsets.iter().enumerate() {
+            let end = varchar_offsets
+                .get(n + 1)
+                .map_or(bytes.len(), |&(_, next_start)| next_start);
+            match bytes.get(start..end) {
+                Some(data) => fields[index] = FieldRef::try_from_bytes(data, Type::Varchar)?,
+                None => return errdata!("Varchar column {index} out of range"),
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Returns the size of the null bitmap of a tuple with `num_fields` fields.
+    fn null_bitmap_size(num_fields: usize) -> usize {
+        num_fields.div_ceil(8)
+    }
 
-                    i += size;
+    /// Reads the null bitmap of the serialized tuple `bytes`, returning the position of each
+    /// column's fixed-size data (or varchar offset) in the payload, or `None` if it's NULL.
+    /// Columns of type NULL are always NULL.
+    ///
+    /// Returns [`Error::InvalidData`] if `bytes` is too short to hold the null bitmap.
+    fn positions(bytes: &[u8], schema: &Schema) -> Result<Vec<Option<usize>>> {
+        let Some(bitmap) = bytes.get(..Self::null_bitmap_size(schema.num_columns())) else {
+            return errdata!(
+                "Tuple of {} bytes is too short for its null bitmap",
+                bytes.len()
+            );
+        };
+        let mut position = bitmap.len();
+        Ok(schema
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let ty = column.field_type();
+                if ty == Type::Null || bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                    return None;
+                }
+                position += ty.size();
+                Some(position - ty.size())
+            })
+            .collect())
+    }
+
+    /// Reads a serialized varchar offset stored at `position` of the payload.
+    fn read_offset(bytes: &[u8], position: usize) -> Result<usize> {
+        let size = size_of::<usize>();
+        match bytes.get(position..position + size) {
+            Some(data) => Ok(usize::from_le_bytes(data.try_into()?)),
+            None => errdata!("Varchar offset at {position} out of range"),
+        }
+    }
+
+    /// Renders the serialized tuple `bytes` as a JSON object keyed by column name, for debugging
+    /// and tooling. NULL is rendered as `null`, and numbers, booleans and strings as their JSON
+    /// counterparts. JSON has no NaN or infinities, so such floats are rendered as the strings
+    /// `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    ///
+    /// Returns [`Error::InvalidData`] if `bytes` doesn't fit the schema.
+    pub fn to_json(bytes: &[u8], schema: &Schema) -> Result<String> {
+        let indices = (0..schema.num_columns()).collect::<Vec<_>>();
+        let fields = Self::deserialize_columns(bytes, schema, &indices)?;
+
+        let mut out = String::from("{");
+        for (i, (field, column)) in fields.iter().zip(schema.columns()).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            json::write_string(&mut out, column.name());
+            out.push(':');
+            match field {
+                Field::Null => out.push_str("null"),
+                Field::Boolean(value) => out.push_str(&value.to_string()),
+                Field::Integer(value) => out.push_str(&value.to_string()),
+                Field::Float(value) if value.is_nan() => out.push_str("\"NaN\""),
+                Field::Float(value) if value.is_infinite() && *value > 0.0 => {
+                    out.push_str("\"Infinity\"")
                 }
-                ty @ _ => {
-                    let size = ty.size();
-                    fields.push(Field::from_bytes(&bytes[i..i + size], ty));
-                    i += size;
+                Field::Float(value) if value.is_infinite() => out.push_str("\"-Infinity\""),
+                // The debug form always has a decimal point or exponent, and is valid JSON.
+                Field::Float(value) => out.push_str(&format!("{value:?}")),
+                Field::Varchar(string) | Field::Char(string) => {
+                    json::write_string(&mut out, string)
                 }
             }
         }
+        out.push('}');
+        Ok(out)
+    }
+
+    /// Renders the serialized tuple `bytes` for debugging, one `name = value` line per column,
+    /// with the names padded to line up the values. Values are rendered by [`Field`]'s `Display`,
+    /// so NULL is `NULL`.
+    ///
+    /// Returns [`Error::InvalidData`] if `bytes` doesn't fit the schema.
+    pub fn format_row(bytes: &[u8], schema: &Schema) -> Result<String> {
+        let indices = (0..schema.num_columns()).collect::<Vec<_>>();
+        let fields = Self::deserialize_columns(bytes, schema, &indices)?;
+        let width = schema
+            .columns()
+            .iter()
+            .map(|column| column.name().chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines = schema
+            .columns()
+            .iter()
+            .zip(&fields)
+            .map(|(column, field)| format!("{:<width$} = {field}", column.name()))
+            .collect::<Vec<_>>();
+        Ok(lines.join("\n"))
+    }
+
+    /// Renders the serialized tuples `rows` as an ASCII table for debugging, with a header row of
+    /// column names. Each column is as wide as its widest name or value, rendered like
+    /// [`Serde::format_row`].
+    ///
+    /// Returns [`Error::InvalidData`] if a tuple doesn't fit the schema.
+    pub fn format_table(rows: &[&[u8]], schema: &Schema) -> Result<String> {
+        let indices = (0..schema.num_columns()).collect::<Vec<_>>();
+        let header = schema
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect::<Vec<_>>();
+        let mut cells = vec![header];
+        for bytes in rows {
+            let fields = Self::deserialize_columns(bytes, schema, &indices)?;
+            cells.push(fields.iter().map(Field::to_string).collect());
+        }
+        let widths = indices
+            .iter()
+            .map(|&i| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+
+        let separator = widths.iter().fold(String::from("+"), |line, width| {
+            line + &"-".repeat(width + 2) + "+"
+        });
+        let mut out = separator.clone();
+        for (n, row) in cells.iter().enumerate() {
+            out.push_str("\n|");
+            for (cell, width) in row.iter().zip(&widths) {
+                out.push_str(&format!(" {cell:<width$} |"));
+            }
+            // The header is set apart from the rows.
+            if n == 0 {
+                out.push('\n');
+                out.push_str(&separator);
+            }
+        }
+        out.push('\n');
+        out.push_str(&separator);
+        Ok(out)
+    }
+
+    /// Serializes a tuple of the given schema from a JSON object, the inverse of
+    /// [`Serde::to_json`]. The object must have exactly one member per column, in any order, and
+    /// each value is converted by [`Field::from_json_value`].
+    ///
+    /// Returns [`Error::InvalidInput`] if `json` isn't such an object, if a value doesn't fit its
+    /// column's type, or if a NOT NULL column is null.
+    pub fn from_json(json: &str, schema: &Schema) -> Result<Vec<u8>> {
+        let mut members = json::parse_object(json)?;
+        if let Some((name, _)) = members
+            .iter()
+            .find(|(name, _)| schema.column_index_of(name).is_none())
+        {
+            return errinput!("Unknown column {name}");
+        }
+
+        let mut row = Vec::with_capacity(schema.num_columns());
+        for column in schema.columns() {
+            let name = column.name();
+            let Some(i) = members.iter().position(|(key, _)| key == name) else {
+                return errinput!("Missing column {name}");
+            };
+            let (_, value) = members.swap_remove(i);
+            match Field::from_json_value(&value, column.field_type()) {
+                Ok(field) => row.push(field),
+                Err(Error::InvalidInput(message)) => {
+                    return errinput!("{message} for column {name}")
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some((name, _)) = members.first() {
+            return errinput!("Duplicate column {name}");
+        }
+        schema.check_fields(&row)?;
+        Self::serialize_with_schema(&row, schema)
+    }
 
-        // Replace dummy varchar fields, if any exist, with their real values.
-        for (n, (i, offset)) in varchar_offsets.iter().enumerate() {
-            assert!(*i < fields.len());
-            if n == varchar_offsets.len() - 1 {
-                fields[*i] = Field::from_bytes(&bytes[*offset..], Type::Varchar);
+    /// Serializes a tuple of the given schema from a line of CSV, for bulk loading.
+    ///
+    /// Fields are separated by commas. A field wrapped in double quotes may contain commas, with
+    /// any double quote inside it written twice. Each field is parsed according to its column's
+    /// type, with booleans written as `true` or `false` in any case. An empty, unquoted field is
+    /// NULL, which only nullable columns accept.
+    ///
+    /// Returns [`Error::InvalidInput`], naming the offending column's index, if a field can't be
+    /// parsed as its column's type, and also if there isn't exactly one field per column.
+    pub fn from_csv_line(line: &str, schema: &Schema) -> Result<Vec<u8>> {
+        let values = Self::split_csv_line(line)?;
+        if values.len() != schema.num_columns() {
+            return errinput!(
+                "Expected {} fields, found {}",
+                schema.num_columns(),
+                values.len()
+            );
+        }
+
+        let mut row = Vec::with_capacity(values.len());
+        for (i, ((value, quoted), column)) in values.into_iter().zip(schema.columns()).enumerate() {
+            let field_type = column.field_type();
+            let field = if value.is_empty() && !quoted {
+                if !column.is_nullable() {
+                    return errinput!("Column {i} is NOT NULL, but its field is empty");
+                }
+                Field::Null
             } else {
-                let (_, next_offset) = varchar_offsets[n + 1];
-                fields[*i] = Field::from_bytes(&bytes[*offset..next_offset], Type::Varchar);
+                match field_type {
+                    Type::Boolean if value.eq_ignore_ascii_case("true") => Field::Boolean(true),
+                    Type::Boolean if value.eq_ignore_ascii_case("false") => Field::Boolean(false),
+                    Type::Integer => match value.parse() {
+                        Ok(value) => Field::Integer(value),
+                        Err(_) => return errinput!("Invalid integer {value:?} in column {i}"),
+                    },
+                    Type::Float => match value.parse() {
+                        Ok(value) => Field::Float(value),
+                        Err(_) => return errinput!("Invalid float {value:?} in column {i}"),
+                    },
+                    Type::Varchar => Field::Varchar(value),
+                    Type::Char(_) => Field::Char(value),
+                    Type::Null | Type::Boolean => {
+                        return errinput!("Invalid {field_type} {value:?} in column {i}")
+                    }
+                }
+            };
+            row.push(field);
+        }
+        Self::serialize_with_schema(&row, schema)
+    }
+
+    /// Splits a line of CSV into its fields, each paired with whether it was quoted.
+    fn split_csv_line(line: &str) -> Result<Vec<(String, bool)>> {
+        let mut fields = Vec::new();
+        let mut chars = line.chars().peekable();
+        loop {
+            let mut field = String::new();
+            let quoted = chars.next_if_eq(&'"').is_some();
+            if quoted {
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => return errinput!("Unterminated quote in column {}", fields.len()),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|&c| c != ',') {
+                    field.push(c);
+                }
+            }
+            fields.push((field, quoted));
+            match chars.next() {
+                Some(',') => {}
+                None => return Ok(fields),
+                Some(_) => {
+                    return errinput!("Expected a comma after quoted column {}", fields.len() - 1)
+                }
             }
         }
+    }
+}
 
-        fields
+/// Appends `value` to `bytes` as a little-endian `u32`.
+pub(crate) fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Appends `value` to `bytes`, prefixed by its length as a little-endian `u32`.
+pub(crate) fn put_str(bytes: &mut Vec<u8>, value: &str) {
+    put_u32(bytes, value.len() as u32);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+/// Removes and returns the first `n` bytes of `bytes`, or returns [`Error::InvalidData`] if there
+/// are fewer than `n`.
+pub(crate) fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if bytes.len() < n {
+        return errdata!("Expected {n} more bytes, found {}", bytes.len());
     }
+    let (front, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Ok(front)
+}
+
+/// Removes and returns a little-endian `u32` from the front of `bytes`.
+pub(crate) fn take_u32(bytes: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(take(bytes, 4)?.try_into()?))
+}
+
+/// Removes and returns a string written by [`put_str`] from the front of `bytes`.
+pub(crate) fn take_str(bytes: &mut &[u8]) -> Result<String> {
+    let len = take_u32(bytes)? as usize;
+    Ok(String::from_utf8(take(bytes, len)?.to_vec())?)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::column::Column;
-    use crate::field::Field;
+    use crate::field::{Field, FieldRef};
     use crate::schema::Schema;
     use crate::serde::Serde;
     use crate::types::Type;
+    use rustdb_error::{Error, Result};
 
     #[test]
     fn test_serde() {
@@ -140,6 +662,498 @@ mod tests {
         assert_eq!(tuple, deserialized_tuple);
     }
 
+    #[test]
+    fn test_null_bitmap() -> Result<()> {
+        let schema = Schema::new(&columns_from(vec![
+            Type::Varchar,
+            Type::Integer,
+            Type::Varchar,
+            Type::Char(3),
+            Type::Varchar,
+            Type::Boolean,
+            Type::Float,
+            Type::Integer,
+            Type::Varchar,
+        ]));
+        let tuple = vec![
+            Field::Varchar("first".to_string()),
+            Field::Null,
+            Field::Null,
+            Field::Char("ab".to_string()),
+            Field::Varchar("third".to_string()),
+            Field::Null,
+            Field::Float(0.5),
+            Field::Integer(9),
+            Field::Null,
+        ];
+        let bytes = Serde::serialize_with_schema(&tuple, &schema)?;
+        // Nine fields need two bytes of bitmap, with bits 1, 2, 5 and 8 set.
+        assert_eq!(&bytes[..2], &[0b0010_0110, 0b0000_0001]);
+
+        assert_eq!(Serde::deserialize(&bytes, &schema), tuple);
+        assert_eq!(
+            Serde::deserialize_columns(&bytes, &schema, &(0..9).collect::<Vec<_>>())?,
+            tuple
+        );
+        for (i, field) in tuple.iter().enumerate() {
+            assert_eq!(&Serde::deserialize_column(&bytes, &schema, i)?, field);
+        }
+
+        // A tuple too short to hold its bitmap is rejected.
+        assert!(matches!(
+            Serde::deserialize_borrowed(&bytes[..1], &schema),
+            Err(Error::InvalidData(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_width() -> Result<()> {
+        let schema = Schema::new(&columns_from(vec![
+            Type::Integer,
+            Type::Char(6),
+            Type::Boolean,
+            Type::Null,
+            Type::Float,
+        ]));
+        let rows = [
+            vec![
+                Field::Integer(-7),
+                Field::Char("ab".to_string()),
+                Field::Boolean(true),
+                Field::Null,
+                Field::Float(2.5),
+            ],
+            vec![
+                Field::Integer(i32::MAX),
+                Field::Char("abcdef".to_string()),
+                Field::Boolean(false),
+                Field::Null,
+                Field::Float(f64::NAN),
+            ],
+        ];
+        for row in &rows {
+            let bytes = Serde::serialize_fixed(row, &schema)?;
+            assert_eq!(bytes.len(), schema.size());
+            assert_eq!(&Serde::deserialize_fixed(&bytes, &schema)?, row);
+        }
+
+        // Records of the wrong length, and rows that don't fit the schema, are rejected.
+        let bytes = Serde::serialize_fixed(&rows[0], &schema)?;
+        assert!(matches!(
+            Serde::deserialize_fixed(&bytes[1..], &schema),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(Serde::serialize_fixed(&[Field::Integer(1)], &schema).is_err());
+
+        // Without a null bitmap, only NULL columns can hold NULL.
+        let mut row = rows[0].clone();
+        row[0] = Field::Null;
+        assert!(matches!(
+            Serde::serialize_fixed(&row, &schema),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // Varchars have no fixed width.
+        let schema = Schema::new(&columns_from(vec![Type::Integer, Type::Varchar]));
+        let row = [Field::Integer(1), "hello".into()];
+        assert!(matches!(
+            Serde::serialize_fixed(&row, &schema),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Serde::deserialize_fixed(&Serde::serialize(&row), &schema),
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_borrowed() -> Result<()> {
+        let schema = Schema::new(&columns_from(vec![
+            Type::Varchar,
+            Type::Integer,
+            Type::Char(5),
+            Type::Varchar,
+            Type::Boolean,
+        ]));
+        let tuple = vec![
+            Field::Varchar("hello".to_string()),
+            Field::Integer(7),
+            Field::Char("ab".to_string()),
+            Field::Varchar("🦀 world".to_string()),
+            Field::Boolean(true),
+        ];
+        let bytes = Serde::serialize_with_schema(&tuple, &schema)?;
+
+        let fields = Serde::deserialize_borrowed(&bytes, &schema)?;
+        assert_eq!(
+            fields.iter().map(FieldRef::to_owned).collect::<Vec<_>>(),
+            tuple
+        );
+
+        // Strings point into the serialized tuple rather than copies of it.
+        let range = bytes.as_ptr_range();
+        for field in &fields {
+            if let FieldRef::Varchar(string) | FieldRef::Char(string) = field {
+                assert!(range.contains(&string.as_ptr()));
+                assert!(string.as_bytes().as_ptr_range().end <= range.end);
+            }
+        }
+        assert_eq!(fields[3], FieldRef::Varchar("🦀 world"));
+
+        // Truncated tuples are rejected rather than read out of bounds.
+        assert!(matches!(
+            Serde::deserialize_borrowed(&bytes[..10], &schema),
+            Err(Error::InvalidData(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_columns() {
+        let schema = Schema::new(&columns_from(vec![
+            Type::Varchar,
+            Type::Integer,
+            Type::Varchar,
+            Type::Boolean,
+            Type::Varchar,
+            Type::Float,
+        ]));
+        let tuple = vec![
+            Field::Varchar("first".to_string()),
+            Field::Integer(339),
+            Field::Varchar("All love 🛸💕🕺".to_string()),
+            Field::Boolean(true),
+            Field::Varchar("".to_string()),
+            Field::Float(-1.5),
+        ];
+        let serialized_tuple = Serde::serialize(&tuple);
+        let deserialized_tuple = Serde::deserialize(&serialized_tuple, &schema);
+
+        for indices in [
+            vec![1, 3],
+            vec![2],
+            vec![4, 0],
+            vec![5, 2, 2],
+            vec![],
+            (0..6).collect(),
+        ] {
+            let expected = indices
+                .iter()
+                .map(|&i| deserialized_tuple[i].clone())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                Serde::deserialize_columns(&serialized_tuple, &schema, &indices),
+                Ok(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_deserialize_columns_errors() {
+        let schema = Schema::new(&columns_from(vec![Type::Integer, Type::Varchar]));
+        let serialized_tuple = Serde::serialize(&[Field::Integer(1), "hello".into()]);
+
+        // Requesting a column the schema doesn't have is out of bounds.
+        assert_eq!(
+            Serde::deserialize_columns(&serialized_tuple, &schema, &[2]),
+            Err(Error::OutOfBounds)
+        );
+
+        // A truncated payload is invalid data.
+        assert!(matches!(
+            Serde::deserialize_columns(&serialized_tuple[..2], &schema, &[0]),
+            Err(Error::InvalidData(_))
+        ));
+
+        // So is a varchar that isn't valid UTF-8.
+        let mut corrupted = serialized_tuple.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] = 0xff;
+        assert!(matches!(
+            Serde::deserialize_columns(&corrupted, &schema, &[1]),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_hash_key() -> Result<()> {
+        let schema = Schema::new(&columns_from(vec![
+            Type::Integer,
+            Type::Varchar,
+            Type::Float,
+            Type::Varchar,
+        ]));
+        let hash = |fields: Vec<Field>, group_columns: &[usize]| {
+            Serde::hash_key(&Serde::serialize(&fields), &schema, group_columns)
+        };
+
+        // Rows that only differ outside the group columns hash alike.
+        let a = vec![Field::Integer(1), "x".into(), Field::Float(0.0), "a".into()];
+        let b = vec![
+            Field::Integer(2),
+            "x".into(),
+            Field::Float(-0.0),
+            "b".into(),
+        ];
+        assert_eq!(hash(a.clone(), &[1, 2])?, hash(b.clone(), &[1, 2])?);
+        assert_ne!(hash(a.clone(), &[0, 1])?, hash(b.clone(), &[0, 1])?);
+
+        // Group columns are hashed in order.
+        let c = vec![Field::Integer(1), "x".into(), Field::Float(0.0), "x".into()];
+        assert_eq!(hash(c.clone(), &[1])?, hash(c.clone(), &[3])?);
+        let d = vec![Field::Integer(1), "x".into(), Field::Float(0.0), "y".into()];
+        assert_ne!(hash(d.clone(), &[1, 3])?, hash(d, &[3, 1])?);
+
+        assert_eq!(hash(a, &[4]), Err(Error::OutOfBounds));
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let schema = Schema::new(&columns_from(vec![
+            Type::Integer,
+            Type::Varchar,
+            Type::Float,
+            Type::Varchar,
+        ]));
+        let old = vec![
+            Field::Integer(1),
+            "x".into(),
+            Field::Float(f64::NAN),
+            "y".into(),
+        ];
+        let diff =
+            |new: &[Field]| Serde::diff(&Serde::serialize(&old), &Serde::serialize(new), &schema);
+
+        assert_eq!(diff(&old)?, vec![]);
+        let mut new = old.clone();
+        new[0] = Field::Integer(2);
+        assert_eq!(diff(&new)?, vec![(0, Field::Integer(1), Field::Integer(2))]);
+
+        // A varchar that changes length shifts the ones after it, which are still unchanged.
+        let mut new = old.clone();
+        new[1] = "a longer value".into();
+        assert_eq!(diff(&new)?, vec![(1, "x".into(), "a longer value".into())]);
+
+        let mut new = old.clone();
+        new[2] = Field::Float(1.0);
+        new[3] = "".into();
+        assert_eq!(
+            diff(&new)?,
+            vec![
+                (2, Field::Float(f64::NAN), Field::Float(1.0)),
+                (3, "y".into(), "".into())
+            ]
+        );
+
+        assert!(Serde::diff(&Serde::serialize(&old), &[0; 4], &schema).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json() -> Result<()> {
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("nothing".to_string(), Type::Null),
+            Column::new("flag".to_string(), Type::Boolean),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("score".to_string(), Type::Float),
+            Column::new("code".to_string(), Type::Char(4)),
+        ]);
+        let row = vec![
+            Field::Integer(-34),
+            Field::Null,
+            Field::Boolean(true),
+            Field::Varchar("say \"hi\"\n🛸".to_string()),
+            Field::Float(0.1),
+            Field::Char("ab".to_string()),
+        ];
+        let bytes = Serde::serialize_with_schema(&row, &schema)?;
+
+        let json = Serde::to_json(&bytes, &schema)?;
+        assert_eq!(
+            json,
+            r#"{"id":-34,"nothing":null,"flag":true,"name":"say \"hi\"\n🛸","score":0.1,"code":"ab"}"#
+        );
+        assert_eq!(Serde::from_json(&json, &schema)?, bytes);
+
+        // Members may come in any order, with whitespace between tokens.
+        let reordered = r#" { "code": "ab", "score": 1e-1, "name": "say \"hi\"\n\ud83d\udef8",
+            "flag": true, "nothing": null, "id": -34 } "#;
+        assert_eq!(Serde::from_json(reordered, &schema)?, bytes);
+
+        // Values are converted like `Field::from_json_value`, so integral floats fit integers.
+        let integral = r#"{"id":-34.0,"nothing":null,"flag":true,"name":"say \"hi\"\n🛸","score":0.1,"code":"ab"}"#;
+        assert_eq!(Serde::from_json(integral, &schema)?, bytes);
+
+        // NULLs round-trip through nullable columns of any type.
+        let nulls = vec![Field::Null; 6];
+        let bytes = Serde::serialize_with_schema(&nulls, &schema)?;
+        let json = Serde::to_json(&bytes, &schema)?;
+        assert_eq!(
+            json,
+            r#"{"id":null,"nothing":null,"flag":null,"name":null,"score":null,"code":null}"#
+        );
+        assert_eq!(Serde::from_json(&json, &schema)?, bytes);
+        let not_null = Schema::new(&[Column::new_with(
+            "id".to_string(),
+            Type::Integer,
+            false,
+            None,
+        )?]);
+        assert!(matches!(
+            Serde::from_json(r#"{"id":null}"#, &not_null),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // Values that don't fit their columns are rejected.
+        for json in [
+            r#"{"id":1.5,"nothing":null,"flag":true,"name":"","score":0,"code":""}"#,
+            r#"{"id":1,"nothing":1,"flag":true,"name":"","score":0,"code":""}"#,
+            r#"{"id":1,"nothing":null,"flag":"true","name":"","score":0,"code":""}"#,
+            r#"{"id":1,"nothing":null,"flag":true,"name":"","score":"1","code":""}"#,
+            r#"{"id":1,"nothing":null,"flag":true,"name":"","score":0,"code":"abcde"}"#,
+            r#"{"id":1,"nothing":null,"flag":true,"name":"","score":0}"#,
+            r#"{"id":1,"nothing":null,"flag":true,"name":"","score":0,"code":"","x":1}"#,
+            r#"{"id":1,"nothing":null,"flag":true,"name":"","score":0,"code":"","id":1}"#,
+        ] {
+            assert!(
+                matches!(Serde::from_json(json, &schema), Err(Error::InvalidInput(_))),
+                "{json}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_table() -> Result<()> {
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("nothing".to_string(), Type::Null),
+        ]);
+        let rows = [
+            Serde::serialize(&[Field::Integer(1), "alice".into(), Field::Null]),
+            Serde::serialize(&[Field::Integer(-20), "".into(), Field::Null]),
+        ];
+
+        assert_eq!(
+            Serde::format_row(&rows[0], &schema)?,
+            "id      = 1\nname    = alice\nnothing = NULL"
+        );
+
+        let rows = rows.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let table = Serde::format_table(&rows, &schema)?;
+        assert_eq!(
+            table,
+            [
+                "+-----+-------+---------+",
+                "| id  | name  | nothing |",
+                "+-----+-------+---------+",
+                "| 1   | alice | NULL    |",
+                "| -20 |       | NULL    |",
+                "+-----+-------+---------+",
+            ]
+            .join("\n")
+        );
+
+        // A table without rows still has its header.
+        let empty = Serde::format_table(&[], &schema)?;
+        assert!(["id", "name", "nothing"]
+            .iter()
+            .all(|name| empty.contains(name)));
+        assert!(Serde::format_table(&[&[0; 2]], &schema).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_non_finite_floats() -> Result<()> {
+        let schema = Schema::new(&columns_from(vec![Type::Float, Type::Float, Type::Float]));
+        let row = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY].map(Field::Float);
+        let bytes = Serde::serialize(&row);
+
+        let json = Serde::to_json(&bytes, &schema)?;
+        assert_eq!(json, r#"{"0":"NaN","1":"Infinity","2":"-Infinity"}"#);
+
+        // NaN doesn't equal itself, but its field does.
+        let fields = Serde::deserialize(&Serde::from_json(&json, &schema)?, &schema);
+        assert_eq!(fields, row);
+        assert!(matches!(fields[0], Field::Float(value) if value.is_nan()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_csv_line() -> Result<()> {
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("active".to_string(), Type::Boolean),
+            Column::new("score".to_string(), Type::Float),
+            Column::new("nothing".to_string(), Type::Null),
+            Column::new("note".to_string(), Type::Varchar),
+        ]);
+
+        // Quoted fields may hold commas and escaped quotes.
+        let bytes = Serde::from_csv_line(r#"7,"Doe, ""JD"" John",TRUE,-1.5,,"""#, &schema)?;
+        assert_eq!(
+            Serde::deserialize(&bytes, &schema),
+            vec![
+                Field::Integer(7),
+                Field::Varchar("Doe, \"JD\" John".to_string()),
+                Field::Boolean(true),
+                Field::Float(-1.5),
+                Field::Null,
+                Field::Varchar("".to_string()),
+            ]
+        );
+
+        // Parse failures name the offending column.
+        for (line, column) in [
+            ("12a,x,true,1,,y", 0),
+            ("1,x,yes,1,,y", 2),
+            ("1,x,true,1.5.,,y", 3),
+        ] {
+            match Serde::from_csv_line(line, &schema) {
+                Err(Error::InvalidInput(message)) => {
+                    assert!(message.contains(&format!("column {column}")), "{message}")
+                }
+                result => panic!("unexpected {result:?} for {line}"),
+            }
+        }
+
+        // Empty fields are NULL in nullable columns of any type.
+        let bytes = Serde::from_csv_line(",,,,,", &schema)?;
+        assert_eq!(Serde::deserialize(&bytes, &schema), vec![Field::Null; 6]);
+
+        // NULL only fits in nullable columns, there must be a field per column, and quotes must be
+        // closed.
+        let not_null = Schema::new(&[
+            Column::new_with("id".to_string(), Type::Integer, false, None)?,
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        assert!(matches!(
+            Serde::from_csv_line(",x", &not_null),
+            Err(Error::InvalidInput(_))
+        ));
+        for line in [
+            r#"1,x,true,1,"",y"#,
+            "1,x,true,1,",
+            r#"1,"x,true,1,,y"#,
+            r#"1,"x"y,true,1,,y"#,
+        ] {
+            assert!(
+                matches!(
+                    Serde::from_csv_line(line, &schema),
+                    Err(Error::InvalidInput(_))
+                ),
+                "{line}"
+            );
+        }
+        Ok(())
+    }
+
     fn columns_from(types: Vec<Type>) -> Vec<Column> {
         types
             .iter()
@@ -1,6 +1,12 @@
+use crate::field::{Field, FieldRef};
+use crate::schema::Schema;
+use crate::serde::Serde;
 use bytes::Bytes;
+use rustdb_error::Result;
 
-#[derive(Debug)]
+/// A serialized row. Cloning a tuple is cheap, since its data is reference-counted, and tuples
+/// compare and hash by their bytes, so that e.g. `DISTINCT` can key a hash set by them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Tuple {
     data: Bytes,
 }
@@ -10,13 +16,282 @@ impl Tuple {
         Self { data }
     }
 
+    /// Serializes `fields` into a tuple with the given schema.
+    ///
+    /// Returns the [`crate::schema::SchemaError`] from [`Schema::check_fields`], converted into
+    /// a [`rustdb_error::Error`], if the fields don't fit the schema, and
+    /// [`rustdb_error::Error::InvalidInput`] if a char field is wider than its column or a
+    /// varchar field is longer than its column's max length.
+    pub fn from_fields(fields: &[Field], schema: &Schema) -> Result<Self> {
+        schema.check_fields(fields)?;
+        Ok(Self::new(
+            Serde::serialize_with_schema(fields, schema)?.into(),
+        ))
+    }
+
+    /// Deserializes this tuple's fields according to the given schema.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidData`] if the tuple's data doesn't fit the schema.
+    pub fn to_fields(&self, schema: &Schema) -> Result<Vec<Field>> {
+        Ok(Serde::deserialize_borrowed(&self.data, schema)?
+            .iter()
+            .map(FieldRef::to_owned)
+            .collect())
+    }
+
+    /// Deserializes only the field at `index` according to the given schema, skipping over the
+    /// other columns rather than decoding them.
+    ///
+    /// Returns [`rustdb_error::Error::OutOfBounds`] if `index` isn't a column of the schema, and
+    /// [`rustdb_error::Error::InvalidData`] if the tuple's data is too short to hold the column.
+    pub fn field_at(&self, index: usize, schema: &Schema) -> Result<Field> {
+        Serde::deserialize_column(&self.data, schema, index)
+    }
+
+    /// Returns the tuple's data as a [`Bytes`] that shares it with the tuple, e.g. to build
+    /// another tuple from it. Prefer [`Tuple::as_slice`] to just read the data.
     pub fn data(&self) -> Bytes {
         // Note this does not copy all the tuple data over into a new container; rather, it returns
         // a reference-counted pointer to the existing container, incrementing the reference count.
         Bytes::clone(&self.data)
     }
 
+    /// Returns the tuple's data as a borrowed slice. Unlike [`Tuple::data`], this doesn't touch
+    /// the data's reference count, so it's the cheaper choice for reading, comparing or copying
+    /// the data, e.g. in a loop over many tuples.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
     pub fn tuple_size(&self) -> usize {
         self.data.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::column::Column;
+    use crate::field::Field;
+    use crate::schema::{Schema, SchemaError};
+    use crate::tuple::Tuple;
+    use crate::types::Type;
+    use rustdb_error::Error;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    fn mixed_schema() -> Schema {
+        Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("active".to_string(), Type::Boolean),
+            Column::new("score".to_string(), Type::Float),
+            Column::new("bio".to_string(), Type::Varchar),
+        ])
+    }
+
+    #[test]
+    fn test_fields_round_trip() {
+        let schema = mixed_schema();
+        let fields = vec![
+            Field::Integer(7),
+            Field::Varchar("Ada".to_string()),
+            Field::Boolean(true),
+            Field::Float(9.5),
+            Field::Varchar("Wrote the first program 🧮".to_string()),
+        ];
+
+        let tuple = Tuple::from_fields(&fields, &schema).unwrap();
+        assert_eq!(tuple.to_fields(&schema).unwrap(), fields);
+    }
+
+    #[test]
+    fn test_from_fields_errors() {
+        let schema = mixed_schema();
+
+        // Wrong type for the "active" column.
+        let mismatched = [
+            Field::Integer(7),
+            Field::Varchar("Ada".to_string()),
+            Field::Integer(1),
+            Field::Float(9.5),
+            Field::Varchar("".to_string()),
+        ];
+        assert!(matches!(
+            Tuple::from_fields(&mismatched, &schema),
+            Err(Error::InvalidData(_))
+        ));
+
+        // Too few fields.
+        assert!(matches!(
+            Tuple::from_fields(&[Field::Integer(7)], &schema),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_fields_schema_errors() {
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new_with("name".to_string(), Type::Varchar, false, None).unwrap(),
+        ]);
+
+        assert_eq!(
+            Tuple::from_fields(&[Field::Integer(1)], &schema),
+            Err(SchemaError::ColumnCountMismatch {
+                expected: 2,
+                got: 1
+            }
+            .into())
+        );
+        assert_eq!(
+            Tuple::from_fields(&[Field::Float(1.5), "Ada".into()], &schema),
+            Err(SchemaError::TypeMismatch {
+                index: 0,
+                expected: Type::Integer,
+                got: Type::Float
+            }
+            .into())
+        );
+        assert_eq!(
+            Tuple::from_fields(&[Field::Integer(1), Field::Null], &schema),
+            Err(SchemaError::NotNullViolation { index: 1 }.into())
+        );
+
+        // The typed errors are kept by Schema::check_fields.
+        assert!(matches!(
+            schema.check_fields(&[Field::Float(1.5), "Ada".into()]),
+            Err(SchemaError::TypeMismatch { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_null_fields() {
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("score".to_string(), Type::Float),
+            Column::new("nick".to_string(), Type::Varchar),
+        ]);
+
+        // Nullable columns of any type can hold NULL, which round-trips through the tuple.
+        let rows = [
+            vec![Field::Null, "Ada".into(), Field::Null, "ada".into()],
+            vec![
+                Field::Integer(1),
+                Field::Null,
+                Field::Float(0.5),
+                "bob".into(),
+            ],
+            vec![
+                Field::Integer(2),
+                "Cy".into(),
+                Field::Float(1.5),
+                Field::Null,
+            ],
+            vec![Field::Null, Field::Null, Field::Null, Field::Null],
+        ];
+        for row in &rows {
+            let tuple = Tuple::from_fields(row, &schema).unwrap();
+            assert_eq!(&tuple.to_fields(&schema).unwrap(), row);
+            for (i, field) in row.iter().enumerate() {
+                assert_eq!(&tuple.field_at(i, &schema).unwrap(), field);
+            }
+        }
+    }
+
+    #[test]
+    fn test_field_at() {
+        let schema = mixed_schema();
+        let fields = vec![
+            Field::Integer(-3),
+            Field::Varchar("".to_string()),
+            Field::Boolean(false),
+            Field::Float(0.25),
+            Field::Varchar("trailing".to_string()),
+        ];
+        let tuple = Tuple::from_fields(&fields, &schema).unwrap();
+
+        let all_fields = tuple.to_fields(&schema).unwrap();
+        for (i, field) in all_fields.iter().enumerate() {
+            assert_eq!(&tuple.field_at(i, &schema).unwrap(), field);
+        }
+        assert!(matches!(
+            tuple.field_at(fields.len(), &schema),
+            Err(Error::OutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn test_char_fields() {
+        let schema = Schema::new(&[
+            Column::new("code".to_string(), Type::Char(4)),
+            Column::new("id".to_string(), Type::Integer),
+        ]);
+
+        // Char columns are padded to their width, so tuples stay fixed-size: a byte of null
+        // bitmap and the fields.
+        for code in ["", "ab", "abcd"] {
+            let fields = vec![Field::Char(code.to_string()), Field::Integer(1)];
+            let tuple = Tuple::from_fields(&fields, &schema).unwrap();
+            assert_eq!(tuple.tuple_size(), 1 + 4 + Type::Integer.size());
+            assert_eq!(tuple.to_fields(&schema).unwrap(), fields);
+        }
+
+        // Values wider than their column are rejected.
+        assert!(matches!(
+            Tuple::from_fields(&[Field::Char("abcde".into()), Field::Integer(1)], &schema),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_varchar_max_len() {
+        let schema = Schema::new(&[Column::new("name".to_string(), Type::Varchar)
+            .with_max_len(5)
+            .unwrap()]);
+        let from_str = |s: &str| Tuple::from_fields(&[s.into()], &schema);
+
+        assert!(from_str("hello").is_ok());
+        assert!(matches!(from_str("hello!"), Err(Error::InvalidInput(_))));
+
+        // The limit is in bytes: "🦀" is one char but four bytes, and "héllo" is five chars but
+        // six bytes.
+        assert!(from_str("🦀").is_ok());
+        assert!(matches!(from_str("🦀🦀"), Err(Error::InvalidInput(_))));
+        assert!(matches!(from_str("héllo"), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_as_slice() {
+        let tuple = Tuple::new(vec![1, 2, 3].into());
+        assert_eq!(tuple.as_slice(), &tuple.data()[..]);
+        assert_eq!(tuple.as_slice(), &[1, 2, 3]);
+        // The slice borrows the tuple's data rather than copying it.
+        assert_eq!(tuple.as_slice().as_ptr(), tuple.data().as_ptr());
+        assert!(Tuple::new(vec![].into()).as_slice().is_empty());
+    }
+
+    #[test]
+    fn test_equality_and_hash() {
+        let hash = |tuple: &Tuple| {
+            let mut hasher = DefaultHasher::new();
+            tuple.hash(&mut hasher);
+            hasher.finish()
+        };
+        let tuple = Tuple::new(vec![1, 2, 3].into());
+
+        // A clone shares the original's data, so it's equal and hashes the same.
+        let clone = tuple.clone();
+        assert_eq!(clone, tuple);
+        assert_eq!(clone.data().as_ptr(), tuple.data().as_ptr());
+        assert_eq!(hash(&clone), hash(&tuple));
+
+        // Separately built tuples with the same bytes are equal too.
+        let same = Tuple::new(vec![1, 2, 3].into());
+        assert_eq!(same, tuple);
+        assert_eq!(hash(&same), hash(&tuple));
+
+        for other in [vec![1, 2, 4], vec![1, 2], vec![1, 2, 3, 0], vec![]] {
+            assert_ne!(Tuple::new(other.into()), tuple);
+        }
+    }
+}
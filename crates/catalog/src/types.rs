@@ -1,3 +1,6 @@
+use crate::serde::take;
+use rustdb_error::{errdata, errinput, Error, Result};
+
 /// An exhaustive enumeration of all the data types of a [`crate::catalog::field::Field`] object.
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub enum Type {
@@ -6,6 +9,9 @@ pub enum Type {
     Integer,
     Float,
     Varchar,
+    /// A fixed-width string of the given number of bytes. Shorter values are padded with trailing
+    /// spaces, so char columns keep tuples fixed-size.
+    Char(u16),
 }
 
 impl Type {
@@ -21,12 +27,141 @@ impl Type {
             Type::Float => 8,
             // Strings are variable-length, so inferring the size from this enum is impossible.
             Type::Varchar => size_of::<usize>(),
+            Type::Char(width) => *width as usize,
+        }
+    }
+
+    /// Returns the byte identifying this type in serialized schemas.
+    pub(crate) fn tag(&self) -> u8 {
+        match self {
+            Type::Null => 0,
+            Type::Boolean => 1,
+            Type::Integer => 2,
+            Type::Float => 3,
+            Type::Varchar => 4,
+            Type::Char(_) => 5,
+        }
+    }
+
+    /// Serializes this type as its tag (see [`Type::tag`]), followed by the width of a char type
+    /// as a little-endian `u16`.
+    pub(crate) fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.tag());
+        if let Type::Char(width) = self {
+            bytes.extend_from_slice(&width.to_le_bytes());
+        }
+    }
+
+    /// Removes and deserializes a type written by [`Type::write_to`] from the front of `bytes`.
+    /// Returns [`Error::InvalidData`] if the bytes are truncated or the tag is unknown.
+    pub(crate) fn read_from(bytes: &mut &[u8]) -> Result<Type> {
+        match take(bytes, 1)?[0] {
+            0 => Ok(Type::Null),
+            1 => Ok(Type::Boolean),
+            2 => Ok(Type::Integer),
+            3 => Ok(Type::Float),
+            4 => Ok(Type::Varchar),
+            5 => Ok(Type::Char(u16::from_le_bytes(take(bytes, 2)?.try_into()?))),
+            tag => errdata!("Unknown type tag {tag}"),
         }
     }
 }
 
 impl std::fmt::Display for Type {
+    /// Writes the canonical SQL keyword for this type.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", format!("{:?}", self))
+        let name = match self {
+            Type::Null => "NULL",
+            Type::Boolean => "BOOLEAN",
+            Type::Integer => "INTEGER",
+            Type::Float => "FLOAT",
+            Type::Varchar => "VARCHAR",
+            Type::Char(width) => return write!(f, "CHAR({width})"),
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for Type {
+    type Err = Error;
+
+    /// Parses a SQL type name, ignoring case. A char type is written `CHAR(n)`, and plain `CHAR`
+    /// is `CHAR(1)` as in SQL. Returns [`Error::InvalidInput`] if `s` isn't the name of a type.
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_uppercase();
+        match name.as_str() {
+            "NULL" => Ok(Type::Null),
+            "BOOL" | "BOOLEAN" => Ok(Type::Boolean),
+            "INT" | "INTEGER" => Ok(Type::Integer),
+            "FLOAT" | "DOUBLE" => Ok(Type::Float),
+            "VARCHAR" | "TEXT" => Ok(Type::Varchar),
+            "CHAR" | "CHARACTER" => Ok(Type::Char(1)),
+            _ => {
+                let width = ["CHARACTER(", "CHAR("]
+                    .iter()
+                    .find_map(|prefix| name.strip_prefix(prefix)?.strip_suffix(')'))
+                    .and_then(|width| width.trim().parse::<u16>().ok());
+                match width {
+                    Some(width) if width > 0 => Ok(Type::Char(width)),
+                    _ => errinput!("Unknown type {s}"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Type;
+    use rustdb_error::Error;
+
+    #[test]
+    fn test_parse_type() {
+        let cases = [
+            ("NULL", Type::Null),
+            ("bool", Type::Boolean),
+            ("Boolean", Type::Boolean),
+            ("INT", Type::Integer),
+            ("integer", Type::Integer),
+            ("FLOAT", Type::Float),
+            ("double", Type::Float),
+            ("VarChar", Type::Varchar),
+            ("TEXT", Type::Varchar),
+            ("char", Type::Char(1)),
+            ("CHAR(12)", Type::Char(12)),
+            ("Character( 3 )", Type::Char(3)),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(name.parse::<Type>().unwrap(), expected, "parsing {name}");
+        }
+
+        assert!(matches!(
+            "BLOB".parse::<Type>(),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!("".parse::<Type>(), Err(Error::InvalidInput(_))));
+        for name in ["CHAR(0)", "CHAR(-1)", "CHAR(70000)", "CHAR(", "CHAR()"] {
+            assert!(
+                matches!(name.parse::<Type>(), Err(Error::InvalidInput(_))),
+                "parsing {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for ty in [
+            Type::Null,
+            Type::Boolean,
+            Type::Integer,
+            Type::Float,
+            Type::Varchar,
+            Type::Char(5),
+        ] {
+            assert_eq!(ty.to_string().parse::<Type>().unwrap(), ty);
+        }
+        assert_eq!(Type::Integer.to_string(), "INTEGER");
+        assert_eq!(Type::Char(5).to_string(), "CHAR(5)");
+        assert_eq!(Type::Char(5).size(), 5);
     }
 }
@@ -21,6 +21,8 @@ pub enum Error {
     BufferPoolError(String),
     /// The page cannot be deleted because it is still pinned.
     PagePinned(u32),
+    /// The buffer pool has no free frame and every frame is pinned, so no page can be brought in.
+    NoEvictableFrame,
 }
 
 impl std::error::Error for Error {}
@@ -36,6 +38,7 @@ impl std::fmt::Display for Error {
             Error::PagePinned(page_id) => {
                 write!(f, "Cannot delete page {}: Page is still pinned", page_id)
             }
+            Error::NoEvictableFrame => write!(f, "No evictable frame in buffer pool"),
         }
     }
 }
@@ -0,0 +1,588 @@
+use std::mem;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+use bytemuck::{Pod, Zeroable};
+use rustdb_catalog::{field::Field, types::Type};
+use rustdb_error::{errdata, errinput};
+
+use crate::{
+    buffer_pool::BufferPoolManager,
+    frame::PAGE_LSN_SIZE,
+    page::{INVALID_PAGE_ID, PAGE_SIZE},
+    record_id::RecordId,
+    typedef::PageId,
+    Result,
+};
+
+/// The header at the start of every B+-tree node page.
+#[repr(C)]
+#[derive(Pod, Zeroable, Copy, Clone)]
+struct NodeHeader {
+    /// The page's LSN, which every page keeps in its first bytes (see
+    /// [`crate::frame::PageFrame::page_lsn`]).
+    page_lsn: [u8; PAGE_LSN_SIZE],
+    /// [`LEAF`] or [`INTERNAL`].
+    kind: u8,
+    _padding: [u8; 1],
+    /// The number of keys in the node.
+    key_cnt: u16,
+    /// The next leaf in key order, or [`INVALID_PAGE_ID`] for the last leaf and internal nodes.
+    next_page_id: PageId,
+}
+
+const NODE_HEADER_SIZE: usize = mem::size_of::<NodeHeader>();
+const LEAF: u8 = 1;
+const INTERNAL: u8 = 2;
+/// The bytes each entry takes besides its key: the key's length, and a record id or child page id.
+const ENTRY_OVERHEAD: usize = mem::size_of::<u16>() + mem::size_of::<u64>();
+
+/// A B+-tree node, decoded from its page.
+///
+/// After the header, a leaf page holds its entries in key order, each a key prefixed by its
+/// length as a `u16`, followed by its record id as a `u64`. An internal page holds the page id of
+/// its first child, followed by each key (prefixed by its length) and the page id of the child
+/// after it. All integers are little-endian.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Leaf {
+        keys: Vec<Field>,
+        rids: Vec<RecordId>,
+        next_page_id: PageId,
+    },
+    /// An internal node with `n` keys has `n + 1` children. Every key in the subtree of child `i`
+    /// is at least key `i - 1` and at most key `i`; keys equal to a separator may be on either
+    /// side of it, since duplicates are allowed.
+    Internal {
+        keys: Vec<Field>,
+        children: Vec<PageId>,
+    },
+}
+
+impl Node {
+    fn keys(&self) -> &[Field] {
+        match self {
+            Node::Leaf { keys, .. } | Node::Internal { keys, .. } => keys,
+        }
+    }
+
+    fn decode(data: &[u8], key_type: Type) -> Result<Self> {
+        let header: &NodeHeader = bytemuck::from_bytes(&data[..NODE_HEADER_SIZE]);
+        let mut bytes = &data[NODE_HEADER_SIZE..];
+        let key_cnt = header.key_cnt as usize;
+        match header.kind {
+            LEAF => {
+                let mut keys = Vec::with_capacity(key_cnt);
+                let mut rids = Vec::with_capacity(key_cnt);
+                for _ in 0..key_cnt {
+                    keys.push(take_key(&mut bytes, key_type)?);
+                    rids.push(RecordId::from(u64::from_le_bytes(
+                        take(&mut bytes, 8)?.try_into()?,
+                    )));
+                }
+                Ok(Node::Leaf {
+                    keys,
+                    rids,
+                    next_page_id: header.next_page_id,
+                })
+            }
+            INTERNAL => {
+                let mut keys = Vec::with_capacity(key_cnt);
+                let mut children = Vec::with_capacity(key_cnt + 1);
+                children.push(PageId::from_le_bytes(take(&mut bytes, 4)?.try_into()?));
+                for _ in 0..key_cnt {
+                    keys.push(take_key(&mut bytes, key_type)?);
+                    children.push(PageId::from_le_bytes(take(&mut bytes, 4)?.try_into()?));
+                }
+                Ok(Node::Internal { keys, children })
+            }
+            kind => errdata!("Invalid B+-tree node kind {kind}"),
+        }
+    }
+
+    /// Writes this node over `data`, keeping the page LSN. The node must fit in the page.
+    fn encode(&self, data: &mut [u8]) {
+        let (kind, next_page_id) = match self {
+            Node::Leaf { next_page_id, .. } => (LEAF, *next_page_id),
+            Node::Internal { .. } => (INTERNAL, INVALID_PAGE_ID),
+        };
+        let header: &mut NodeHeader = bytemuck::from_bytes_mut(&mut data[..NODE_HEADER_SIZE]);
+        header.kind = kind;
+        header.key_cnt = self.keys().len() as u16;
+        header.next_page_id = next_page_id;
+
+        let mut bytes = Vec::new();
+        match self {
+            Node::Leaf { keys, rids, .. } => {
+                for (key, rid) in keys.iter().zip(rids) {
+                    put_key(&mut bytes, key);
+                    bytes.extend(u64::from(rid.clone()).to_le_bytes());
+                }
+            }
+            Node::Internal { keys, children } => {
+                bytes.extend(children[0].to_le_bytes());
+                for (key, child) in keys.iter().zip(&children[1..]) {
+                    put_key(&mut bytes, key);
+                    bytes.extend(child.to_le_bytes());
+                }
+            }
+        }
+        data[NODE_HEADER_SIZE..NODE_HEADER_SIZE + bytes.len()].copy_from_slice(&bytes);
+    }
+}
+
+fn put_key(bytes: &mut Vec<u8>, key: &Field) {
+    let key = key.to_bytes();
+    bytes.extend((key.len() as u16).to_le_bytes());
+    bytes.extend(key);
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if bytes.len() < n {
+        return errdata!("B+-tree node is truncated");
+    }
+    let (front, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Ok(front)
+}
+
+fn take_key(bytes: &mut &[u8], key_type: Type) -> Result<Field> {
+    let len = u16::from_le_bytes(take(bytes, 2)?.try_into()?) as usize;
+    Field::try_from_bytes(take(bytes, len)?, key_type)
+}
+
+/// A B+-tree index over a single column, whose nodes are stored in buffer pool pages. It maps
+/// keys, ordered by [`Field`]'s `Ord`, to the record ids of the tuples holding them, and supports
+/// point lookups and range scans. Keys need not be unique.
+///
+/// Every node holds fewer than `order` keys, so an internal node has at most `order` children.
+/// Leaves are chained in key order through their next page ids, so a range scan only descends
+/// the tree once.
+pub(crate) struct BPlusTree {
+    bpm: Arc<RwLock<BufferPoolManager>>,
+    root_page_id: PageId,
+    key_type: Type,
+    order: usize,
+}
+
+impl BPlusTree {
+    /// Creates an empty B+-tree over keys of type `key_type`, allocating its root leaf.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if `order` is less than 3, or if `order`
+    /// keys of a fixed-size `key_type` don't fit in a page.
+    pub(crate) fn new(
+        bpm: Arc<RwLock<BufferPoolManager>>,
+        key_type: Type,
+        order: usize,
+    ) -> Result<Self> {
+        if order < 3 {
+            return errinput!("B+-tree order must be at least 3, got {order}");
+        }
+        let tree = Self {
+            bpm,
+            root_page_id: INVALID_PAGE_ID,
+            key_type,
+            order,
+        };
+        let max_key_size = tree.max_key_size();
+        if max_key_size == 0 || (key_type != Type::Varchar && key_type.size() > max_key_size) {
+            return errinput!("{order} keys of type {key_type} don't fit in a B+-tree page");
+        }
+
+        let root = Node::Leaf {
+            keys: Vec::new(),
+            rids: Vec::new(),
+            next_page_id: INVALID_PAGE_ID,
+        };
+        let root_page_id = tree.create_node(&root)?;
+        Ok(Self {
+            root_page_id,
+            ..tree
+        })
+    }
+
+    /// Returns the id of the root page, which changes whenever the root splits.
+    pub(crate) fn root_page_id(&self) -> PageId {
+        self.root_page_id
+    }
+
+    /// The size of the largest key that fits in a node, such that `order` entries always do.
+    fn max_key_size(&self) -> usize {
+        ((PAGE_SIZE - NODE_HEADER_SIZE - mem::size_of::<PageId>()) / self.order)
+            .saturating_sub(ENTRY_OVERHEAD)
+    }
+
+    /// Returns [`rustdb_error::Error::InvalidInput`] if `key` can't be inserted into the tree,
+    /// because it isn't of the tree's key type or is a varchar too long to fit in a node.
+    pub(crate) fn check_key(&self, key: &Field) -> Result<()> {
+        if !key.matches_type(self.key_type) || *key == Field::Null {
+            return errinput!("Key {key} doesn't have type {}", self.key_type);
+        }
+        if key.to_bytes().len() > self.max_key_size() {
+            return errinput!("Key {key} is longer than {} bytes", self.max_key_size());
+        }
+        Ok(())
+    }
+
+    /// Adds an entry mapping `key` to `rid`. Entries with equal keys are kept in insertion order.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if `key` isn't of the tree's key type, or is
+    /// a varchar too long to fit in a node.
+    pub(crate) fn insert(&mut self, key: &Field, rid: RecordId) -> Result<()> {
+        self.check_key(key)?;
+        if let Some((separator, right_page_id)) = self.insert_into(self.root_page_id, key, rid)? {
+            // The root split, so the tree grows a level.
+            let root = Node::Internal {
+                keys: vec![separator],
+                children: vec![self.root_page_id, right_page_id],
+            };
+            self.root_page_id = self.create_node(&root)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts the entry into the subtree rooted at `page_id`. If the subtree's root splits,
+    /// returns the key separating it from its new right sibling, and the sibling's page id.
+    fn insert_into(
+        &self,
+        page_id: PageId,
+        key: &Field,
+        rid: RecordId,
+    ) -> Result<Option<(Field, PageId)>> {
+        let mut node = self.read_node(page_id)?;
+        // Insert after any equal keys.
+        let i = node.keys().partition_point(|k| k <= key);
+        match &mut node {
+            Node::Leaf { keys, rids, .. } => {
+                keys.insert(i, key.clone());
+                rids.insert(i, rid);
+            }
+            Node::Internal { keys, children } => {
+                let Some((separator, right_page_id)) = self.insert_into(children[i], key, rid)?
+                else {
+                    return Ok(None);
+                };
+                keys.insert(i, separator);
+                children.insert(i + 1, right_page_id);
+            }
+        }
+
+        if node.keys().len() < self.order {
+            self.write_node(page_id, &node)?;
+            return Ok(None);
+        }
+        let (separator, right) = Self::split(&mut node);
+        let right_page_id = self.create_node(&right)?;
+        if let Node::Leaf { next_page_id, .. } = &mut node {
+            *next_page_id = right_page_id;
+        }
+        self.write_node(page_id, &node)?;
+        Ok(Some((separator, right_page_id)))
+    }
+
+    /// Moves the upper half of an overfull node into a new right sibling, returning the key that
+    /// separates them and the sibling. A leaf's separator is copied from the sibling's first key,
+    /// while an internal node's is moved up out of the node.
+    fn split(node: &mut Node) -> (Field, Node) {
+        match node {
+            Node::Leaf {
+                keys,
+                rids,
+                next_page_id,
+            } => {
+                let mid = keys.len() / 2;
+                let right = Node::Leaf {
+                    keys: keys.split_off(mid),
+                    rids: rids.split_off(mid),
+                    next_page_id: *next_page_id,
+                };
+                (right.keys()[0].clone(), right)
+            }
+            Node::Internal { keys, children } => {
+                let mid = keys.len() / 2;
+                let right_keys = keys.split_off(mid + 1);
+                let separator = keys.pop().unwrap();
+                let right = Node::Internal {
+                    keys: right_keys,
+                    children: children.split_off(mid + 1),
+                };
+                (separator, right)
+            }
+        }
+    }
+
+    /// Removes the entry mapping `key` to `rid`, returning whether there was one.
+    ///
+    /// The entry is simply taken out of its leaf, which is left as is even if it becomes underfull
+    /// or empty, so the tree never shrinks. Inserts refill such leaves, and scans skip over them.
+    pub(crate) fn remove(&mut self, key: &Field, rid: &RecordId) -> Result<bool> {
+        // Descend to the leftmost leaf that may hold `key`, as for a range scan.
+        let mut page_id = self.root_page_id;
+        let mut node = self.read_node(page_id)?;
+        while let Node::Internal { keys, children } = &node {
+            page_id = children[keys.partition_point(|k| k < key)];
+            node = self.read_node(page_id)?;
+        }
+
+        // Entries with equal keys may span several leaves.
+        loop {
+            let Node::Leaf {
+                keys,
+                rids,
+                next_page_id,
+            } = &mut node
+            else {
+                return errdata!("B+-tree leaves must only link to leaves");
+            };
+            let start = keys.partition_point(|k| k < key);
+            let end = keys.partition_point(|k| k <= key);
+            if let Some(i) = (start..end).find(|&i| rids[i] == *rid) {
+                keys.remove(i);
+                rids.remove(i);
+                self.write_node(page_id, &node)?;
+                return Ok(true);
+            }
+            if end < keys.len() || *next_page_id == INVALID_PAGE_ID {
+                return Ok(false);
+            }
+            page_id = *next_page_id;
+            node = self.read_node(page_id)?;
+        }
+    }
+
+    /// Returns the record ids of every entry with the given key, in insertion order.
+    pub(crate) fn lookup(&self, key: &Field) -> Result<Vec<RecordId>> {
+        self.range_scan(key, key)
+    }
+
+    /// Returns the record ids of every entry whose key is between `low` and `high`, inclusive,
+    /// sorted by key.
+    pub(crate) fn range_scan(&self, low: &Field, high: &Field) -> Result<Vec<RecordId>> {
+        self.range_scan_bounds(Bound::Included(low), Bound::Included(high))
+    }
+
+    /// Returns the record ids of every entry whose key is within the given bounds, sorted by key.
+    pub(crate) fn range_scan_bounds(
+        &self,
+        low: Bound<&Field>,
+        high: Bound<&Field>,
+    ) -> Result<Vec<RecordId>> {
+        // Descend to the leftmost leaf that may hold `low`, since equal keys may precede a
+        // separator equal to it.
+        let mut node = self.read_node(self.root_page_id)?;
+        while let Node::Internal { keys, children } = &node {
+            let i = match low {
+                Bound::Included(low) | Bound::Excluded(low) => keys.partition_point(|k| k < low),
+                Bound::Unbounded => 0,
+            };
+            node = self.read_node(children[i])?;
+        }
+
+        let mut result = Vec::new();
+        loop {
+            let Node::Leaf {
+                keys,
+                rids,
+                next_page_id,
+            } = node
+            else {
+                return errdata!("B+-tree leaves must only link to leaves");
+            };
+            for (key, rid) in keys.iter().zip(rids) {
+                let below_high = match high {
+                    Bound::Included(high) => key <= high,
+                    Bound::Excluded(high) => key < high,
+                    Bound::Unbounded => true,
+                };
+                if !below_high {
+                    return Ok(result);
+                }
+                let above_low = match low {
+                    Bound::Included(low) => key >= low,
+                    Bound::Excluded(low) => key > low,
+                    Bound::Unbounded => true,
+                };
+                if above_low {
+                    result.push(rid);
+                }
+            }
+            if next_page_id == INVALID_PAGE_ID {
+                return Ok(result);
+            }
+            node = self.read_node(next_page_id)?;
+        }
+    }
+
+    fn read_node(&self, page_id: PageId) -> Result<Node> {
+        let page_handle = BufferPoolManager::fetch_page_handle(&self.bpm, page_id)?;
+        Node::decode(page_handle.data(), self.key_type)
+    }
+
+    fn write_node(&self, page_id: PageId, node: &Node) -> Result<()> {
+        let mut page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, page_id)?;
+        node.encode(page_handle.data_mut());
+        Ok(())
+    }
+
+    /// Writes `node` to a newly allocated page, returning its id.
+    fn create_node(&self, node: &Node) -> Result<PageId> {
+        let mut page_handle = BufferPoolManager::create_page_handle(&self.bpm)?;
+        node.encode(page_handle.data_mut());
+        Ok(page_handle.page_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use rustdb_catalog::{field::Field, types::Type};
+    use rustdb_error::Error;
+
+    use super::{BPlusTree, Node};
+    use crate::{
+        buffer_pool::BufferPoolManager, disk::memory_disk_manager::MemoryDiskManager,
+        record_id::RecordId, replacer::lru_k_replacer::LrukReplacer, Result,
+    };
+
+    fn get_bpm(pool_size: usize) -> Arc<RwLock<BufferPoolManager>> {
+        let disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let replacer = Box::new(LrukReplacer::new(2));
+        Arc::new(RwLock::new(BufferPoolManager::new(
+            pool_size, disk, replacer,
+        )))
+    }
+
+    /// Returns the number of levels of the tree.
+    fn height(tree: &BPlusTree) -> Result<usize> {
+        let mut height = 1;
+        let mut node = tree.read_node(tree.root_page_id())?;
+        while let Node::Internal { children, .. } = node {
+            node = tree.read_node(children[0])?;
+            height += 1;
+        }
+        Ok(height)
+    }
+
+    fn rid(i: i32) -> RecordId {
+        RecordId::new(i as u32, i as u32 * 10)
+    }
+
+    #[test]
+    fn test_insert_and_lookup() -> Result<()> {
+        // A small pool makes nodes get evicted and read back from disk.
+        let mut tree = BPlusTree::new(get_bpm(4), Type::Integer, 3)?;
+        // Insert out of order, so that splits happen all over the tree.
+        let keys = (0..30).map(|i| (i * 7) % 30).collect::<Vec<_>>();
+        for &key in &keys {
+            tree.insert(&Field::Integer(key), rid(key))?;
+        }
+        // With at most two keys per node, 30 keys need leaf and internal splits.
+        assert!(height(&tree)? >= 3);
+
+        for key in 0..30 {
+            assert_eq!(tree.lookup(&Field::Integer(key))?, vec![rid(key)]);
+        }
+        assert!(tree.lookup(&Field::Integer(30))?.is_empty());
+        assert!(tree.lookup(&Field::Integer(-1))?.is_empty());
+
+        // Range scans are inclusive and sorted by key.
+        assert_eq!(
+            tree.range_scan(&Field::Integer(5), &Field::Integer(12))?,
+            (5..=12).map(rid).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            tree.range_scan(&Field::Integer(-10), &Field::Integer(100))?,
+            (0..30).map(rid).collect::<Vec<_>>()
+        );
+        assert!(tree
+            .range_scan(&Field::Integer(12), &Field::Integer(5))?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_keys() -> Result<()> {
+        let mut tree = BPlusTree::new(get_bpm(10), Type::Integer, 4)?;
+        // Enough duplicates to span several leaves.
+        for i in 0..20 {
+            tree.insert(&Field::Integer(i % 3), rid(i))?;
+        }
+        assert_eq!(
+            tree.lookup(&Field::Integer(1))?,
+            (0..20).filter(|i| i % 3 == 1).map(rid).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            tree.range_scan(&Field::Integer(1), &Field::Integer(2))?
+                .len(),
+            13
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove() -> Result<()> {
+        let mut tree = BPlusTree::new(get_bpm(4), Type::Integer, 3)?;
+        for i in 0..30 {
+            tree.insert(&Field::Integer(i % 10), rid(i))?;
+        }
+
+        // Only the entry with both the key and the record id is removed, even when entries with
+        // the key span several leaves.
+        assert!(tree.remove(&Field::Integer(4), &rid(14))?);
+        assert!(!tree.remove(&Field::Integer(4), &rid(14))?);
+        assert!(!tree.remove(&Field::Integer(5), &rid(14))?);
+        assert_eq!(tree.lookup(&Field::Integer(4))?, vec![rid(4), rid(24)]);
+
+        // Emptying leaves leaves the rest of the tree intact, and the keys can be inserted again.
+        for i in (0..30).filter(|i| i % 10 < 5 && *i != 14) {
+            assert!(tree.remove(&Field::Integer(i % 10), &rid(i))?);
+        }
+        assert!(tree
+            .range_scan(&Field::Integer(0), &Field::Integer(4))?
+            .is_empty());
+        assert_eq!(
+            tree.range_scan(&Field::Integer(5), &Field::Integer(5))?,
+            vec![rid(5), rid(15), rid(25)]
+        );
+        assert_eq!(
+            tree.range_scan(&Field::Integer(0), &Field::Integer(9))?
+                .len(),
+            15
+        );
+        tree.insert(&Field::Integer(2), rid(2))?;
+        assert_eq!(tree.lookup(&Field::Integer(2))?, vec![rid(2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_varchar_keys() -> Result<()> {
+        let mut tree = BPlusTree::new(get_bpm(10), Type::Varchar, 4)?;
+        let words = [
+            "pear", "apple", "fig", "kiwi", "banana", "cherry", "date", "grape",
+        ];
+        for (i, word) in words.iter().enumerate() {
+            tree.insert(&(*word).into(), rid(i as i32))?;
+        }
+        assert_eq!(tree.lookup(&"kiwi".into())?, vec![rid(3)]);
+        assert_eq!(
+            tree.range_scan(&"b".into(), &"date".into())?,
+            vec![rid(4), rid(5), rid(6)]
+        );
+
+        // Keys must have the tree's type and fit in a node.
+        assert!(matches!(
+            tree.insert(&Field::Integer(1), rid(0)),
+            Err(Error::InvalidInput(_))
+        ));
+        let long = "x".repeat(tree.max_key_size() + 1);
+        assert!(matches!(
+            tree.insert(&long.as_str().into(), rid(0)),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(BPlusTree::new(get_bpm(1), Type::Integer, 2).is_err());
+        assert!(BPlusTree::new(get_bpm(1), Type::Integer, 1000).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A counting Bloom filter over byte-string keys.
+///
+/// Each key sets `num_hashes` counters, chosen by double hashing. A key may be in the filter if
+/// all of its counters are non-zero, and is definitely not in the filter otherwise. Unlike a plain
+/// Bloom filter, keys can be removed by decrementing their counters. Counters saturate at
+/// `u8::MAX` and are never decremented after that, since their true count is unknown; this only
+/// costs accuracy, never a false negative.
+#[derive(Debug)]
+pub(crate) struct BloomFilter {
+    counters: Vec<u8>,
+    num_hashes: usize,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter with `num_counters` counters, setting `num_hashes` of them per key.
+    pub(crate) fn new(num_counters: usize, num_hashes: usize) -> Self {
+        assert!(num_counters > 0 && num_hashes > 0);
+        Self {
+            counters: vec![0; num_counters],
+            num_hashes,
+        }
+    }
+
+    /// Adds `key` to the filter.
+    pub(crate) fn insert(&mut self, key: &[u8]) {
+        for i in self.indexes(key) {
+            self.counters[i] = self.counters[i].saturating_add(1);
+        }
+    }
+
+    /// Removes one occurrence of `key` from the filter. `key` must have been inserted before.
+    pub(crate) fn remove(&mut self, key: &[u8]) {
+        for i in self.indexes(key) {
+            if self.counters[i] != u8::MAX {
+                self.counters[i] = self.counters[i].saturating_sub(1);
+            }
+        }
+    }
+
+    /// Returns `false` if `key` is definitely not in the filter, and `true` if it might be.
+    pub(crate) fn might_contain(&self, key: &[u8]) -> bool {
+        self.indexes(key).all(|i| self.counters[i] > 0)
+    }
+
+    /// Returns the indexes of the counters for `key`.
+    fn indexes(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let h1 = Self::hash(0, key);
+        // An odd step never cycles back early when the number of counters is a power of two.
+        let h2 = Self::hash(1, key) | 1;
+        let len = self.counters.len() as u64;
+        (0..self.num_hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+
+    fn hash(seed: u64, key: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut filter = BloomFilter::new(1024, 4);
+        assert!(!filter.might_contain(b"a"));
+
+        filter.insert(b"a");
+        filter.insert(b"a");
+        filter.insert(b"b");
+        assert!(filter.might_contain(b"a"));
+        assert!(filter.might_contain(b"b"));
+
+        // A key inserted twice stays until it is removed twice.
+        filter.remove(b"a");
+        assert!(filter.might_contain(b"a"));
+        filter.remove(b"a");
+        assert!(!filter.might_contain(b"a"));
+        assert!(filter.might_contain(b"b"));
+    }
+}
@@ -1,16 +1,69 @@
 use rustdb_error::Error;
 
-use crate::disk::disk_manager::DiskManager;
+use crate::disk::disk::Disk;
 use crate::frame::PageFrame;
 use crate::frame_handle::{PageFrameMutHandle, PageFrameRefHandle};
 use crate::typedef::{FrameId, PageId};
+use crate::wal::WalManager;
 use std::collections::{HashMap, VecDeque};
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LockResult, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use crate::Result;
 
 use crate::replacer::replacer::Replacer;
 
+/// The number of times a lock poisoned by a panicking thread has been recovered.
+static POISON_RECOVERIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Unwraps the result of acquiring a lock. If another thread panicked while holding the lock, it
+/// is recovered instead of propagating the panic: the recovery is counted (see
+/// [`poison_recoveries`]), and the caller gets the guard as the panicking thread left it.
+pub(crate) fn recover_poisoned<G>(result: LockResult<G>) -> G {
+    result.unwrap_or_else(|e| {
+        POISON_RECOVERIES.fetch_add(1, Ordering::Relaxed);
+        e.into_inner()
+    })
+}
+
+/// Returns the number of times a buffer pool or page lock has been recovered after a thread
+/// panicked while holding it. Any recovery means the system is running in a degraded state.
+pub(crate) fn poison_recoveries() -> usize {
+    POISON_RECOVERIES.load(Ordering::Relaxed)
+}
+
+/// The number of times a background flusher failed to write back the dirty pages.
+static BACKGROUND_FLUSH_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of times a thread started by
+/// [`BufferPoolManager::start_background_flusher`] failed to write back the dirty pages. Each
+/// failure is retried on the next interval, but repeated failures mean pages are only written
+/// back when they're evicted.
+pub(crate) fn background_flush_failures() -> usize {
+    BACKGROUND_FLUSH_FAILURES.load(Ordering::Relaxed)
+}
+
+/// Counts of page fetches from the buffer pool (see [`BufferPoolManager::stats`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BufferPoolStats {
+    /// Fetches of pages that were already in the buffer pool.
+    pub(crate) hits: usize,
+    /// Fetches of pages that had to be read from disk.
+    pub(crate) misses: usize,
+}
+
+/// A callback run with the id of each page evicted from the buffer pool (see
+/// [`BufferPoolManager::set_evict_callback`]).
+struct EvictCallback(Box<dyn Fn(PageId) + Send + Sync>);
+
+impl std::fmt::Debug for EvictCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EvictCallback")
+    }
+}
+
 /// Manages page allocation, caching, and eviction in the buffer pool.
 #[derive(Debug)]
 pub struct BufferPoolManager {
@@ -18,18 +71,24 @@ pub struct BufferPoolManager {
     page_table: HashMap<PageId, FrameId>, // Maps page IDs to frame IDs
     replacer: Box<dyn Replacer>, // Handles page replacement policy (e.g., LRU)
     free_list: VecDeque<FrameId>, // List of free frames
-    disk_manager: Arc<Mutex<DiskManager>>, // Manages reading/writing pages to disk
+    disk_manager: Arc<Mutex<dyn Disk>>, // Manages reading/writing pages to disk
+    wal: Option<Arc<Mutex<WalManager>>>, // The log that must be flushed before pages are written
+    flusher_stop: Option<Arc<AtomicBool>>, // Tells the background flusher, if running, to stop
+    stats: BufferPoolStats, // Counts page fetches that hit and missed the buffer pool
+    evict_callback: Option<EvictCallback>, // Told about every evicted page, if set
 }
 
 impl BufferPoolManager {
-    /// Initializes the buffer pool with a given size.
+    /// Initializes the buffer pool with a given size. Its frames hold pages of the disk's page
+    /// size.
     pub(crate) fn new(
         pool_size: usize,
-        disk_manager: Arc<Mutex<DiskManager>>,
+        disk_manager: Arc<Mutex<dyn Disk>>,
         replacer: Box<dyn Replacer>,
     ) -> Self {
+        let page_size = disk_manager.lock().unwrap().page_size();
         let mut pages = Vec::with_capacity(pool_size);
-        pages.resize_with(pool_size, PageFrame::new);
+        pages.resize_with(pool_size, || PageFrame::new(page_size));
 
         Self {
             frames: pages,
@@ -37,7 +96,103 @@ impl BufferPoolManager {
             replacer,
             free_list: (0..pool_size).collect(),
             disk_manager,
+            wal: None,
+            flusher_stop: None,
+            stats: BufferPoolStats::default(),
+            evict_callback: None,
+        }
+    }
+
+    /// Makes the buffer pool follow the write-ahead logging rule for `wal`: a dirty page is only
+    /// written back to disk once the log has been flushed up to the page's LSN.
+    pub(crate) fn set_wal(&mut self, wal: Arc<Mutex<WalManager>>) {
+        self.wal = Some(wal);
+    }
+
+    /// Makes the buffer pool call `callback` with the id of every page it evicts, once the page
+    /// has been written back and before it's removed from the page table, e.g. to invalidate
+    /// caches keyed by page id. Replaces any previous callback. The callback runs while the
+    /// buffer pool is locked, so it must not use the buffer pool.
+    pub(crate) fn set_evict_callback(&mut self, callback: Box<dyn Fn(PageId) + Send + Sync>) {
+        self.evict_callback = Some(EvictCallback(callback));
+    }
+
+    /// Returns the log set with [`BufferPoolManager::set_wal`], if any.
+    pub(crate) fn wal(&self) -> Option<Arc<Mutex<WalManager>>> {
+        self.wal.clone()
+    }
+
+    /// Returns the disk that the buffer pool reads pages from and writes pages to, e.g. for
+    /// temporary pages that bypass the buffer pool.
+    pub(crate) fn disk(&self) -> Arc<Mutex<dyn Disk>> {
+        self.disk_manager.clone()
+    }
+
+    /// Returns whether the frame's page can be written to disk, i.e. whether every log record that
+    /// modified it has been flushed.
+    fn can_write_back(&self, frame_id: FrameId) -> Result<bool> {
+        match &self.wal {
+            Some(wal) => Ok(self.frames[frame_id].page_lsn() <= wal.lock()?.flushed_lsn()),
+            None => Ok(true),
+        }
+    }
+
+    /// Takes a frame to evict from the replacer, or `None` if no frame can be evicted. Clean frames
+    /// are preferred among those the replacer ranks equally, since evicting them costs no write.
+    /// Dirty frames that can't be written back yet are skipped, and remain evictable.
+    fn evict_frame(&mut self) -> Result<Option<FrameId>> {
+        let mut held_back = Vec::new();
+        let result = loop {
+            let frames = &self.frames;
+            let is_clean = |frame_id: FrameId| !frames[frame_id].is_dirty();
+            let Some(frame_id) = self.replacer.evict_preferring_clean(&is_clean) else {
+                break Ok(None);
+            };
+            if !self.frames[frame_id].is_dirty() {
+                break Ok(Some(frame_id));
+            }
+            match self.can_write_back(frame_id) {
+                Ok(true) => break Ok(Some(frame_id)),
+                Ok(false) => held_back.push(frame_id),
+                Err(e) => {
+                    held_back.push(frame_id);
+                    break Err(e);
+                }
+            }
+        };
+
+        for frame_id in held_back {
+            self.replacer.record_access(frame_id);
+            self.replacer.unpin(frame_id);
+        }
+        result
+    }
+
+    /// Initializes the buffer pool with a given size, whose frames hold pages of `page_size` bytes.
+    ///
+    /// Returns [`Error::InvalidInput`] if the disk uses a different page size, or if `page_size`
+    /// can't be addressed by the 16-bit offsets of a table page.
+    pub(crate) fn with_page_size(
+        pool_size: usize,
+        page_size: usize,
+        disk_manager: Arc<Mutex<dyn Disk>>,
+        replacer: Box<dyn Replacer>,
+    ) -> Result<Self> {
+        if page_size > u16::MAX as usize {
+            return Err(Error::InvalidInput(format!(
+                "Page size {} is larger than the maximum of {}",
+                page_size,
+                u16::MAX
+            )));
         }
+        let disk_page_size = disk_manager.lock()?.page_size();
+        if page_size != disk_page_size {
+            return Err(Error::InvalidInput(format!(
+                "Buffer pool page size {} does not match disk page size {}",
+                page_size, disk_page_size
+            )));
+        }
+        Ok(Self::new(pool_size, disk_manager, replacer))
     }
 
     /// Returns a free frame or evicts a page if necessary.
@@ -47,9 +202,29 @@ impl BufferPoolManager {
         }
 
         // Evict a page if no free frames are available
-        let frame_id = self.replacer.evict().ok_or(Error::BufferPoolError(
-            "No evictable frame in buffer pool".to_string(),
-        ))?;
+        self.evict_page()?.ok_or(Error::NoEvictableFrame)
+    }
+
+    /// Evicts up to `n` pages, writing dirty ones back to disk, and adds their frames to the free
+    /// list, so that later misses can take a free frame instead of evicting one themselves.
+    /// Returns the number of frames freed, which is less than `n` if fewer pages can be evicted.
+    pub(crate) fn evict_batch(&mut self, n: usize) -> Result<usize> {
+        for freed in 0..n {
+            match self.evict_page()? {
+                Some(frame_id) => self.free_list.push_back(frame_id),
+                None => return Ok(freed),
+            }
+        }
+        Ok(n)
+    }
+
+    /// Evicts a page, writing it back to disk if it's dirty and then passing its id to the evict
+    /// callback, if any, and returns the id of its now-empty frame, or `None` if no page can be
+    /// evicted.
+    fn evict_page(&mut self) -> Result<Option<FrameId>> {
+        let Some(frame_id) = self.evict_frame()? else {
+            return Ok(None);
+        };
         let frame = &mut self.frames[frame_id];
         assert_eq!(
             frame.pin_count(),
@@ -63,192 +238,154 @@ impl BufferPoolManager {
             disk.write(frame.page_id(), frame.data())?;
         }
 
+        if let Some(EvictCallback(callback)) = &self.evict_callback {
+            callback(frame.page_id());
+        }
+
         // Remove old page from the page table
         self.page_table.remove(&frame.page_id());
 
         // Reset the frame for reuse
         frame.reset();
 
-        Ok(frame_id)
+        Ok(Some(frame_id))
     }
 
     /// Allocates a new page and loads it into a free frame.
     fn create_page(&mut self) -> Result<&mut PageFrame> {
         // get a free frame
         let frame_id = self.get_free_frame()?;
-        let frame = &mut self.frames[frame_id];
-
-        // allocate a new page
-        let page_id = self.disk_manager.lock()?.allocate_page(); // assign new page id
-        let pid = page_id?;
-        frame.set_page_id(pid);
 
-        // initialize the frame
-        frame.reset(); // clear data and metadata
-        frame.set_dirty(false);
+        // allocate a new page id on disk, returning the frame to the free list on failure
+        let page_id = match self.disk_manager.lock()?.allocate_page() {
+            Ok(page_id) => page_id,
+            Err(e) => {
+                self.free_list.push_back(frame_id);
+                return Err(e);
+            }
+        };
 
-        // insert the page into the page table
-        self.page_table.insert(pid, frame_id);
+        // initialize the frame, pinned by the caller
+        let frame = &mut self.frames[frame_id];
+        frame.reset();
+        frame.set_page_id(page_id);
+        frame.set_pin_count(1);
 
-        // update the replacer
-        self.replacer.pin(frame_id);
+        // insert the page into the page table and update the replacer
+        self.page_table.insert(page_id, frame_id);
         self.replacer.record_access(frame_id);
+        self.replacer.pin(frame_id);
 
-        // return the frame
         Ok(frame)
     }
 
-    /// Fetches a mutable reference to a page, loading it from disk if necessary.
-    fn fetch_page_mut(&mut self, page_id: PageId) -> Result<&mut PageFrame> {
-        // check if the page is already in memory
-        // if yes: get the frame id
+    /// Pins the page with the given id into a frame, loading it from disk if necessary, and
+    /// returns the id of that frame.
+    fn pin_page(&mut self, page_id: PageId) -> Result<FrameId> {
+        // if the page is already in memory, just pin it
         if let Some(&frame_id) = self.page_table.get(&page_id) {
-            let frame = &mut self.frames[frame_id];
-            self.replacer.record_access(frame_id); // update replacer
+            self.frames[frame_id].increment_pin_count();
+            self.replacer.record_access(frame_id);
             self.replacer.pin(frame_id);
+            return Ok(frame_id);
+        }
 
-            return Ok(frame); // return mutable reference to the frame
-        } else {
-            // if no: get a free frame
-            let frame_id = self.get_free_frame()?;
-            let frame = &mut self.frames[frame_id];
+        // otherwise, load the page from disk into a free frame
+        let frame_id = self.get_free_frame()?;
+        let data = match self.disk_manager.lock()?.read(page_id) {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                self.free_list.push_back(frame_id);
+                return Err(Error::BufferPoolError(format!(
+                    "Page {:?} not found on disk",
+                    page_id
+                )));
+            }
+            Err(e) => {
+                self.free_list.push_back(frame_id);
+                return Err(e);
+            }
+        };
 
-            // load page from disk
-            let mut disk = self.disk_manager.lock()?;
-            disk.read(page_id)?;
+        let frame = &mut self.frames[frame_id];
+        frame.data_mut().copy_from_slice(&data);
+        frame.set_page_id(page_id);
+        frame.set_dirty(false);
+        frame.set_pin_count(1);
 
-            // set frame metadata
-            frame.set_page_id(page_id);
-            frame.set_dirty(false);
+        // update page table and replacer
+        self.page_table.insert(page_id, frame_id);
+        self.replacer.record_access(frame_id);
+        self.replacer.pin(frame_id);
 
-            // update page table and replacer
-            self.page_table.insert(page_id, frame_id);
-            self.replacer.record_access(frame_id);
-            self.replacer.pin(frame_id);
+        Ok(frame_id)
+    }
 
-            // return mutable reference to the frame
-            Ok(frame)
-        }
+    /// Fetches a mutable reference to a page, loading it from disk if necessary.
+    fn fetch_page_mut(&mut self, page_id: PageId) -> Result<&mut PageFrame> {
+        self.count_fetch(page_id);
+        let frame_id = self.pin_page(page_id)?;
+        Ok(&mut self.frames[frame_id])
     }
 
     /// Fetches an immutable reference to a page.
     fn fetch_page(&mut self, page_id: PageId) -> Result<&PageFrame> {
-        // check if the page is already i nmemory
-        // if yes: get the frame id
-        if let Some(&frame_id) = self.page_table.get(&page_id) {
-            let frame = &mut self.frames[frame_id];
-            self.replacer.pin(frame_id);
-            self.replacer.record_access(frame_id); // update replacer
+        self.count_fetch(page_id);
+        let frame_id = self.pin_page(page_id)?;
+        Ok(&self.frames[frame_id])
+    }
 
-            // return immutable reference to the frame
-            return Ok(&*frame);
+    /// Counts a fetch of the page with the given id as a hit or a miss.
+    fn count_fetch(&mut self, page_id: PageId) {
+        if self.page_table.contains_key(&page_id) {
+            self.stats.hits += 1;
         } else {
-            // if no: get a free frame
-            let frame_id = self.get_free_frame()?;
-            let frame = &mut self.frames[frame_id];
+            self.stats.misses += 1;
+        }
+    }
 
-            // load page from disk
-            let mut disk = self.disk_manager.lock()?;
-            disk.read(page_id)?;
+    /// Returns the number of page fetches that hit and missed the buffer pool so far. Prefetches
+    /// (see [`BufferPoolManager::prefetch`]) aren't counted.
+    pub(crate) fn stats(&self) -> BufferPoolStats {
+        self.stats
+    }
 
-            // set frame metadata
-            frame.set_page_id(page_id);
-            frame.set_dirty(false);
+    /// Unpins a page, allowing it to be evicted if necessary. Has no effect if the page isn't in
+    /// the buffer pool.
+    pub(crate) fn unpin_page(&mut self, page_id: PageId, is_dirty: bool) {
+        let Some(&frame_id) = self.page_table.get(&page_id) else {
+            return;
+        };
+        let frame = &mut self.frames[frame_id];
 
-            // update page table and replacer
-            self.page_table.insert(page_id, frame_id);
-            self.replacer.record_access(frame_id);
+        // mark frame as dirty if necessary
+        if is_dirty {
+            frame.set_dirty(true);
+        }
 
-            // return immutable reference to the frame
-            return Ok(&*frame);
+        // decrement pin count, making the frame evictable once nobody is using it
+        frame.decrement_pin_count();
+        if frame.pin_count() == 0 {
+            self.replacer.unpin(frame_id);
         }
     }
 
-    /// Unpins a page, allowing it to be evicted if necessary.
-    pub(crate) fn unpin_page(&mut self, page_id: PageId, is_dirty: bool) {
+    /// Marks a page as modified, so that it is written back by the next flush or eviction. Has no
+    /// effect if the page isn't in the buffer pool.
+    pub(crate) fn mark_dirty(&mut self, page_id: PageId) {
         if let Some(&frame_id) = self.page_table.get(&page_id) {
-            // check if page is in memory
-            let frame = &mut self.frames[frame_id];
-
-            // decrement pin count--must stay above zero
-            let current_pin = frame.pin_count();
-            if current_pin > 0 {
-                self.replacer.unpin(frame_id);
-            } else {
-                panic!("Attempted to unpin a page with pin_count = 0");
-            }
-
-            // mark frame as dirty if necessary
-            if is_dirty {
-                frame.set_dirty(true);
-            }
-
-            // update replacer
-            if frame.pin_count() == 0 {
-                //self.replacer.set_evictable(&frame_id, true);
-                self.replacer.unpin(frame_id);
-            } else {
-                // greater than zero
-                //self.replacer.set_evictable(&frame_id, false);
-                self.replacer.pin(frame_id);
-            }
-        } else {
-            // page not in memory
-            panic!("Attempted to unpin a page not in buffer pool");
+            self.frames[frame_id].set_dirty(true);
         }
     }
 
-    /// Deletes a page from the buffer pool and disk.
+    /// Deletes a page from the buffer pool and disk. Returns [`Error::PagePinned`] if the page is
+    /// still pinned.
     pub(crate) fn delete_page(&mut self, page_id: PageId) -> Result<()> {
-        // check if page is in memory
-        // if let Some(&frame_id) = self.page_table.get(&page_id) {
-        //     let frame = &mut self.frames[frame_id];
-
-        //     // can't delete if the page is pinned
-        //     if frame.pin_count() > 0 {
-        //         return Err(Error::BufferPoolError(format!(
-        //             "Page {:?} is pinned and cannot be deleted",
-        //             page_id
-        //         )));
-        //     }
-
-        //     // if dirty, flush to disk
-        //     if frame.is_dirty() {
-        //         self.flush_page(&page_id)?;
-        //     }
-
-        //     // remove from page table and replacer
-        //     self.page_table.remove(&page_id);
-        //     self.replacer.remove(frame_id);
-
-        //     // reset the frame
-        //     frame.reset();
-
-        //     // add frame back to free list
-        //     self.free_list.push_back(frame_id);
-        // }
-        // // delete the page from disk
-        // let mut disk = self.disk_manager.lock()?;
-        // disk.deallocate_page(page_id)?;
-
-        // Ok(())
         // check if page is in memory
         if let Some(&frame_id) = self.page_table.get(&page_id) {
-            // --- check pin count in its own scope ---
-            {
-                let frame = &self.frames[frame_id];
-                // can't delete if the page is pinned
-                if frame.pin_count() > 0 {
-                    return Err(Error::BufferPoolError(format!(
-                        "Page {:?} is pinned and cannot be deleted",
-                        page_id
-                    )));
-                }
-            } // borrow of `frame` ends here
-
-            // --- check dirty status safely ---
-            if self.frames[frame_id].is_dirty() {
-                self.flush_page(&page_id)?; // now safe — no overlapping mutable borrows
+            // can't delete if the page is pinned
+            if self.frames[frame_id].pin_count() > 0 {
+                return Err(Error::PagePinned(page_id));
             }
 
             // remove from page table and replacer
@@ -260,17 +397,25 @@ impl BufferPoolManager {
             self.free_list.push_back(frame_id);
         }
 
-        // delete the page from disk (safe to do outside the frame borrow)
+        // delete the page from disk
         let mut disk = self.disk_manager.lock()?;
         disk.deallocate_page(page_id)?;
 
         Ok(())
     }
 
-    /// Flushes a specific page to disk.
+    /// Flushes a specific page to disk. Returns [`Error::BufferPoolError`] if the page is dirty and
+    /// the log hasn't been flushed up to its LSN.
     pub(crate) fn flush_page(&mut self, page_id: &PageId) -> Result<()> {
         // check if page is in memory
         if let Some(&frame_id) = self.page_table.get(page_id) {
+            // the page can't be written until the log records that modified it are on disk
+            if self.frames[frame_id].is_dirty() && !self.can_write_back(frame_id)? {
+                return Err(Error::BufferPoolError(format!(
+                    "Page {:?} has log records that have not been flushed",
+                    page_id
+                )));
+            }
             let frame = &mut self.frames[frame_id];
 
             // if the frame is dirty, write it to disk
@@ -291,6 +436,115 @@ impl BufferPoolManager {
         }
     }
 
+    /// Writes every dirty, unpinned page back to disk and marks it clean, so that evicting it later
+    /// doesn't have to. Pages whose log records haven't been flushed yet are skipped. Returns the
+    /// number of pages written.
+    pub(crate) fn flush_unpinned_pages(&mut self) -> Result<usize> {
+        let mut flushed = 0;
+        for (&page_id, &frame_id) in &self.page_table {
+            let frame = &self.frames[frame_id];
+            if !frame.is_dirty() || frame.pin_count() > 0 || !self.can_write_back(frame_id)? {
+                continue;
+            }
+            self.disk_manager.lock()?.write(page_id, frame.data())?;
+            self.frames[frame_id].set_dirty(false);
+            flushed += 1;
+        }
+        Ok(flushed)
+    }
+
+    /// Like [`BufferPoolManager::flush_unpinned_pages`], but only holds the buffer pool's lock to
+    /// copy the pages and to mark them clean afterwards, not while writing them.
+    ///
+    /// The disk's lock is taken before the buffer pool's is released, so that a later write of the
+    /// same page, e.g. by an eviction, can't be overtaken by the copy. A page is only marked clean
+    /// if it is still unpinned in the same frame with the data that was written.
+    fn flush_unpinned_pages_unlocked(bpm: &Arc<RwLock<Self>>) -> Result<usize> {
+        // 1. copy the pages to write under the buffer pool's lock
+        let disk_manager = Self::lock(bpm).disk_manager.clone();
+        let (mut disk, pages) = {
+            let bpm = Self::lock(bpm);
+            let mut pages = Vec::new();
+            for (&page_id, &frame_id) in &bpm.page_table {
+                let frame = &bpm.frames[frame_id];
+                if frame.is_dirty() && frame.pin_count() == 0 && bpm.can_write_back(frame_id)? {
+                    pages.push((page_id, frame_id, frame.data().to_vec()));
+                }
+            }
+            (disk_manager.lock()?, pages)
+        };
+
+        // 2. write them with only the disk locked
+        for (page_id, _, data) in &pages {
+            disk.write(*page_id, data)?;
+        }
+        drop(disk);
+
+        // 3. mark the pages that haven't changed since clean
+        let mut guard = Self::lock(bpm);
+        let bpm = &mut *guard;
+        let mut flushed = 0;
+        for (page_id, frame_id, data) in pages {
+            let frame = &mut bpm.frames[frame_id];
+            if bpm.page_table.get(&page_id) == Some(&frame_id)
+                && frame.pin_count() == 0
+                && frame.data() == data.as_slice()
+            {
+                frame.set_dirty(false);
+                flushed += 1;
+            }
+        }
+        Ok(flushed)
+    }
+
+    /// Starts a thread that writes back every dirty, unpinned page every `interval`, like
+    /// [`BufferPoolManager::flush_unpinned_pages`], so that dirty pages are usually already written
+    /// back by the time they're evicted. Other threads can use the buffer pool while the pages are
+    /// being written. The thread runs until [`BufferPoolManager::stop_background_flusher`] is
+    /// called or the buffer pool is dropped, since it only holds a weak reference to the pool. A
+    /// failed flush is counted (see [`background_flush_failures`]) and retried on the next
+    /// interval.
+    ///
+    /// Returns [`Error::BufferPoolError`] if a background flusher is already running.
+    pub(crate) fn start_background_flusher(
+        bpm: Arc<RwLock<Self>>,
+        interval: Duration,
+    ) -> Result<JoinHandle<()>> {
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let mut guard = Self::lock(&bpm);
+            if guard.flusher_stop.is_some() {
+                return Err(Error::BufferPoolError(
+                    "Background flusher is already running".to_string(),
+                ));
+            }
+            guard.flusher_stop = Some(stop.clone());
+        }
+        let bpm = Arc::downgrade(&bpm);
+        Ok(thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                thread::sleep(interval);
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Some(bpm) = bpm.upgrade() else {
+                    break;
+                };
+                if Self::flush_unpinned_pages_unlocked(&bpm).is_err() {
+                    BACKGROUND_FLUSH_FAILURES.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }))
+    }
+
+    /// Signals the thread started by [`BufferPoolManager::start_background_flusher`] to stop. It
+    /// exits within one interval, and a new flusher can be started right away.
+    pub(crate) fn stop_background_flusher(&mut self) {
+        if let Some(stop) = self.flusher_stop.take() {
+            stop.store(true, Ordering::SeqCst);
+        }
+    }
+
     /// Returns the total number of frames in the buffer pool.
     fn capacity(&self) -> usize {
         self.frames.len()
@@ -301,6 +555,21 @@ impl BufferPoolManager {
         self.free_list.len() + self.replacer.evictable_count()
     }
 
+    /// Returns the id, pin count, and dirty flag of every page in the buffer pool, sorted by page
+    /// id. Useful for checkpointing and for finding pages that are never unpinned.
+    pub(crate) fn resident_pages(&self) -> Vec<(PageId, u16, bool)> {
+        let mut pages = self
+            .page_table
+            .iter()
+            .map(|(&page_id, &frame_id)| {
+                let frame = &self.frames[frame_id];
+                (page_id, frame.pin_count(), frame.is_dirty())
+            })
+            .collect::<Vec<_>>();
+        pages.sort_unstable_by_key(|&(page_id, ..)| page_id);
+        pages
+    }
+
     /// Returns the pin count of a page, or `None` if it is not in the buffer pool.
     fn get_pin_count(&self, page_id: PageId) -> Option<u16> {
         let frame_id = self.page_table.get(&page_id)?;
@@ -309,12 +578,30 @@ impl BufferPoolManager {
         Some(self.frames[*frame_id].pin_count())
     }
 
+    /// Acquires the write lock on `bpm`. If a thread panicked while holding the lock, it is
+    /// recovered, so that one failed thread doesn't take down every other thread using the buffer
+    /// pool. The panicking thread may have left a page half-modified, but the buffer pool's own
+    /// bookkeeping is only changed under this lock by code that doesn't panic midway.
+    pub(crate) fn lock(bpm: &Arc<RwLock<BufferPoolManager>>) -> RwLockWriteGuard<'_, Self> {
+        let guard = recover_poisoned(bpm.write());
+        bpm.clear_poison();
+        guard
+    }
+
+    /// Acquires a read lock on `bpm`, recovering it like [`BufferPoolManager::lock`] if a thread
+    /// panicked while holding it.
+    pub(crate) fn read_lock(bpm: &Arc<RwLock<BufferPoolManager>>) -> RwLockReadGuard<'_, Self> {
+        let guard = recover_poisoned(bpm.read());
+        bpm.clear_poison();
+        guard
+    }
+
     /// Creates a new page and returns a handle for it.
     pub(crate) fn create_page_handle(
         bpm: &Arc<RwLock<BufferPoolManager>>,
-    ) -> Result<PageFrameMutHandle> {
+    ) -> Result<PageFrameMutHandle<'_>> {
         let page_frame = {
-            let mut bpm_guard = bpm.write()?;
+            let mut bpm_guard = Self::lock(bpm);
             // SAFETY:
             // This function needs to return a handle that contains both a reference to a
             // page (created via `create_page()`) and the Arc to the BufferPoolManager.
@@ -327,7 +614,7 @@ impl BufferPoolManager {
             // To work around this limitation, we temporarily extract a raw pointer from the locked
             // BufferPoolManager. This allows us to call `create_page()` and obtain a reference to the page
             // without having to keep the full `bpm_guard` active. Since we hold exclusive access via
-            // `bpm.write().unwrap()`, we know that the page reference is valid and will not be modified
+            // `Self::lock(bpm)`, we know that the page reference is valid and will not be modified
             // by other threads.
             //
             // In summary, we use `unsafe` here solely to bypass the borrow check that prevents
@@ -340,13 +627,35 @@ impl BufferPoolManager {
         Ok(PageFrameMutHandle::new(&bpm, page_frame))
     }
 
+    /// Loads the pages with the given ids into free frames, unpinned, so that fetching them later
+    /// hits the buffer pool. Pages already in the buffer pool are skipped. No page is evicted to
+    /// make room, so this stops early once there are no free frames left (see
+    /// [`BufferPoolManager::evict_batch`]).
+    pub(crate) fn prefetch(
+        bpm: &Arc<RwLock<BufferPoolManager>>,
+        page_ids: &[PageId],
+    ) -> Result<()> {
+        let mut bpm = Self::lock(bpm);
+        for &page_id in page_ids {
+            if bpm.page_table.contains_key(&page_id) {
+                continue;
+            }
+            if bpm.free_list.is_empty() {
+                break;
+            }
+            bpm.pin_page(page_id)?;
+            bpm.unpin_page(page_id, false);
+        }
+        Ok(())
+    }
+
     /// Fetches a read-only handle to a page.
     pub(crate) fn fetch_page_handle(
         bpm: &Arc<RwLock<BufferPoolManager>>,
         page_id: PageId,
-    ) -> Result<PageFrameRefHandle> {
+    ) -> Result<PageFrameRefHandle<'_>> {
         let page_frame = {
-            let mut bpm_guard = bpm.write()?;
+            let mut bpm_guard = Self::lock(bpm);
             // SAFETY: see `create_page_handle`
             let bpm_ptr = &mut *bpm_guard as *mut BufferPoolManager;
             unsafe { (*bpm_ptr).fetch_page(page_id)? }
@@ -359,9 +668,9 @@ impl BufferPoolManager {
     pub(crate) fn fetch_page_mut_handle(
         bpm: &Arc<RwLock<BufferPoolManager>>,
         page_id: PageId,
-    ) -> Result<PageFrameMutHandle> {
+    ) -> Result<PageFrameMutHandle<'_>> {
         let page_frame = {
-            let mut bpm_guard = bpm.write()?;
+            let mut bpm_guard = Self::lock(bpm);
             // SAFETY: see `create_page_handle`
             let bpm_ptr = &mut *bpm_guard as *mut BufferPoolManager;
             unsafe { (*bpm_ptr).fetch_page_mut(page_id)? }
@@ -369,21 +678,52 @@ impl BufferPoolManager {
 
         Ok(PageFrameMutHandle::new(&bpm, page_frame))
     }
+
+    /// Like [`BufferPoolManager::fetch_page_mut_handle`], but if every frame is pinned
+    /// ([`Error::NoEvictableFrame`]), which under contention is often only briefly, retries up to
+    /// `max_attempts` times in all, sleeping `backoff` before the first retry and twice as long
+    /// before each one after that. Other errors are returned right away. The fetch is attempted
+    /// at least once.
+    pub(crate) fn fetch_page_mut_handle_retry(
+        bpm: &Arc<RwLock<BufferPoolManager>>,
+        page_id: PageId,
+        max_attempts: usize,
+        backoff: Duration,
+    ) -> Result<PageFrameMutHandle<'_>> {
+        let mut delay = backoff;
+        for _ in 1..max_attempts {
+            match Self::fetch_page_mut_handle(bpm, page_id) {
+                Err(Error::NoEvictableFrame) => {
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        Self::fetch_page_mut_handle(bpm, page_id)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::disk::disk_manager::DiskManager;
+    use crate::disk::memory_disk_manager::MemoryDiskManager;
+    use crate::frame::PAGE_LSN_SIZE;
     use crate::frame_handle::{PageFrameMutHandle, PageFrameRefHandle};
     use crate::page::PAGE_SIZE;
     use crate::replacer::lru_k_replacer::LrukReplacer;
-    use crate::{buffer_pool::BufferPoolManager, typedef::PageId};
+    use crate::wal::{LogRecord, WalManager};
+    use crate::{
+        buffer_pool::{background_flush_failures, poison_recoveries, BufferPoolManager},
+        typedef::PageId,
+    };
     use rand::{rng, Rng};
+    use rustdb_error::Error;
     use serial_test::serial;
     use std::collections::HashMap;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::RwLock;
-    use std::sync::{Arc, Condvar, Mutex};
+    use std::sync::{Arc, Barrier, Condvar, Mutex};
     use std::thread;
     use std::time::Duration;
 
@@ -407,6 +747,18 @@ mod tests {
         BufferPoolManager::new(pool_size, disk_manager, replacer)
     }
 
+    // Helper function to create a buffer pool manager with `n` pages, backed by memory instead of
+    // a file.
+    fn get_memory_bpm_arc_with_pool_size(pool_size: usize) -> Arc<RwLock<BufferPoolManager>> {
+        let disk_manager = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let replacer = Box::new(LrukReplacer::new(5));
+        Arc::new(RwLock::new(BufferPoolManager::new(
+            pool_size,
+            disk_manager,
+            replacer,
+        )))
+    }
+
     fn get_bpm_with_pool_size_and_file_name(
         pool_size: usize,
         file_name: &str,
@@ -417,7 +769,10 @@ mod tests {
     }
 
     // Helper function to create `n` pages in the buffer pool.
-    fn create_n_pages(bpm: &Arc<RwLock<BufferPoolManager>>, n: usize) -> Vec<PageFrameMutHandle> {
+    fn create_n_pages(
+        bpm: &Arc<RwLock<BufferPoolManager>>,
+        n: usize,
+    ) -> Vec<PageFrameMutHandle<'_>> {
         let mut pages = Vec::new();
         for _ in 0..n {
             let page_handle =
@@ -430,8 +785,17 @@ mod tests {
     #[test]
     #[serial]
     fn test_bpm_create_pages_beyond_capacity() {
+        create_pages_beyond_capacity(get_bpm_arc_with_pool_size);
+    }
+
+    #[test]
+    fn test_bpm_create_pages_beyond_capacity_in_memory() {
+        create_pages_beyond_capacity(get_memory_bpm_arc_with_pool_size);
+    }
+
+    fn create_pages_beyond_capacity(new_bpm: fn(usize) -> Arc<RwLock<BufferPoolManager>>) {
         let pool_size = 10;
-        let bpm = get_bpm_arc_with_pool_size(pool_size);
+        let bpm = new_bpm(pool_size);
 
         assert_eq!(pool_size, bpm.read().unwrap().free_frame_count());
 
@@ -463,6 +827,36 @@ mod tests {
         }
         assert_eq!(pool_size, bpm.read().unwrap().free_frame_count());
     }
+
+    #[test]
+    fn test_bpm_page_size_must_match_disk() {
+        for page_size in [4096, 8192] {
+            let disk_manager = Arc::new(Mutex::new(MemoryDiskManager::with_page_size(page_size)));
+            let replacer = Box::new(LrukReplacer::new(5));
+            let bpm = BufferPoolManager::with_page_size(2, page_size, disk_manager, replacer)
+                .expect("Page sizes should match");
+            let bpm = Arc::new(RwLock::new(bpm));
+
+            // The whole page is usable, and survives eviction.
+            let page_id = {
+                let mut page_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+                assert_eq!(page_handle.data().len(), page_size);
+                page_handle.write(page_size - 4, b"tail").unwrap();
+                page_handle.page_id()
+            };
+            drop(create_n_pages(&bpm, 2));
+            let page_handle = BufferPoolManager::fetch_page_handle(&bpm, page_id).unwrap();
+            assert_eq!(&page_handle.data()[page_size - 4..], b"tail");
+        }
+
+        let disk_manager = Arc::new(Mutex::new(MemoryDiskManager::with_page_size(4096)));
+        let replacer = Box::new(LrukReplacer::new(5));
+        assert!(matches!(
+            BufferPoolManager::with_page_size(2, 8192, disk_manager, replacer),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
     #[test]
     #[serial]
     fn test_bpm_cannot_create_page_beyond_buffer_pool_size() {
@@ -497,8 +891,17 @@ mod tests {
     #[test]
     #[serial]
     fn test_bpm_new_page_evict_frame() {
+        new_page_evict_frame(get_bpm_arc_with_pool_size);
+    }
+
+    #[test]
+    fn test_bpm_new_page_evict_frame_in_memory() {
+        new_page_evict_frame(get_memory_bpm_arc_with_pool_size);
+    }
+
+    fn new_page_evict_frame(new_bpm: fn(usize) -> Arc<RwLock<BufferPoolManager>>) {
         let pool_size = 10;
-        let bpm = get_bpm_arc_with_pool_size(pool_size);
+        let bpm = new_bpm(pool_size);
 
         let mut page_handles = Vec::new(); // Store page handles to prevent dropping
 
@@ -547,8 +950,17 @@ mod tests {
     #[test]
     #[serial]
     fn test_bpm_fetch_page_not_in_buffer() {
+        fetch_page_not_in_buffer(get_bpm_arc_with_pool_size);
+    }
+
+    #[test]
+    fn test_bpm_fetch_page_not_in_buffer_in_memory() {
+        fetch_page_not_in_buffer(get_memory_bpm_arc_with_pool_size);
+    }
+
+    fn fetch_page_not_in_buffer(new_bpm: fn(usize) -> Arc<RwLock<BufferPoolManager>>) {
         let pool_size = 10;
-        let bpm = get_bpm_arc_with_pool_size(pool_size);
+        let bpm = new_bpm(pool_size);
 
         // Fill buffer pool to capacity with new pages.
         let page_id_to_evict = {
@@ -668,7 +1080,7 @@ mod tests {
         let page_data = {
             let mut page_handle = BufferPoolManager::fetch_page_mut_handle(&bpm, page_id)
                 .expect("Failed to fetch page for writing");
-            page_handle.write(0, data);
+            page_handle.write(0, data).unwrap();
             page_handle.data().to_vec()
         };
 
@@ -697,8 +1109,17 @@ mod tests {
     #[test]
     #[serial]
     fn test_bpm_evict_flush_page() {
+        evict_flush_page(get_bpm_arc_with_pool_size);
+    }
+
+    #[test]
+    fn test_bpm_evict_flush_page_in_memory() {
+        evict_flush_page(get_memory_bpm_arc_with_pool_size);
+    }
+
+    fn evict_flush_page(new_bpm: fn(usize) -> Arc<RwLock<BufferPoolManager>>) {
         let pool_size = 5;
-        let bpm = get_bpm_arc_with_pool_size(pool_size);
+        let bpm = new_bpm(pool_size);
 
         // Create a new page and modify it
         let page_id = BufferPoolManager::create_page_handle(&bpm)
@@ -709,7 +1130,7 @@ mod tests {
         let page_data = {
             let mut page_handle = BufferPoolManager::fetch_page_mut_handle(&bpm, page_id)
                 .expect("Failed to fetch page for writing");
-            page_handle.write(0, data);
+            page_handle.write(0, data).unwrap();
             page_handle.data().to_vec()
         };
 
@@ -748,11 +1169,345 @@ mod tests {
         assert!(bpm.delete_page(page_id).is_ok());
     }
 
+    #[test]
+    fn test_bpm_holds_back_pages_until_logged() {
+        let bpm = get_memory_bpm_arc_with_pool_size(1);
+        let log_disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let wal = Arc::new(Mutex::new(WalManager::new(log_disk).unwrap()));
+        bpm.write().unwrap().set_wal(wal.clone());
+
+        // Modify a page as if by the second log record.
+        let pid = {
+            let mut page_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+            page_handle.set_page_lsn(2);
+            page_handle.write(PAGE_LSN_SIZE, b"logged").unwrap();
+            page_handle.page_id()
+        };
+
+        // The page can be neither flushed nor evicted before the log catches up.
+        assert!(bpm.write().unwrap().flush_page(&pid).is_err());
+        assert!(BufferPoolManager::create_page_handle(&bpm).is_err());
+        assert_eq!(bpm.read().unwrap().resident_pages(), vec![(pid, 0, true)]);
+
+        // Once the log has been flushed past the page's LSN, the page is written back on eviction.
+        {
+            let mut wal = wal.lock().unwrap();
+            wal.append(&LogRecord::Begin { txn: 1 }).unwrap();
+            let lsn = wal.append(&LogRecord::CommitTxn { txn: 1 }).unwrap();
+            wal.flush_to(lsn).unwrap();
+        }
+        let other_pid = BufferPoolManager::create_page_handle(&bpm)
+            .unwrap()
+            .page_id();
+        assert_ne!(other_pid, pid);
+
+        let page_handle = BufferPoolManager::fetch_page_handle(&bpm, pid).unwrap();
+        assert_eq!(page_handle.page_lsn(), 2);
+        assert_eq!(
+            &page_handle.data()[PAGE_LSN_SIZE..PAGE_LSN_SIZE + 6],
+            b"logged"
+        );
+    }
+
+    #[test]
+    fn test_bpm_write_out_of_bounds() {
+        let bpm = get_memory_bpm_arc_with_pool_size(5);
+        let mut page_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let page_size = page_handle.data().len();
+
+        // Writes that run past the end of the page fail without modifying it.
+        assert_eq!(page_handle.write(page_size - 3, b"tail"), Err(Error::OutOfBounds));
+        assert_eq!(page_handle.write(page_size + 1, b""), Err(Error::OutOfBounds));
+        assert_eq!(page_handle.write(usize::MAX, b"x"), Err(Error::OutOfBounds));
+        assert!(page_handle.data().iter().all(|&b| b == 0));
+
+        // A write that ends exactly at the end of the page succeeds.
+        assert_eq!(page_handle.write(page_size - 4, b"tail"), Ok(()));
+        assert_eq!(&page_handle.data()[page_size - 4..], b"tail");
+    }
+
+    #[test]
+    fn test_bpm_resident_pages() {
+        let bpm = get_memory_bpm_arc_with_pool_size(5);
+        assert!(bpm.read().unwrap().resident_pages().is_empty());
+
+        let handle1 = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let handle2 = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let handle3 = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let (pid1, pid2, pid3) = (handle1.page_id(), handle2.page_id(), handle3.page_id());
+
+        // Dropping a mutable handle unpins its page and marks it dirty.
+        drop(handle2);
+        assert_eq!(
+            bpm.read().unwrap().resident_pages(),
+            vec![(pid1, 1, false), (pid2, 0, true), (pid3, 1, false)]
+        );
+
+        // Every handle on a page counts towards its pin count.
+        let read_handle = BufferPoolManager::fetch_page_handle(&bpm, pid2).unwrap();
+        drop(handle3);
+        assert_eq!(
+            bpm.read().unwrap().resident_pages(),
+            vec![(pid1, 1, false), (pid2, 1, true), (pid3, 0, true)]
+        );
+
+        drop(read_handle);
+        drop(handle1);
+        assert!(bpm
+            .read()
+            .unwrap()
+            .resident_pages()
+            .iter()
+            .all(|&(_, pin_count, _)| pin_count == 0));
+    }
+
+    #[test]
+    fn test_bpm_evict_batch() {
+        let bpm = get_memory_bpm_arc_with_pool_size(10);
+        let page_ids = create_n_pages(&bpm, 10)
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut page_handle)| {
+                page_handle.write(0, &[i as u8; 4]).unwrap();
+                page_handle.page_id()
+            })
+            .collect::<Vec<_>>();
+        assert!(bpm.read().unwrap().free_list.is_empty());
+
+        // Every page is unpinned, so a batch frees exactly as many frames as requested.
+        assert_eq!(bpm.write().unwrap().evict_batch(5), Ok(5));
+        assert_eq!(bpm.read().unwrap().free_list.len(), 5);
+        assert_eq!(bpm.read().unwrap().resident_pages().len(), 5);
+
+        // A pinned page can't be evicted, so a larger batch frees only what's left.
+        let pinned = BufferPoolManager::fetch_page_handle(&bpm, page_ids[9]).unwrap();
+        assert_eq!(bpm.write().unwrap().evict_batch(10), Ok(4));
+        assert_eq!(bpm.read().unwrap().free_list.len(), 9);
+        drop(pinned);
+
+        // Dirty pages were written back before being evicted.
+        for (i, &page_id) in page_ids.iter().enumerate() {
+            let page_handle = BufferPoolManager::fetch_page_handle(&bpm, page_id).unwrap();
+            assert_eq!(&page_handle.data()[..4], &[i as u8; 4]);
+        }
+    }
+
+    #[test]
+    fn test_bpm_prefetch() {
+        let bpm = get_memory_bpm_arc_with_pool_size(3);
+        // Each handle is dropped before the next page is created, so the pool fits them all.
+        let page_ids = (0..4)
+            .map(|_| {
+                BufferPoolManager::create_page_handle(&bpm)
+                    .unwrap()
+                    .page_id()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(bpm.write().unwrap().evict_batch(3), Ok(3));
+        assert!(bpm.read().unwrap().resident_pages().is_empty());
+
+        // Prefetched pages are resident and unpinned, and fetching them counts as hits.
+        BufferPoolManager::prefetch(&bpm, &page_ids[..3]).unwrap();
+        assert_eq!(
+            bpm.read().unwrap().resident_pages(),
+            page_ids[..3]
+                .iter()
+                .map(|&page_id| (page_id, 0, false))
+                .collect::<Vec<_>>()
+        );
+        let before = bpm.read().unwrap().stats();
+        for &page_id in &page_ids[..3] {
+            BufferPoolManager::fetch_page_handle(&bpm, page_id).unwrap();
+        }
+        let after = bpm.read().unwrap().stats();
+        assert_eq!(after.hits, before.hits + 3);
+        assert_eq!(after.misses, before.misses);
+
+        // Without free frames, prefetching stops rather than evicting a page.
+        BufferPoolManager::prefetch(&bpm, &page_ids[3..]).unwrap();
+        assert_eq!(bpm.read().unwrap().resident_pages().len(), 3);
+        assert!(!bpm.read().unwrap().page_table.contains_key(&page_ids[3]));
+        BufferPoolManager::fetch_page_handle(&bpm, page_ids[3]).unwrap();
+        assert_eq!(bpm.read().unwrap().stats().misses, after.misses + 1);
+    }
+
+    #[test]
+    fn test_bpm_evict_callback() {
+        let bpm = get_memory_bpm_arc_with_pool_size(2);
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let callback_evicted = evicted.clone();
+        bpm.write()
+            .unwrap()
+            .set_evict_callback(Box::new(move |page_id| {
+                callback_evicted.lock().unwrap().push(page_id)
+            }));
+
+        let page_ids = (0..2)
+            .map(|_| {
+                BufferPoolManager::create_page_handle(&bpm)
+                    .unwrap()
+                    .page_id()
+            })
+            .collect::<Vec<_>>();
+        assert!(evicted.lock().unwrap().is_empty());
+
+        // A third page evicts the least recently used one.
+        let handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        assert_eq!(*evicted.lock().unwrap(), vec![page_ids[0]]);
+        drop(handle);
+
+        assert_eq!(bpm.write().unwrap().evict_batch(1), Ok(1));
+        assert_eq!(*evicted.lock().unwrap(), page_ids);
+    }
+
+    #[test]
+    fn test_bpm_background_flusher() {
+        let bpm = get_memory_bpm_arc_with_pool_size(5);
+        for mut page_handle in create_n_pages(&bpm, 3) {
+            page_handle.write(0, b"dirty").unwrap();
+        }
+        assert!(bpm
+            .read()
+            .unwrap()
+            .resident_pages()
+            .iter()
+            .all(|&(_, _, is_dirty)| is_dirty));
+
+        // Waits for the flusher to leave every page clean, failing after a generous deadline.
+        let wait_until_clean = || {
+            let deadline = std::time::Instant::now() + Duration::from_secs(10);
+            while bpm
+                .read()
+                .unwrap()
+                .resident_pages()
+                .iter()
+                .any(|&(_, _, is_dirty)| is_dirty)
+            {
+                assert!(std::time::Instant::now() < deadline, "pages never flushed");
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        let interval = Duration::from_millis(10);
+        let flusher = BufferPoolManager::start_background_flusher(bpm.clone(), interval).unwrap();
+        wait_until_clean();
+
+        // The pages were written back and are clean, but still resident.
+        let resident_pages = bpm.read().unwrap().resident_pages();
+        assert_eq!(resident_pages.len(), 3);
+        assert_eq!(background_flush_failures(), 0);
+
+        // Only one flusher can run at a time.
+        assert!(BufferPoolManager::start_background_flusher(bpm.clone(), interval).is_err());
+
+        // A page dirtied again is written back again on a later interval.
+        let mut page_handle =
+            BufferPoolManager::fetch_page_mut_handle(&bpm, resident_pages[0].0).unwrap();
+        page_handle.write(0, b"again").unwrap();
+        drop(page_handle);
+        assert!(bpm.read().unwrap().resident_pages()[0].2);
+        wait_until_clean();
+
+        bpm.write().unwrap().stop_background_flusher();
+        flusher.join().unwrap();
+
+        // Once stopped, a new flusher can be started.
+        let flusher = BufferPoolManager::start_background_flusher(bpm.clone(), interval).unwrap();
+        bpm.write().unwrap().stop_background_flusher();
+        flusher.join().unwrap();
+
+        // A flusher that isn't stopped exits once the buffer pool is dropped.
+        let flusher = BufferPoolManager::start_background_flusher(bpm.clone(), interval).unwrap();
+        let weak = Arc::downgrade(&bpm);
+        drop(bpm);
+        flusher.join().unwrap();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_bpm_delete_pinned_page_error() {
+        let bpm = get_memory_bpm_arc_with_pool_size(5);
+        let handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let pid = handle.page_id();
+
+        // The page is pinned by the handle, so it can't be deleted...
+        let result = bpm.write().unwrap().delete_page(pid);
+        assert_eq!(result, Err(Error::PagePinned(pid)));
+
+        // ...until the handle is dropped.
+        drop(handle);
+        assert_eq!(bpm.write().unwrap().delete_page(pid), Ok(()));
+    }
+
+    #[test]
+    fn test_bpm_recovers_from_panicked_threads() {
+        let bpm = get_memory_bpm_arc_with_pool_size(5);
+        let recoveries = poison_recoveries();
+        let page_id = {
+            let mut page_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+            page_handle.write(0, b"before").unwrap();
+            page_handle.page_id()
+        };
+
+        thread::scope(|s| {
+            // A thread panics while holding a mutable handle, poisoning the page's lock...
+            let writer = s.spawn(|| {
+                let mut page_handle =
+                    BufferPoolManager::fetch_page_mut_handle(&bpm, page_id).unwrap();
+                page_handle.write(0, b"during").unwrap();
+                panic!("writer failed");
+            });
+            assert!(writer.join().is_err());
+
+            // ...and another panics while holding the buffer pool's lock, poisoning it.
+            let holder = s.spawn(|| {
+                let _guard = bpm.write().unwrap();
+                panic!("holder failed");
+            });
+            assert!(holder.join().is_err());
+            assert!(bpm.is_poisoned());
+
+            // Other threads can still fetch the page, and see the panicked writer's changes.
+            let reader = s.spawn(|| {
+                let page_handle = BufferPoolManager::fetch_page_handle(&bpm, page_id).unwrap();
+                assert_eq!(&page_handle.data()[..6], b"during");
+            });
+            reader.join().unwrap();
+        });
+
+        // Both locks were recovered, and the page was unpinned by each of the handles.
+        assert!(poison_recoveries() >= recoveries + 2);
+        assert!(!bpm.is_poisoned());
+        assert_eq!(bpm.read().unwrap().get_pin_count(page_id), Some(0));
+
+        // Read locks are recovered the same way.
+        let holder = thread::spawn({
+            let bpm = bpm.clone();
+            move || {
+                let _guard = bpm.write().unwrap();
+                panic!("holder failed");
+            }
+        });
+        assert!(holder.join().is_err());
+        assert!(bpm.is_poisoned());
+        assert_eq!(BufferPoolManager::read_lock(&bpm).get_pin_count(page_id), Some(0));
+        assert!(!bpm.is_poisoned());
+    }
+
     #[test]
     #[serial]
     fn test_bpm_very_basic_test() {
+        very_basic_test(get_bpm_arc_with_pool_size);
+    }
+
+    #[test]
+    fn test_bpm_very_basic_test_in_memory() {
+        very_basic_test(get_memory_bpm_arc_with_pool_size);
+    }
+
+    fn very_basic_test(new_bpm: fn(usize) -> Arc<RwLock<BufferPoolManager>>) {
         let pool_size = 10;
-        let bpm = get_bpm_arc_with_pool_size(pool_size);
+        let bpm = new_bpm(pool_size);
 
         // Create a new page
         let pid = {
@@ -767,7 +1522,7 @@ mod tests {
             let mut write_guard = BufferPoolManager::fetch_page_mut_handle(&bpm, pid)
                 .expect("Failed to fetch page for writing");
 
-            write_guard.write(0, str_data);
+            write_guard.write(0, str_data).unwrap();
             let stored_data = &write_guard.data()[..str_data.len()];
 
             assert_eq!(stored_data, str_data);
@@ -826,11 +1581,11 @@ mod tests {
         {
             let mut page0_write = BufferPoolManager::fetch_page_mut_handle(&bpm, pageid0)
                 .expect("Failed to fetch page 0 for writing");
-            page0_write.write(0, str0);
+            page0_write.write(0, str0).unwrap();
 
             let mut page1_write = BufferPoolManager::fetch_page_mut_handle(&bpm, pageid1)
                 .expect("Failed to fetch page 1 for writing");
-            page1_write.write(0, str1);
+            page1_write.write(0, str1).unwrap();
 
             // Ensure pin counts are still 1
             assert_eq!(
@@ -886,7 +1641,7 @@ mod tests {
                         BufferPoolManager::fetch_page_mut_handle(&writer_bpm, pid)
                             .expect("Failed to fetch page for writing");
                     let data = i.to_string().into_bytes();
-                    page_handle.write(0, &data);
+                    page_handle.write(0, &data).unwrap();
                 } // Write lock is dropped here
             }
         });
@@ -951,7 +1706,7 @@ mod tests {
 
                     // Write the iteration number as bytes.
                     let data = i.to_string().into_bytes();
-                    page_handle.write(0, &data);
+                    page_handle.write(0, &data).unwrap();
 
                     // Dropping `page_handle` releases the page lock and unpins the page.
                 }
@@ -964,6 +1719,53 @@ mod tests {
         }
     }
 
+    /// Test that a retrying fetch waits out another thread pinning the only frame, while a plain
+    /// fetch fails right away.
+    #[test]
+    fn test_bpm_fetch_page_mut_handle_retry() {
+        let bpm = get_memory_bpm_arc_with_pool_size(1);
+        let page_id = BufferPoolManager::create_page_handle(&bpm)
+            .unwrap()
+            .page_id();
+        let pinned_page_id = BufferPoolManager::create_page_handle(&bpm)
+            .unwrap()
+            .page_id();
+
+        let pinned = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let _handle =
+                    BufferPoolManager::fetch_page_mut_handle(&bpm, pinned_page_id).unwrap();
+                pinned.wait();
+                thread::sleep(Duration::from_millis(50));
+            });
+            pinned.wait();
+
+            assert!(matches!(
+                BufferPoolManager::fetch_page_mut_handle(&bpm, page_id),
+                Err(Error::NoEvictableFrame)
+            ));
+            let handle = BufferPoolManager::fetch_page_mut_handle_retry(
+                &bpm,
+                page_id,
+                10,
+                Duration::from_millis(1),
+            )
+            .unwrap();
+            assert_eq!(handle.page_id(), page_id);
+        });
+
+        // Running out of attempts returns the error.
+        let _handle = BufferPoolManager::fetch_page_mut_handle(&bpm, page_id).unwrap();
+        let result = BufferPoolManager::fetch_page_mut_handle_retry(
+            &bpm,
+            pinned_page_id,
+            3,
+            Duration::from_millis(1),
+        );
+        assert!(matches!(result, Err(Error::NoEvictableFrame)));
+    }
+
     #[test]
     #[serial]
     fn test_bpm_page_pin_hard() {
@@ -994,7 +1796,7 @@ mod tests {
 
             // Write i
             let data = i.to_string().into_bytes();
-            page.write(0, &data);
+            page.write(0, &data).unwrap();
 
             // Keep pinned
             pages_write.insert(pid, page);
@@ -1118,7 +1920,7 @@ mod tests {
             {
                 let mut page5 = fetch_page_mut_handle_or_none(&bpm, pid5)
                     .expect("Should be able to pin page5 for writing");
-                page5.write(0, updated5);
+                page5.write(0, updated5).unwrap();
                 // Dropping => unpin
             }
 
@@ -1126,14 +1928,14 @@ mod tests {
             {
                 let mut page6 = fetch_page_mut_handle_or_none(&bpm, pid6)
                     .expect("Should be able to pin page6 for writing");
-                page6.write(0, updated6);
+                page6.write(0, updated6).unwrap();
             }
 
             // Write updates to page7
             {
                 let mut page7 = fetch_page_mut_handle_or_none(&bpm, pid7)
                     .expect("Should be able to pin page7 for writing");
-                page7.write(0, updated7);
+                page7.write(0, updated7).unwrap();
             }
 
             // After dropping them, each pin count => 0
@@ -1192,7 +1994,7 @@ mod tests {
 
             // Write another update
             let updated6_12345 = b"12345updatedpage6";
-            page6_write_opt.write(0, updated6_12345);
+            page6_write_opt.write(0, updated6_12345).unwrap();
 
             // Try creating a new page => might fail if everything is pinned
             let new_pid3 = {
@@ -1245,7 +2047,7 @@ mod tests {
     fn fetch_page_handle_or_none(
         bpm: &Arc<RwLock<BufferPoolManager>>,
         pid: PageId,
-    ) -> Option<PageFrameRefHandle> {
+    ) -> Option<PageFrameRefHandle<'_>> {
         match BufferPoolManager::fetch_page_handle(bpm, pid) {
             Ok(h) => Some(h),
             Err(_) => None,
@@ -1255,7 +2057,7 @@ mod tests {
     fn fetch_page_mut_handle_or_none(
         bpm: &Arc<RwLock<BufferPoolManager>>,
         pid: PageId,
-    ) -> Option<PageFrameMutHandle> {
+    ) -> Option<PageFrameMutHandle<'_>> {
         match BufferPoolManager::fetch_page_mut_handle(bpm, pid) {
             Ok(h) => Some(h),
             Err(_) => None,
@@ -1292,7 +2094,7 @@ mod tests {
         }
 
         // Write random data to the page and verify it immediately.
-        page0_write.write(0, &random_data);
+        page0_write.write(0, &random_data).unwrap();
         assert_eq!(&page0_write.data()[..], &random_data[..]);
 
         // Unpin page0 by dropping it.
@@ -1414,7 +2216,7 @@ mod tests {
 
             // Write data (e.g., the index `i`) for clarity
             let data = i.to_string().into_bytes();
-            page_handle.write(0, &data);
+            page_handle.write(0, &data).unwrap();
 
             // Store the pinned handle
             pgs.push(page_handle);
@@ -1454,7 +2256,7 @@ mod tests {
                 .expect("Failed to fetch new page for writing");
             // Optionally write some data
             let data = b"some new data";
-            page_handle.write(0, data);
+            page_handle.write(0, data).unwrap();
 
             pgs.push(page_handle);
         }
@@ -1481,7 +2283,7 @@ mod tests {
             };
             let mut page_handle = BufferPoolManager::fetch_page_mut_handle(&bpm, page_id)
                 .expect("Failed to fetch page for writing");
-            page_handle.write(0, b"some fresh data");
+            page_handle.write(0, b"some fresh data").unwrap();
             pgs.push(page_handle);
         }
 
@@ -1685,7 +2487,7 @@ mod tests {
 
                         // Write the page_id as string
                         let data = page_id.to_string().into_bytes();
-                        page_handle.write(0, &data);
+                        page_handle.write(0, &data).unwrap();
 
                         // Keep pinned
                         pages.push(page_handle);
@@ -1772,7 +2574,7 @@ mod tests {
 
         let writer1 = thread::spawn(move || {
             let mut page = BufferPoolManager::fetch_page_mut_handle(&bpm1, pid).unwrap();
-            page.write(0, b"Writer1");
+            page.write(0, b"Writer1").unwrap();
             thread::sleep(Duration::from_millis(50));
             assert!(std::str::from_utf8(page.data())
                 .unwrap()
@@ -1782,7 +2584,7 @@ mod tests {
         let writer2 = thread::spawn(move || {
             thread::sleep(Duration::from_millis(10));
             let mut page = BufferPoolManager::fetch_page_mut_handle(&bpm2, pid).unwrap();
-            page.write(0, b"Writer2");
+            page.write(0, b"Writer2").unwrap();
         });
 
         writer1.join().unwrap();
@@ -0,0 +1,33 @@
+use std::fmt::Debug;
+
+use bytes::Bytes;
+
+use crate::typedef::PageId;
+use crate::Result;
+
+/// A backend that the buffer pool reads pages from and writes pages to.
+pub trait Disk: Send + Debug {
+    /// Returns the size of each page, in bytes.
+    fn page_size(&self) -> usize;
+
+    /// Allocates a new, zeroed page and returns its id.
+    fn allocate_page(&mut self) -> Result<PageId>;
+
+    /// Deallocates a page, freeing its space for future allocations.
+    /// Returns an error if the page does not exist.
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<()>;
+
+    /// Returns whether a page exists, i.e. it has been allocated or written and not deallocated
+    /// since.
+    fn contains_page(&self, page_id: PageId) -> bool;
+
+    /// Reads a page, returning `None` if it does not exist.
+    fn read(&mut self, page_id: PageId) -> Result<Option<Bytes>>;
+
+    /// Writes data to the start of a page, creating the page if it does not exist.
+    /// The data must not exceed the page size.
+    fn write(&mut self, page_id: PageId, data: &[u8]) -> Result<()>;
+
+    /// Makes every write so far durable, for backends that don't already do so on each write.
+    fn sync(&mut self) -> Result<()>;
+}
@@ -1,21 +1,95 @@
+use crate::disk::disk::Disk;
+use crate::page::PAGE_SIZE;
 use crate::typedef::PageId;
 use crate::Result;
 use bytes::{Bytes, BytesMut};
 use fs2::FileExt;
+use rustdb_catalog::catalog::CatalogStore;
 use rustdb_error::{errdata, Error};
 use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub(crate) const DATA_DIR: &str = "src/disk/data/";
-const PAGE_SIZE_BYTES: usize = 4096;
 
-const EMPTY_BUFFER: &[u8] = &[0; PAGE_SIZE_BYTES];
+/// The magic bytes at the start of every database file.
+const FILE_MAGIC: &[u8; 8] = b"RUSTDB01";
+/// The size of the file header: the magic bytes, the page size as a `u32`, and the creation time
+/// in seconds since the Unix epoch as a `u64`, all little-endian. The header takes up the first
+/// slot of the file, so that pages stay aligned.
+const FILE_HEADER_SIZE: usize = FILE_MAGIC.len() + size_of::<u32>() + size_of::<u64>();
+
+/// The size of the checksum stored in front of each page in the file: a little-endian CRC-32 of
+/// the page (see [`crc32`]). Each page takes up a slot of `CHECKSUM_SIZE + page_size` bytes.
+const CHECKSUM_SIZE: usize = size_of::<u32>();
+
+/// The number of slots at the start of the file before the first page: one for the file header,
+/// and one for the catalog.
+const HEADER_SLOTS: u64 = 2;
+
+/// The id under which errors report the catalog's slot, which is the second slot of the file.
+/// Page ids handed out by `allocate_page` start at 1, so no page has this id.
+const CATALOG_PAGE_ID: PageId = 0;
+
+/// The lookup table for [`crc32`], one entry per byte value.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-32 (the variant used by zlib) of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// How a [`DiskManager`] grows its file once it's out of room for new pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum GrowthPolicy {
+    /// Doubles the file's capacity.
+    #[default]
+    Double,
+    /// Grows the file by a fixed number of pages (at least one).
+    FixedChunk(usize),
+    /// Sizes the file for the given total number of pages up front, so that loading up to that
+    /// many pages never resizes it. Past that, the file grows like [`GrowthPolicy::Double`].
+    Reserve(usize),
+}
+
+/// When a [`DiskManager`] syncs its writes to the storage device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Durability {
+    /// Syncs the file after every page write, so that each write is durable once it returns.
+    #[default]
+    SyncEachWrite,
+    /// Only syncs the file on [`Disk::sync`], so that many writes can share a single sync. Writes
+    /// since the last sync may be lost on a crash.
+    SyncOnFlush,
+}
 
 #[derive(Debug)]
 pub struct DiskManager {
     file: RefCell<std::fs::File>,
+    /// The size of each page, in bytes.
+    page_size: usize,
     /// The maximum capacity (in pages) that the file can hold before we resize it.
     page_capacity: usize,
     /// Tracks the highest page_id allocated so far.
@@ -24,12 +98,29 @@ pub struct DiskManager {
     pages: HashMap<PageId, u64>,
     /// Free file offsets to reuse for future page allocations.
     free_slots: VecDeque<u64>,
+    /// How the file grows once it reaches `page_capacity`.
+    growth_policy: GrowthPolicy,
+    /// The number of times the file has been resized.
+    resize_count: usize,
+    /// When writes are synced to the storage device.
+    durability: Durability,
+    /// The number of times the file has been synced.
+    sync_count: usize,
+    /// Whether the file was opened by [`DiskManager::open_read_only`], rejecting modifications.
+    read_only: bool,
 }
 
 impl DiskManager {
-    /// Creates a new disk manager for the given database file `filename`.
-    /// The file is truncated and locked exclusively at creation.
+    /// Creates a new disk manager for the given database file `filename`, using the default page
+    /// size. The file is truncated and locked exclusively at creation.
     pub(crate) fn new(filename: &str) -> Result<Self> {
+        Self::with_page_size(filename, PAGE_SIZE)
+    }
+
+    /// Creates a new disk manager for the given database file `filename`, with pages of
+    /// `page_size` bytes. The file is truncated and locked exclusively at creation, and starts
+    /// with a header identifying it (see [`DiskManager::open`]).
+    pub(crate) fn with_page_size(filename: &str, page_size: usize) -> Result<Self> {
         let path = Path::new(DATA_DIR).join(filename);
 
         // Open or create the file, truncating it
@@ -48,20 +139,268 @@ impl DiskManager {
         // Build the DiskManager struct
         let mut dm = Self {
             file: RefCell::new(file),
+            page_size,
             page_capacity: 32, // Start with 32 as the default capacity
             last_allocated_pid: 0,
             pages: HashMap::new(),
             free_slots: VecDeque::new(),
+            growth_policy: GrowthPolicy::default(),
+            resize_count: 0,
+            durability: Durability::default(),
+            sync_count: 0,
+            read_only: false,
         };
 
-        // Initialize the file with enough space for `page_capacity + 1` pages
+        // Initialize the file with enough space for `page_capacity` pages after the file header
+        // and the catalog
         dm.resize_file()?;
+        dm.write_header()?;
 
         Ok(dm)
     }
 
+    /// Opens the existing database file `filename`, created by [`DiskManager::new`] with pages of
+    /// the default size, and locks it exclusively.
+    ///
+    /// Returns [`Error::InvalidData`] if the file doesn't start with a valid header (see
+    /// [`FILE_MAGIC`]) or its pages aren't of the default size.
+    ///
+    /// The mapping from page ids to file offsets isn't stored in the file, so the file is read as
+    /// if its pages were allocated in order and never deallocated: page `p` is in the `p`-th slot
+    /// after the file header and the catalog, for every page that fits in the file.
+    pub(crate) fn open(filename: &str) -> Result<Self> {
+        Self::open_existing(filename, false)
+    }
+
+    /// Opens the existing database file `filename` like [`DiskManager::open`], but for reading
+    /// only, e.g. so that a tool can inspect a database alongside the process that owns it. The
+    /// file is locked shared, which fails rather than waits if another disk manager holds the
+    /// exclusive lock, and writing, allocating and deallocating pages fail with [`Error::IO`].
+    pub(crate) fn open_read_only(filename: &str) -> Result<Self> {
+        Self::open_existing(filename, true)
+    }
+
+    fn open_existing(filename: &str, read_only: bool) -> Result<Self> {
+        let path = Path::new(DATA_DIR).join(filename);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(!read_only)
+            .open(&path)
+            .map_err(|e| Error::IO(format!("Unable to open file {}: {}", path.display(), e)))?;
+        if read_only {
+            file.try_lock_shared()
+                .map_err(|e| Error::IO(format!("Failed to acquire shared file lock: {}", e)))?;
+        } else {
+            file.lock_exclusive()
+                .map_err(|e| Error::IO(format!("Failed to acquire exclusive file lock: {}", e)))?;
+        }
+
+        let page_size = PAGE_SIZE;
+        let file = RefCell::new(file);
+        Self::check_header(&mut file.borrow_mut(), page_size)?;
+        let file_size = file
+            .borrow()
+            .metadata()
+            .map_err(|e| Error::IO(format!("Failed to get file size: {}", e)))?
+            .len();
+        let slot_size = (CHECKSUM_SIZE + page_size) as u64;
+        let page_count = (file_size / slot_size).saturating_sub(HEADER_SLOTS);
+        let pages = (1..=page_count)
+            .map(|p| (p as PageId, (p - 1 + HEADER_SLOTS) * slot_size))
+            .collect();
+        Ok(Self {
+            file,
+            page_size,
+            page_capacity: (page_count as usize).max(1),
+            last_allocated_pid: page_count as PageId,
+            pages,
+            free_slots: VecDeque::new(),
+            growth_policy: GrowthPolicy::default(),
+            resize_count: 0,
+            durability: Durability::default(),
+            sync_count: 0,
+            read_only,
+        })
+    }
+
+    /// Writes the file header, identifying the file as a database with pages of `page_size`
+    /// bytes created now.
+    fn write_header(&self) -> Result<()> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        let mut header = Vec::with_capacity(FILE_HEADER_SIZE);
+        header.extend_from_slice(FILE_MAGIC);
+        header.extend_from_slice(&(self.page_size as u32).to_le_bytes());
+        header.extend_from_slice(&created_at.to_le_bytes());
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(&header))
+            .map_err(|e| Error::IO(format!("Failed to write file header: {}", e)))
+    }
+
+    /// Checks that `file` starts with a valid header for pages of `page_size` bytes, returning
+    /// [`Error::InvalidData`] otherwise.
+    fn check_header(file: &mut std::fs::File, page_size: usize) -> Result<()> {
+        let mut header = [0; FILE_HEADER_SIZE];
+        match file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| file.read_exact(&mut header))
+        {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return errdata!("File is too short to be a database file");
+            }
+            Err(e) => return Err(Error::IO(format!("Failed to read file header: {}", e))),
+        }
+        if &header[..FILE_MAGIC.len()] != FILE_MAGIC {
+            return errdata!("File is not a database file: bad magic bytes");
+        }
+        let page_size_bytes = &header[FILE_MAGIC.len()..FILE_MAGIC.len() + size_of::<u32>()];
+        let file_page_size = u32::from_le_bytes(page_size_bytes.try_into()?) as usize;
+        if file_page_size != page_size {
+            return errdata!(
+                "File has pages of {file_page_size} bytes, expected pages of {page_size} bytes"
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the size of each slot of the file: a page and its checksum.
+    fn slot_size(&self) -> u64 {
+        (CHECKSUM_SIZE + self.page_size) as u64
+    }
+
+    /// Returns the offset of the slot holding the catalog, right after the file header.
+    fn catalog_offset(&self) -> u64 {
+        self.slot_size()
+    }
+
+    /// Returns [`Error::IO`] if the disk manager is read-only, naming the rejected `operation`.
+    fn check_writable(&self, operation: &str) -> Result<()> {
+        if self.read_only {
+            return Err(Error::IO(format!("Cannot {operation}: disk is read-only")));
+        }
+        Ok(())
+    }
+
+    /// Sets how the file grows once it's out of room for new pages. With
+    /// [`GrowthPolicy::Reserve`], the file is resized for the reserved number of pages right away.
+    pub(crate) fn with_growth_policy(mut self, growth_policy: GrowthPolicy) -> Result<Self> {
+        self.growth_policy = growth_policy;
+        if let GrowthPolicy::Reserve(total_pages) = growth_policy {
+            if total_pages > self.page_capacity {
+                self.page_capacity = total_pages;
+                self.resize_file()?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Sets when writes are synced to the storage device.
+    pub(crate) fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Helper: For new pages, we either reuse a free offset from `free_slots_` or append at the end.
+    fn allocate_offset(&mut self) -> Result<u64> {
+        // If we have a free offset from a previously deleted page, reuse it
+        if let Some(off) = self.free_slots.pop_front() {
+            return Ok(off);
+        }
+
+        // Otherwise, offset is pages_.len() * page_size,
+        // but only if we have capacity
+        let used_pages = self.pages.len() as u64;
+        if used_pages + 1 >= self.page_capacity as u64 {
+            // resize (per the growth policy) if needed
+            self.page_capacity = match self.growth_policy {
+                GrowthPolicy::Double | GrowthPolicy::Reserve(_) => self.page_capacity * 2,
+                GrowthPolicy::FixedChunk(pages) => self.page_capacity + pages.max(1),
+            };
+            self.resize_file()?;
+        }
+
+        // The new offset is after the used slots, including those of the file header and catalog
+        let offset = (used_pages + HEADER_SLOTS) * self.slot_size();
+        Ok(offset)
+    }
+
+    /// Actually resizes the underlying file to (page_capacity + HEADER_SLOTS) slots
+    fn resize_file(&mut self) -> Result<()> {
+        let size = (self.page_capacity as u64 + HEADER_SLOTS) * self.slot_size();
+        let file = self.file.borrow();
+        file.set_len(size)
+            .map_err(|e| Error::IO(format!("Failed to resize file: {}", e)))?;
+        self.resize_count += 1;
+        Ok(())
+    }
+
+    /// Reads the page with id `page_id` from the slot at `offset` in the file, or `None` if the
+    /// slot was never written. IO errors name the page and offset.
+    ///
+    /// Returns [`Error::InvalidData`] if the page doesn't match its checksum.
+    fn read_at(&self, page_id: PageId, offset: u64) -> Result<Option<Bytes>> {
+        let mut file = self.file.borrow_mut();
+        let mut slot = BytesMut::zeroed(CHECKSUM_SIZE + self.page_size);
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut slot))
+            .map_err(|e| {
+                Error::IO(format!(
+                    "Failed to read page {} at offset {}: {}",
+                    page_id, offset, e
+                ))
+            })?;
+        let data = slot.split_off(CHECKSUM_SIZE);
+        let checksum = u32::from_le_bytes(slot[..].try_into()?);
+        // Slots that were never written are all zeros, without a checksum.
+        if checksum == 0 && data.iter().all(|&byte| byte == 0) {
+            return Ok(None);
+        }
+        if checksum != crc32(&data) {
+            return errdata!("Checksum mismatch for page {page_id} at offset {offset}");
+        }
+        Ok(Some(data.freeze()))
+    }
+
+    /// Writes `data`, padded with zeros to the page size, and its checksum to the slot at
+    /// `offset` in the file, for the page with id `page_id`. IO errors name the page and offset.
+    fn write_at(&self, page_id: PageId, offset: u64, data: &[u8]) -> Result<()> {
+        let mut slot = vec![0; CHECKSUM_SIZE + self.page_size];
+        slot[CHECKSUM_SIZE..CHECKSUM_SIZE + data.len()].copy_from_slice(data);
+        let checksum = crc32(&slot[CHECKSUM_SIZE..]);
+        slot[..CHECKSUM_SIZE].copy_from_slice(&checksum.to_le_bytes());
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.write_all(&slot))
+            .map_err(|e| {
+                Error::IO(format!(
+                    "Failed to write page {} at offset {}: {}",
+                    page_id, offset, e
+                ))
+            })
+    }
+
+    /// Returns the current size of the database file.
+    pub fn get_db_file_size(&self) -> Result<u64> {
+        let file = self.file.borrow();
+        file.metadata()
+            .map(|meta| meta.len())
+            .map_err(|e| Error::IO(format!("Failed to get file size: {}", e)))
+    }
+}
+
+impl Disk for DiskManager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
     /// Allocate a new page_id and a file offset for storing it.
-    pub fn allocate_page(&mut self) -> Result<PageId> {
+    fn allocate_page(&mut self) -> Result<PageId> {
+        self.check_writable("allocate a page")?;
         self.last_allocated_pid += 1;
         let pid = self.last_allocated_pid;
 
@@ -70,14 +409,15 @@ impl DiskManager {
         // Record pid -> offset
         self.pages.insert(pid, new_offset);
         // Initialize the page with empty data
-        self.write(pid, EMPTY_BUFFER)?;
+        self.write(pid, &vec![0; self.page_size])?;
 
         Ok(pid)
     }
 
     /// Deallocates a page and adds its offset to the free list.
     /// Returns an error if the page ID does not exist.
-    pub fn deallocate_page(&mut self, page_id: PageId) -> Result<()> {
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<()> {
+        self.check_writable(&format!("deallocate page {page_id}"))?;
         if let Some(offset) = self.pages.remove(&page_id) {
             self.free_slots.push_back(offset);
             Ok(())
@@ -89,8 +429,14 @@ impl DiskManager {
         }
     }
 
+    fn contains_page(&self, page_id: PageId) -> bool {
+        self.pages.contains_key(&page_id)
+    }
+
     /// Read a page if it exists. If not found, returns None or an error.
-    pub(crate) fn read(&mut self, page_id: PageId) -> Result<Option<Bytes>> {
+    ///
+    /// Returns [`Error::InvalidData`] if the page doesn't match the checksum written with it.
+    fn read(&mut self, page_id: PageId) -> Result<Option<Bytes>> {
         let offset = match self.pages.get(&page_id) {
             Some(&off) => off,
             None => {
@@ -99,17 +445,17 @@ impl DiskManager {
             }
         };
 
-        let mut file = self.file.borrow_mut();
-        file.seek(SeekFrom::Start(offset))?;
-
-        let mut bytes = BytesMut::zeroed(PAGE_SIZE_BYTES);
-        file.read_exact(&mut bytes)?;
-        Ok(Some(bytes.freeze()))
+        // Pages that fit in an opened file but were never written read as empty pages.
+        let page = self.read_at(page_id, offset)?;
+        Ok(Some(page.unwrap_or_else(|| {
+            BytesMut::zeroed(self.page_size).freeze()
+        })))
     }
 
-    /// Write data to a page. Must not exceed PAGE_SIZE_BYTES.
-    pub(crate) fn write(&mut self, page_id: PageId, data: &[u8]) -> Result<()> {
-        if data.len() > PAGE_SIZE_BYTES {
+    /// Write data to a page. Must not exceed the page size, and is padded with zeros up to it.
+    fn write(&mut self, page_id: PageId, data: &[u8]) -> Result<()> {
+        self.check_writable(&format!("write page {page_id}"))?;
+        if data.len() > self.page_size {
             return errdata!("Page data must fit in a page.");
         }
 
@@ -123,50 +469,40 @@ impl DiskManager {
             }
         };
 
-        let mut file = self.file.borrow_mut();
-        file.seek(std::io::SeekFrom::Start(offset))?;
-        file.write_all(data)?;
-        file.sync_all()?;
+        self.write_at(page_id, offset, data)?;
+        if self.durability == Durability::SyncEachWrite {
+            self.sync()?;
+        }
 
         Ok(())
     }
 
-    /// Helper: For new pages, we either reuse a free offset from `free_slots_` or append at the end.
-    fn allocate_offset(&mut self) -> Result<u64> {
-        // If we have a free offset from a previously deleted page, reuse it
-        if let Some(off) = self.free_slots.pop_front() {
-            return Ok(off);
-        }
-
-        // Otherwise, offset is pages_.len() * PAGE_SIZE_BYTES,
-        // but only if we have capacity
-        let used_pages = self.pages.len() as u64;
-        if used_pages + 1 >= self.page_capacity as u64 {
-            // resize (double capacity) if needed
-            self.page_capacity *= 2;
-            self.resize_file()?;
-        }
-
-        // The new offset is used_pages * PAGE_SIZE_BYTES
-        let offset = used_pages * PAGE_SIZE_BYTES as u64;
-        Ok(offset)
+    fn sync(&mut self) -> Result<()> {
+        self.file.borrow().sync_all()?;
+        self.sync_count += 1;
+        Ok(())
     }
+}
 
-    /// Actually resizes the underlying file to (page_capacity + 1) * PAGE_SIZE_BYTES
-    fn resize_file(&mut self) -> Result<()> {
-        let size = (self.page_capacity as u64 + 1) * PAGE_SIZE_BYTES as u64;
-        let file = self.file.borrow();
-        file.set_len(size)
-            .map_err(|e| Error::IO(format!("Failed to resize file: {}", e)))?;
-        Ok(())
+impl CatalogStore for DiskManager {
+    /// Reads the catalog from its slot after the file header, or `None` if it was never
+    /// written.
+    fn read_catalog(&mut self) -> Result<Option<Vec<u8>>> {
+        let page = self.read_at(CATALOG_PAGE_ID, self.catalog_offset())?;
+        Ok(page.map(|bytes| bytes.to_vec()))
     }
 
-    /// Returns the current size of the database file.
-    pub fn get_db_file_size(&self) -> Result<u64> {
-        let file = self.file.borrow();
-        file.metadata()
-            .map(|meta| meta.len())
-            .map_err(|e| Error::IO(format!("Failed to get file size: {}", e)))
+    /// Writes the catalog to its slot after the file header. Must not exceed the page size.
+    fn write_catalog(&mut self, bytes: &[u8]) -> Result<()> {
+        self.check_writable("write the catalog")?;
+        if bytes.len() > self.page_size {
+            return errdata!("Catalog must fit in a page.");
+        }
+        self.write_at(CATALOG_PAGE_ID, self.catalog_offset(), bytes)?;
+        if self.durability == Durability::SyncEachWrite {
+            self.sync()?;
+        }
+        Ok(())
     }
 }
 
@@ -184,4 +520,254 @@ impl Drop for DiskManager {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use rand::Rng;
+
+    use super::{crc32, DiskManager, Durability, GrowthPolicy, DATA_DIR, HEADER_SLOTS};
+    use crate::disk::disk::Disk;
+    use crate::page::PAGE_SIZE;
+    use crate::Result;
+    use rustdb_catalog::catalog::CatalogStore;
+    use rustdb_error::Error;
+
+    /// Test that each growth policy resizes the file as often as expected.
+    #[test]
+    #[serial]
+    fn test_growth_policy() -> Result<()> {
+        // Reserving room up front resizes the file once, and never again while it has room.
+        let mut disk =
+            DiskManager::new("test.db")?.with_growth_policy(GrowthPolicy::Reserve(1000))?;
+        let resize_count = disk.resize_count;
+        for _ in 0..900 {
+            disk.allocate_page()?;
+        }
+        assert_eq!(disk.resize_count, resize_count);
+        assert_eq!(
+            disk.get_db_file_size()?,
+            (1000 + HEADER_SLOTS) * disk.slot_size()
+        );
+        drop(disk);
+
+        // Fixed chunks grow the file by the same number of pages each time.
+        let mut disk =
+            DiskManager::new("test.db")?.with_growth_policy(GrowthPolicy::FixedChunk(10))?;
+        let resize_count = disk.resize_count;
+        for _ in 0..50 {
+            disk.allocate_page()?;
+        }
+        assert_eq!(disk.resize_count, resize_count + 2);
+        assert_eq!(disk.page_capacity, 52);
+        drop(disk);
 
+        // By default, the capacity doubles.
+        let mut disk = DiskManager::new("test.db")?;
+        let resize_count = disk.resize_count;
+        for _ in 0..100 {
+            disk.allocate_page()?;
+        }
+        assert_eq!(disk.resize_count, resize_count + 2);
+        assert_eq!(disk.page_capacity, 128);
+        Ok(())
+    }
+
+    /// Test that deferring syncs until an explicit sync leaves the same pages as syncing on every
+    /// write.
+    #[test]
+    #[serial]
+    fn test_sync_on_flush() -> Result<()> {
+        let write_pages = |durability| -> Result<(Vec<u8>, usize)> {
+            let mut disk = DiskManager::new("test.db")?.with_durability(durability);
+            let sync_count = disk.sync_count;
+            for i in 0..10u8 {
+                let page_id = disk.allocate_page()?;
+                disk.write(page_id, &vec![i; disk.page_size() / 2])?;
+            }
+            disk.sync()?;
+            let syncs = disk.sync_count - sync_count;
+            let page_size = disk.page_size();
+            drop(disk);
+            // Skip the file header, whose creation time may differ.
+            let file = std::fs::read(format!("{DATA_DIR}test.db"))?;
+            Ok((file[page_size..].to_vec(), syncs))
+        };
+
+        let (each_write, each_write_syncs) = write_pages(Durability::SyncEachWrite)?;
+        let (on_flush, on_flush_syncs) = write_pages(Durability::SyncOnFlush)?;
+        assert_eq!(on_flush, each_write);
+        assert_eq!(each_write_syncs, 21);
+        assert_eq!(on_flush_syncs, 1);
+        Ok(())
+    }
+
+    /// Test that opening a database file checks its header.
+    #[test]
+    #[serial]
+    fn test_open() -> Result<()> {
+        let mut disk = DiskManager::new("test.db")?;
+        let page_id = disk.allocate_page()?;
+        disk.write(page_id, &[7; 16])?;
+        drop(disk);
+
+        // A freshly created file opens, with its pages where they were written.
+        let mut disk = DiskManager::open("test.db")?;
+        assert_eq!(
+            disk.read(page_id)?.map(|page| page[..16].to_vec()),
+            Some(vec![7; 16])
+        );
+        let new_page_id = disk.allocate_page()?;
+        assert_ne!(new_page_id, page_id);
+        drop(disk);
+
+        // Files with other page sizes don't.
+        drop(DiskManager::with_page_size("test.db", 2 * PAGE_SIZE)?);
+        assert!(matches!(
+            DiskManager::open("test.db"),
+            Err(Error::InvalidData(_))
+        ));
+
+        // Nor do random files, or files too short for a header.
+        let path = format!("{DATA_DIR}test.db");
+        let mut random = vec![0; 4 * PAGE_SIZE];
+        rand::rng().fill(&mut random[..]);
+        for contents in [random, b"RUSTDB".to_vec()] {
+            std::fs::write(&path, contents)?;
+            for result in [
+                DiskManager::open("test.db").map(|_| ()),
+                DiskManager::open_read_only("test.db").map(|_| ()),
+            ] {
+                assert!(matches!(result, Err(Error::InvalidData(_))), "{result:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Test that a read-only disk manager reads the pages written by a writer, but can't modify
+    /// them, and can share the file with other readers.
+    #[test]
+    #[serial]
+    fn test_open_read_only() -> Result<()> {
+        let mut writer = DiskManager::new("test.db")?;
+        let mut page_ids = Vec::new();
+        for i in 0..5u8 {
+            let page_id = writer.allocate_page()?;
+            writer.write(page_id, &vec![i; writer.page_size()])?;
+            page_ids.push(page_id);
+        }
+        // Readers can't share the file with a writer.
+        assert!(matches!(
+            DiskManager::open_read_only("test.db"),
+            Err(Error::IO(_))
+        ));
+        drop(writer);
+
+        let mut reader = DiskManager::open_read_only("test.db")?;
+        let mut other_reader = DiskManager::open_read_only("test.db")?;
+        for (i, &page_id) in page_ids.iter().enumerate() {
+            let expected = vec![i as u8; reader.page_size()];
+            assert_eq!(reader.read(page_id)?.as_deref(), Some(expected.as_slice()));
+            assert_eq!(other_reader.read(page_id)?, reader.read(page_id)?);
+        }
+
+        for result in [
+            reader.write(page_ids[0], &[1, 2, 3]),
+            reader.allocate_page().map(|_| ()),
+            reader.deallocate_page(page_ids[0]),
+        ] {
+            match result {
+                Err(Error::IO(message)) => assert!(message.contains("read-only"), "{message}"),
+                result => panic!("expected an IO error, got {result:?}"),
+            }
+        }
+        assert_eq!(reader.read(page_ids[0])?.map(|page| page[0]), Some(0));
+        Ok(())
+    }
+
+    /// Test that the catalog is kept apart from the pages, and survives reopening the file.
+    #[test]
+    #[serial]
+    fn test_catalog_survives_reopen() -> Result<()> {
+        let mut disk = DiskManager::new("test.db")?;
+        assert_eq!(disk.read_catalog()?, None);
+        let page_id = disk.allocate_page()?;
+        disk.write(page_id, &[1; 16])?;
+        disk.write_catalog(&[2; 16])?;
+        drop(disk);
+
+        let mut disk = DiskManager::open("test.db")?;
+        let catalog = disk.read_catalog()?.unwrap();
+        assert_eq!(catalog[..16], [2; 16]);
+        assert_eq!(
+            disk.read(page_id)?.map(|page| page[..16].to_vec()),
+            Some(vec![1; 16])
+        );
+
+        // Allocating and writing pages leaves the catalog alone.
+        for _ in 0..100 {
+            let page_id = disk.allocate_page()?;
+            disk.write(page_id, &vec![3; disk.page_size()])?;
+        }
+        assert_eq!(disk.read_catalog()?, Some(catalog));
+        Ok(())
+    }
+
+    /// Test that reading a page whose bytes changed on disk fails its checksum.
+    #[test]
+    #[serial]
+    fn test_checksum() -> Result<()> {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut disk = DiskManager::new("test.db")?;
+        let page_ids = [disk.allocate_page()?, disk.allocate_page()?];
+        for &page_id in &page_ids {
+            disk.write(page_id, &[page_id as u8; 16])?;
+        }
+        disk.write_catalog(&[9; 16])?;
+        let offset = disk.pages[&page_ids[0]];
+        let catalog_offset = disk.catalog_offset();
+        drop(disk);
+
+        // Flip a byte in the first page and in the catalog.
+        let path = format!("{DATA_DIR}test.db");
+        let mut file = std::fs::read(&path)?;
+        file[offset as usize + 100] ^= 1;
+        file[catalog_offset as usize + 8] ^= 1;
+        std::fs::write(&path, file)?;
+
+        let mut disk = DiskManager::open("test.db")?;
+        match disk.read(page_ids[0]) {
+            Err(Error::InvalidData(message)) => {
+                assert!(
+                    message.contains(&format!("page {}", page_ids[0])),
+                    "{message}"
+                )
+            }
+            result => panic!("expected a checksum mismatch, got {result:?}"),
+        }
+        assert!(matches!(disk.read_catalog(), Err(Error::InvalidData(_))));
+        assert_eq!(
+            disk.read(page_ids[1])?.map(|page| page[..16].to_vec()),
+            Some(vec![page_ids[1] as u8; 16])
+        );
+        Ok(())
+    }
+
+    /// Test that IO errors say which page and offset they happened at.
+    #[test]
+    #[serial]
+    fn test_io_error_context() -> Result<()> {
+        let mut disk = DiskManager::new("test.db")?;
+        let offset = disk.get_db_file_size()? + 10 * disk.page_size() as u64;
+        disk.pages.insert(42, offset);
+        match disk.read(42) {
+            Err(Error::IO(message)) => {
+                assert!(message.contains("page 42"), "{message}");
+                assert!(message.contains(&format!("offset {offset}")), "{message}");
+            }
+            result => panic!("expected an IO error, got {result:?}"),
+        }
+        Ok(())
+    }
+}
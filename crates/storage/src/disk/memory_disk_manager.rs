@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use rustdb_error::{errdata, Error};
+
+use crate::disk::disk::Disk;
+use crate::page::PAGE_SIZE;
+use crate::typedef::PageId;
+use crate::Result;
+
+/// A [`Disk`] that keeps its pages in memory, so nothing is persisted. Useful for tests that don't
+/// need to touch the filesystem.
+#[derive(Debug)]
+pub struct MemoryDiskManager {
+    /// The size of each page, in bytes.
+    page_size: usize,
+    /// Map from page_id -> page data
+    pages: HashMap<PageId, Box<[u8]>>,
+    /// Tracks the highest page_id allocated so far.
+    last_allocated_pid: PageId,
+}
+
+impl MemoryDiskManager {
+    /// Creates a new, empty in-memory disk, using the default page size.
+    pub(crate) fn new() -> Self {
+        Self::with_page_size(PAGE_SIZE)
+    }
+
+    /// Creates a new, empty in-memory disk with pages of `page_size` bytes.
+    pub(crate) fn with_page_size(page_size: usize) -> Self {
+        Self {
+            page_size,
+            pages: HashMap::new(),
+            last_allocated_pid: 0,
+        }
+    }
+
+    fn empty_page(&self) -> Box<[u8]> {
+        vec![0; self.page_size].into_boxed_slice()
+    }
+}
+
+impl Disk for MemoryDiskManager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn allocate_page(&mut self) -> Result<PageId> {
+        self.last_allocated_pid += 1;
+        let pid = self.last_allocated_pid;
+        self.pages.insert(pid, self.empty_page());
+        Ok(pid)
+    }
+
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<()> {
+        match self.pages.remove(&page_id) {
+            Some(_) => Ok(()),
+            None => Err(Error::InvalidInput(format!(
+                "Page ID {} not found",
+                page_id
+            ))),
+        }
+    }
+
+    fn contains_page(&self, page_id: PageId) -> bool {
+        self.pages.contains_key(&page_id)
+    }
+
+    fn read(&mut self, page_id: PageId) -> Result<Option<Bytes>> {
+        Ok(self
+            .pages
+            .get(&page_id)
+            .map(|page| Bytes::copy_from_slice(page)))
+    }
+
+    fn write(&mut self, page_id: PageId, data: &[u8]) -> Result<()> {
+        if data.len() > self.page_size {
+            return errdata!("Page data must fit in a page.");
+        }
+        let empty_page = self.empty_page();
+        let page = self.pages.entry(page_id).or_insert(empty_page);
+        page[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Memory is never durable, so there's nothing to sync.
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryDiskManager;
+    use crate::disk::disk::Disk;
+    use crate::page::PAGE_SIZE;
+
+    #[test]
+    fn test_memory_disk_manager() {
+        let mut disk = MemoryDiskManager::new();
+
+        let pid1 = disk.allocate_page().unwrap();
+        let pid2 = disk.allocate_page().unwrap();
+        assert_ne!(pid1, pid2);
+        assert_eq!(
+            disk.read(pid1).unwrap().unwrap().to_vec(),
+            vec![0; PAGE_SIZE]
+        );
+
+        disk.write(pid1, b"hello").unwrap();
+        let page = disk.read(pid1).unwrap().unwrap();
+        assert_eq!(&page[..5], b"hello");
+        assert!(page[5..].iter().all(|&b| b == 0));
+
+        assert!(disk.write(pid1, &[0; PAGE_SIZE + 1]).is_err());
+
+        disk.deallocate_page(pid2).unwrap();
+        assert!(disk.read(pid2).unwrap().is_none());
+        assert!(disk.deallocate_page(pid2).is_err());
+    }
+}
@@ -1 +1,3 @@
+pub(crate) mod disk;
 pub(crate) mod disk_manager;
+pub(crate) mod memory_disk_manager;
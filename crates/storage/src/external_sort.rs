@@ -0,0 +1,339 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use rustdb_catalog::{
+    field::{Field, NullOrder},
+    schema::{RecordId, Schema},
+    tuple::Tuple,
+};
+use rustdb_error::{errdata, errinput};
+
+use crate::{disk::disk::Disk, typedef::PageId, Result};
+
+/// Each run page starts with the number of tuples in it.
+const RUN_PAGE_HEADER_SIZE: usize = size_of::<u32>();
+/// The bytes each tuple in a run page takes besides its data: its record id and its length.
+const ENTRY_OVERHEAD: usize = size_of::<u64>() + size_of::<u32>();
+
+/// Sorts `rows` by the column at `key` of `schema`, in ascending or descending order with NULLs
+/// placed as `nulls` says, holding at most `run_size` of them in memory while reading them. Each
+/// `run_size` rows are sorted and spilled to `disk` as a [`SortedRun`], and the runs are then
+/// merged lazily by the returned [`MergeIter`]. Like
+/// [`rustdb_catalog::catalog::StorageApi::scan_sorted`], the sort is stable.
+///
+/// Returns [`rustdb_error::Error::InvalidInput`] if `run_size` is 0.
+pub(crate) fn external_sort(
+    rows: impl Iterator<Item = Result<(RecordId, Tuple)>>,
+    disk: Arc<Mutex<dyn Disk>>,
+    key: usize,
+    ascending: bool,
+    nulls: NullOrder,
+    schema: &Schema,
+    run_size: usize,
+) -> Result<MergeIter> {
+    if run_size == 0 {
+        return errinput!("Sort runs must hold at least one tuple");
+    }
+
+    let mut runs = Vec::new();
+    let mut run = Vec::with_capacity(run_size);
+    let mut rows = rows.peekable();
+    while rows.peek().is_some() {
+        run.clear();
+        for row in rows.by_ref().take(run_size) {
+            let (rid, tuple) = row?;
+            run.push((tuple.field_at(key, schema)?, rid, tuple));
+        }
+        run.sort_by(|(a, ..), (b, ..)| a.cmp_for_sort(b, ascending, nulls));
+        let sorted = run.drain(..).map(|(_, rid, tuple)| (rid, tuple));
+        runs.push(SortedRun::write(disk.clone(), sorted)?);
+    }
+    MergeIter::new(runs, key, ascending, nulls, schema.clone())
+}
+
+/// Merges sorted runs into one sorted iterator, holding only the next tuple of each run in its
+/// heap and the rest of that tuple's page in the run's iterator. Tuples with equal keys are
+/// ordered by the run they're from, so merging runs of consecutive rows keeps the sort stable.
+/// The runs' pages are deallocated once the iterator is dropped.
+pub(crate) struct MergeIter {
+    runs: Vec<SortedRunIter>,
+    heap: BinaryHeap<MergeEntry>,
+    key: usize,
+    ascending: bool,
+    nulls: NullOrder,
+    schema: Schema,
+}
+
+impl MergeIter {
+    fn new(
+        runs: Vec<SortedRun>,
+        key: usize,
+        ascending: bool,
+        nulls: NullOrder,
+        schema: Schema,
+    ) -> Result<Self> {
+        let mut merge = Self {
+            heap: BinaryHeap::with_capacity(runs.len()),
+            runs: runs.into_iter().map(SortedRun::into_iter).collect(),
+            key,
+            ascending,
+            nulls,
+            schema,
+        };
+        for run in 0..merge.runs.len() {
+            merge.push_next(run)?;
+        }
+        Ok(merge)
+    }
+
+    /// Pushes the next tuple of run `run`, if it has one, onto the heap.
+    fn push_next(&mut self, run: usize) -> Result<()> {
+        if let Some(row) = self.runs[run].next() {
+            let (rid, tuple) = row?;
+            self.heap.push(MergeEntry {
+                key: tuple.field_at(self.key, &self.schema)?,
+                ascending: self.ascending,
+                nulls: self.nulls,
+                run,
+                rid,
+                tuple,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for MergeIter {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let MergeEntry {
+            run, rid, tuple, ..
+        } = self.heap.pop()?;
+        if let Err(e) = self.push_next(run) {
+            // The run can't be read any further, so the merge would skip its tuples.
+            self.heap.clear();
+            return Some(Err(e));
+        }
+        Some(Ok((rid, tuple)))
+    }
+}
+
+/// The next tuple of one of the runs being merged.
+struct MergeEntry {
+    key: Field,
+    ascending: bool,
+    nulls: NullOrder,
+    run: usize,
+    rid: RecordId,
+    tuple: Tuple,
+}
+
+impl Ord for MergeEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_key = self
+            .key
+            .cmp_for_sort(&other.key, self.ascending, self.nulls);
+        // A `BinaryHeap` pops its greatest entry, so the entry that goes first compares greatest.
+        by_key.then(self.run.cmp(&other.run)).reverse()
+    }
+}
+
+impl PartialOrd for MergeEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeEntry {}
+
+/// A sorted run of tuples, spilled to pages allocated directly from a disk, bypassing the buffer
+/// pool. The pages are deallocated when the run is dropped.
+///
+/// Each page holds the number of tuples in it as a `u32`, followed by each tuple's record id as a
+/// `u64`, its length as a `u32`, and its data. All integers are little-endian.
+pub(crate) struct SortedRun {
+    disk: Arc<Mutex<dyn Disk>>,
+    page_ids: Vec<PageId>,
+}
+
+impl SortedRun {
+    /// Writes `rows`, in order, to newly allocated pages of `disk`.
+    ///
+    /// Returns [`rustdb_error::Error::InvalidInput`] if a tuple doesn't fit in a page.
+    pub(crate) fn write(
+        disk: Arc<Mutex<dyn Disk>>,
+        rows: impl IntoIterator<Item = (RecordId, Tuple)>,
+    ) -> Result<Self> {
+        let page_size = disk.lock()?.page_size();
+        let mut run = Self {
+            disk,
+            page_ids: Vec::new(),
+        };
+        let mut page = Vec::with_capacity(page_size);
+        let mut count = 0u32;
+        for (rid, tuple) in rows {
+            let size = ENTRY_OVERHEAD + tuple.tuple_size();
+            if RUN_PAGE_HEADER_SIZE + size > page_size {
+                return errinput!(
+                    "Tuple of {} bytes doesn't fit in a page",
+                    tuple.tuple_size()
+                );
+            }
+            if page.len() + size > page_size {
+                run.write_page(&mut page, count)?;
+                count = 0;
+            }
+            if page.is_empty() {
+                page.extend_from_slice(&[0; RUN_PAGE_HEADER_SIZE]);
+            }
+            page.extend_from_slice(&rid.to_le_bytes());
+            page.extend_from_slice(&(tuple.tuple_size() as u32).to_le_bytes());
+            page.extend_from_slice(tuple.as_slice());
+            count += 1;
+        }
+        if count > 0 {
+            run.write_page(&mut page, count)?;
+        }
+        Ok(run)
+    }
+
+    /// Returns the number of pages the run takes up.
+    pub(crate) fn page_count(&self) -> usize {
+        self.page_ids.len()
+    }
+
+    /// Writes `page`, holding `count` tuples after room for its header, to a new page.
+    fn write_page(&mut self, page: &mut Vec<u8>, count: u32) -> Result<()> {
+        page[..RUN_PAGE_HEADER_SIZE].copy_from_slice(&count.to_le_bytes());
+        let mut disk = self.disk.lock()?;
+        let page_id = disk.allocate_page()?;
+        // Kept before writing, so that the page is deallocated even if the write fails.
+        self.page_ids.push(page_id);
+        disk.write(page_id, page)?;
+        page.clear();
+        Ok(())
+    }
+
+    fn read_page(&self, page_id: PageId) -> Result<Vec<(RecordId, Tuple)>> {
+        let Some(data) = self.disk.lock()?.read(page_id)? else {
+            return errdata!("Sorted run page {page_id} doesn't exist");
+        };
+        let mut offset = 0;
+        let mut take = |len: usize| -> Result<Bytes> {
+            match data.get(offset..offset + len) {
+                Some(_) => {
+                    offset += len;
+                    Ok(data.slice(offset - len..offset))
+                }
+                None => errdata!("Sorted run page {page_id} is truncated"),
+            }
+        };
+
+        let count = u32::from_le_bytes(take(RUN_PAGE_HEADER_SIZE)?[..].try_into()?);
+        (0..count)
+            .map(|_| {
+                let rid = u64::from_le_bytes(take(size_of::<u64>())?[..].try_into()?);
+                let len = u32::from_le_bytes(take(size_of::<u32>())?[..].try_into()?);
+                Ok((rid, Tuple::new(take(len as usize)?)))
+            })
+            .collect()
+    }
+}
+
+impl IntoIterator for SortedRun {
+    type Item = Result<(RecordId, Tuple)>;
+    type IntoIter = SortedRunIter;
+
+    /// Returns an iterator over the run's tuples in order, reading one page at a time.
+    fn into_iter(self) -> SortedRunIter {
+        SortedRunIter {
+            run: self,
+            next_page: 0,
+            rows: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl Drop for SortedRun {
+    fn drop(&mut self) {
+        if let Ok(mut disk) = self.disk.lock() {
+            for &page_id in &self.page_ids {
+                // Nothing else refers to the pages, so failing to free one only leaks its space.
+                let _ = disk.deallocate_page(page_id);
+            }
+        }
+    }
+}
+
+/// An iterator over the tuples of a [`SortedRun`], which owns the run and so frees its pages once
+/// dropped.
+pub(crate) struct SortedRunIter {
+    run: SortedRun,
+    next_page: usize,
+    rows: std::vec::IntoIter<(RecordId, Tuple)>,
+}
+
+impl Iterator for SortedRunIter {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(Ok(row));
+            }
+            let &page_id = self.run.page_ids.get(self.next_page)?;
+            self.next_page += 1;
+            match self.run.read_page(page_id) {
+                Ok(rows) => self.rows = rows.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use rustdb_catalog::tuple::Tuple;
+
+    use super::SortedRun;
+    use crate::disk::{disk::Disk, memory_disk_manager::MemoryDiskManager};
+    use crate::page::PAGE_SIZE;
+    use crate::Result;
+
+    /// Test that a run spanning several pages reads back in order, and frees its pages once
+    /// dropped.
+    #[test]
+    fn test_sorted_run() -> Result<()> {
+        let disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let rows = (0..20u64)
+            .map(|i| (i, Tuple::new(vec![i as u8; PAGE_SIZE / 8].into())))
+            .collect::<Vec<_>>();
+
+        let run = SortedRun::write(disk.clone(), rows.clone())?;
+        assert!(run.page_count() >= 3);
+        let page_ids = run.page_ids.clone();
+        let mut iter = run.into_iter();
+        assert_eq!(iter.by_ref().collect::<Result<Vec<_>>>()?, rows);
+
+        drop(iter);
+        for page_id in page_ids {
+            assert_eq!(disk.lock()?.read(page_id)?, None);
+        }
+
+        // Tuples that don't fit in a page are rejected.
+        let too_big = (0, Tuple::new(vec![0; PAGE_SIZE].into()));
+        assert!(SortedRun::write(disk, [too_big]).is_err());
+        Ok(())
+    }
+}
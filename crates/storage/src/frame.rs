@@ -5,17 +5,23 @@ use std::sync::{
 };
 
 use crate::{
-    page::{INVALID_PAGE_ID, PAGE_SIZE},
-    typedef::PageId,
+    buffer_pool::recover_poisoned,
+    page::INVALID_PAGE_ID,
+    typedef::{Lsn, PageId},
+    Result,
 };
+use rustdb_error::Error;
+
+/// The number of bytes at the start of every page that hold its LSN.
+pub(crate) const PAGE_LSN_SIZE: usize = size_of::<Lsn>();
 
 /// Represents a page in the buffer pool with metadata and data storage.
 pub struct PageFrame {
-    page_id: PageId,       // Unique identifier for the page
-    is_dirty: bool,        // Tracks whether the page has been modified
-    pin_cnt: AtomicU16,    // Pin count indicating active users (now atomic)
-    lock: RwLock<()>,      // Read-Write lock for thread safety
-    data: [u8; PAGE_SIZE], // Page data storage
+    page_id: PageId,    // Unique identifier for the page
+    is_dirty: bool,     // Tracks whether the page has been modified
+    pin_cnt: AtomicU16, // Pin count indicating active users (now atomic)
+    lock: RwLock<()>,   // Read-Write lock for thread safety
+    data: Box<[u8]>,    // Page data storage
 }
 
 impl fmt::Debug for PageFrame {
@@ -29,14 +35,14 @@ impl fmt::Debug for PageFrame {
 }
 
 impl PageFrame {
-    /// Creates a new, uninitialized page.
-    pub(crate) fn new() -> Self {
+    /// Creates a new, uninitialized page holding `page_size` bytes of data.
+    pub(crate) fn new(page_size: usize) -> Self {
         Self {
             page_id: INVALID_PAGE_ID,
             is_dirty: false,
             pin_cnt: AtomicU16::new(0),
             lock: RwLock::new(()),
-            data: [0; PAGE_SIZE],
+            data: vec![0; page_size].into_boxed_slice(),
         }
     }
 
@@ -55,6 +61,17 @@ impl PageFrame {
         self.pin_cnt.load(Ordering::Acquire)
     }
 
+    /// Returns the LSN of the last log record that modified this page. It is stored in the first
+    /// [`PAGE_LSN_SIZE`] bytes of the page data, so that it is persisted along with the page.
+    pub fn page_lsn(&self) -> Lsn {
+        Lsn::from_le_bytes(self.data[..PAGE_LSN_SIZE].try_into().unwrap())
+    }
+
+    /// Sets the LSN of the last log record that modified this page.
+    pub(crate) fn set_page_lsn(&mut self, lsn: Lsn) {
+        self.data[..PAGE_LSN_SIZE].copy_from_slice(&lsn.to_le_bytes());
+    }
+
     /// Provides read-only access to page data.
     pub fn data(&self) -> &[u8] {
         &self.data
@@ -104,21 +121,28 @@ impl PageFrame {
         self.data.fill(0);
     }
 
-    /// Writes data to the page at the given offset.
-    pub(crate) fn write(&mut self, offset: usize, data: &[u8]) {
-        if offset + data.len() > PAGE_SIZE {
-            panic!("Write out of bounds");
-        }
-        self.data[offset..offset + data.len()].copy_from_slice(data);
+    /// Writes data to the page at the given offset. Returns [`Error::OutOfBounds`] if the data
+    /// doesn't fit in the page.
+    pub(crate) fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        let end = offset.checked_add(data.len()).ok_or(Error::OutOfBounds)?;
+        let target = self.data.get_mut(offset..end).ok_or(Error::OutOfBounds)?;
+        target.copy_from_slice(data);
+        Ok(())
     }
 
-    /// Acquires a read lock on the page.
+    /// Acquires a read lock on the page, recovering it if a thread panicked while writing to the
+    /// page.
     pub(crate) fn read_lock(&self) -> std::sync::RwLockReadGuard<'_, ()> {
-        self.lock.read().unwrap()
+        let guard = recover_poisoned(self.lock.read());
+        self.lock.clear_poison();
+        guard
     }
 
-    /// Acquires a write lock on the page.
+    /// Acquires a write lock on the page, recovering it if a thread panicked while writing to the
+    /// page.
     pub(crate) fn write_lock(&self) -> std::sync::RwLockWriteGuard<'_, ()> {
-        self.lock.write().unwrap()
+        let guard = recover_poisoned(self.lock.write());
+        self.lock.clear_poison();
+        guard
     }
 }
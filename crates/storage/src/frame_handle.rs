@@ -1,5 +1,7 @@
 use crate::buffer_pool::BufferPoolManager;
 use crate::frame::PageFrame;
+use crate::typedef::PageId;
+use crate::Result;
 use core::fmt;
 use std::ops::{Deref, DerefMut};
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -10,6 +12,8 @@ use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 /// the page, allowing it to be evicted if necessary.
 pub struct PageFrameRefHandle<'a> {
     bpm: &'a Arc<RwLock<BufferPoolManager>>,
+    // The id of the page this handle pins, used to detect the frame being reused underneath it.
+    page_id: PageId,
     page_frame: &'a PageFrame,
     lock_guard: RwLockReadGuard<'a, ()>,
 }
@@ -31,24 +35,44 @@ impl<'a> PageFrameRefHandle<'a> {
         let lock_guard = unsafe { (*fp_ptr).read_lock() };
         PageFrameRefHandle {
             bpm,
+            page_id: page_frame.page_id(),
             page_frame,
             lock_guard,
         }
     }
+
+    /// Returns the pin count of the page, which is at least 1 while this handle is held.
+    pub fn pin_count(&self) -> u16 {
+        self.page_frame.pin_count()
+    }
+
+    /// Returns whether the page has been modified since it was last written to disk.
+    pub fn is_dirty(&self) -> bool {
+        self.page_frame.is_dirty()
+    }
 }
 
 impl<'a> Drop for PageFrameRefHandle<'a> {
     fn drop(&mut self) {
-        self.bpm
-            .write()
-            .unwrap()
-            .unpin_page(self.page_frame.page_id(), false);
+        // Unpin first, so that a failed check below doesn't leave the page pinned.
+        let frame_page_id = self.page_frame.page_id();
+        BufferPoolManager::lock(self.bpm).unpin_page(self.page_id, false);
+        // Panicking while already unwinding would abort the process.
+        if !std::thread::panicking() {
+            assert_eq!(
+                frame_page_id, self.page_id,
+                "Frame was reused while page {} was still pinned",
+                self.page_id
+            );
+        }
     }
 }
 
 /// Mutable page handle for write access.
 pub struct PageFrameMutHandle<'a> {
     bpm: &'a Arc<RwLock<BufferPoolManager>>,
+    // The id of the page this handle pins, used to detect the frame being reused underneath it.
+    page_id: PageId,
     page_frame: &'a mut PageFrame,
     lock_guard: RwLockWriteGuard<'a, ()>,
 }
@@ -72,18 +96,49 @@ impl<'a> PageFrameMutHandle<'a> {
         let lock_guard = unsafe { (*fp_ptr).write_lock() };
         PageFrameMutHandle {
             bpm,
+            page_id: page_frame.page_id(),
             page_frame,
             lock_guard,
         }
     }
+
+    /// Returns the pin count of the page, which is at least 1 while this handle is held.
+    pub fn pin_count(&self) -> u16 {
+        self.page_frame.pin_count()
+    }
+
+    /// Returns whether the page has been modified since it was last written to disk. Note that
+    /// the page is only marked dirty once this handle is dropped.
+    pub fn is_dirty(&self) -> bool {
+        self.page_frame.is_dirty()
+    }
+
+    /// Writes the page to disk now, while still holding its latch, for callers that need a write
+    /// to be durable before going on. Otherwise pages are only written back lazily, when flushed
+    /// or evicted after this handle is dropped.
+    ///
+    /// Returns [`rustdb_error::Error::BufferPoolError`] if the log hasn't been flushed up to the
+    /// page's LSN (see [`BufferPoolManager::flush_page`]).
+    pub fn flush(&self) -> Result<()> {
+        let mut bpm = BufferPoolManager::lock(self.bpm);
+        bpm.mark_dirty(self.page_id);
+        bpm.flush_page(&self.page_id)
+    }
 }
 
 impl<'a> Drop for PageFrameMutHandle<'a> {
     fn drop(&mut self) {
-        self.bpm
-            .write()
-            .unwrap()
-            .unpin_page(self.page_frame.page_id(), true);
+        // Unpin first, so that a failed check below doesn't leave the page pinned.
+        let frame_page_id = self.page_frame.page_id();
+        BufferPoolManager::lock(self.bpm).unpin_page(self.page_id, true);
+        // Panicking while already unwinding would abort the process.
+        if !std::thread::panicking() {
+            assert_eq!(
+                frame_page_id, self.page_id,
+                "Frame was reused while page {} was still pinned",
+                self.page_id
+            );
+        }
     }
 }
 
@@ -111,3 +166,82 @@ impl<'a> DerefMut for PageFrameMutHandle<'a> {
         self.page_frame
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use serial_test::serial;
+
+    use crate::{
+        buffer_pool::BufferPoolManager,
+        disk::{disk::Disk, disk_manager::DiskManager, memory_disk_manager::MemoryDiskManager},
+        frame::PAGE_LSN_SIZE,
+        replacer::lru_k_replacer::LrukReplacer,
+        Result,
+    };
+
+    fn get_bpm() -> Arc<RwLock<BufferPoolManager>> {
+        let disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let replacer = Box::new(LrukReplacer::new(2));
+        Arc::new(RwLock::new(BufferPoolManager::new(5, disk, replacer)))
+    }
+
+    #[test]
+    fn test_handle_pin_count_and_dirty_flag() {
+        let bpm = get_bpm();
+        let pid = {
+            let handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+            assert_eq!(handle.pin_count(), 1);
+            assert!(!handle.is_dirty());
+            handle.page_id()
+        };
+
+        // Read handles share the page, and each one pins it.
+        let first = BufferPoolManager::fetch_page_handle(&bpm, pid).unwrap();
+        assert!(first.pin_count() >= 1);
+        let second = BufferPoolManager::fetch_page_handle(&bpm, pid).unwrap();
+        assert_eq!(first.pin_count(), 2);
+        assert_eq!(second.pin_count(), 2);
+        // The dropped mutable handle marked the page dirty.
+        assert!(first.is_dirty());
+
+        drop(second);
+        assert_eq!(first.pin_count(), 1);
+    }
+
+    /// Test that flushing through a handle writes the page to the file right away, while pages
+    /// written without flushing only reach it when written back.
+    #[test]
+    #[serial]
+    fn test_handle_flush() -> Result<()> {
+        let disk = Arc::new(Mutex::new(DiskManager::new("test.db")?));
+        let replacer = Box::new(LrukReplacer::new(2));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(
+            5,
+            disk.clone(),
+            replacer,
+        )));
+        let marker = b"flushed";
+        // Reads the page from the file, bypassing the buffer pool.
+        let read_page = |page_id| -> Result<Vec<u8>> {
+            let page = disk.lock().unwrap().read(page_id)?.unwrap();
+            Ok(page[PAGE_LSN_SIZE..PAGE_LSN_SIZE + marker.len()].to_vec())
+        };
+
+        let mut flushed = BufferPoolManager::create_page_handle(&bpm)?;
+        flushed.write(PAGE_LSN_SIZE, marker)?;
+        flushed.flush()?;
+        assert!(!flushed.is_dirty());
+        let flushed_page_id = flushed.page_id();
+        drop(flushed);
+        assert_eq!(read_page(flushed_page_id)?, marker);
+
+        let mut lazy = BufferPoolManager::create_page_handle(&bpm)?;
+        lazy.write(PAGE_LSN_SIZE, marker)?;
+        let lazy_page_id = lazy.page_id();
+        drop(lazy);
+        assert_ne!(read_page(lazy_page_id)?, marker);
+        Ok(())
+    }
+}
@@ -1,2 +1,3 @@
+pub(crate) mod reverse_table_tuple_iterator;
 pub(crate) mod table_heap;
 pub(crate) mod table_tuple_iterator;
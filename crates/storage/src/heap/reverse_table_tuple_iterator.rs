@@ -0,0 +1,148 @@
+use std::sync::{Arc, RwLock};
+
+use crate::page::INVALID_PAGE_ID;
+use crate::record_id::RecordId;
+use crate::{
+    buffer_pool::BufferPoolManager, page::table_page::TablePageRef, typedef::PageId, Result,
+};
+use rustdb_catalog::tuple::Tuple;
+
+use crate::heap::table_heap::TableHeap;
+
+/// An iterator over all non-deleted tuples in a table heap, emitted newest-first (i.e. in the
+/// reverse order of [`super::table_tuple_iterator::TableTupleIterator`]).
+///
+/// Since table pages only link forward via `next_page_id`, the page ids in the table heap are
+/// collected up front when the iterator is created.
+pub struct ReverseTableTupleIterator {
+    bpm: Arc<RwLock<BufferPoolManager>>,
+    /// The page ids that haven't been visited yet, in forward order.
+    page_ids: Vec<PageId>,
+    current_page_id: PageId,
+    /// The number of slots in the current page that haven't been visited yet.
+    remaining_slots: u32,
+}
+
+impl ReverseTableTupleIterator {
+    /// Creates a new `ReverseTableTupleIterator`, walking the table heap's page chain to find
+    /// all of its pages.
+    pub fn new(
+        bpm: Arc<RwLock<BufferPoolManager>>,
+        table_heap: Arc<RwLock<TableHeap>>,
+    ) -> Result<Self> {
+        let mut page_ids = Vec::new();
+        let mut page_id = table_heap.read().unwrap().first_page_id();
+        while page_id != INVALID_PAGE_ID {
+            page_ids.push(page_id);
+            let page_handle = BufferPoolManager::fetch_page_handle(&bpm, page_id)?;
+            page_id = TablePageRef::from(page_handle).next_page_id();
+        }
+
+        Ok(Self {
+            bpm,
+            page_ids,
+            current_page_id: INVALID_PAGE_ID,
+            remaining_slots: 0,
+        })
+    }
+}
+
+impl Iterator for ReverseTableTupleIterator {
+    type Item = Result<(rustdb_catalog::schema::RecordId, Tuple)>;
+
+    /// Emits the next non-deleted tuple, walking the pages from last to first and the slots of
+    /// each page from last to first. Returns `None` once the first slot of the first page has
+    /// been visited.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // move on to the previous page once this one has no slots left to visit
+            if self.remaining_slots == 0 {
+                self.current_page_id = self.page_ids.pop()?;
+                let page_handle =
+                    match BufferPoolManager::fetch_page_handle(&self.bpm, self.current_page_id) {
+                        Ok(handle) => handle,
+                        Err(e) => return Some(Err(e)),
+                    };
+                self.remaining_slots = TablePageRef::from(page_handle).tuple_count();
+                continue;
+            }
+
+            // get the current page from the buffer pool
+            let page_handle =
+                match BufferPoolManager::fetch_page_handle(&self.bpm, self.current_page_id) {
+                    Ok(handle) => handle,
+                    Err(e) => return Some(Err(e)),
+                };
+            let table_page = TablePageRef::from(page_handle);
+
+            // try to fetch tuple at the current slot
+            self.remaining_slots -= 1;
+            let rid = RecordId::new(self.current_page_id, self.remaining_slots);
+            match table_page.get_tuple(&rid) {
+                // skip deleted tuples
+                Ok((metadata, _)) if metadata.is_deleted() => continue,
+                Ok((_, tuple)) => return Some(Ok((rid.into(), tuple))),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use rustdb_catalog::tuple::Tuple;
+    use serial_test::serial;
+
+    use crate::{
+        buffer_pool::BufferPoolManager,
+        disk::disk_manager::DiskManager,
+        heap::{table_heap::TableHeap, table_tuple_iterator::TableTupleIterator},
+        page::{table_page::TABLE_PAGE_HEADER_SIZE, PAGE_SIZE},
+        replacer::lru_k_replacer::LrukReplacer,
+        Result,
+    };
+
+    use super::ReverseTableTupleIterator;
+
+    /// Test that the reverse iterator emits tuples in the opposite order of the forward iterator,
+    /// across multiple pages and skipping deleted tuples.
+    #[test]
+    #[serial]
+    fn test_reverse_table_iterator() -> Result<()> {
+        let disk = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(3));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(10, disk, replacer)));
+
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+
+        // Each tuple fills a bit under half of a page, so the tuples span three pages.
+        let tuple_size = (PAGE_SIZE - TABLE_PAGE_HEADER_SIZE) / 2 - 16;
+        let mut rids = Vec::new();
+        for i in 1..=5u8 {
+            rids.push(table_heap.insert_tuple(&Tuple::new(vec![i; tuple_size].into()))?);
+        }
+        table_heap.delete_tuple(&rids[3])?;
+        assert_ne!(rids[0].page_id(), rids[4].page_id());
+
+        let table_heap = Arc::new(RwLock::new(table_heap));
+        let forward =
+            TableTupleIterator::new(bpm.clone(), table_heap.clone()).collect::<Result<Vec<_>>>()?;
+        let reverse =
+            ReverseTableTupleIterator::new(bpm.clone(), table_heap)?.collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(reverse.len(), 4);
+        assert_eq!(
+            reverse.iter().map(|(_, t)| t.data()[0]).collect::<Vec<_>>(),
+            vec![5, 3, 2, 1]
+        );
+        assert!(forward
+            .iter()
+            .rev()
+            .zip(reverse.iter())
+            .all(|((f_rid, f), (r_rid, r))| f_rid == r_rid && f.data() == r.data()));
+
+        Ok(())
+    }
+}
@@ -1,23 +1,48 @@
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use rustdb_catalog::tuple::Tuple;
-use rustdb_error::Error;
+use rustdb_catalog::{catalog::TableSize, tuple::Tuple};
+use rustdb_error::{errinput, Error};
 
 use crate::page::INVALID_PAGE_ID;
 use crate::{
     buffer_pool::BufferPoolManager,
-    page::table_page::{TablePageMut, TablePageRef, TupleMetadata},
+    page::rid_map_page::{RidMapPageMut, RidMapPageRef},
+    page::table_page::{
+        TablePageMut, TablePageRef, TupleMetadata, TABLE_PAGE_HEADER_SIZE, TUPLE_INFO_SIZE,
+    },
     record_id::RecordId,
     typedef::PageId,
+    wal::LogRecord,
     Result,
 };
 
+/// The space reclaimed by [`TableHeap::vacuum`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VacuumReport {
+    /// The number of deleted tuples whose data was discarded.
+    pub reclaimed_tuples: usize,
+    /// The number of pages freed because all of their tuples were deleted.
+    pub freed_pages: usize,
+}
+
 pub struct TableHeap {
     table_name: String,
     page_cnt: u32,
     bpm: Arc<RwLock<BufferPoolManager>>,
     first_page_id: PageId,
     last_page_id: PageId,
+    /// Maps page id -> bytes available for inserts in that page (see
+    /// [`TablePage::available_space`](crate::page::table_page::TablePage::available_space)), so
+    /// that inserts can reuse space on any page of the table heap, including that of deleted
+    /// tuples.
+    free_space: HashMap<PageId, usize>,
+    /// The pages of the RID map, which maps the logical ids of tuples inserted with
+    /// [`TableHeap::insert_tuple_stable`] to their current record ids. Logical ids are assigned
+    /// in order, filling each page before allocating the next. Allocated on first use, and like
+    /// allocating pages, changes to it aren't logged. The heap's first page points to the map's
+    /// first page, and each page of the map to the next, so that [`TableHeap::open`] finds them.
+    rid_map_page_ids: Vec<PageId>,
 }
 
 impl TableHeap {
@@ -38,11 +63,75 @@ impl TableHeap {
             bpm,
             first_page_id: table_page.page_id(),
             last_page_id: table_page.page_id(),
+            free_space: HashMap::from([(table_page.page_id(), table_page.available_space())]),
+            rid_map_page_ids: Vec::new(),
+        }
+    }
+
+    /// Open an existing table heap whose pages begin at the page with id `first_page_id`.
+    pub fn open(
+        name: &str,
+        bpm: Arc<RwLock<BufferPoolManager>>,
+        first_page_id: PageId,
+    ) -> Result<TableHeap> {
+        // walk the page chain to find the last page and the free space of each page
+        let mut free_space = HashMap::new();
+        let mut last_page_id = first_page_id;
+        let mut page_id = first_page_id;
+        while page_id != INVALID_PAGE_ID {
+            let table_page =
+                TablePageRef::from(BufferPoolManager::fetch_page_handle(&bpm, page_id)?);
+            free_space.insert(page_id, table_page.available_space());
+            last_page_id = page_id;
+            page_id = table_page.next_page_id();
+        }
+
+        // walk the RID map's page chain, which starts at the first page
+        let mut rid_map_page_ids = Vec::new();
+        let first_page =
+            TablePageRef::from(BufferPoolManager::fetch_page_handle(&bpm, first_page_id)?);
+        let mut map_page_id = first_page.rid_map_page_id();
+        drop(first_page);
+        while map_page_id != INVALID_PAGE_ID {
+            rid_map_page_ids.push(map_page_id);
+            let map_page =
+                RidMapPageRef::from(BufferPoolManager::fetch_page_handle(&bpm, map_page_id)?);
+            map_page_id = map_page.next_page_id();
+        }
+
+        Ok(TableHeap {
+            table_name: name.to_string(),
+            page_cnt: free_space.len() as u32,
+            bpm,
+            first_page_id,
+            last_page_id,
+            free_space,
+            rid_map_page_ids,
+        })
+    }
+
+    /// Returns the size of the largest tuple that fits in a page, i.e. in an empty page along with
+    /// its slot.
+    pub fn max_tuple_size(&self) -> Result<usize> {
+        let page_size = BufferPoolManager::lock(&self.bpm)
+            .disk()
+            .lock()?
+            .page_size();
+        Ok(page_size.saturating_sub(TABLE_PAGE_HEADER_SIZE + TUPLE_INFO_SIZE))
+    }
+
+    /// Returns [`Error::InvalidInput`] if `rid` is invalid (see [`RecordId::is_valid`]), rather
+    /// than trying to fetch a page that doesn't exist.
+    fn check_valid(rid: &RecordId) -> Result<()> {
+        if !rid.is_valid() {
+            return errinput!("Invalid record id {rid}");
         }
+        Ok(())
     }
 
     /// Retrieve a tuple given its record id.
     pub fn get_tuple(&self, rid: &RecordId) -> Result<(TupleMetadata, Tuple)> {
+        Self::check_valid(rid)?;
         //  get the page from the buffer pool
         let page_handle = BufferPoolManager::fetch_page_handle(&self.bpm, rid.page_id())?;
 
@@ -54,8 +143,10 @@ impl TableHeap {
         Ok((metadata, tuple))
     }
 
-    /// Delete a tuple given its record id, returning the deleted tuple (and its metadata).
-    pub fn delete_tuple(&self, rid: &RecordId) -> Result<(TupleMetadata, Tuple)> {
+    /// Delete a tuple given its record id, returning the deleted tuple (and its metadata prior to
+    /// deletion).
+    pub fn delete_tuple(&mut self, rid: &RecordId) -> Result<(TupleMetadata, Tuple)> {
+        Self::check_valid(rid)?;
         // 1. get the page from the buffer pool
         let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, rid.page_id())?;
 
@@ -63,24 +154,51 @@ impl TableHeap {
         let mut table_page = TablePageMut::from(page_handle);
 
         // 3. delete tuple
-        let (mut metadata, tuple) = table_page.get_tuple(rid)?;
+        let (metadata, tuple) = table_page.get_tuple(rid)?;
 
         if metadata.is_deleted() {
             return Ok((metadata, tuple));
         }
 
-        metadata.set_deleted(true);
-        table_page.update_tuple_metadata(rid, metadata.clone())?;
+        let mut deleted_metadata = metadata;
+        deleted_metadata.set_deleted(true);
+        table_page.update_tuple_metadata(rid, deleted_metadata)?;
+        self.log(&mut table_page, LogRecord::Delete { rid: rid.clone() })?;
+        self.free_space
+            .insert(rid.page_id(), table_page.available_space());
 
-        // 4. return tuple and its metadata
+        // 4. return tuple and its metadata from before the deletion
         Ok((metadata, tuple))
     }
 
     /// Insert a tuple into the table heap.
+    ///
+    /// Returns [`Error::InvalidInput`] without touching any page if the tuple is too large to fit
+    /// in an empty page (see [`TableHeap::max_tuple_size`]).
     pub fn insert_tuple(&mut self, tuple: &Tuple) -> Result<RecordId> {
+        let max_tuple_size = self.max_tuple_size()?;
+        if tuple.tuple_size() > max_tuple_size {
+            return errinput!(
+                "Tuple of {} bytes exceeds the maximum of {max_tuple_size} bytes",
+                tuple.tuple_size()
+            );
+        }
+
         // For a newly inserted tuple the metadata is by default not deleted
         let metadata = TupleMetadata::new(false);
 
+        // First try a page that is known to have enough free space for the tuple and its slot.
+        if let Some(page_id) = self.find_page_with_free_space(tuple.tuple_size() + TUPLE_INFO_SIZE)
+        {
+            let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, page_id)?;
+            let mut table_page = TablePageMut::from(page_handle);
+            let rid = table_page.insert_tuple(&metadata, tuple)?;
+            self.log(&mut table_page, Self::insert_record(&rid, tuple))?;
+            self.free_space
+                .insert(page_id, table_page.available_space());
+            return Ok(rid);
+        }
+
         // Try to fetch a mutable handle for the current last page.
         let mut current_table_page = {
             let page_handle =
@@ -94,7 +212,12 @@ impl TableHeap {
         // Try inserting the tuple into the current page.
         match current_table_page.insert_tuple(&metadata, tuple) {
             // It worked!
-            Ok(rid) => Ok(rid),
+            Ok(rid) => {
+                self.log(&mut current_table_page, Self::insert_record(&rid, tuple))?;
+                self.free_space
+                    .insert(self.last_page_id, current_table_page.available_space());
+                Ok(rid)
+            }
             // Uh oh, there isn’t enough free space in the current page...
             Err(Error::OutOfBounds) => {
                 // Allocate a new page.
@@ -110,9 +233,12 @@ impl TableHeap {
 
                 // Try inserting the tuple into the new page.
                 let rid = new_table_page.insert_tuple(&metadata, tuple)?;
+                self.log(&mut new_table_page, Self::insert_record(&rid, tuple))?;
                 // Update the table heap’s bookkeeping.
                 self.last_page_id = new_page_id;
                 self.page_cnt += 1;
+                self.free_space
+                    .insert(new_page_id, new_table_page.available_space());
 
                 Ok(rid)
             }
@@ -120,6 +246,284 @@ impl TableHeap {
         }
     }
 
+    /// Replace the tuple at `rid` with `tuple`, returning the record id of the updated tuple.
+    ///
+    /// If `tuple` fits in the space of the tuple it replaces, it is overwritten in place and
+    /// `rid` is returned. Otherwise, `tuple` is inserted elsewhere in the table heap, the old
+    /// tuple is marked deleted, and the new record id is returned. If deleting the old tuple
+    /// fails, the new one is deleted again, so the table never holds both.
+    pub fn update_tuple(&mut self, rid: &RecordId, tuple: &Tuple) -> Result<RecordId> {
+        {
+            // 1. get the page from the buffer pool
+            let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, rid.page_id())?;
+            let mut table_page = TablePageMut::from(page_handle);
+
+            // 2. only live tuples can be updated
+            let (metadata, old_tuple) = table_page.get_tuple(rid)?;
+            if metadata.is_deleted() {
+                return Err(Error::InvalidInput(format!("Tuple {} is deleted", rid)));
+            }
+
+            // 3. try to overwrite the tuple in place
+            match table_page.update_tuple_in_place(rid, tuple) {
+                Ok(()) => {
+                    let record = LogRecord::Update {
+                        rid: rid.clone(),
+                        before: old_tuple.as_slice().to_vec(),
+                        after: tuple.as_slice().to_vec(),
+                    };
+                    self.log(&mut table_page, record)?;
+                    return Ok(rid.clone());
+                }
+                Err(Error::OutOfBounds) => {}
+                Err(e) => return Err(e),
+            }
+            // the page handle is dropped here, since the insert below may need the same page
+        }
+
+        // 4. the tuple doesn't fit, so insert it elsewhere and delete the old one, undoing the
+        // insert if the delete fails
+        let new_rid = self.insert_tuple(tuple)?;
+        if let Err(e) = self.delete_tuple(rid) {
+            self.delete_tuple(&new_rid)?;
+            return Err(e);
+        }
+        Ok(new_rid)
+    }
+
+    /// Insert a tuple into the table heap, returning a logical id for it that, unlike its record
+    /// id, stays the same when [`TableHeap::update_tuple_stable`] relocates the tuple.
+    pub fn insert_tuple_stable(&mut self, tuple: &Tuple) -> Result<u64> {
+        let rid = self.insert_tuple(tuple)?;
+
+        // Append the record id to the last RID map page, allocating a new one if it's full. Every
+        // page but the last is full, and they all have the same capacity.
+        if let Some(&page_id) = self.rid_map_page_ids.last() {
+            let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, page_id)?;
+            let mut map_page = RidMapPageMut::from(page_handle);
+            match map_page.push(&rid) {
+                Ok(slot) => {
+                    let page = self.rid_map_page_ids.len() - 1;
+                    return Ok((page * map_page.capacity() + slot) as u64);
+                }
+                Err(Error::OutOfBounds) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let mut map_page = RidMapPageMut::from(BufferPoolManager::create_page_handle(&self.bpm)?);
+        map_page.init_header();
+        let slot = map_page.push(&rid)?;
+        let (new_page_id, capacity) = (map_page.page_id(), map_page.capacity());
+        drop(map_page);
+
+        // Link the new page from the map's last page, or from the first page if the map is new.
+        match self.rid_map_page_ids.last() {
+            Some(&last_page_id) => {
+                let page_handle =
+                    BufferPoolManager::fetch_page_mut_handle(&self.bpm, last_page_id)?;
+                RidMapPageMut::from(page_handle).set_next_page_id(new_page_id);
+            }
+            None => {
+                let page_handle =
+                    BufferPoolManager::fetch_page_mut_handle(&self.bpm, self.first_page_id)?;
+                TablePageMut::from(page_handle).set_rid_map_page_id(new_page_id);
+            }
+        }
+        let page = self.rid_map_page_ids.len();
+        self.rid_map_page_ids.push(new_page_id);
+        Ok((page * capacity + slot) as u64)
+    }
+
+    /// Retrieve a tuple given its logical id (see [`TableHeap::insert_tuple_stable`]).
+    pub fn get_tuple_stable(&self, logical_id: u64) -> Result<(TupleMetadata, Tuple)> {
+        self.get_tuple(&self.resolve(logical_id)?)
+    }
+
+    /// Replace the tuple with the given logical id with `tuple`, like [`TableHeap::update_tuple`].
+    /// If the tuple is relocated, only its RID map entry changes, so its logical id stays valid.
+    pub fn update_tuple_stable(&mut self, logical_id: u64, tuple: &Tuple) -> Result<()> {
+        let rid = self.resolve(logical_id)?;
+        let new_rid = self.update_tuple(&rid, tuple)?;
+        if new_rid != rid {
+            let (page_id, slot) = self.rid_map_entry(logical_id)?;
+            let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, page_id)?;
+            RidMapPageMut::from(page_handle).set(slot, &new_rid)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the current record id of the tuple with the given logical id (see
+    /// [`TableHeap::insert_tuple_stable`]), or [`Error::InvalidInput`] if there's no such tuple.
+    pub fn resolve(&self, logical_id: u64) -> Result<RecordId> {
+        let (page_id, slot) = self.rid_map_entry(logical_id)?;
+        let page_handle = BufferPoolManager::fetch_page_handle(&self.bpm, page_id)?;
+        match RidMapPageRef::from(page_handle).get(slot) {
+            Err(Error::OutOfBounds) => Err(Self::unknown_logical_id(logical_id)),
+            result => result,
+        }
+    }
+
+    /// Returns the RID map page and slot of the entry for `logical_id`.
+    fn rid_map_entry(&self, logical_id: u64) -> Result<(PageId, usize)> {
+        let Some(&first_page_id) = self.rid_map_page_ids.first() else {
+            return Err(Self::unknown_logical_id(logical_id));
+        };
+        let page_handle = BufferPoolManager::fetch_page_handle(&self.bpm, first_page_id)?;
+        let per_page = RidMapPageRef::from(page_handle).capacity() as u64;
+        usize::try_from(logical_id / per_page)
+            .ok()
+            .and_then(|page| self.rid_map_page_ids.get(page))
+            .map(|&page_id| (page_id, (logical_id % per_page) as usize))
+            .ok_or_else(|| Self::unknown_logical_id(logical_id))
+    }
+
+    fn unknown_logical_id(logical_id: u64) -> Error {
+        Error::InvalidInput(format!("No tuple with logical id {} in table", logical_id))
+    }
+
+    /// Returns the number of live (not deleted) tuples in the table heap. Only the page headers
+    /// are read, so this takes time proportional to the number of pages rather than tuples.
+    pub fn live_tuple_count(&self) -> Result<usize> {
+        let mut count = 0;
+        let mut page_id = self.first_page_id;
+        while page_id != INVALID_PAGE_ID {
+            let page_handle = BufferPoolManager::fetch_page_handle(&self.bpm, page_id)?;
+            let table_page = TablePageRef::from(page_handle);
+            count += (table_page.tuple_count() - table_page.deleted_tuple_count()) as usize;
+            page_id = table_page.next_page_id();
+        }
+        Ok(count)
+    }
+
+    /// Returns the size of the table heap, reading only the page headers. Pages the disk doesn't
+    /// have yet, e.g. new pages that haven't been written back, take up no bytes on disk.
+    pub fn table_size(&self) -> Result<TableSize> {
+        let mut size = TableSize {
+            page_count: 0,
+            live_tuples: 0,
+            dead_tuples: 0,
+            bytes_on_disk: 0,
+        };
+        let disk = BufferPoolManager::lock(&self.bpm).disk();
+        let mut page_id = self.first_page_id;
+        while page_id != INVALID_PAGE_ID {
+            let page_handle = BufferPoolManager::fetch_page_handle(&self.bpm, page_id)?;
+            let table_page = TablePageRef::from(page_handle);
+            let dead_tuples = table_page.deleted_tuple_count() as usize;
+            size.page_count += 1;
+            size.live_tuples += table_page.tuple_count() as usize - dead_tuples;
+            size.dead_tuples += dead_tuples;
+            let disk = disk.lock()?;
+            if disk.contains_page(page_id) {
+                size.bytes_on_disk += disk.page_size() as u64;
+            }
+            page_id = table_page.next_page_id();
+        }
+        Ok(size)
+    }
+
+    /// Compact the page with id `page_id`, making the space used by its deleted tuples available
+    /// to future inserts.
+    pub fn compact_page(&mut self, page_id: PageId) -> Result<()> {
+        if !self.free_space.contains_key(&page_id) {
+            return Err(Error::InvalidInput(format!(
+                "Page {} is not part of table {}",
+                page_id, self.table_name
+            )));
+        }
+
+        let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, page_id)?;
+        let mut table_page = TablePageMut::from(page_handle);
+        table_page.compact();
+        self.free_space
+            .insert(page_id, table_page.available_space());
+        Ok(())
+    }
+
+    /// Reclaims the space of every deleted tuple in the table heap. Pages with deleted tuples are
+    /// compacted, and pages whose tuples are all deleted are unlinked from the page chain and
+    /// deallocated, except for the first page, which identifies the table.
+    ///
+    /// The record ids of live tuples don't change, but those of deleted tuples stop referring to
+    /// anything, and once their page is freed and its id reused, they may come to refer to new
+    /// tuples. Any external index holding entries for deleted tuples must therefore be rebuilt.
+    /// Like allocating pages, vacuuming isn't logged.
+    pub fn vacuum(&mut self) -> Result<VacuumReport> {
+        let mut report = VacuumReport::default();
+        let mut prev_page_id = INVALID_PAGE_ID;
+        let mut page_id = self.first_page_id;
+        while page_id != INVALID_PAGE_ID {
+            let (next_page_id, is_empty) = {
+                let page_handle = BufferPoolManager::fetch_page_mut_handle(&self.bpm, page_id)?;
+                let mut table_page = TablePageMut::from(page_handle);
+                let slots = table_page.slot_array();
+                // Deleted tuples whose data was already discarded take up no space.
+                report.reclaimed_tuples += slots
+                    .iter()
+                    .filter(|slot| slot.metadata().is_deleted() && slot.size_bytes() > 0)
+                    .count();
+                let is_empty = slots.iter().all(|slot| slot.metadata().is_deleted());
+                table_page.compact();
+                self.free_space
+                    .insert(page_id, table_page.available_space());
+                (table_page.next_page_id(), is_empty)
+            };
+
+            if !is_empty || page_id == self.first_page_id {
+                prev_page_id = page_id;
+                page_id = next_page_id;
+                continue;
+            }
+
+            // Unlink the empty page, whose handle has been dropped so that it can be deleted.
+            {
+                let page_handle =
+                    BufferPoolManager::fetch_page_mut_handle(&self.bpm, prev_page_id)?;
+                TablePageMut::from(page_handle).set_next_page_id(next_page_id);
+            }
+            BufferPoolManager::lock(&self.bpm).delete_page(page_id)?;
+            self.free_space.remove(&page_id);
+            self.page_cnt -= 1;
+            if self.last_page_id == page_id {
+                self.last_page_id = prev_page_id;
+            }
+            report.freed_pages += 1;
+            page_id = next_page_id;
+        }
+        Ok(report)
+    }
+
+    /// Appends `record`, a change to `table_page`, to the buffer pool's write-ahead log and sets
+    /// the page's LSN to that of the record. Does nothing if the buffer pool has no log.
+    ///
+    /// Called while `table_page` is still pinned, so that the page can't be written back before
+    /// its LSN is set. Relocating updates are logged as an insert and a delete. Allocating and
+    /// linking pages isn't logged.
+    fn log(&self, table_page: &mut TablePageMut, record: LogRecord) -> Result<()> {
+        let Some(wal) = BufferPoolManager::read_lock(&self.bpm).wal() else {
+            return Ok(());
+        };
+        let lsn = wal.lock()?.append(&record)?;
+        table_page.set_page_lsn(lsn);
+        Ok(())
+    }
+
+    fn insert_record(rid: &RecordId, tuple: &Tuple) -> LogRecord {
+        LogRecord::Insert {
+            rid: rid.clone(),
+            tuple_bytes: tuple.as_slice().to_vec(),
+        }
+    }
+
+    /// Returns the lowest page id with at least `size` free bytes, if there is one.
+    fn find_page_with_free_space(&self, size: usize) -> Option<PageId> {
+        self.free_space
+            .iter()
+            .filter(|(_, free)| **free >= size)
+            .map(|(page_id, _)| *page_id)
+            .min()
+    }
+
     pub(crate) fn first_page_id(&self) -> PageId {
         self.first_page_id
     }
@@ -128,15 +532,19 @@ impl TableHeap {
 #[cfg(test)]
 mod tests {
     use rustdb_catalog::tuple::Tuple;
+    use rustdb_error::Error;
     use serial_test::serial;
 
     use crate::replacer::lru_k_replacer::LrukReplacer;
     use std::sync::{Arc, Mutex, RwLock};
 
     use crate::disk::disk_manager::DiskManager;
-    use crate::heap::table_heap::TableHeap;
+    use crate::disk::memory_disk_manager::MemoryDiskManager;
+    use crate::heap::table_heap::{TableHeap, VacuumReport};
+    use crate::heap::table_tuple_iterator::TableTupleIterator;
     use crate::page::table_page::{TABLE_PAGE_HEADER_SIZE, TUPLE_INFO_SIZE};
     use crate::page::PAGE_SIZE;
+    use crate::record_id::INVALID_RECORD_ID;
     use crate::{buffer_pool::BufferPoolManager, Result};
 
     pub fn get_bpm_with_pool_size(pool_size: usize) -> BufferPoolManager {
@@ -201,6 +609,195 @@ mod tests {
         Ok(())
     }
 
+    /// Test that an update no larger than the original tuple is written in place.
+    #[test]
+    #[serial]
+    fn test_update_tuple_in_place() -> Result<()> {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+
+        let rid1 = table_heap.insert_tuple(&Tuple::new(vec![1, 2, 3, 4].into()))?;
+        let rid2 = table_heap.insert_tuple(&Tuple::new(vec![5, 6, 7, 8].into()))?;
+
+        let new_rid = table_heap.update_tuple(&rid1, &Tuple::new(vec![9, 9].into()))?;
+        assert_eq!(new_rid, rid1);
+
+        let (meta, tuple) = table_heap.get_tuple(&rid1)?;
+        assert!(!meta.is_deleted());
+        assert_eq!(tuple.data().as_ref(), &[9, 9]);
+        assert_eq!(
+            table_heap.get_tuple(&rid2)?.1.data().as_ref(),
+            &[5, 6, 7, 8]
+        );
+
+        Ok(())
+    }
+
+    /// Test that an update larger than the original tuple moves the tuple to a new record id.
+    #[test]
+    #[serial]
+    fn test_update_tuple_relocates() -> Result<()> {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+
+        let rid1 = table_heap.insert_tuple(&Tuple::new(vec![1, 2].into()))?;
+        let rid2 = table_heap.insert_tuple(&Tuple::new(vec![3, 4].into()))?;
+
+        let new_rid = table_heap.update_tuple(&rid1, &Tuple::new(vec![5, 6, 7, 8, 9].into()))?;
+        assert_ne!(new_rid, rid1);
+        assert!(table_heap.get_tuple(&rid1)?.0.is_deleted());
+        assert_eq!(
+            table_heap.get_tuple(&new_rid)?.1.data().as_ref(),
+            &[5, 6, 7, 8, 9]
+        );
+
+        // A deleted tuple can't be updated.
+        assert!(table_heap
+            .update_tuple(&rid1, &Tuple::new(vec![0].into()))
+            .is_err());
+
+        // A scan sees only the live tuples.
+        let iter = TableTupleIterator::new(bpm.clone(), Arc::new(RwLock::new(table_heap)));
+        let tuples = iter.collect::<Result<Vec<_>>>()?;
+        assert_eq!(tuples.len(), 2);
+        assert_eq!(tuples[0].0, u64::from(rid2));
+        assert_eq!(tuples[0].1.data().as_ref(), &[3, 4]);
+        assert_eq!(tuples[1].0, u64::from(new_rid));
+        assert_eq!(tuples[1].1.data().as_ref(), &[5, 6, 7, 8, 9]);
+
+        Ok(())
+    }
+
+    /// Test that vacuuming reclaims deleted tuples and frees pages left empty.
+    #[test]
+    #[serial]
+    fn test_vacuum() -> Result<()> {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+
+        // Four of these tuples fill a page, so they span two pages.
+        let rids = (0..8)
+            .map(|i| table_heap.insert_tuple(&Tuple::new(vec![i; 1000].into())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(rids[0].page_id(), rids[3].page_id());
+        assert_eq!(rids[4].page_id(), rids[7].page_id());
+        assert_ne!(rids[0].page_id(), rids[4].page_id());
+
+        // Delete all but one tuple of the first page and all of the second.
+        for rid in rids.iter().filter(|rid| **rid != rids[2]) {
+            table_heap.delete_tuple(rid)?;
+        }
+        assert_eq!(
+            table_heap.vacuum()?,
+            VacuumReport {
+                reclaimed_tuples: 7,
+                freed_pages: 1,
+            }
+        );
+        assert_eq!(table_heap.page_cnt, 1);
+
+        // The surviving tuple keeps its record id, and nothing is left to reclaim.
+        assert_eq!(
+            table_heap.get_tuple(&rids[2])?.1.data().as_ref(),
+            &[2; 1000]
+        );
+        assert_eq!(table_heap.vacuum()?, VacuumReport::default());
+
+        // The freed page is no longer part of the table, and inserts go to the remaining page.
+        assert!(table_heap.compact_page(rids[4].page_id()).is_err());
+        let rid = table_heap.insert_tuple(&Tuple::new(vec![9; 1000].into()))?;
+        assert_eq!(rid.page_id(), rids[0].page_id());
+
+        let iter = TableTupleIterator::new(bpm.clone(), Arc::new(RwLock::new(table_heap)));
+        let tuples = iter
+            .map(|item| Ok(item?.1.data().to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(tuples, vec![vec![2; 1000], vec![9; 1000]]);
+
+        Ok(())
+    }
+
+    /// Test that the space of tuples deleted from an earlier page is reused by later inserts.
+    #[test]
+    #[serial]
+    fn test_insert_reuses_deleted_space() -> Result<()> {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+
+        // Fill the first page, so the next tuple goes to a second page.
+        let huge_tuple_size = PAGE_SIZE - TABLE_PAGE_HEADER_SIZE - TUPLE_INFO_SIZE - 5;
+        let rid1 = table_heap.insert_tuple(&Tuple::new(vec![1; huge_tuple_size].into()))?;
+        let rid2 = table_heap.insert_tuple(&Tuple::new(vec![2; 16].into()))?;
+        assert_ne!(rid1.page_id(), rid2.page_id());
+
+        // Deleting the tuple makes its space available, so the next insert goes back to the
+        // first page, compacting it.
+        table_heap.delete_tuple(&rid1)?;
+        let rid3 = table_heap.insert_tuple(&Tuple::new(vec![3; 16].into()))?;
+        assert_eq!(rid3.page_id(), rid1.page_id());
+        assert_eq!(table_heap.get_tuple(&rid3)?.1.data().as_ref(), &[3; 16]);
+
+        // The same goes for space reclaimed by compacting the page explicitly.
+        table_heap.delete_tuple(&rid3)?;
+        table_heap.compact_page(rid1.page_id())?;
+        let rid4 = table_heap.insert_tuple(&Tuple::new(vec![4; 16].into()))?;
+        assert_eq!(rid4.page_id(), rid1.page_id());
+        assert_eq!(table_heap.get_tuple(&rid4)?.1.data().as_ref(), &[4; 16]);
+        assert_eq!(table_heap.page_cnt, 2);
+
+        Ok(())
+    }
+
+    /// Test that a tuple too large for a page is rejected up front, with a descriptive error, and
+    /// that the largest tuple that fits is accepted.
+    #[test]
+    fn test_table_heap_max_tuple_size() -> Result<()> {
+        let disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let replacer = Box::new(LrukReplacer::new(2));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(5, disk, replacer)));
+        let mut table_heap = TableHeap::new("table", bpm);
+        let max = PAGE_SIZE - TABLE_PAGE_HEADER_SIZE - TUPLE_INFO_SIZE;
+        assert_eq!(table_heap.max_tuple_size()?, max);
+
+        let oversized = Tuple::new(vec![1; max + 1].into());
+        assert_eq!(
+            table_heap.insert_tuple(&oversized),
+            Err(Error::InvalidInput(format!(
+                "Tuple of {} bytes exceeds the maximum of {max} bytes",
+                max + 1
+            )))
+        );
+        // No page was allocated for it.
+        assert_eq!(table_heap.page_cnt, 1);
+
+        let rid = table_heap.insert_tuple(&Tuple::new(vec![2; max].into()))?;
+        assert_eq!(table_heap.get_tuple(&rid)?.1.data().len(), max);
+        Ok(())
+    }
+
+    /// Test that a tuple too large for a 4K page fits in an 8K page.
+    #[test]
+    fn test_table_heap_page_sizes() -> Result<()> {
+        let large_tuple = Tuple::new(vec![7; 5000].into());
+
+        for page_size in [4096, 8192] {
+            let disk_manager = Arc::new(Mutex::new(MemoryDiskManager::with_page_size(page_size)));
+            let replacer = Box::new(LrukReplacer::new(5));
+            let bpm = BufferPoolManager::with_page_size(10, page_size, disk_manager, replacer)?;
+            let mut table_heap = TableHeap::new("table", Arc::new(RwLock::new(bpm)));
+
+            let result = table_heap.insert_tuple(&large_tuple);
+            if page_size < large_tuple.tuple_size() {
+                assert!(result.is_err());
+            } else {
+                let (_, tuple) = table_heap.get_tuple(&result?)?;
+                assert_eq!(tuple.data(), large_tuple.data());
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_tuple_deletion() {
@@ -266,4 +863,107 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[serial]
+    fn test_stable_tuple_ids() -> Result<()> {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+
+        // Enough tuples that the RID map spans several pages.
+        let ids = (0..1000)
+            .map(|i: u32| {
+                table_heap.insert_tuple_stable(&Tuple::new(i.to_le_bytes().to_vec().into()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert!(table_heap.rid_map_page_ids.len() > 1);
+        assert_eq!(ids, (0..1000).collect::<Vec<u64>>());
+        for &id in &[0, 999] {
+            let (_, tuple) = table_heap.get_tuple_stable(id)?;
+            assert_eq!(tuple.data(), (id as u32).to_le_bytes().to_vec());
+        }
+
+        // A growing update relocates the tuple, but its logical id follows it.
+        let rid = table_heap.resolve(7)?;
+        let grown = Tuple::new(vec![7; 100].into());
+        table_heap.update_tuple_stable(7, &grown)?;
+        assert_ne!(table_heap.resolve(7)?, rid);
+        assert!(table_heap.get_tuple(&rid)?.0.is_deleted());
+        assert_eq!(table_heap.get_tuple_stable(7)?.1.data(), grown.data());
+
+        // A shrinking update stays in place.
+        let rid = table_heap.resolve(7)?;
+        table_heap.update_tuple_stable(7, &Tuple::new(vec![1].into()))?;
+        assert_eq!(table_heap.resolve(7)?, rid);
+        assert_eq!(table_heap.get_tuple_stable(7)?.1.data(), vec![1]);
+        assert_eq!(
+            table_heap.get_tuple_stable(8)?.1.data(),
+            8u32.to_le_bytes().to_vec()
+        );
+
+        assert!(matches!(
+            table_heap.get_tuple_stable(1000),
+            Err(Error::InvalidInput(_))
+        ));
+
+        // Reopening the table heap finds its RID map.
+        let reopened = TableHeap::open("table", bpm.clone(), table_heap.first_page_id())?;
+        assert_eq!(reopened.rid_map_page_ids, table_heap.rid_map_page_ids);
+        for id in [0, 7, 999] {
+            assert_eq!(reopened.resolve(id)?, table_heap.resolve(id)?);
+        }
+        assert_eq!(reopened.get_tuple_stable(7)?.1.data(), vec![1]);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_live_tuple_count() -> Result<()> {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+        assert_eq!(table_heap.live_tuple_count()?, 0);
+
+        // Tuples large enough that they span several pages.
+        let rids = (0..5)
+            .map(|i| table_heap.insert_tuple(&Tuple::new(vec![i; PAGE_SIZE / 3].into())))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(table_heap.page_cnt > 1);
+        assert_eq!(table_heap.live_tuple_count()?, 5);
+
+        // Deleting a tuple twice only counts once.
+        table_heap.delete_tuple(&rids[1])?;
+        table_heap.delete_tuple(&rids[3])?;
+        table_heap.delete_tuple(&rids[3])?;
+        assert_eq!(table_heap.live_tuple_count()?, 3);
+
+        let scanned = TableTupleIterator::new(bpm.clone(), Arc::new(RwLock::new(table_heap)))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(scanned.len(), 3);
+        Ok(())
+    }
+
+    /// Test that invalid record ids are rejected without fetching a page.
+    #[test]
+    fn test_invalid_record_id() -> Result<()> {
+        let disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(
+            5,
+            disk,
+            Box::new(LrukReplacer::new(2)),
+        )));
+        let mut table_heap = TableHeap::new("table", bpm);
+        let rid = table_heap.insert_tuple(&Tuple::new(vec![1, 2, 3].into()))?;
+        assert!(rid.is_valid());
+
+        assert!(matches!(
+            table_heap.get_tuple(&INVALID_RECORD_ID),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            table_heap.delete_tuple(&INVALID_RECORD_ID),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(!table_heap.get_tuple(&rid)?.0.is_deleted());
+        Ok(())
+    }
 }
@@ -1,12 +1,16 @@
 use std::sync::{Arc, RwLock};
 
+use crate::frame::PageFrame;
 use crate::page::INVALID_PAGE_ID;
 use crate::record_id::RecordId;
 use crate::{
-    buffer_pool::BufferPoolManager, page::table_page::TablePageRef, typedef::PageId, Result,
+    buffer_pool::BufferPoolManager,
+    page::table_page::{TablePage, TablePageRef, TablePageSnapshot, TupleMetadata},
+    typedef::PageId,
+    Result,
 };
 use rustdb_catalog::tuple::Tuple;
-use rustdb_error::Error;
+use std::ops::Deref;
 
 use crate::heap::table_heap::TableHeap;
 
@@ -17,8 +21,15 @@ use crate::heap::table_heap::TableHeap;
 /// (i.e. unmodified) during iteration.
 pub struct TableTupleIterator {
     bpm: Arc<RwLock<BufferPoolManager>>,
+    first_page_id: PageId,
     current_page_id: PageId,
     current_slot: u32,
+    // Whether to read each page from a copy taken when the iterator reaches it.
+    snapshot: bool,
+    // The copy of the current page, if `snapshot` is set.
+    page_snapshot: Option<TablePageSnapshot>,
+    // Whether to emit deleted tuples too, as [`RawTableTupleIterator`] does.
+    include_deleted: bool,
 }
 
 impl TableTupleIterator {
@@ -26,10 +37,144 @@ impl TableTupleIterator {
     /// It acquires the read guard internally.
     pub fn new(bpm: Arc<RwLock<BufferPoolManager>>, table_heap: Arc<RwLock<TableHeap>>) -> Self {
         let first_page_id = table_heap.read().unwrap().first_page_id();
+        Self::from_first_page(bpm, first_page_id)
+    }
+
+    /// Creates a new `TableTupleIterator` over the table heap whose first page is
+    /// `first_page_id`, for callers that already hold a lock on the table heap.
+    pub(crate) fn from_first_page(
+        bpm: Arc<RwLock<BufferPoolManager>>,
+        first_page_id: PageId,
+    ) -> Self {
         Self {
             bpm,
+            first_page_id,
             current_page_id: first_page_id,
             current_slot: 0,
+            snapshot: false,
+            page_snapshot: None,
+            include_deleted: false,
+        }
+    }
+
+    /// Sets whether the iterator reads each page from a snapshot. A snapshot copies the page
+    /// under its read latch when the iterator reaches it, so every tuple emitted from that page
+    /// reflects the page as of that moment, even if writers modify it while the scan runs.
+    pub fn with_snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self.page_snapshot = None;
+        self
+    }
+
+    /// Creates a new `TableTupleIterator` that starts emitting tuples at or after `start_rid`.
+    pub fn new_from(
+        bpm: Arc<RwLock<BufferPoolManager>>,
+        table_heap: Arc<RwLock<TableHeap>>,
+        start_rid: &RecordId,
+    ) -> Self {
+        let mut iter = Self::new(bpm, table_heap);
+        iter.seek(start_rid);
+        iter
+    }
+
+    /// Moves the iterator so that it resumes emitting tuples at or after `rid`.
+    ///
+    /// If the page of `rid` isn't part of the table heap's page chain, the iterator is exhausted.
+    pub fn seek(&mut self, rid: &RecordId) {
+        let mut page_id = self.first_page_id;
+        while page_id != INVALID_PAGE_ID && page_id != rid.page_id() {
+            page_id = match BufferPoolManager::fetch_page_handle(&self.bpm, page_id) {
+                Ok(handle) => TablePageRef::from(handle).next_page_id(),
+                Err(_) => INVALID_PAGE_ID,
+            };
+        }
+
+        self.current_page_id = page_id;
+        self.current_slot = rid.slot_id();
+        self.page_snapshot = None;
+    }
+
+    /// Turns this iterator into one that emits every tuple in the table heap, including deleted
+    /// ones, along with its metadata.
+    pub fn all_tuples(mut self) -> RawTableTupleIterator {
+        self.include_deleted = true;
+        RawTableTupleIterator { inner: self }
+    }
+
+    /// Moves past the slot at the current position on `page`, returning its tuple and metadata
+    /// unless the tuple is deleted and deleted tuples aren't included. If the page has no tuples
+    /// left, moves on to the next page instead.
+    fn advance<T: Deref<Target = PageFrame>>(
+        &mut self,
+        page: &TablePage<T>,
+    ) -> Option<Result<(RecordId, TupleMetadata, Tuple)>> {
+        // no tuples left on this page, so move on to the next one
+        if self.current_slot >= page.tuple_count() {
+            self.current_page_id = page.next_page_id();
+            self.current_slot = 0;
+            return None;
+        }
+
+        // try to fetch tuple at the current slot
+        let rid = RecordId::new(self.current_page_id, self.current_slot);
+        self.current_slot += 1;
+        match page.get_tuple(&rid) {
+            // skip deleted tuples
+            Ok((metadata, _)) if metadata.is_deleted() && !self.include_deleted => None,
+            Ok((metadata, tuple)) => Some(Ok((rid, metadata, tuple))),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Wraps this iterator to skip its first `offset` tuples and then emit at most `limit` of the
+    /// rest, as in `LIMIT limit OFFSET offset`. No limit emits every remaining tuple.
+    pub fn with_limit_offset(self, limit: Option<usize>, offset: usize) -> LimitOffset<Self> {
+        LimitOffset::new(self, limit, offset)
+    }
+}
+
+/// An iterator over a window of the tuples emitted by another scan, created by
+/// [`TableTupleIterator::with_limit_offset`]. Errors from the inner iterator are always passed
+/// through, and don't count towards either the offset or the limit.
+pub struct LimitOffset<I> {
+    inner: I,
+    remaining: Option<usize>,
+    skip: usize,
+}
+
+impl<I> LimitOffset<I> {
+    /// Creates an iterator that skips the first `offset` tuples of `inner` and then emits at most
+    /// `limit` of the rest.
+    pub fn new(inner: I, limit: Option<usize>, offset: usize) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            skip: offset,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<(rustdb_catalog::schema::RecordId, Tuple)>>> Iterator
+    for LimitOffset<I>
+{
+    type Item = I::Item;
+
+    /// Emits the next tuple in the window, stopping without touching the inner iterator once the
+    /// limit has been reached.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        loop {
+            let item = self.inner.next()?;
+            if item.is_ok() && self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            if item.is_ok() {
+                self.remaining = self.remaining.map(|remaining| remaining - 1);
+            }
+            return Some(item);
         }
     }
 }
@@ -46,141 +191,89 @@ impl Iterator for TableTupleIterator {
     /// (The exception to this is an out-of-bounds error, which might signal that the current page
     /// doesn't have more tuples to emit and that the iterator should move to the next page.)
     fn next(&mut self) -> Option<Self::Item> {
+        let item = self.next_entry()?;
+        Some(item.map(|(rid, _, tuple)| (rid.into(), tuple)))
+    }
+}
+
+impl TableTupleIterator {
+    /// Emits the next tuple that hasn't yet been emitted by this iterator, with its record id and
+    /// metadata, skipping deleted tuples unless they're included.
+    fn next_entry(&mut self) -> Option<Result<(RecordId, TupleMetadata, Tuple)>> {
         loop {
-            //     // stop iterating when we reach the end of the table
-            //     if self.current_page_id == INVALID_PAGE_ID {
-            //         return None;
-            //     }
-
-            //     // get the current page from the buffer pool
-            //     let page_handle =
-            //         match BufferPoolManager::fetch_page_handle(&self.bpm, self.current_page_id) {
-            //             Ok(handle) => handle,          // successfully fetched the page
-            //             Err(e) => return Some(Err(e)), // error
-            //         };
-
-            //     // create a table page from the page handle
-            //     let table_page = TablePageRef::from(page_handle);
-
-            //     // get the next tuple offset
-            //     match table_page.get_next_tuple_offset(self.current_slot) {
-            //         Ok(Some(rid)) => {
-            //             // there is a tuple at this offset
-
-            //             // Try to fetch the tuple at this RecordId
-            //             match table_page.get_tuple(&rid) {
-            //                 Ok((metadata, tuple)) => {
-            //                     // advance to the next slot for the next iteration
-            //                     self.current_slot = rid.slot_id() + 1;
-
-            //                     // return tuples that are not deleted
-            //                     if !metadata.is_deleted() {
-            //                         //return Some(Ok((rid, tuple)));
-            //                         return Some(Ok((rid.into(), tuple))); // return the tuple
-            //                     } else {
-            //                         continue; // skip deleted tuples
-            //                     }
-            //                 }
-            //                 Err(e) => return Some(Err(e)),
-            //             }
-            //         }
-            //         Ok(None) => {
-            //             // no more tuples in this page
-
-            //             // move to the next page
-            //             match table_page.next_page_id() {
-            //                 Some(next_page_id) => {
-            //                     self.current_page_id = next_page_id;
-            //                     self.current_slot = 0;
-            //                 }
-            //                 None => {
-            //                     // reached end of table
-            //                     self.current_page_id = INVALID_PAGE_ID;
-            //                 }
-            //             }
-            //         }
-            //         Err(e) => return Some(Err(e)),
-            //     }
-            // }
             // stop iterating when we reach the end of the table
             if self.current_page_id == INVALID_PAGE_ID {
                 return None;
             }
 
-            // get the current page from the buffer pool
-            let page_handle =
-                match BufferPoolManager::fetch_page_handle(&self.bpm, self.current_page_id) {
-                    Ok(handle) => handle,
-                    Err(e) => return Some(Err(e)),
-                };
+            // reuse the snapshot of the current page, if we've taken one
+            if let Some(page) = self
+                .page_snapshot
+                .take_if(|page| page.page_id() == self.current_page_id)
+            {
+                let item = self.advance(&page);
+                self.page_snapshot = Some(page);
+                match item {
+                    Some(item) => return Some(item),
+                    None => continue,
+                }
+            }
 
-            // create a table page from the page handle
-            let table_page = TablePageRef::from(page_handle);
+            // get the current page from the buffer pool, through our own reference to it so that
+            // the page can be read while `self` advances
+            let (bpm, page_id) = (self.bpm.clone(), self.current_page_id);
+            let table_page = match BufferPoolManager::fetch_page_handle(&bpm, page_id) {
+                Ok(handle) => TablePageRef::from(handle),
+                Err(e) => return Some(Err(e)),
+            };
 
-            // try to fetch tuple at the current slot
-            let rid = RecordId::new(self.current_page_id, self.current_slot);
+            // copy the page if we're taking snapshots
+            if self.snapshot {
+                self.page_snapshot = Some(table_page.snapshot());
+                continue;
+            }
+            if let Some(item) = self.advance(&table_page) {
+                return Some(item);
+            }
+        }
+    }
+}
 
-            match table_page.get_tuple(&rid) {
-                Ok((metadata, tuple)) => {
-                    self.current_slot += 1; // move to next slot
+/// An iterator over every tuple in a table heap, including deleted ones, along with its metadata,
+/// created by [`TableTupleIterator::all_tuples`]. Meant for garbage collection and diagnostics,
+/// which need to see the deleted tuples that regular scans skip.
+pub struct RawTableTupleIterator {
+    inner: TableTupleIterator,
+}
 
-                    if !metadata.is_deleted() {
-                        return Some(Ok((rid.into(), tuple)));
-                    }
-                    // if deleted, continue to next slot
-                    continue;
-                }
-                Err(Error::OutOfBounds) => {
-                    // No tuple at this slot → likely end of page.
-                    // Move to the next page.
-                    // match table_page.next_page_id() {
-                    //     Some(next_page_id) => {
-                    //         self.current_page_id = next_page_id;
-                    //         self.current_slot = 0;
-                    //         continue;
-                    //     }
-                    //     None => {
-                    //         // reached end of table
-                    //         self.current_page_id = INVALID_PAGE_ID;
-                    //         return None;
-                    //     }
-                    // }
-                    let next_page_id = table_page.next_page_id();
-                    if next_page_id == INVALID_PAGE_ID {
-                        self.current_page_id = INVALID_PAGE_ID;
-                        return None;
-                    } else {
-                        self.current_page_id = next_page_id;
-                        self.current_slot = 0;
-                        continue;
-                    }
+impl Iterator for RawTableTupleIterator {
+    type Item = Result<(rustdb_catalog::schema::RecordId, TupleMetadata, Tuple)>;
 
-                }
-                Err(e) => {
-                    // any other error should be propagated
-                    return Some(Err(e));
-                }
-            }
-        }
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next_entry()?;
+        Some(item.map(|(rid, metadata, tuple)| (rid.into(), metadata, tuple)))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::{Arc, Mutex, RwLock};
+    use std::sync::{Arc, Barrier, Mutex, RwLock};
+    use std::thread;
 
     use rustdb_catalog::tuple::Tuple;
+    use serial_test::serial;
 
     use crate::{
         buffer_pool::BufferPoolManager, disk::disk_manager::DiskManager,
-        heap::table_heap::TableHeap, replacer::lru_k_replacer::LrukReplacer, Result,
+        heap::table_heap::TableHeap, record_id::RecordId, replacer::lru_k_replacer::LrukReplacer,
+        Result,
     };
 
     use super::TableTupleIterator;
 
-    
     /// Test that the iterator correctly visits all non-deleted tuples in the table heap.
     #[test]
+    #[serial]
     fn test_table_iterator() -> Result<()> {
         // Set up a test disk and buffer pool manager.
         let disk = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
@@ -216,4 +309,161 @@ mod tests {
 
         Ok(())
     }
+
+    /// Test that the raw iterator visits deleted tuples too, flagging them as deleted.
+    #[test]
+    #[serial]
+    fn test_table_iterator_all_tuples() -> Result<()> {
+        let disk = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(3));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(10, disk, replacer)));
+
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+        let mut rids = Vec::new();
+        for i in 0..3u8 {
+            rids.push(table_heap.insert_tuple(&Tuple::new(vec![i; 3].into()))?);
+        }
+        table_heap.delete_tuple(&rids[1])?;
+        let table_heap = Arc::new(RwLock::new(table_heap));
+
+        let tuples = TableTupleIterator::new(bpm.clone(), table_heap.clone())
+            .all_tuples()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(tuples.len(), 3);
+        for (i, (rid, metadata, tuple)) in tuples.into_iter().enumerate() {
+            assert_eq!(rid, u64::from(rids[i].clone()));
+            assert_eq!(metadata.is_deleted(), i == 1);
+            assert_eq!(tuple.data().to_vec(), vec![i as u8; 3]);
+        }
+
+        // The regular scan still skips the deleted tuple.
+        assert_eq!(TableTupleIterator::new(bpm, table_heap).count(), 2);
+        Ok(())
+    }
+
+    /// Test that seeking to a record id resumes iteration at that record id.
+    #[test]
+    #[serial]
+    fn test_table_iterator_seek() -> Result<()> {
+        let disk = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(3));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(10, disk, replacer)));
+
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+        let mut rids = Vec::new();
+        for i in 0..10u8 {
+            rids.push(table_heap.insert_tuple(&Tuple::new(vec![i; 3].into()))?);
+        }
+        let table_heap = Arc::new(RwLock::new(table_heap));
+
+        // Starting from the sixth tuple yields exactly the last five.
+        let iter = TableTupleIterator::new_from(bpm.clone(), table_heap.clone(), &rids[5]);
+        let tuples = iter.collect::<Result<Vec<_>>>()?;
+        assert_eq!(tuples.len(), 5);
+        for (i, (rid, tuple)) in tuples.into_iter().enumerate() {
+            assert_eq!(rid, u64::from(rids[i + 5].clone()));
+            assert_eq!(tuple.data().to_vec(), vec![i as u8 + 5; 3]);
+        }
+
+        // Seeking to a page outside of the table heap terminates the iterator.
+        let mut iter = TableTupleIterator::new(bpm.clone(), table_heap);
+        iter.seek(&RecordId::new(rids[0].page_id() + 100, 0));
+        assert!(iter.next().is_none());
+
+        Ok(())
+    }
+
+    /// Test that a limit and offset select a window of the non-deleted tuples.
+    #[test]
+    #[serial]
+    fn test_table_iterator_limit_offset() -> Result<()> {
+        let disk = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(3));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(10, disk, replacer)));
+
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+        let mut rids = Vec::new();
+        for i in 0..10u8 {
+            rids.push(table_heap.insert_tuple(&Tuple::new(vec![i; 3].into()))?);
+        }
+        let table_heap = Arc::new(RwLock::new(table_heap));
+        let scan = |limit, offset| -> Result<Vec<u8>> {
+            TableTupleIterator::new(bpm.clone(), table_heap.clone())
+                .with_limit_offset(limit, offset)
+                .map(|item| Ok(item?.1.data()[0]))
+                .collect()
+        };
+
+        // LIMIT 3 OFFSET 2.
+        assert_eq!(scan(Some(3), 2)?, vec![2, 3, 4]);
+        // LIMIT 0 emits nothing, and a limit past the end of the table emits the rest.
+        assert!(scan(Some(0), 0)?.is_empty());
+        assert_eq!(scan(Some(5), 8)?, vec![8, 9]);
+        assert_eq!(scan(None, 7)?, vec![7, 8, 9]);
+        assert!(scan(None, 10)?.is_empty());
+
+        // Deleted tuples don't count towards the offset.
+        table_heap.write().unwrap().delete_tuple(&rids[0])?;
+        assert_eq!(scan(Some(3), 2)?, vec![3, 4, 5]);
+
+        Ok(())
+    }
+
+    /// Test that a snapshot scan emits each page as it was when the scan reached it, even while a
+    /// writer modifies the rest of the page.
+    #[test]
+    #[serial]
+    fn test_table_iterator_snapshot() -> Result<()> {
+        let disk = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(3));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(10, disk, replacer)));
+
+        let mut table_heap = TableHeap::new("table", bpm.clone());
+        let mut rids = Vec::new();
+        for i in 0..10u8 {
+            rids.push(table_heap.insert_tuple(&Tuple::new(vec![i; 3].into()))?);
+        }
+        let table_heap = Arc::new(RwLock::new(table_heap));
+
+        // Scans the table while a writer bumps every tuple after the first by `bump`, once the
+        // scan has emitted the first tuple.
+        let scan_during_writes = |snapshot: bool, bump: u8| -> Result<Vec<u8>> {
+            let (scanned_first, wrote) = (Barrier::new(2), Barrier::new(2));
+            thread::scope(|s| {
+                s.spawn(|| {
+                    scanned_first.wait();
+                    let mut table_heap = table_heap.write().unwrap();
+                    for rid in &rids[1..] {
+                        let (_, tuple) = table_heap.get_tuple(rid).unwrap();
+                        let bumped = tuple.data().iter().map(|b| b + bump).collect::<Vec<_>>();
+                        table_heap
+                            .update_tuple(rid, &Tuple::new(bumped.into()))
+                            .unwrap();
+                    }
+                    drop(table_heap);
+                    wrote.wait();
+                });
+
+                let mut iter = TableTupleIterator::new(bpm.clone(), table_heap.clone())
+                    .with_snapshot(snapshot);
+                let first = iter.next();
+                scanned_first.wait();
+                wrote.wait();
+                first
+                    .into_iter()
+                    .chain(iter)
+                    .map(|item| Ok(item?.1.data()[0]))
+                    .collect()
+            })
+        };
+
+        // The snapshot scan only sees the table as it was before the writes...
+        assert_eq!(scan_during_writes(true, 10)?, (0..10).collect::<Vec<_>>());
+        // ...while a regular scan sees them once they land, mixing both versions of the table.
+        let mut expected = vec![0];
+        expected.extend(21..30);
+        assert_eq!(scan_during_writes(false, 10)?, expected);
+
+        Ok(())
+    }
 }
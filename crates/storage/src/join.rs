@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::Result;
+use rustdb_catalog::{
+    field::Field,
+    schema::{RecordId, Schema},
+    tuple::Tuple,
+};
+
+/// Joins the tuples of `left` and `right` on equality of the `left_key`-th column of `left` and
+/// the `right_key`-th column of `right`, returning each matching `(left, right)` pair.
+///
+/// A hash table is built over `left`, which should therefore be the smaller input, and then
+/// probed with each tuple of `right`. Pairs are returned in the order of `right`, and the pairs
+/// of a single right tuple in the order of `left`. As in SQL, a NULL key never matches anything,
+/// not even another NULL.
+///
+/// Returns the first error emitted by either input, or [`rustdb_error::Error::OutOfBounds`] if a
+/// key isn't a column of its schema.
+pub fn hash_join(
+    left: impl Iterator<Item = Result<(RecordId, Tuple)>>,
+    left_key: usize,
+    left_schema: &Schema,
+    right: impl Iterator<Item = Result<(RecordId, Tuple)>>,
+    right_key: usize,
+    right_schema: &Schema,
+) -> Result<Vec<(Tuple, Tuple)>> {
+    // Build phase: map each non-NULL key of the left input to its tuples.
+    let mut table: HashMap<Field, Vec<Tuple>> = HashMap::new();
+    for item in left {
+        let (_, tuple) = item?;
+        match tuple.field_at(left_key, left_schema)? {
+            Field::Null => {}
+            key => table.entry(key).or_default().push(tuple),
+        }
+    }
+
+    // Probe phase: pair each right tuple with the left tuples sharing its key.
+    let mut pairs = Vec::new();
+    for item in right {
+        let (_, tuple) = item?;
+        let key = tuple.field_at(right_key, right_schema)?;
+        if let Some(matches) = table.get(&key) {
+            // Tuples share their underlying data, so these copies are cheap.
+            pairs.extend(
+                matches
+                    .iter()
+                    .map(|left| (Tuple::new(left.data()), Tuple::new(tuple.data()))),
+            );
+        }
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use rustdb_catalog::{
+        column::Column, field::Field, schema::Schema, serde::Serde, tuple::Tuple, types::Type,
+    };
+    use rustdb_error::Error;
+
+    use super::hash_join;
+    use crate::Result;
+
+    /// Returns the scan output of a table holding the serialized `rows`.
+    fn scan(rows: &[Vec<Field>]) -> impl Iterator<Item = Result<(u64, Tuple)>> {
+        rows.iter()
+            .enumerate()
+            .map(|(i, row)| Ok((i as u64, Tuple::new(Serde::serialize(row).into()))))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_hash_join() -> Result<()> {
+        let users = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        let orders = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("user_id".to_string(), Type::Integer),
+        ]);
+        let user_rows = [
+            vec![Field::Integer(1), "alice".into()],
+            vec![Field::Integer(2), "bob".into()],
+        ];
+        let order_rows = [
+            vec![Field::Integer(10), Field::Integer(2)],
+            vec![Field::Integer(11), Field::Integer(3)],
+            vec![Field::Integer(12), Field::Integer(2)],
+        ];
+
+        let pairs = hash_join(scan(&user_rows), 0, &users, scan(&order_rows), 1, &orders)?;
+        let pairs = pairs
+            .iter()
+            .map(|(user, order)| Ok((user.to_fields(&users)?, order.to_fields(&orders)?)))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            pairs,
+            vec![
+                (user_rows[1].clone(), order_rows[0].clone()),
+                (user_rows[1].clone(), order_rows[2].clone()),
+            ]
+        );
+
+        // Joining on a column of NULLs matches nothing.
+        let nulls = Schema::new(&[Column::new("nothing".to_string(), Type::Null)]);
+        let null_rows = [vec![Field::Null], vec![Field::Null]];
+        let pairs = hash_join(scan(&null_rows), 0, &nulls, scan(&null_rows), 0, &nulls)?;
+        assert!(pairs.is_empty());
+
+        // Errors from the inputs are propagated.
+        let failing = std::iter::once(Err(Error::OutOfBounds));
+        assert!(hash_join(failing, 0, &users, scan(&order_rows), 1, &orders).is_err());
+
+        Ok(())
+    }
+}
@@ -1,13 +1,19 @@
 #![allow(dead_code)]
+pub(crate) mod b_plus_tree;
+pub(crate) mod bloom_filter;
 pub(crate) mod buffer_pool;
 pub(crate) mod disk;
+pub(crate) mod external_sort;
 pub(crate) mod frame;
 pub(crate) mod frame_handle;
 pub(crate) mod heap;
+pub mod join;
 pub(crate) mod lock;
 pub(crate) mod page;
 pub(crate) mod record_id;
+pub(crate) mod recovery;
 pub(crate) mod replacer;
 pub mod storage;
 pub(crate) mod typedef;
+pub(crate) mod wal;
 pub(crate) type Result<T> = std::result::Result<T, rustdb_error::Error>;
@@ -1,12 +1,209 @@
+use std::future::Future;
 use std::hash::Hash;
 use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
 
-/// A map of identifiers to async read-write locks.
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard};
+
+/// The mode in which a lock is acquired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// Any number of holders may share the lock, e.g. to read a tuple.
+    Shared,
+    /// A single holder has the lock to itself, e.g. to modify a tuple.
+    Exclusive,
+}
+
+/// The locks of a [`LockManager`], by id.
+type LockMap<T, I> =
+    std::sync::Mutex<std::collections::HashMap<T, Arc<tokio::sync::RwLock<Option<I>>>>>;
+
+/// A held lock, in the mode it was acquired in.
+#[derive(Debug)]
+enum HeldLock<I> {
+    Shared(OwnedRwLockReadGuard<Option<I>>),
+    Exclusive(OwnedRwLockWriteGuard<Option<I>>),
+}
+
+/// A lock acquired from a [`LockManager`]. The lock is released when the guard is dropped, and
+/// once no other guard holds or waits for it, it is removed from the lock manager.
+#[derive(Debug)]
+pub struct LockGuard<T, I>
+where
+    T: Eq + PartialEq + Hash,
+{
+    held: Option<HeldLock<I>>,
+    lock: Arc<tokio::sync::RwLock<Option<I>>>,
+    locks: Arc<LockMap<T, I>>,
+    id: T,
+}
+
+impl<T, I> LockGuard<T, I>
+where
+    T: Eq + PartialEq + Hash,
+{
+    /// Returns the mode the lock was acquired in.
+    pub fn mode(&self) -> LockMode {
+        match self.held {
+            Some(HeldLock::Shared(_)) => LockMode::Shared,
+            Some(HeldLock::Exclusive(_)) | None => LockMode::Exclusive,
+        }
+    }
+}
+
+impl<T, I> Drop for LockGuard<T, I>
+where
+    T: Eq + PartialEq + Hash,
+{
+    fn drop(&mut self) {
+        // Release the lock before checking whether anyone else still refers to it. References are
+        // only taken while the map is locked, so the count can't go up while we check it.
+        self.held = None;
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        // One reference is the map's and the other is ours.
+        if Arc::strong_count(&self.lock) == 2 {
+            locks.remove(&self.id);
+        }
+    }
+}
+
+/// A map of identifiers to async read-write locks. An identifier's lock exists only while some
+/// [`LockGuard`] holds or waits for it.
 pub(crate) struct LockManager<T, I>
 where
     T: Eq + PartialEq + Hash,
 {
-    locks: std::sync::Mutex<std::collections::HashMap<T, Arc<tokio::sync::RwLock<Option<I>>>>>,
+    locks: Arc<LockMap<T, I>>,
+}
+
+impl<T, I> LockManager<T, I>
+where
+    T: Eq + PartialEq + Hash + Clone,
+{
+    /// Creates a lock manager that doesn't hold any locks.
+    pub(crate) fn new() -> Self {
+        Self {
+            locks: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+        }
+    }
+
+    /// Acquires the lock on `id` in the given mode, blocking the current thread until the lock is
+    /// available.
+    pub(crate) fn lock(&self, id: T, mode: LockMode) -> LockGuard<T, I> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(id.clone())
+            .or_default()
+            .clone();
+        let held = match mode {
+            LockMode::Shared => HeldLock::Shared(block_on(lock.clone().read_owned())),
+            LockMode::Exclusive => HeldLock::Exclusive(block_on(lock.clone().write_owned())),
+        };
+        LockGuard {
+            held: Some(held),
+            lock,
+            locks: self.locks.clone(),
+            id,
+        }
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking the thread while it is pending.
+///
+/// The storage engine is synchronous, so this lets it wait on tokio's locks without an async
+/// runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
 }
 
-impl<T, I> LockManager<T, I> where T: Eq + PartialEq + Hash {}
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{LockManager, LockMode};
+
+    #[test]
+    fn test_shared_and_exclusive_locks() {
+        let lock_manager = Arc::new(LockManager::<u32, ()>::new());
+
+        // Shared locks on the same id, and exclusive locks on different ids, don't conflict.
+        let shared1 = lock_manager.lock(1, LockMode::Shared);
+        let shared2 = lock_manager.lock(1, LockMode::Shared);
+        let exclusive = lock_manager.lock(2, LockMode::Exclusive);
+        assert_eq!(shared1.mode(), LockMode::Shared);
+        assert_eq!(exclusive.mode(), LockMode::Exclusive);
+
+        // An exclusive lock waits for the shared locks to be released.
+        let acquired = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let lock_manager = lock_manager.clone();
+            let acquired = acquired.clone();
+            std::thread::spawn(move || {
+                let _guard = lock_manager.lock(1, LockMode::Exclusive);
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!acquired.load(Ordering::SeqCst));
+
+        drop(shared1);
+        drop(shared2);
+        handle.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_unused_locks_are_removed() {
+        let lock_manager = LockManager::<u32, ()>::new();
+        let num_locks = || lock_manager.locks.lock().unwrap().len();
+
+        let shared1 = lock_manager.lock(1, LockMode::Shared);
+        let shared2 = lock_manager.lock(1, LockMode::Shared);
+        let exclusive = lock_manager.lock(2, LockMode::Exclusive);
+        assert_eq!(num_locks(), 2);
+
+        // A lock stays until its last guard is dropped.
+        drop(shared1);
+        assert_eq!(num_locks(), 2);
+        drop(shared2);
+        assert_eq!(num_locks(), 1);
+        drop(exclusive);
+        assert_eq!(num_locks(), 0);
+
+        // A lock that is waited for isn't removed when its holder releases it.
+        let lock_manager = Arc::new(lock_manager);
+        let exclusive = lock_manager.lock(1, LockMode::Exclusive);
+        let waiter = {
+            let lock_manager = lock_manager.clone();
+            std::thread::spawn(move || {
+                let _guard = lock_manager.lock(1, LockMode::Exclusive);
+                std::thread::sleep(Duration::from_millis(50));
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        drop(exclusive);
+        assert_eq!(lock_manager.locks.lock().unwrap().len(), 1);
+        waiter.join().unwrap();
+        assert_eq!(lock_manager.locks.lock().unwrap().len(), 0);
+    }
+}
@@ -1,3 +1,6 @@
 //! The lock manager for the storage engine. Maintains locks of both row and table level
 //! granularity.
 mod lock_manager;
+
+pub(crate) use lock_manager::LockManager;
+pub use lock_manager::{LockGuard, LockMode};
@@ -1,5 +1,7 @@
 use crate::typedef::PageId;
+pub(crate) mod rid_map_page;
 pub(crate) mod table_page;
 
 pub(crate) const INVALID_PAGE_ID: PageId = 0;
+/// The default page size, in bytes.
 pub(crate) const PAGE_SIZE: usize = 4096;
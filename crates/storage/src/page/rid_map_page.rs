@@ -0,0 +1,131 @@
+use crate::frame_handle::{PageFrameMutHandle, PageFrameRefHandle};
+use crate::page::INVALID_PAGE_ID;
+use crate::record_id::RecordId;
+use crate::Result;
+use crate::{
+    frame::{PageFrame, PAGE_LSN_SIZE},
+    typedef::PageId,
+};
+use bytemuck::{Pod, Zeroable};
+use rustdb_error::Error;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Copy, Clone)]
+pub(crate) struct RidMapPageHeader {
+    /// The page's LSN, which every page keeps in its first bytes (see [`PageFrame::page_lsn`]).
+    page_lsn: [u8; PAGE_LSN_SIZE],
+    entry_cnt: u32,
+    /// The next page of the RID map, or [`INVALID_PAGE_ID`] if this is the last one.
+    next_page_id: PageId,
+}
+
+pub(crate) const RID_MAP_PAGE_HEADER_SIZE: usize = mem::size_of::<RidMapPageHeader>();
+/// Entries are stored as the `u64` form of their record id (see `From<RecordId> for u64`).
+pub(crate) const RID_MAP_ENTRY_SIZE: usize = mem::size_of::<u64>();
+
+/// A page of a table heap's RID map, which maps stable logical ids to the current record ids of
+/// their tuples (see [`crate::heap::table_heap::TableHeap::insert_tuple_stable`]). The page holds
+/// an array of entries after its header, one per logical id, filled from the front. The map's
+/// pages are chained through their headers, starting from the heap's first page.
+pub struct RidMapPage<T> {
+    page_frame_handle: T,
+}
+
+impl<T: Deref<Target = PageFrame>> RidMapPage<T> {
+    pub(crate) fn page_id(&self) -> PageId {
+        self.page_frame_handle.page_id()
+    }
+
+    pub(crate) fn next_page_id(&self) -> PageId {
+        self.header().next_page_id
+    }
+
+    /// Returns the number of entries in this page.
+    pub(crate) fn entry_count(&self) -> usize {
+        self.header().entry_cnt as usize
+    }
+
+    /// Returns the number of entries this page can hold.
+    pub(crate) fn capacity(&self) -> usize {
+        (self.page_frame_handle.data().len() - RID_MAP_PAGE_HEADER_SIZE) / RID_MAP_ENTRY_SIZE
+    }
+
+    /// Returns the record id in entry `slot`, or [`Error::OutOfBounds`] if there's no such entry.
+    pub(crate) fn get(&self, slot: usize) -> Result<RecordId> {
+        if slot >= self.entry_count() {
+            return Err(Error::OutOfBounds);
+        }
+        let offset = Self::entry_offset(slot);
+        let bytes = &self.page_frame_handle.data()[offset..offset + RID_MAP_ENTRY_SIZE];
+        Ok(RecordId::from(u64::from_le_bytes(bytes.try_into()?)))
+    }
+
+    fn header(&self) -> &RidMapPageHeader {
+        bytemuck::from_bytes(&self.page_frame_handle.data()[..RID_MAP_PAGE_HEADER_SIZE])
+    }
+
+    fn entry_offset(slot: usize) -> usize {
+        RID_MAP_PAGE_HEADER_SIZE + slot * RID_MAP_ENTRY_SIZE
+    }
+}
+
+impl<T: DerefMut<Target = PageFrame> + Deref<Target = PageFrame>> RidMapPage<T> {
+    pub(crate) fn init_header(&mut self) {
+        let header = self.header_mut();
+        *header = RidMapPageHeader {
+            page_lsn: header.page_lsn,
+            entry_cnt: 0,
+            next_page_id: INVALID_PAGE_ID,
+        };
+    }
+
+    pub(crate) fn set_next_page_id(&mut self, next_page_id: PageId) {
+        self.header_mut().next_page_id = next_page_id;
+    }
+
+    /// Appends an entry holding `rid`, returning its slot, or [`Error::OutOfBounds`] if the page
+    /// is full.
+    pub(crate) fn push(&mut self, rid: &RecordId) -> Result<usize> {
+        let slot = self.entry_count();
+        if slot >= self.capacity() {
+            return Err(Error::OutOfBounds);
+        }
+        self.header_mut().entry_cnt += 1;
+        self.set(slot, rid)?;
+        Ok(slot)
+    }
+
+    /// Overwrites the record id in entry `slot`, or returns [`Error::OutOfBounds`] if there's no
+    /// such entry.
+    pub(crate) fn set(&mut self, slot: usize, rid: &RecordId) -> Result<()> {
+        if slot >= self.entry_count() {
+            return Err(Error::OutOfBounds);
+        }
+        let value = u64::from(rid.clone());
+        self.page_frame_handle
+            .write(Self::entry_offset(slot), &value.to_le_bytes())
+    }
+
+    fn header_mut(&mut self) -> &mut RidMapPageHeader {
+        bytemuck::from_bytes_mut(&mut self.page_frame_handle.data_mut()[..RID_MAP_PAGE_HEADER_SIZE])
+    }
+}
+
+/// Type alias for immutable RidMapPage
+pub type RidMapPageRef<'a> = RidMapPage<PageFrameRefHandle<'a>>;
+/// Type alias for mutable RidMapPage
+pub type RidMapPageMut<'a> = RidMapPage<PageFrameMutHandle<'a>>;
+
+impl<'a> From<PageFrameRefHandle<'a>> for RidMapPageRef<'a> {
+    fn from(page_frame_handle: PageFrameRefHandle<'a>) -> Self {
+        RidMapPage { page_frame_handle }
+    }
+}
+
+impl<'a> From<PageFrameMutHandle<'a>> for RidMapPageMut<'a> {
+    fn from(page_frame_handle: PageFrameMutHandle<'a>) -> Self {
+        RidMapPage { page_frame_handle }
+    }
+}
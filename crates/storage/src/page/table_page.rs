@@ -1,9 +1,13 @@
 use crate::frame_handle::{PageFrameMutHandle, PageFrameRefHandle};
-use crate::page::PAGE_SIZE;
+use crate::page::INVALID_PAGE_ID;
 use crate::record_id::RecordId;
 use crate::Result;
-use crate::{frame::PageFrame, typedef::PageId};
+use crate::{
+    frame::{PageFrame, PAGE_LSN_SIZE},
+    typedef::{Lsn, PageId},
+};
 use bytemuck::{Pod, Zeroable};
+use bytes::Bytes;
 use rustdb_catalog::tuple::Tuple;
 use rustdb_error::Error;
 use std::mem;
@@ -12,10 +16,14 @@ use std::ops::{Deref, DerefMut};
 #[repr(C)]
 #[derive(Pod, Zeroable, Copy, Clone)]
 pub(crate) struct TablePageHeader {
+    /// The page's LSN, which every page keeps in its first bytes (see [`PageFrame::page_lsn`]).
+    page_lsn: [u8; PAGE_LSN_SIZE],
     next_page_id: PageId,
     tuple_cnt: u32,
     deleted_tuple_cnt: u32,
-    _padding: [u8; 4],
+    /// The first page of the table heap's RID map, if it has one. Only set on the heap's first
+    /// page, so that the map can be found when the heap is reopened.
+    rid_map_page_id: PageId,
 }
 
 #[repr(C)]
@@ -34,6 +42,10 @@ impl TupleInfo {
     pub(crate) fn size_bytes(&self) -> u16 {
         self.size_bytes
     }
+
+    pub(crate) fn metadata(&self) -> TupleMetadata {
+        self.metadata
+    }
 }
 
 pub(crate) const TABLE_PAGE_HEADER_SIZE: usize = mem::size_of::<TablePageHeader>();
@@ -54,7 +66,8 @@ impl TupleMetadata {
         }
     }
 
-    pub(crate) fn is_deleted(&self) -> bool {
+    /// Returns whether the tuple is marked deleted.
+    pub fn is_deleted(&self) -> bool {
         self.is_deleted != 0
     }
 
@@ -77,10 +90,26 @@ impl<T: Deref<Target = PageFrame>> TablePage<T> {
         self.header().next_page_id
     }
 
+    /// Returns the first page of the table heap's RID map, or [`INVALID_PAGE_ID`] if this isn't
+    /// the heap's first page or the heap has no RID map.
+    pub(crate) fn rid_map_page_id(&self) -> PageId {
+        self.header().rid_map_page_id
+    }
+
     pub(crate) fn tuple_count(&self) -> u32 {
         self.header().tuple_cnt
     }
 
+    /// Returns the number of this page's tuples that are marked deleted.
+    pub(crate) fn deleted_tuple_count(&self) -> u32 {
+        self.header().deleted_tuple_cnt
+    }
+
+    /// Returns the LSN of the last log record that modified this page.
+    pub(crate) fn page_lsn(&self) -> Lsn {
+        self.page_frame_handle.page_lsn()
+    }
+
     /// Immutable access to the header
     pub(crate) fn header(&self) -> &TablePageHeader {
         bytemuck::from_bytes(&self.page_frame_handle.data()[..TABLE_PAGE_HEADER_SIZE])
@@ -93,47 +122,82 @@ impl<T: Deref<Target = PageFrame>> TablePage<T> {
         bytemuck::cast_slice(&self.page_frame_handle.data()[TABLE_PAGE_HEADER_SIZE..slots_end])
     }
 
+    /// Copies this page into a private buffer, whose contents can't change even once the page's
+    /// handle is released.
+    pub(crate) fn snapshot(&self) -> TablePageSnapshot {
+        let data = self.page_frame_handle.data();
+        let mut page_frame = PageFrame::new(data.len());
+        page_frame.set_page_id(self.page_id());
+        page_frame.data_mut().copy_from_slice(data);
+        TablePage {
+            page_frame_handle: Box::new(page_frame),
+        }
+    }
+
     pub(crate) fn get_tuple(&self, rid: &RecordId) -> Result<(TupleMetadata, Tuple)> {
-        // 1. check that the record id is valid  
+        // 1. check that the record id is valid
         self.validate_record_id(rid)?;
         // 2. get the slot
         let slot = &self.slot_array()[rid.slot_id() as usize];
-        // 3. read the tuple  
+        // 3. read the tuple
         let offset = slot.offset() as usize;
         let size = slot.size_bytes() as usize;
-        let tuple_data = self.page_frame_handle.data()[offset..offset + size].to_vec();
-        // 4. return the tuple 
-        Ok((slot.metadata, Tuple::new(tuple_data.into())))
+        let tuple_data = &self.page_frame_handle.data()[offset..offset + size];
+        // 4. return the tuple
+        Ok((
+            slot.metadata,
+            Tuple::new(Bytes::copy_from_slice(tuple_data)),
+        ))
     }
 
-    fn get_next_tuple_offset(&mut self, tuple: &Tuple) -> Result<u16> {
-        
-        
-        // let slots_end = TABLE_PAGE_HEADER_SIZE + (tuple_count * TUPLE_INFO_SIZE);
-        // let tuple_size = tuple.size_bytes() as usize;
-        // let next_tuple_offset = slots_end + tuple_size;
+    /// Returns the number of free bytes between the end of the slot array and the start of the
+    /// tuple data. Inserting a tuple uses its size plus [`TUPLE_INFO_SIZE`] bytes of this space.
+    pub(crate) fn free_space(&self) -> usize {
+        let slots_end = TABLE_PAGE_HEADER_SIZE + self.tuple_count() as usize * TUPLE_INFO_SIZE;
+        self.free_space_end().saturating_sub(slots_end)
+    }
 
-        // 1. calculate space used for header and slots
-        let header_size = TABLE_PAGE_HEADER_SIZE;
-        let tuple_count = self.tuple_count() as usize;
-        let tuples_total_size = tuple_count * TUPLE_INFO_SIZE;
-        
-        // 2. calculate where free space begins
-        // free space starts at the end of the last tuple
-        let used_space = header_size + tuples_total_size;
-        
-        // 3. calculate location of next tuple
-        let tuple_size = tuple.data().len();
-        let new_offset = PAGE_SIZE - tuple_size;
-        
-        // 4. check if the next tuple fits 
-        if new_offset < used_space {
-            return Err(Error::OutOfBounds)
-            
+    /// Returns the number of bytes available to [`TablePage::insert_tuple`], i.e. the free space
+    /// plus the space of deleted tuples, which inserting reclaims by compacting the page.
+    pub(crate) fn available_space(&self) -> usize {
+        self.free_space() + self.reclaimable_space()
+    }
+
+    /// Returns the number of bytes that compacting this page would add to its free space, i.e.
+    /// those used by the data of deleted tuples.
+    fn reclaimable_space(&self) -> usize {
+        if self.deleted_tuple_count() == 0 {
+            return 0;
         }
+        self.slot_array()
+            .iter()
+            .filter(|slot| slot.metadata().is_deleted())
+            .map(|slot| slot.size_bytes() as usize)
+            .sum()
+    }
 
-        // 5. return the new offset
-        Ok(new_offset as u16)
+    /// Tuples are stored back-to-front from the end of the page, so free space ends where the
+    /// lowest tuple begins.
+    fn free_space_end(&self) -> usize {
+        self.slot_array()
+            .iter()
+            .map(|slot| slot.offset() as usize)
+            .min()
+            .unwrap_or(self.page_frame_handle.data().len())
+    }
+
+    /// Returns the offset at which `tuple` would be stored if it were inserted into this page, or
+    /// [`Error::OutOfBounds`] if there isn't enough free space for both the tuple and its slot.
+    fn get_next_tuple_offset(&self, tuple: &Tuple) -> Result<u16> {
+        let free_space_end = self.free_space_end();
+        // The slot array grows front-to-back after the header, and needs room for one more slot.
+        let slots_end =
+            TABLE_PAGE_HEADER_SIZE + (self.tuple_count() as usize + 1) * TUPLE_INFO_SIZE;
+
+        match free_space_end.checked_sub(tuple.tuple_size()) {
+            Some(offset) if offset >= slots_end => Ok(offset as u16),
+            _ => Err(Error::OutOfBounds),
+        }
     }
 
     fn validate_record_id(&self, rid: &RecordId) -> Result<()> {
@@ -163,13 +227,18 @@ impl<T: DerefMut<Target = PageFrame> + Deref<Target = PageFrame>> TablePage<T> {
     pub(crate) fn init_header(&mut self, next_page_id: PageId) {
         let header = self.header_mut();
         *header = TablePageHeader {
+            page_lsn: header.page_lsn,
             next_page_id,
             tuple_cnt: 0,
             deleted_tuple_cnt: 0,
-            _padding: [0; 4],
+            rid_map_page_id: INVALID_PAGE_ID,
         };
     }
 
+    pub(crate) fn set_rid_map_page_id(&mut self, rid_map_page_id: PageId) {
+        self.header_mut().rid_map_page_id = rid_map_page_id;
+    }
+
     pub(crate) fn set_next_page_id(&mut self, next_page_id: PageId) {
         let header = self.header_mut();
         header.next_page_id = next_page_id;
@@ -180,70 +249,48 @@ impl<T: DerefMut<Target = PageFrame> + Deref<Target = PageFrame>> TablePage<T> {
         header.tuple_cnt = tuple_count;
     }
 
+    pub(crate) fn set_page_lsn(&mut self, lsn: Lsn) {
+        self.page_frame_handle.set_page_lsn(lsn);
+    }
+
+    /// Inserts `tuple` into this page, returning its record id, or [`Error::OutOfBounds`] if it
+    /// doesn't fit. If it only fits once the space of deleted tuples is reclaimed, the page is
+    /// compacted first.
     pub(crate) fn insert_tuple(&mut self, meta: &TupleMetadata, tuple: &Tuple) -> Result<RecordId> {
-        // // 1. get the next tuple offset
-        // let offset = self.get_next_tuple_offset(tuple)?;
-        // // 2. get the slot id
-        // let slot_id = self.tuple_count();
-        // // 3. update the header
-        // self.set_tuple_count(slot_id + 1);
-        // // 4. update the slot
-        // let slot = &mut self.slot_array_mut()[slot_id as usize];
-        // slot.offset = offset;
-        // slot.size_bytes = tuple.size_bytes() as u16;
-        // slot.metadata = *meta;
-        // // 5. write the tuple
-        // let tuple_data = tuple.data();
-        // let tuple_size = tuple_data.len();
-        // self.page_frame_handle.data_mut()[offset as usize..offset as usize + tuple_size]
-        //     .copy_from_slice(tuple_data);
-        // // 6. return the record id
-
-        // 1. calculate the next tuple offset
-        let tuple_size = tuple.data().len();
-        let tuple_count = self.header().tuple_cnt as usize;
-        let offset = self.get_next_tuple_offset(tuple)? as usize;
-        
-        // 2. check that the tuple fits in the page
-        let used_space = TABLE_PAGE_HEADER_SIZE + (tuple_count + 1) * TUPLE_INFO_SIZE;
-        if offset < used_space {
-            return Err(Error::OutOfBounds);
-        }
-        
-        // 3. write the tuple to the page
-        let start = offset;
-        let end = offset + tuple_size;
-        self.page_frame_handle.data_mut()[start..end].copy_from_slice(&tuple.data());
+        // 1. find where the tuple goes, making sure it (and its slot) fits in the page
+        let offset = match self.get_next_tuple_offset(tuple) {
+            Err(Error::OutOfBounds)
+                if self.available_space() >= tuple.tuple_size() + TUPLE_INFO_SIZE =>
+            {
+                self.compact();
+                self.get_next_tuple_offset(tuple)?
+            }
+            result => result?,
+        } as usize;
+        let tuple_count = self.tuple_count() as usize;
+
+        // 2. write the tuple to the page
+        let tuple_size = tuple.tuple_size();
+        self.page_frame_handle.write(offset, tuple.as_slice())?;
 
-        // 4. add a new slot
+        // 3. append a new slot to the slot array
         let new_info = TupleInfo {
             offset: offset as u16,
             size_bytes: tuple_size as u16,
             metadata: *meta,
         };
-        
-        // 5. update slot array
-        // get metadata section of the page 
-        let slots_mut = self.slot_array_mut();
-        // if there is space for the new slot, add it 
-        if slots_mut.len() > tuple_count {
-            slots_mut[tuple_count] = new_info;
-        // else, add the new slot to the end of the page
-        } else {
-            // calculate the start and end of the new slot
-            let start_bytes = TABLE_PAGE_HEADER_SIZE + tuple_count * TUPLE_INFO_SIZE;
-            let end_bytes = start_bytes + TUPLE_INFO_SIZE;
-            let bytes = &mut self.page_frame_handle.data_mut()[start_bytes..end_bytes];
-            bytes.copy_from_slice(bytemuck::bytes_of(&new_info));
-        }
+        let slot_start = TABLE_PAGE_HEADER_SIZE + tuple_count * TUPLE_INFO_SIZE;
+        self.page_frame_handle
+            .write(slot_start, bytemuck::bytes_of(&new_info))?;
 
-        // 6. update header
+        // 4. update header
         self.header_mut().tuple_cnt += 1;
+        if meta.is_deleted() {
+            self.header_mut().deleted_tuple_cnt += 1;
+        }
 
-        // 7. return the record id
+        // 5. return the record id
         Ok(RecordId::new(self.page_id(), tuple_count as u32))
-
-        
     }
 
     pub(crate) fn update_tuple_metadata(
@@ -251,28 +298,124 @@ impl<T: DerefMut<Target = PageFrame> + Deref<Target = PageFrame>> TablePage<T> {
         rid: &RecordId,
         metadata: TupleMetadata,
     ) -> Result<()> {
-        
         // 1. validate record id
         self.validate_record_id(rid)?;
-        
+
         // 2. get mutable access to slot array
         let slots_mut = self.slot_array_mut();
-        
+
         // 3. find the correct slot for this metadata
         let slot = &mut slots_mut[rid.slot_id() as usize];
-        
-        // 4. update the metadata
+
+        // 4. update the metadata, keeping the header's count of deleted tuples in sync
+        let was_deleted = slot.metadata.is_deleted();
         slot.metadata = metadata;
-        
+        match (was_deleted, metadata.is_deleted()) {
+            (false, true) => self.header_mut().deleted_tuple_cnt += 1,
+            (true, false) => self.header_mut().deleted_tuple_cnt -= 1,
+            _ => {}
+        }
+
         // 5. return ok if successful
         Ok(())
     }
+
+    /// Reclaims the space used by deleted tuples by packing the live tuples against the end of the
+    /// page. Slots (and thus record ids) are kept, but the data of deleted tuples is discarded.
+    pub(crate) fn compact(&mut self) {
+        // 1. copy out the data of the live tuples
+        let live_tuples = self
+            .slot_array()
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| !slot.metadata.is_deleted())
+            .map(|(slot_id, slot)| {
+                let offset = slot.offset() as usize;
+                let data = self.page_frame_handle.data()
+                    [offset..offset + slot.size_bytes() as usize]
+                    .to_vec();
+                (slot_id, data)
+            })
+            .collect::<Vec<_>>();
+
+        // 2. write the live tuples back-to-front from the end of the page
+        let mut free_space_end = self.page_frame_handle.data().len();
+        for (slot_id, data) in &live_tuples {
+            free_space_end -= data.len();
+            self.page_frame_handle.data_mut()[free_space_end..free_space_end + data.len()]
+                .copy_from_slice(data);
+            self.slot_array_mut()[*slot_id].offset = free_space_end as u16;
+        }
+
+        // 3. deleted tuples no longer take up any space
+        for slot in self.slot_array_mut() {
+            if slot.metadata.is_deleted() {
+                slot.offset = free_space_end as u16;
+                slot.size_bytes = 0;
+            }
+        }
+    }
+
+    /// Overwrites the tuple at `rid` with `tuple`, reusing its existing space in the page.
+    /// Returns [`Error::OutOfBounds`] if `tuple` is larger than the tuple it replaces.
+    pub(crate) fn update_tuple_in_place(&mut self, rid: &RecordId, tuple: &Tuple) -> Result<()> {
+        // 1. validate record id
+        self.validate_record_id(rid)?;
+
+        // 2. make sure the new tuple fits in the old tuple's space
+        let slot = self.slot_array()[rid.slot_id() as usize];
+        let tuple_size = tuple.tuple_size();
+        if tuple_size > slot.size_bytes() as usize {
+            return Err(Error::OutOfBounds);
+        }
+
+        // 3. overwrite the tuple data
+        let offset = slot.offset() as usize;
+        self.page_frame_handle.write(offset, tuple.as_slice())?;
+
+        // 4. shrink the slot to the new tuple's size
+        self.slot_array_mut()[rid.slot_id() as usize].size_bytes = tuple_size as u16;
+
+        Ok(())
+    }
+
+    /// Overwrites the tuple at `rid` with `tuple`, which unlike with
+    /// [`TablePage::update_tuple_in_place`] may be larger than the tuple it replaces, as long as
+    /// it fits before the next tuple in the page. This lets an in-place update that shrank a tuple
+    /// be undone, unless the page has been compacted since. Returns [`Error::OutOfBounds`] if
+    /// `tuple` doesn't fit.
+    pub(crate) fn restore_tuple(&mut self, rid: &RecordId, tuple: &Tuple) -> Result<()> {
+        // 1. validate record id
+        self.validate_record_id(rid)?;
+
+        // 2. the tuple's space extends up to the next tuple, or the end of the page
+        let offset = self.slot_array()[rid.slot_id() as usize].offset() as usize;
+        let space_end = self
+            .slot_array()
+            .iter()
+            .map(|slot| slot.offset() as usize)
+            .filter(|&other| other > offset)
+            .min()
+            .unwrap_or(self.page_frame_handle.data().len());
+        let tuple_size = tuple.tuple_size();
+        if offset + tuple_size > space_end {
+            return Err(Error::OutOfBounds);
+        }
+
+        // 3. overwrite the tuple data and resize the slot
+        self.page_frame_handle.write(offset, tuple.as_slice())?;
+        self.slot_array_mut()[rid.slot_id() as usize].size_bytes = tuple_size as u16;
+
+        Ok(())
+    }
 }
 
 /// Type alias for immutable TablePage
 pub type TablePageRef<'a> = TablePage<PageFrameRefHandle<'a>>;
 /// Type alias for mutable TablePage
 pub type TablePageMut<'a> = TablePage<PageFrameMutHandle<'a>>;
+/// Type alias for a read-only copy of a TablePage (see [`TablePage::snapshot`])
+pub type TablePageSnapshot = TablePage<Box<PageFrame>>;
 
 impl<'a> From<PageFrameRefHandle<'a>> for TablePageRef<'a> {
     fn from(page_frame_handle: PageFrameRefHandle<'a>) -> Self {
@@ -294,7 +437,7 @@ mod tests {
     use serial_test::serial;
 
     use crate::{
-        buffer_pool::BufferPoolManager, disk::disk_manager::DiskManager,
+        buffer_pool::BufferPoolManager, disk::disk_manager::DiskManager, page::INVALID_PAGE_ID,
         replacer::lru_k_replacer::LrukReplacer,
     };
 
@@ -311,6 +454,7 @@ mod tests {
     }
 
     #[test]
+    #[serial]
     pub fn test_insert_tuple() {
         let bpm = get_bpm_arc_with_pool_size(10);
         let frame_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
@@ -389,6 +533,7 @@ mod tests {
     }
 
     #[test]
+    #[serial]
     fn test_insert_and_get_tuple() {
         let bpm = get_bpm_arc_with_pool_size(10);
 
@@ -435,4 +580,85 @@ mod tests {
         assert_eq!(retrieved_tuple.data(), &tuple_data);
     }
 
+    #[test]
+    #[serial]
+    fn test_compact() {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let frame_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let mut table_page = TablePageMut::from(frame_handle);
+        table_page.init_header(INVALID_PAGE_ID);
+
+        let meta = TupleMetadata::new(false);
+        let rids = (0..3u8)
+            .map(|i| {
+                let tuple = Tuple::new(vec![i; 100].into());
+                table_page.insert_tuple(&meta, &tuple).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let free_space = table_page.free_space();
+
+        table_page
+            .update_tuple_metadata(&rids[1], TupleMetadata::new(true))
+            .unwrap();
+        assert_eq!(table_page.free_space(), free_space);
+
+        // Compacting reclaims the deleted tuple's space and keeps the live tuples' record ids.
+        table_page.compact();
+        assert_eq!(table_page.free_space(), free_space + 100);
+        assert_eq!(
+            table_page.get_tuple(&rids[0]).unwrap().1.data().to_vec(),
+            vec![0; 100]
+        );
+        assert_eq!(
+            table_page.get_tuple(&rids[2]).unwrap().1.data().to_vec(),
+            vec![2; 100]
+        );
+        assert!(table_page.get_tuple(&rids[1]).unwrap().0.is_deleted());
+    }
+
+    #[test]
+    #[serial]
+    fn test_insert_compacts_fragmented_page() {
+        let bpm = get_bpm_arc_with_pool_size(10);
+        let frame_handle = BufferPoolManager::create_page_handle(&bpm).unwrap();
+        let mut table_page = TablePageMut::from(frame_handle);
+        table_page.init_header(INVALID_PAGE_ID);
+
+        // Fill the page with equal-size tuples.
+        let meta = TupleMetadata::new(false);
+        let tuple_size = 500;
+        let mut rids = Vec::new();
+        let mut i = 0u8;
+        while let Ok(rid) = table_page.insert_tuple(&meta, &Tuple::new(vec![i; tuple_size].into()))
+        {
+            rids.push(rid);
+            i += 1;
+        }
+        assert!(table_page.free_space() < tuple_size + TUPLE_INFO_SIZE);
+
+        // Once an early tuple is deleted, a same-size tuple only fits after compaction, which
+        // happens as part of the insert.
+        table_page
+            .update_tuple_metadata(&rids[0], TupleMetadata::new(true))
+            .unwrap();
+        let rid = table_page
+            .insert_tuple(&meta, &Tuple::new(vec![u8::MAX; tuple_size].into()))
+            .unwrap();
+        assert_eq!(
+            table_page.get_tuple(&rid).unwrap().1.data().to_vec(),
+            vec![u8::MAX; tuple_size]
+        );
+        for (i, rid) in rids.iter().enumerate().skip(1) {
+            assert_eq!(
+                table_page.get_tuple(rid).unwrap().1.data().to_vec(),
+                vec![i as u8; tuple_size]
+            );
+        }
+
+        // The reclaimed space is used up, so another insert still doesn't fit.
+        assert!(matches!(
+            table_page.insert_tuple(&meta, &Tuple::new(vec![0; tuple_size].into())),
+            Err(Error::OutOfBounds)
+        ));
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{page::INVALID_PAGE_ID, typedef::PageId};
+use rustdb_error::{errinput, Error};
 
-#[derive(Clone, Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RecordId {
     /// The ID of the page the record lives inside.
     page_id: PageId,
@@ -42,10 +43,6 @@ impl RecordId {
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{}:{}", self.page_id, self.slot_id)
-    }
-
     pub fn page_id(&self) -> PageId {
         self.page_id
     }
@@ -53,15 +50,36 @@ impl RecordId {
     pub fn slot_id(&self) -> u32 {
         self.slot_id
     }
+
+    /// Returns whether the record id can refer to a record, i.e. it doesn't point into the invalid
+    /// page (like [`INVALID_RECORD_ID`] does).
+    pub fn is_valid(&self) -> bool {
+        self.page_id != INVALID_PAGE_ID
+    }
 }
 
-impl PartialEq<Self> for RecordId {
-    fn eq(&self, other: &Self) -> bool {
-        self.page_id == other.page_id && self.slot_id == other.slot_id
+/// Formats the record id as `page_id:slot_id`, e.g. `3:7`.
+impl std::fmt::Display for RecordId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.page_id, self.slot_id)
     }
 }
 
-impl Eq for RecordId {} // implement Eq trait for RecordId, uses PartialEq
+/// Parses a record id formatted as `page_id:slot_id`, returning [`Error::InvalidInput`] if the
+/// input is malformed.
+impl std::str::FromStr for RecordId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((page_id, slot_id)) = s.split_once(':') else {
+            return errinput!("Invalid record id {s}, expected page_id:slot_id");
+        };
+        match (page_id.parse(), slot_id.parse()) {
+            (Ok(page_id), Ok(slot_id)) => Ok(RecordId::new(page_id, slot_id)),
+            _ => errinput!("Invalid record id {s}, expected page_id:slot_id"),
+        }
+    }
+}
 
 impl Ord for RecordId {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
@@ -87,4 +105,33 @@ impl PartialOrd for RecordId {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{RecordId, INVALID_RECORD_ID};
+    use rustdb_error::Error;
 
+    #[test]
+    fn test_display_and_parse() {
+        let rid = RecordId::new(3, 7);
+        assert_eq!(rid.to_string(), "3:7");
+        assert_eq!(format!("{rid}"), "3:7");
+        assert_eq!("3:7".parse::<RecordId>().unwrap(), rid);
+
+        let rid = RecordId::new(u32::MAX, 0);
+        assert_eq!(rid.to_string().parse::<RecordId>().unwrap(), rid);
+
+        for malformed in ["", "3", "3:", ":7", "3:7:1", "a:7", "3:-7", "3 : 7"] {
+            assert!(
+                matches!(malformed.parse::<RecordId>(), Err(Error::InvalidInput(_))),
+                "parsing {malformed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(!INVALID_RECORD_ID.is_valid());
+        assert!(!RecordId::new(0, 7).is_valid());
+        assert!(RecordId::new(1, 0).is_valid());
+    }
+}
@@ -0,0 +1,217 @@
+use std::sync::{Arc, RwLock};
+
+use rustdb_catalog::tuple::Tuple;
+use rustdb_error::{errdata, errinput};
+
+use crate::{
+    buffer_pool::BufferPoolManager,
+    page::table_page::{TablePageMut, TupleMetadata},
+    storage::StorageEngine,
+    typedef::{Lsn, TxnId},
+    wal::{LogRecord, WalManager},
+    Result,
+};
+
+/// Brings the table pages back in line with the write-ahead log after a crash.
+pub(crate) struct Recovery;
+
+impl Recovery {
+    /// Replays the durable records of `wal` against the pages of `storage`'s buffer pool.
+    ///
+    /// Every change is redone in LSN order, unless its page already reflects it, i.e. the page's
+    /// LSN is at least the record's. The changes of transactions that never committed are then
+    /// undone, newest first, using the before images in the log.
+    ///
+    /// Changes aren't tagged with their transaction in the log: a change belongs to the last
+    /// transaction begun before it, unless that transaction had already committed, in which case
+    /// the change is treated as committed. Allocating and linking pages isn't logged either, so
+    /// the page chains of the tables must be intact on disk.
+    ///
+    /// Must run before any table of `storage` is created or opened, since table heaps cache the
+    /// state of their pages, and returns [`rustdb_error::Error::InvalidInput`] otherwise. Returns
+    /// [`rustdb_error::Error::InvalidData`] if the log doesn't match the pages.
+    pub(crate) fn redo(wal: &WalManager, storage: &StorageEngine) -> Result<()> {
+        if storage.has_tables() {
+            return errinput!("Recovery must run before any table is opened");
+        }
+        let bpm = storage.bpm();
+        let records = wal.read_records()?;
+
+        // Redo every change, setting aside those of transactions that never commit.
+        let mut running: Option<(TxnId, Vec<&LogRecord>)> = None;
+        let mut uncommitted = Vec::new();
+        for (lsn, record) in &records {
+            match record {
+                LogRecord::Begin { txn } => {
+                    if let Some((_, changes)) = running.replace((*txn, Vec::new())) {
+                        uncommitted.extend(changes);
+                    }
+                }
+                LogRecord::CommitTxn { txn } => match running.take() {
+                    Some((running_txn, _)) if running_txn == *txn => {}
+                    _ => return errdata!("Log record {lsn} commits {txn}, which isn't running"),
+                },
+                change => {
+                    Self::redo_change(bpm, *lsn, change)?;
+                    if let Some((_, changes)) = running.as_mut() {
+                        changes.push(change);
+                    }
+                }
+            }
+        }
+        uncommitted.extend(running.into_iter().flat_map(|(_, changes)| changes));
+
+        for change in uncommitted.into_iter().rev() {
+            Self::undo_change(bpm, change)?;
+        }
+        Ok(())
+    }
+
+    /// Applies `change`, the record with LSN `lsn`, to its page if the page doesn't reflect it yet.
+    fn redo_change(
+        bpm: &Arc<RwLock<BufferPoolManager>>,
+        lsn: Lsn,
+        change: &LogRecord,
+    ) -> Result<()> {
+        let Some(rid) = change.rid() else {
+            return Ok(());
+        };
+        let page_handle = BufferPoolManager::fetch_page_mut_handle(bpm, rid.page_id())?;
+        let mut table_page = TablePageMut::from(page_handle);
+        if table_page.page_lsn() >= lsn {
+            return Ok(());
+        }
+
+        match change {
+            LogRecord::Insert { tuple_bytes, .. } => {
+                let tuple = Tuple::new(tuple_bytes.clone().into());
+                // Changes are redone in order, so the tuple lands in the slot it was logged in.
+                let new_rid = table_page.insert_tuple(&TupleMetadata::new(false), &tuple)?;
+                if new_rid != *rid {
+                    return errdata!(
+                        "Log record {lsn} inserted {rid}, but redoing it inserted {new_rid}"
+                    );
+                }
+            }
+            LogRecord::Delete { .. } => {
+                table_page.update_tuple_metadata(rid, TupleMetadata::new(true))?;
+            }
+            LogRecord::Update { after, .. } => {
+                table_page.update_tuple_in_place(rid, &Tuple::new(after.clone().into()))?;
+            }
+            LogRecord::CommitTxn { .. } | LogRecord::Begin { .. } => {}
+        }
+        table_page.set_page_lsn(lsn);
+        Ok(())
+    }
+
+    /// Reverts `change`, which its page must reflect.
+    fn undo_change(bpm: &Arc<RwLock<BufferPoolManager>>, change: &LogRecord) -> Result<()> {
+        let Some(rid) = change.rid() else {
+            return Ok(());
+        };
+        let page_handle = BufferPoolManager::fetch_page_mut_handle(bpm, rid.page_id())?;
+        let mut table_page = TablePageMut::from(page_handle);
+
+        match change {
+            LogRecord::Insert { .. } => {
+                table_page.update_tuple_metadata(rid, TupleMetadata::new(true))
+            }
+            LogRecord::Delete { .. } => {
+                table_page.update_tuple_metadata(rid, TupleMetadata::new(false))
+            }
+            LogRecord::Update { before, .. } => {
+                table_page.restore_tuple(rid, &Tuple::new(before.clone().into()))
+            }
+            LogRecord::CommitTxn { .. } | LogRecord::Begin { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use rustdb_catalog::{catalog::StorageApi, schema::Schema, tuple::Tuple};
+    use rustdb_error::Error;
+
+    use super::Recovery;
+    use crate::{
+        buffer_pool::BufferPoolManager,
+        disk::{disk::Disk, memory_disk_manager::MemoryDiskManager},
+        replacer::lru_k_replacer::LrukReplacer,
+        storage::StorageEngine,
+        wal::WalManager,
+        Result,
+    };
+
+    /// Returns a storage engine over `disk`, logging to the log stored on `log_disk`.
+    fn get_storage_engine(
+        disk: &Arc<Mutex<dyn Disk>>,
+        log_disk: &Arc<Mutex<dyn Disk>>,
+    ) -> Result<(StorageEngine, Arc<Mutex<WalManager>>)> {
+        let wal = Arc::new(Mutex::new(WalManager::new(log_disk.clone())?));
+        let replacer = Box::new(LrukReplacer::new(5));
+        let mut bpm = BufferPoolManager::new(10, disk.clone(), replacer);
+        bpm.set_wal(wal.clone());
+        Ok((StorageEngine::new(Arc::new(RwLock::new(bpm))), wal))
+    }
+
+    fn tuple(data: &[u8]) -> Tuple {
+        Tuple::new(data.to_vec().into())
+    }
+
+    #[test]
+    fn test_redo_after_crash() -> Result<()> {
+        let disk: Arc<Mutex<dyn Disk>> = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let log_disk: Arc<Mutex<dyn Disk>> = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let schema = Schema::new(&[]);
+
+        let first_page_id = {
+            let (engine, wal) = get_storage_engine(&disk, &log_disk)?;
+            engine.create_table(0, "table", &schema)?;
+            let first_page_id = engine.first_page_id(0)?;
+
+            let txn = engine.begin_txn()?;
+            let a = engine.insert_tuple(0, &tuple(b"a1"))?;
+            let b = engine.insert_tuple(0, &tuple(b"b1"))?;
+            let c = engine.insert_tuple(0, &tuple(b"c1"))?;
+            engine.update_tuple(0, b, &tuple(b"b2"))?;
+            engine.delete_tuple(0, c)?;
+            engine.commit_txn(txn)?;
+            // The page on disk reflects the first transaction, whose changes must not be redone.
+            engine.bpm().write()?.flush_page(&first_page_id)?;
+
+            let txn = engine.begin_txn()?;
+            let d = engine.insert_tuple(0, &tuple(b"d1"))?;
+            engine.commit_txn(txn)?;
+
+            // The last transaction's changes are logged, but it never commits.
+            engine.begin_txn()?;
+            engine.insert_tuple(0, &tuple(b"e1"))?;
+            engine.update_tuple(0, a, &tuple(b"a"))?;
+            engine.delete_tuple(0, d)?;
+            wal.lock()?.flush()?;
+
+            // Crash without flushing the buffer pool.
+            first_page_id
+        };
+
+        let (engine, wal) = get_storage_engine(&disk, &log_disk)?;
+        Recovery::redo(&*wal.lock()?, &engine)?;
+        engine.open_table(0, "table", first_page_id, &schema)?;
+        let tuples = engine
+            .scan(0)?
+            .map(|item| Ok(item?.1.data().to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(tuples, vec![b"a1".to_vec(), b"b2".to_vec(), b"d1".to_vec()]);
+
+        // Recovery can't run once tables are open.
+        assert!(matches!(
+            Recovery::redo(&*wal.lock()?, &engine),
+            Err(Error::InvalidInput(_))
+        ));
+
+        Ok(())
+    }
+}
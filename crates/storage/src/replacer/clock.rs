@@ -0,0 +1,62 @@
+use std::fmt::Debug;
+
+/// A source of the timestamps that replacers record frame accesses with.
+pub(crate) trait Clock: Send + Sync + Debug {
+    /// Returns the current time. Times never decrease between calls, but may repeat, so
+    /// replacers must break ties between equal timestamps themselves.
+    fn now(&mut self) -> u64;
+}
+
+/// A clock that counts calls to [`Clock::now`], so that every access gets its own timestamp.
+/// Rather than wrapping around, the count stops at `u64::MAX`, after which every access has the
+/// same timestamp.
+#[derive(Debug, Default)]
+pub(crate) struct LogicalClock {
+    next: u64,
+}
+
+impl Clock for LogicalClock {
+    fn now(&mut self) -> u64 {
+        let now = self.next;
+        self.next = self.next.saturating_add(1);
+        now
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::{Clock, LogicalClock};
+
+    /// A clock that's only moved by the test holding it, through a shared handle.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct FakeClock {
+        time: Arc<AtomicU64>,
+    }
+
+    impl FakeClock {
+        pub(crate) fn set(&self, time: u64) {
+            self.time.store(time, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&mut self) -> u64 {
+            self.time.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn test_logical_clock_saturates() {
+        let mut clock = LogicalClock::default();
+        assert_eq!(clock.now(), 0);
+        assert_eq!(clock.now(), 1);
+
+        clock.next = u64::MAX - 1;
+        assert_eq!(clock.now(), u64::MAX - 1);
+        assert_eq!(clock.now(), u64::MAX);
+        assert_eq!(clock.now(), u64::MAX);
+    }
+}
@@ -1,3 +1,4 @@
+use super::clock::{Clock, LogicalClock};
 use super::replacer::Replacer;
 use crate::typedef::FrameId;
 use std::collections::{HashMap, VecDeque};
@@ -46,9 +47,17 @@ impl LrukNode {
         }
     }
 
-    /// Inserts a new access timestamp, maintaining the last K timestamps.
-    fn insert_history_timestamp(&mut self, current_timestamp: u64) {
-        assert!(self.history.is_empty() || current_timestamp > *self.history.back().unwrap());
+    /// Inserts a new access timestamp, maintaining the last K timestamps. An access less than
+    /// `crp` after the most recent one is correlated with it, so it replaces that timestamp
+    /// rather than counting as another reference.
+    fn insert_history_timestamp(&mut self, current_timestamp: u64, crp: u64) {
+        if let Some(last) = self.history.back_mut() {
+            assert!(current_timestamp >= *last);
+            if current_timestamp - *last < crp {
+                *last = current_timestamp;
+                return;
+            }
+        }
         self.history.push_back(current_timestamp);
         if self.history.len() > self.k {
             self.history.pop_front();
@@ -61,26 +70,52 @@ impl LrukNode {
 pub(crate) struct LrukReplacer {
     node_store: HashMap<FrameId, LrukNode>,
     evictable_size: usize, // Number of evictable nodes
-    current_timestamp: u64,
-    k: usize, // Number of accesses to track
+    clock: Box<dyn Clock>,
+    current_timestamp: u64, // The time of the latest access
+    k: usize,               // Number of accesses to track
+    crp: u64,               // Correlated reference period
 }
 
 impl LrukReplacer {
-    /// Creates a new LRU-K replacer instance.
+    /// Creates a new LRU-K replacer instance, which timestamps accesses by counting them.
     pub(crate) fn new(k: usize) -> Self {
+        Self::with_clock(k, Box::new(LogicalClock::default()))
+    }
+
+    /// Creates a new LRU-K replacer instance that timestamps accesses with `clock`.
+    pub(crate) fn with_clock(k: usize, clock: Box<dyn Clock>) -> Self {
         LrukReplacer {
             node_store: HashMap::new(),
             evictable_size: 0,
+            clock,
             current_timestamp: 0,
             k,
+            crp: 0,
         }
     }
 
-    /// Increments and returns the current timestamp.
+    /// Sets the correlated reference period: accesses to a frame less than `crp` ticks of the
+    /// clock after its previous one count as the same reference, so that a burst of accesses
+    /// isn't mistaken for K separate ones. The default of 0 counts every access.
+    pub(crate) fn with_crp(mut self, crp: u64) -> Self {
+        self.crp = crp;
+        self
+    }
+
+    /// Reads the clock, returning the timestamp of the access being recorded.
     fn advance_timestamp(&mut self) -> u64 {
-        let old_timestamp = self.current_timestamp;
-        self.current_timestamp += 1;
-        old_timestamp
+        self.current_timestamp = self.clock.now();
+        self.current_timestamp
+    }
+
+    /// Checks that `evictable_size` counts exactly the evictable nodes. Only runs in debug builds,
+    /// since it visits every node.
+    fn debug_check_evictable_size(&self) {
+        debug_assert_eq!(
+            self.evictable_size,
+            self.node_store.values().filter(|n| n.is_evictable).count(),
+            "evictable_size is out of sync with the evictable nodes"
+        );
     }
 }
 
@@ -97,7 +132,8 @@ impl Replacer for LrukReplacer {
             .or_insert_with(|| LrukNode::new(frame_id, self.k));
 
         // 3. update the timestamp history
-        node.insert_history_timestamp(current_ts);
+        node.insert_history_timestamp(current_ts, self.crp);
+        self.debug_check_evictable_size();
     }
 
     /// Pins a frame, making it non-evictable.
@@ -109,13 +145,11 @@ impl Replacer for LrukReplacer {
             .entry(frame_id)
             .or_insert_with(|| LrukNode::new(frame_id, self.k));
         // 2. update the evictable status
-        if let Some(node) = self.node_store.get_mut(&frame_id) {
-            // first check that the frame is in the replacer
-            if node.is_evictable {
-                node.is_evictable = false; // make non-evictable
-                self.evictable_size -= 1; // update number of evictable frames
-            }
+        if node.is_evictable {
+            node.is_evictable = false; // make non-evictable
+            self.evictable_size -= 1; // update number of evictable frames
         }
+        self.debug_check_evictable_size();
     }
 
     /// Unpins a frame, making it evictable.
@@ -130,17 +164,24 @@ impl Replacer for LrukReplacer {
             node.is_evictable = true; // make evictable
             self.evictable_size += 1; // update number of evictable frames
         }
+        self.debug_check_evictable_size();
     }
 
     /// Evicts the frame with the largest backward k-distance.
     fn evict(&mut self) -> Option<FrameId> {
+        self.evict_preferring_clean(&|_| true)
+    }
+
+    /// Evicts the frame with the largest backward k-distance, preferring clean frames among those
+    /// with the same k-distance and oldest timestamp.
+    fn evict_preferring_clean(&mut self, is_clean: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
         // 1. handle the case where there are no evictable frames
         if self.evictable_size == 0 {
             return None;
         }
 
         let current_ts = self.current_timestamp;
-        let mut candidate: Option<(FrameId, u64, u64)> = None;
+        let mut candidate: Option<(FrameId, u64, u64, bool)> = None;
 
         // 2. iterate over all the frames in the replacer
         for node in self.node_store.values() {
@@ -152,43 +193,51 @@ impl Replacer for LrukReplacer {
             // 3. calculate the backward k-distance and oldest timestamp for each frame
             let dist = node.get_backwards_k_distance(current_ts);
             let earliest = node.get_earliest_timestamp();
+            let clean = is_clean(node.frame_id);
 
             // choose the best candidate
             match &candidate {
-                None => candidate = Some((node.frame_id, dist, earliest)),
-                Some((_, best_dist, best_ts)) => {
+                None => candidate = Some((node.frame_id, dist, earliest, clean)),
+                Some((best_frame, best_dist, best_ts, best_clean)) => {
                     if dist > *best_dist // this frame's k-distance is bigger -> less recently used -> better eviction candidate
                         || (dist == *best_dist && earliest < *best_ts)
-                    // k-distances are the same -> choose the one with the older timestamp
+                        // k-distances are the same -> choose the one with the older timestamp
+                        || (dist == *best_dist && earliest == *best_ts && clean && !*best_clean)
+                        // timestamps are the same too -> choose a clean frame, which is free to evict
+                        || (dist == *best_dist
+                            && earliest == *best_ts
+                            && clean == *best_clean
+                            && node.frame_id < *best_frame)
+                    // both or neither are clean -> choose the lower frame id, whatever the order
+                    // of the node store
                     {
-                        candidate = Some((node.frame_id, dist, earliest));
+                        candidate = Some((node.frame_id, dist, earliest, clean));
                     }
                 }
             }
         }
 
         // 4. evict the candidate frame
-        if let Some((frame_id, _, _)) = candidate {
+        let evicted = candidate.map(|(frame_id, ..)| {
             self.node_store.remove(&frame_id); // remove
             self.evictable_size -= 1; // update number of evictable frames
-            Some(frame_id) // return evicted frame id so the buffer pool knows which one to evict
-        } else {
-            None
-        }
+            frame_id // return evicted frame id so the buffer pool knows which one to evict
+        });
+        self.debug_check_evictable_size();
+        evicted
     }
 
-    /// Removes a frame from the replacer if it is evictable.
+    /// Removes a frame and its access history from the replacer, whether or not it is evictable.
     fn remove(&mut self, frame_id: FrameId) {
-        if let Some(node) = self.node_store.get(&frame_id) {
-            // first check that the frame is in the replacer
+        // only evictable frames are counted in the evictable size
+        if let Some(node) = self.node_store.remove(&frame_id) {
             if node.is_evictable {
-                self.node_store.remove(&frame_id); // remove the frame
                 self.evictable_size -= 1; // update number of evictable frames
             }
         }
+        self.debug_check_evictable_size();
     }
 
-
     /// Returns the number of evictable frames.
     fn evictable_count(&self) -> usize {
         self.evictable_size
@@ -198,6 +247,7 @@ impl Replacer for LrukReplacer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::replacer::clock::tests::FakeClock;
 
     #[test]
     fn test_lruk_replacer_one() {
@@ -364,6 +414,115 @@ mod tests {
         lru_replacer.unpin(6);
     }
 
+    #[test]
+    fn test_lruk_replacer_equal_timestamps() {
+        let clock = FakeClock::default();
+        let mut lru_replacer = LrukReplacer::with_clock(2, Box::new(clock.clone()));
+
+        // Frames accessed at the same times are evicted in frame id order.
+        for frame_id in [5, 3, 8, 1] {
+            lru_replacer.record_access(frame_id);
+            lru_replacer.unpin(frame_id);
+        }
+        clock.set(10);
+        for frame_id in [8, 1, 5, 3] {
+            lru_replacer.record_access(frame_id);
+        }
+        // Frame 9 has fewer than k accesses, so it goes first despite being accessed last.
+        lru_replacer.record_access(9);
+        lru_replacer.unpin(9);
+
+        let evicted = std::iter::from_fn(|| lru_replacer.evict()).collect::<Vec<_>>();
+        assert_eq!(evicted, vec![9, 1, 3, 5, 8]);
+    }
+
+    #[test]
+    fn test_lruk_replacer_prefers_clean_frames() {
+        let clock = FakeClock::default();
+        let mut lru_replacer = LrukReplacer::with_clock(2, Box::new(clock.clone()));
+        let is_clean = |frame_id| frame_id != 1;
+
+        // Frames 1 and 2 are equally old, but only frame 2 is clean, so it goes first.
+        for frame_id in [1, 2] {
+            lru_replacer.record_access(frame_id);
+            lru_replacer.unpin(frame_id);
+        }
+        clock.set(10);
+        lru_replacer.record_access(3);
+        lru_replacer.unpin(3);
+        assert_eq!(Some(2), lru_replacer.evict_preferring_clean(&is_clean));
+
+        // Being clean doesn't outrank being older.
+        assert_eq!(Some(1), lru_replacer.evict_preferring_clean(&is_clean));
+        assert_eq!(Some(3), lru_replacer.evict_preferring_clean(&is_clean));
+        assert_eq!(None, lru_replacer.evict_preferring_clean(&is_clean));
+
+        // Without a preference, ties go to the lower frame id.
+        for frame_id in [1, 2] {
+            lru_replacer.record_access(frame_id);
+            lru_replacer.unpin(frame_id);
+        }
+        assert_eq!(Some(1), lru_replacer.evict());
+    }
+
+    #[test]
+    fn test_lruk_replacer_correlated_references() {
+        let clock = FakeClock::default();
+        let mut lru_replacer = LrukReplacer::with_clock(2, Box::new(clock.clone())).with_crp(5);
+
+        // Two accesses in quick succession count as a single reference, so frame 1's backward
+        // k-distance stays infinite.
+        lru_replacer.record_access(1);
+        clock.set(3);
+        lru_replacer.record_access(1);
+        assert_eq!(lru_replacer.node_store[&1].history, [3]);
+        assert_eq!(
+            lru_replacer.node_store[&1].get_backwards_k_distance(3),
+            u64::MAX
+        );
+
+        // Frame 2's accesses are far enough apart to count as two references.
+        clock.set(4);
+        lru_replacer.record_access(2);
+        clock.set(9);
+        lru_replacer.record_access(2);
+        assert_eq!(lru_replacer.node_store[&2].history, [4, 9]);
+
+        // So frame 1 is evicted first, even though it was accessed before frame 2.
+        lru_replacer.unpin(2);
+        lru_replacer.unpin(1);
+        assert_eq!(Some(1), lru_replacer.evict());
+        assert_eq!(Some(2), lru_replacer.evict());
+    }
+
+    #[test]
+    fn test_lruk_replacer_remove_pinned() {
+        let mut lru_replacer = LrukReplacer::new(2);
+        lru_replacer.record_access(1);
+        lru_replacer.record_access(2);
+        lru_replacer.unpin(1);
+        lru_replacer.pin(2);
+        assert_eq!(1, lru_replacer.evictable_count());
+
+        // Removing a pinned frame drops it without touching the evictable count.
+        lru_replacer.remove(2);
+        assert_eq!(1, lru_replacer.evictable_count());
+        assert!(!lru_replacer.node_store.contains_key(&2));
+
+        // Once re-added, the frame starts with a fresh history.
+        lru_replacer.record_access(2);
+        assert_eq!(1, lru_replacer.node_store[&2].history.len());
+        lru_replacer.unpin(2);
+        assert_eq!(2, lru_replacer.evictable_count());
+
+        // Removing an evictable frame, or one that isn't there, keeps the count in sync.
+        lru_replacer.remove(1);
+        lru_replacer.remove(7);
+        assert_eq!(1, lru_replacer.evictable_count());
+        assert_eq!(Some(2), lru_replacer.evict());
+        assert_eq!(0, lru_replacer.evictable_count());
+    }
+
     #[test]
     fn test_lruk_replacer_evict() {
         {
@@ -1,6 +1,7 @@
 use crate::typedef::FrameId;
 use std::collections::HashMap;
 
+use super::clock::{Clock, LogicalClock};
 use super::replacer::Replacer;
 
 #[derive(Debug)]
@@ -14,50 +15,93 @@ struct LruNode {
 pub(crate) struct LruReplacer {
     node_store: HashMap<FrameId, LruNode>,
     evictable_count: usize, // Tracks evictable nodes
-    current_timestamp: u64,
+    clock: Box<dyn Clock>,
 }
 
 impl LruReplacer {
     pub(crate) fn new() -> Self {
+        Self::with_clock(Box::new(LogicalClock::default()))
+    }
+
+    /// Creates an LRU replacer that timestamps accesses with `clock`.
+    pub(crate) fn with_clock(clock: Box<dyn Clock>) -> Self {
         LruReplacer {
             node_store: HashMap::new(),
             evictable_count: 0,
-            current_timestamp: 0,
+            clock,
         }
     }
 
     fn current_timestamp(&mut self) -> u64 {
-        let old_timestamp = self.current_timestamp;
-        self.current_timestamp += 1;
-        return old_timestamp;
+        self.clock.now()
     }
 }
 
 impl Replacer for LruReplacer {
-    /// Evicts the least recently used evictable frame.
+    /// Evicts the least recently used evictable frame, or the one with the lowest id among frames
+    /// last used at the same time.
     fn evict(&mut self) -> Option<FrameId> {
-        todo!("Implement eviction")
+        self.evict_preferring_clean(&|_| true)
+    }
+
+    /// Evicts like [`LruReplacer::evict`], but among frames last used at the same time, evicts a
+    /// clean one first.
+    fn evict_preferring_clean(&mut self, is_clean: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let frame_id = self
+            .node_store
+            .values()
+            .filter(|node| node.is_evictable)
+            .min_by_key(|node| {
+                let dirty = !is_clean(node.frame_id);
+                (node.last_accessed_timestamp, dirty, node.frame_id)
+            })?
+            .frame_id;
+        self.node_store.remove(&frame_id);
+        self.evictable_count -= 1;
+        Some(frame_id)
     }
 
     /// Marks a frame as not evictable (i.e., pinned).
     fn pin(&mut self, frame_id: FrameId) {
-       todo!("Implement pin")
+        if let Some(node) = self.node_store.get_mut(&frame_id) {
+            if node.is_evictable {
+                node.is_evictable = false;
+                self.evictable_count -= 1;
+            }
+        }
     }
 
     /// Marks a frame as evictable
     fn unpin(&mut self, frame_id: FrameId) {
-        todo!("Implement unpin")
+        if let Some(node) = self.node_store.get_mut(&frame_id) {
+            if !node.is_evictable {
+                node.is_evictable = true;
+                self.evictable_count += 1;
+            }
+        }
     }
 
     /// Records an access and updates the timestamp.
     /// If the frame_id is new, create a new node.
     fn record_access(&mut self, frame_id: FrameId) {
-        todo!("Implement record_access")
+        let timestamp = self.current_timestamp();
+        self.node_store
+            .entry(frame_id)
+            .or_insert(LruNode {
+                frame_id,
+                is_evictable: false,
+                last_accessed_timestamp: timestamp,
+            })
+            .last_accessed_timestamp = timestamp;
     }
 
     /// Removes a frame from LRU entirely.
     fn remove(&mut self, frame_id: FrameId) {
-        todo!("Implement remove")
+        if let Some(node) = self.node_store.remove(&frame_id) {
+            if node.is_evictable {
+                self.evictable_count -= 1;
+            }
+        }
     }
 
     /// Returns the number of evictable frames.
@@ -65,3 +109,28 @@ impl Replacer for LruReplacer {
         self.evictable_count
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LruReplacer;
+    use crate::replacer::{clock::tests::FakeClock, replacer::Replacer};
+
+    #[test]
+    fn test_lru_replacer_equal_timestamps() {
+        let clock = FakeClock::default();
+        let mut lru_replacer = LruReplacer::with_clock(Box::new(clock.clone()));
+
+        for frame_id in [4, 2, 6] {
+            lru_replacer.record_access(frame_id);
+            lru_replacer.unpin(frame_id);
+        }
+        clock.set(1);
+        lru_replacer.record_access(0);
+        lru_replacer.unpin(0);
+        lru_replacer.record_access(2);
+
+        // Frames last accessed at the same time are evicted in frame id order.
+        let evicted = std::iter::from_fn(|| lru_replacer.evict()).collect::<Vec<_>>();
+        assert_eq!(evicted, vec![4, 6, 0, 2]);
+    }
+}
@@ -1,3 +1,4 @@
+pub(crate) mod clock;
 pub(crate) mod lru_k_replacer;
 pub(crate) mod lru_replacer;
 pub(crate) mod replacer;
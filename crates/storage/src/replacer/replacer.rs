@@ -17,9 +17,17 @@ pub trait Replacer: Send + Sync + Debug {
     /// Returns `Some(frame_id)` if a page in frame is evicted, otherwise `None`.
     fn evict(&mut self) -> Option<FrameId>;
 
+    /// Like [`Replacer::evict`], but among candidates the policy ranks equally, evicts one whose
+    /// frame `is_clean` first, since evicting a dirty frame costs a disk write. Replacers that
+    /// don't rank candidates by cost just [`Replacer::evict`].
+    fn evict_preferring_clean(&mut self, is_clean: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let _ = is_clean;
+        self.evict()
+    }
+
     /// Returns the number of evictable frames in the replacer.
     fn evictable_count(&self) -> usize;
 
-    /// Removes a page from the replacer. This should only be called on a page that is evictable
+    /// Removes a frame and its access history from the replacer, whether or not it is evictable.
     fn remove(&mut self, frame_id: FrameId);
 }
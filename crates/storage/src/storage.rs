@@ -1,17 +1,144 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{ErrorKind, Read, Write};
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
 use crate::{
+    b_plus_tree::BPlusTree,
+    bloom_filter::BloomFilter,
     buffer_pool::BufferPoolManager,
+    external_sort::external_sort,
     heap::{table_heap::TableHeap, table_tuple_iterator::TableTupleIterator},
+    lock::LockManager,
+    record_id::RecordId,
+    typedef::TxnId,
+    wal::{LogRecord, WalManager},
     Result,
 };
 use rustdb_catalog::{
-    catalog::{self, StorageApi},
-    schema,
+    catalog::{self, AggOp, ColumnStats, StorageApi, TableSize, TableStats},
+    field::{Field, NullOrder},
+    schema::{self, Schema},
+    serde::Serde,
     tuple::Tuple,
+    types::Type,
 };
-use rustdb_error::Error;
+use rustdb_error::{errdata, errinput, Error};
+
+pub use crate::lock::{LockGuard, LockMode};
+
+/// The number of counters in each table's primary key filter.
+const KEY_FILTER_COUNTERS: usize = 1 << 16;
+/// The number of counters set per key in each table's primary key filter.
+const KEY_FILTER_HASHES: usize = 4;
+
+/// The first bytes of a table export (see [`StorageApi::export_table`]). The export continues
+/// with the table's schema (see [`Schema::to_bytes`]) and then its tuples, each as a chunk of a
+/// little-endian `u32` length followed by that many bytes.
+const EXPORT_MAGIC: &[u8; 8] = b"RDBTABLE";
+
+/// Writes `data` to `writer` as a chunk of a table export, prefixed by its length.
+fn write_chunk(writer: &mut dyn Write, data: &[u8]) -> Result<()> {
+    let len = u32::try_from(data.len())?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Reads a chunk written by [`write_chunk`] from `reader`, or returns `None` if `reader` ends
+/// before it.
+fn read_chunk(reader: &mut dyn Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; size_of::<u32>()];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return errdata!("Table export ends in a chunk length"),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let mut data = vec![0; u32::from_le_bytes(len) as usize];
+    match reader.read_exact(&mut data) {
+        Ok(()) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            errdata!("Table export ends in a chunk of {} bytes", data.len())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A Bloom filter over the serialized primary keys of the live tuples in a table.
+struct KeyFilter {
+    schema: Schema,
+    bloom: BloomFilter,
+}
+
+impl KeyFilter {
+    /// Creates an empty filter for a table with the given schema, or `None` if the schema has no
+    /// primary key.
+    fn new(schema: &Schema) -> Option<Self> {
+        if schema.primary_key().is_empty() {
+            return None;
+        }
+        Some(Self {
+            schema: schema.clone(),
+            bloom: BloomFilter::new(KEY_FILTER_COUNTERS, KEY_FILTER_HASHES),
+        })
+    }
+
+    /// Returns the serialized primary key of `tuple`.
+    fn key_of(&self, tuple: &Tuple) -> Result<Vec<u8>> {
+        let key =
+            Serde::deserialize_columns(tuple.as_slice(), &self.schema, self.schema.primary_key())?;
+        Ok(Serde::serialize(&key))
+    }
+}
+
+/// The order of the B+-trees of range indexes.
+const RANGE_INDEX_ORDER: usize = 16;
+
+/// A B+-tree over one column of a table (see [`StorageApi::create_range_index`]), kept up to date
+/// as tuples are inserted, updated and deleted.
+struct RangeIndex {
+    table_id: catalog::TableId,
+    column_index: usize,
+    schema: Schema,
+    tree: BPlusTree,
+}
+
+impl RangeIndex {
+    /// Returns the key of `tuple`, or `None` if it's NULL, which no range contains. Returns an
+    /// error if the key can't be inserted into the tree.
+    fn key_of(&self, tuple: &Tuple) -> Result<Option<Field>> {
+        match tuple.field_at(self.column_index, &self.schema)? {
+            Field::Null => Ok(None),
+            key => {
+                self.tree.check_key(&key)?;
+                Ok(Some(key))
+            }
+        }
+    }
+
+    /// Adds an entry for `tuple`, unless its key is NULL.
+    fn insert(&mut self, rid: RecordId, tuple: &Tuple) -> Result<()> {
+        match self.key_of(tuple)? {
+            Some(key) => self.tree.insert(&key, rid),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes the entry for `tuple`, which had record id `rid`, if it has one.
+    fn remove(&mut self, rid: &RecordId, tuple: &Tuple) -> Result<()> {
+        if let Some(key) = self.key_of(tuple)? {
+            self.tree.remove(&key, rid)?;
+        }
+        Ok(())
+    }
+}
 
 /// A storage engine that implements StorageApi using a table heap.
 /// It maintains a mapping from table IDs to table heaps (each wrapped in an RwLock).
@@ -19,6 +146,15 @@ pub struct StorageEngine {
     bpm: Arc<RwLock<BufferPoolManager>>,
     // Each table heap is now wrapped in an RwLock for internal synchronization.
     tables: RwLock<HashMap<catalog::TableId, Arc<RwLock<TableHeap>>>>,
+    // Row-level locks, keyed by record id.
+    record_locks: LockManager<schema::RecordId, ()>,
+    // Primary key filters of the tables that have a primary key. Only accessed while holding the
+    // lock on the corresponding table heap.
+    key_filters: RwLock<HashMap<catalog::TableId, KeyFilter>>,
+    // Range indexes by id. Only modified while holding the lock on the indexed table's heap.
+    range_indexes: RwLock<HashMap<catalog::IndexId, RangeIndex>>,
+    // The id to give the next transaction.
+    next_txn_id: AtomicU64,
 }
 
 impl StorageEngine {
@@ -27,8 +163,148 @@ impl StorageEngine {
         Self {
             bpm: Arc::clone(&bpm),
             tables: RwLock::new(HashMap::new()),
+            record_locks: LockManager::new(),
+            key_filters: RwLock::new(HashMap::new()),
+            range_indexes: RwLock::new(HashMap::new()),
+            next_txn_id: AtomicU64::new(1),
         }
     }
+
+    pub(crate) fn bpm(&self) -> &Arc<RwLock<BufferPoolManager>> {
+        &self.bpm
+    }
+
+    /// Returns whether any table has been created or opened.
+    pub(crate) fn has_tables(&self) -> bool {
+        !self.tables.read().unwrap().is_empty()
+    }
+
+    /// Returns an iterator over the tuples of every table, ordered by table id, e.g. to write a
+    /// checkpoint or a backup. The tables are those registered when this is called, and each
+    /// iterator holds its own references to its table heap and the buffer pool, so tables created
+    /// or opened afterwards aren't included.
+    pub fn scan_all(&self) -> Result<Vec<(catalog::TableId, TableTupleIterator)>> {
+        let tables = self.tables.read()?;
+        let mut iters = tables
+            .iter()
+            .map(|(&table_id, table_heap_lock)| {
+                let iter = TableTupleIterator::new(self.bpm.clone(), table_heap_lock.clone());
+                (table_id, iter)
+            })
+            .collect::<Vec<_>>();
+        iters.sort_by_key(|(table_id, _)| *table_id);
+        Ok(iters)
+    }
+
+    /// Begins a transaction in the buffer pool's write-ahead log, returning its id. Every change
+    /// logged from now until the transaction commits belongs to it, so transactions must not
+    /// overlap.
+    ///
+    /// Returns [`Error::InvalidInput`] if the buffer pool has no log.
+    pub(crate) fn begin_txn(&self) -> Result<TxnId> {
+        let txn = self.next_txn_id.fetch_add(1, Ordering::Relaxed);
+        self.wal()?.lock()?.append(&LogRecord::Begin { txn })?;
+        Ok(txn)
+    }
+
+    /// Commits the transaction `txn`, making its changes durable by flushing the log.
+    ///
+    /// Returns [`Error::InvalidInput`] if the buffer pool has no log.
+    pub(crate) fn commit_txn(&self, txn: TxnId) -> Result<()> {
+        let wal = self.wal()?;
+        let mut wal = wal.lock()?;
+        let lsn = wal.append(&LogRecord::CommitTxn { txn })?;
+        wal.flush_to(lsn)
+    }
+
+    fn wal(&self) -> Result<Arc<Mutex<WalManager>>> {
+        match BufferPoolManager::read_lock(&self.bpm).wal() {
+            Some(wal) => Ok(wal),
+            None => errinput!("The buffer pool has no write-ahead log"),
+        }
+    }
+
+    /// Returns the serialized primary keys of `tuples`, or `None` if the table has no primary key.
+    fn keys_of(
+        &self,
+        table_id: catalog::TableId,
+        tuples: &[Tuple],
+    ) -> Result<Option<Vec<Vec<u8>>>> {
+        let key_filters = self.key_filters.read().unwrap();
+        key_filters
+            .get(&table_id)
+            .map(|filter| tuples.iter().map(|tuple| filter.key_of(tuple)).collect())
+            .transpose()
+    }
+
+    /// Checks that `tuples` can be added to the range indexes of the table with id `table_id`,
+    /// so that a tuple that can't be indexed is rejected before it's inserted into the table heap.
+    fn check_index_keys(&self, table_id: catalog::TableId, tuples: &[Tuple]) -> Result<()> {
+        let range_indexes = self.range_indexes.read().unwrap();
+        for index in range_indexes.values() {
+            if index.table_id == table_id {
+                for tuple in tuples {
+                    index.key_of(tuple)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `tuple`, just inserted into the table with id `table_id` at record id `rid`, to the
+    /// table's range indexes.
+    fn index_tuple(&self, table_id: catalog::TableId, rid: &RecordId, tuple: &Tuple) -> Result<()> {
+        let mut range_indexes = self.range_indexes.write().unwrap();
+        for index in range_indexes.values_mut() {
+            if index.table_id == table_id {
+                index.insert(rid.clone(), tuple)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes `tuple`, which was just deleted from record id `rid` of the table with id
+    /// `table_id`, from the table's range indexes.
+    fn unindex_tuple(
+        &self,
+        table_id: catalog::TableId,
+        rid: &RecordId,
+        tuple: &Tuple,
+    ) -> Result<()> {
+        let mut range_indexes = self.range_indexes.write().unwrap();
+        for index in range_indexes.values_mut() {
+            if index.table_id == table_id {
+                index.remove(rid, tuple)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the key of a range index lookup bound, which must have exactly one field.
+    fn range_bound(bound: Bound<&[Field]>) -> Result<Bound<&Field>> {
+        match bound {
+            Bound::Included([key]) => Ok(Bound::Included(key)),
+            Bound::Excluded([key]) => Ok(Bound::Excluded(key)),
+            Bound::Unbounded => Ok(Bound::Unbounded),
+            Bound::Included(key) | Bound::Excluded(key) => {
+                errinput!("Range index keys have one field, got {}", key.len())
+            }
+        }
+    }
+
+    /// Acquires the lock on the tuple with record id `rid` in the given mode, blocking until it is
+    /// available. The lock is released when the returned guard is dropped.
+    ///
+    /// [`StorageApi::get_tuple`] holds a shared lock on the tuple it reads, and
+    /// [`StorageApi::update_tuple`] and [`StorageApi::delete_tuple`] hold an exclusive lock on the
+    /// tuple they modify, so readers never observe a partially applied update.
+    pub fn lock_record(
+        &self,
+        rid: schema::RecordId,
+        mode: LockMode,
+    ) -> LockGuard<schema::RecordId, ()> {
+        self.record_locks.lock(rid, mode)
+    }
 }
 
 impl StorageApi for StorageEngine {
@@ -37,9 +313,10 @@ impl StorageApi for StorageEngine {
 
     /// Creates a new table.
     ///
-    /// In a full system this would create a new table heap and a catalog entry.
-    /// Here we simply create a new TableHeap, wrap it in an RwLock, and store it in our map.
-    fn create_table(&self, table_id: catalog::TableId, name: &str) -> Result<&catalog::TableInfo> {
+    /// The catalog entry for the table is owned by the catalog; here we simply create a new
+    /// TableHeap, wrap it in an RwLock, and store it in our map. If the schema has a primary key,
+    /// an empty primary key filter is created for the table as well.
+    fn create_table(&self, table_id: catalog::TableId, name: &str, schema: &Schema) -> Result<()> {
         let mut tables = self.tables.write().unwrap();
         if tables.contains_key(&table_id) {
             return Err(Error::InvalidInput("Table already exists".to_string()));
@@ -47,11 +324,54 @@ impl StorageApi for StorageEngine {
         let table_heap = TableHeap::new(name, self.bpm.clone());
         // Wrap the TableHeap in an RwLock.
         tables.insert(table_id, Arc::new(RwLock::new(table_heap)));
-        todo!("Return a reference to the newly created TableInfo")
+        if let Some(filter) = KeyFilter::new(schema) {
+            self.key_filters.write().unwrap().insert(table_id, filter);
+        }
+        Ok(())
+    }
+
+    /// Reopens an existing table by attaching a TableHeap to its first page. If the schema has a
+    /// primary key, the table's primary key filter is rebuilt by scanning the table.
+    fn open_table(
+        &self,
+        table_id: catalog::TableId,
+        name: &str,
+        first_page_id: u32,
+        schema: &Schema,
+    ) -> Result<()> {
+        let mut tables = self.tables.write().unwrap();
+        if tables.contains_key(&table_id) {
+            return Err(Error::InvalidInput("Table already exists".to_string()));
+        }
+        let table_heap = Arc::new(RwLock::new(TableHeap::open(
+            name,
+            self.bpm.clone(),
+            first_page_id,
+        )?));
+        if let Some(mut filter) = KeyFilter::new(schema) {
+            for item in TableTupleIterator::new(self.bpm.clone(), table_heap.clone()) {
+                let (_, tuple) = item?;
+                filter.bloom.insert(&filter.key_of(&tuple)?);
+            }
+            self.key_filters.write().unwrap().insert(table_id, filter);
+        }
+        tables.insert(table_id, table_heap);
+        Ok(())
+    }
+
+    /// Returns the id of the first page of the table's heap.
+    fn first_page_id(&self, table_id: catalog::TableId) -> Result<u32> {
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        let first_page_id = table_heap_lock.read().unwrap().first_page_id();
+        Ok(first_page_id)
     }
 
     /// Retrieves a tuple given its record id.
     fn get_tuple(&self, table_id: catalog::TableId, rid: schema::RecordId) -> Result<Tuple> {
+        let _lock = self.lock_record(rid, LockMode::Shared);
         let tables = self.tables.read().unwrap();
         let table_heap_lock = tables
             .get(&table_id)
@@ -65,28 +385,541 @@ impl StorageApi for StorageEngine {
 
     /// Deletes a tuple given its record id.
     fn delete_tuple(&self, table_id: catalog::TableId, rid: schema::RecordId) -> Result<()> {
+        let _lock = self.lock_record(rid, LockMode::Exclusive);
         let tables = self.tables.read().unwrap();
         let table_heap_lock = tables
             .get(&table_id)
             .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
         // Acquire a write lock to modify the table heap.
-        let table_heap = table_heap_lock.write().unwrap();
-        table_heap.delete_tuple(&rid.into())?;
+        let mut table_heap = table_heap_lock.write().unwrap();
+        let (metadata, tuple) = table_heap.delete_tuple(&rid.into())?;
+        // Only remove the key of a tuple that was live until now.
+        if !metadata.is_deleted() {
+            if let Some(filter) = self.key_filters.write().unwrap().get_mut(&table_id) {
+                let key = filter.key_of(&tuple)?;
+                filter.bloom.remove(&key);
+            }
+            self.unindex_tuple(table_id, &rid.into(), &tuple)?;
+        }
         Ok(())
     }
 
-    /// Inserts a tuple into the specified table.
+    /// Inserts a tuple into the specified table. The table map is only locked to look up the
+    /// table heap, so inserts into different tables run concurrently.
     fn insert_tuple(&self, table_id: catalog::TableId, tuple: &Tuple) -> Result<schema::RecordId> {
-        let mut tables = self.tables.write().unwrap();
-        let table_heap_lock = tables
-            .get_mut(&table_id)
+        let table_heap_lock = self
+            .tables
+            .read()
+            .unwrap()
+            .get(&table_id)
+            .cloned()
             .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
         // Acquire a write lock for insertion.
         let mut table_heap = table_heap_lock.write().unwrap();
+        // Compute the primary key and check the index keys first, so that a malformed tuple is
+        // rejected before insertion.
+        let key = self.keys_of(table_id, std::slice::from_ref(tuple))?;
+        self.check_index_keys(table_id, std::slice::from_ref(tuple))?;
         let rid = table_heap.insert_tuple(tuple)?;
+        if let Some(filter) = self.key_filters.write().unwrap().get_mut(&table_id) {
+            key.iter()
+                .flatten()
+                .for_each(|key| filter.bloom.insert(key));
+        }
+        self.index_tuple(table_id, &rid, tuple)?;
         Ok(rid.into())
     }
 
+    /// Inserts tuples into the specified table, holding the table heap's write lock for the whole
+    /// batch. Tuples inserted before an error are not rolled back.
+    fn insert_tuples(
+        &self,
+        table_id: catalog::TableId,
+        tuples: &[Tuple],
+    ) -> Result<Vec<schema::RecordId>> {
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        // Acquire the write lock once for all of the insertions.
+        let mut table_heap = table_heap_lock.write().unwrap();
+        let keys = self.keys_of(table_id, tuples)?;
+        self.check_index_keys(table_id, tuples)?;
+        let mut key_filters = self.key_filters.write().unwrap();
+        let mut filter = key_filters.get_mut(&table_id);
+        tuples
+            .iter()
+            .enumerate()
+            .map(|(i, tuple)| {
+                let rid = table_heap.insert_tuple(tuple)?;
+                if let (Some(filter), Some(keys)) = (filter.as_mut(), keys.as_ref()) {
+                    filter.bloom.insert(&keys[i]);
+                }
+                self.index_tuple(table_id, &rid, tuple)?;
+                Ok(rid.into())
+            })
+            .collect()
+    }
+
+    /// Inserts a tuple into the specified table unless its primary key is already taken, holding
+    /// the table heap's write lock so that no other insert can take the key in between. The
+    /// table's primary key filter rules out most new keys, and only a possible duplicate is
+    /// checked by scanning the table.
+    fn insert_unique(
+        &self,
+        table_id: catalog::TableId,
+        tuple: &Tuple,
+        schema: &Schema,
+    ) -> Result<schema::RecordId> {
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        let mut table_heap = table_heap_lock.write().unwrap();
+        let key = schema.extract_key(&tuple.to_fields(schema)?)?;
+        let serialized_key = Serde::serialize(&key);
+        self.check_index_keys(table_id, std::slice::from_ref(tuple))?;
+
+        let mut key_filters = self.key_filters.write().unwrap();
+        let mut filter = key_filters.get_mut(&table_id);
+        let might_exist = filter
+            .as_ref()
+            .is_none_or(|filter| filter.bloom.might_contain(&serialized_key));
+        if might_exist {
+            let first_page_id = table_heap.first_page_id();
+            for item in TableTupleIterator::from_first_page(self.bpm.clone(), first_page_id) {
+                let (_, existing) = item?;
+                if schema.extract_key(&existing.to_fields(schema)?)? == key {
+                    return errinput!("Duplicate key {key:?}");
+                }
+            }
+        }
+
+        let rid = table_heap.insert_tuple(tuple)?;
+        if let Some(filter) = filter.as_mut() {
+            filter.bloom.insert(&serialized_key);
+        }
+        self.index_tuple(table_id, &rid, tuple)?;
+        Ok(rid.into())
+    }
+
+    /// Updates a tuple given its record id, returning the (possibly new) record id of the tuple.
+    fn update_tuple(
+        &self,
+        table_id: catalog::TableId,
+        rid: schema::RecordId,
+        tuple: &Tuple,
+    ) -> Result<schema::RecordId> {
+        let _lock = self.lock_record(rid, LockMode::Exclusive);
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        // Acquire a write lock, since the update may need to insert into the table heap.
+        let mut table_heap = table_heap_lock.write().unwrap();
+        let new_key = self.keys_of(table_id, std::slice::from_ref(tuple))?;
+        self.check_index_keys(table_id, std::slice::from_ref(tuple))?;
+        let (_, old_tuple) = table_heap.get_tuple(&rid.into())?;
+        let new_rid = table_heap.update_tuple(&rid.into(), tuple)?;
+        if let Some(filter) = self.key_filters.write().unwrap().get_mut(&table_id) {
+            let old_key = filter.key_of(&old_tuple)?;
+            filter.bloom.remove(&old_key);
+            new_key
+                .iter()
+                .flatten()
+                .for_each(|key| filter.bloom.insert(key));
+        }
+        self.unindex_tuple(table_id, &rid.into(), &old_tuple)?;
+        self.index_tuple(table_id, &new_rid, tuple)?;
+        Ok(new_rid.into())
+    }
+
+    /// Checks the table's primary key filter for `key`.
+    ///
+    /// Returns [`Error::InvalidInput`] if the table doesn't exist or has no primary key.
+    fn might_contain_key(&self, table_id: catalog::TableId, key: &[Field]) -> Result<bool> {
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        let _table_heap = table_heap_lock.read().unwrap();
+        let key_filters = self.key_filters.read().unwrap();
+        match key_filters.get(&table_id) {
+            Some(filter) => Ok(filter.bloom.might_contain(&Serde::serialize(key))),
+            None => errinput!("Table {table_id} has no primary key"),
+        }
+    }
+
+    /// Scans the specified table for tuples whose column at `column_index` equals `value`.
+    ///
+    /// Returns [`Error::OutOfBounds`] if `column_index` isn't a column of `schema`.
+    fn scan_filter(
+        &self,
+        table_id: catalog::TableId,
+        column_index: usize,
+        value: Field,
+        schema: &Schema,
+    ) -> Result<Vec<(schema::RecordId, Tuple)>> {
+        let mut rows = Vec::new();
+        for item in self.scan(table_id)? {
+            let (rid, tuple) = item?;
+            if tuple.field_at(column_index, schema)? == value {
+                rows.push((rid, tuple));
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Scans the specified table and sorts its tuples by the column at `column_index`. The sort is
+    /// stable, so tuples with equal keys stay in scan order.
+    ///
+    /// Returns [`Error::OutOfBounds`] if `column_index` isn't a column of `schema`.
+    fn scan_sorted(
+        &self,
+        table_id: catalog::TableId,
+        column_index: usize,
+        ascending: bool,
+        nulls: NullOrder,
+        schema: &Schema,
+    ) -> Result<Vec<(schema::RecordId, Tuple)>> {
+        let mut rows = self
+            .scan(table_id)?
+            .map(|item| {
+                let (rid, tuple) = item?;
+                Ok((tuple.field_at(column_index, schema)?, rid, tuple))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rows.sort_by(|(a, ..), (b, ..)| a.cmp_for_sort(b, ascending, nulls));
+        Ok(rows
+            .into_iter()
+            .map(|(_, rid, tuple)| (rid, tuple))
+            .collect())
+    }
+
+    /// Sorts the table with an external merge sort, spilling the sorted runs to pages allocated
+    /// directly from the buffer pool's disk, so that they don't take up buffer frames.
+    ///
+    /// Returns [`Error::OutOfBounds`] if `column_index` isn't a column of `schema`.
+    fn scan_sorted_external(
+        &self,
+        table_id: catalog::TableId,
+        column_index: usize,
+        ascending: bool,
+        nulls: NullOrder,
+        schema: &Schema,
+        run_size: usize,
+    ) -> Result<Box<dyn catalog::ScanIterator>> {
+        let disk = BufferPoolManager::read_lock(&self.bpm).disk();
+        Ok(Box::new(external_sort(
+            self.scan(table_id)?,
+            disk,
+            column_index,
+            ascending,
+            nulls,
+            schema,
+            run_size,
+        )?))
+    }
+
+    fn scan_distinct(&self, table_id: catalog::TableId) -> Result<Vec<(schema::RecordId, Tuple)>> {
+        let mut seen = HashSet::new();
+        let mut rows = Vec::new();
+        for item in self.scan(table_id)? {
+            let (rid, tuple) = item?;
+            if seen.insert(tuple.clone()) {
+                rows.push((rid, tuple));
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Computes an aggregate over a column of the specified table in a single scan, keeping only
+    /// a running aggregate rather than the column's values.
+    ///
+    /// Returns [`Error::OutOfBounds`] if `column_index` isn't a column of `schema`.
+    fn aggregate(
+        &self,
+        table_id: catalog::TableId,
+        column_index: usize,
+        op: AggOp,
+        schema: &Schema,
+    ) -> Result<Field> {
+        let field_type = schema.column_at(column_index)?.field_type();
+        let numeric = matches!(field_type, Type::Integer | Type::Float);
+        if matches!(op, AggOp::Sum | AggOp::Avg) && !numeric {
+            return errdata!(
+                "Cannot compute {op:?} over column {column_index} of type {field_type}"
+            );
+        }
+
+        // Fold the non-NULL values of the column into a running aggregate in a single pass,
+        // counting every row along the way. `acc` is the sum, min or max so far.
+        let mut num_rows = 0;
+        let mut num_values = 0;
+        let mut acc = Field::Null;
+        let mut float_sum = 0.0;
+        for item in self.scan(table_id)? {
+            let (_, tuple) = item?;
+            num_rows += 1;
+            let field = tuple.field_at(column_index, schema)?;
+            if field == Field::Null {
+                continue;
+            }
+            num_values += 1;
+            match op {
+                AggOp::Count | AggOp::CountAll => {}
+                AggOp::Sum => {
+                    acc = match (acc, field) {
+                        (Field::Null, field) => field,
+                        (Field::Integer(a), Field::Integer(b)) => {
+                            Field::Integer(a.checked_add(b).ok_or(Error::ArithmeticOverflow)?)
+                        }
+                        (sum, field) => sum + field,
+                    }
+                }
+                AggOp::Min if acc == Field::Null || field < acc => acc = field,
+                AggOp::Max if field > acc => acc = field,
+                AggOp::Min | AggOp::Max => {}
+                AggOp::Avg => {
+                    float_sum += match field {
+                        Field::Integer(i) => i as f64,
+                        Field::Float(f) => f,
+                        _ => unreachable!("Column {column_index} is numeric"),
+                    }
+                }
+            }
+        }
+
+        let count = |n: usize| {
+            i32::try_from(n)
+                .map(Field::Integer)
+                .map_err(|_| Error::ArithmeticOverflow)
+        };
+        let result = match op {
+            AggOp::Count => count(num_values)?,
+            AggOp::CountAll => count(num_rows)?,
+            AggOp::Sum | AggOp::Min | AggOp::Max => acc,
+            AggOp::Avg if num_values == 0 => Field::Null,
+            AggOp::Avg => Field::Float(float_sum / num_values as f64),
+        };
+        Ok(result)
+    }
+
+    /// Computes statistics over every column of the specified table in a single scan. Distinct
+    /// values are counted by their hashes, so that only a hash needs to be kept per value.
+    fn analyze(&self, table_id: catalog::TableId, schema: &Schema) -> Result<TableStats> {
+        let mut row_count = 0;
+        let mut columns = vec![
+            ColumnStats {
+                min: Field::Null,
+                max: Field::Null,
+                null_count: 0,
+                approx_distinct: 0,
+            };
+            schema.num_columns()
+        ];
+        let mut hashes = vec![HashSet::new(); schema.num_columns()];
+
+        for item in self.scan(table_id)? {
+            let (_, tuple) = item?;
+            row_count += 1;
+            for (field, (stats, hashes)) in tuple
+                .to_fields(schema)?
+                .into_iter()
+                .zip(columns.iter_mut().zip(&mut hashes))
+            {
+                if field == Field::Null {
+                    stats.null_count += 1;
+                    continue;
+                }
+                let mut hasher = DefaultHasher::new();
+                field.hash(&mut hasher);
+                hashes.insert(hasher.finish());
+
+                if stats.min == Field::Null || field < stats.min {
+                    stats.min = field.clone();
+                }
+                if stats.max == Field::Null || field > stats.max {
+                    stats.max = field;
+                }
+            }
+        }
+
+        for (stats, hashes) in columns.iter_mut().zip(hashes) {
+            stats.approx_distinct = hashes.len();
+        }
+        Ok(TableStats { row_count, columns })
+    }
+
+    fn table_size(&self, table_id: catalog::TableId) -> Result<TableSize> {
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        let table_size = table_heap_lock.read().unwrap().table_size();
+        table_size
+    }
+
+    fn scan_rows(
+        &self,
+        table_id: catalog::TableId,
+        schema: &Schema,
+    ) -> Result<Vec<(schema::RecordId, Vec<Field>)>> {
+        self.scan(table_id)?
+            .map(|item| {
+                let (rid, tuple) = item?;
+                Ok((rid, tuple.to_fields(schema)?))
+            })
+            .collect()
+    }
+
+    /// Returns an iterator that holds its own copy of the schema, so it outlives the borrow.
+    fn scan_column(
+        &self,
+        table_id: catalog::TableId,
+        column_index: usize,
+        schema: &Schema,
+    ) -> Result<impl Iterator<Item = Result<Field>>> {
+        schema.column_at(column_index)?;
+        let schema = schema.clone();
+        Ok(self.scan(table_id)?.map(move |item| {
+            let (_, tuple) = item?;
+            tuple.field_at(column_index, &schema)
+        }))
+    }
+
+    /// Creates a range index, building its B+-tree from a scan of the table while holding the
+    /// table heap's read lock, so that no insert is missed. Varchar columns can't be indexed,
+    /// since their values may be too long for a B+-tree node.
+    fn create_range_index(
+        &self,
+        index_id: catalog::IndexId,
+        table_id: catalog::TableId,
+        column_index: usize,
+        schema: &Schema,
+    ) -> Result<()> {
+        let key_type = schema.column_at(column_index)?.field_type();
+        if key_type == Type::Varchar {
+            return errinput!("Cannot create a range index over varchar column {column_index}");
+        }
+        let tables = self.tables.read().unwrap();
+        let table_heap_lock = tables
+            .get(&table_id)
+            .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+        let table_heap = table_heap_lock.read().unwrap();
+        if self.range_indexes.read().unwrap().contains_key(&index_id) {
+            return errinput!("Index {index_id} already exists");
+        }
+
+        let mut index = RangeIndex {
+            table_id,
+            column_index,
+            schema: schema.clone(),
+            tree: BPlusTree::new(self.bpm.clone(), key_type, RANGE_INDEX_ORDER)?,
+        };
+        let first_page_id = table_heap.first_page_id();
+        for item in TableTupleIterator::from_first_page(self.bpm.clone(), first_page_id) {
+            let (rid, tuple) = item?;
+            index.insert(rid.into(), &tuple)?;
+        }
+        self.range_indexes.write().unwrap().insert(index_id, index);
+        Ok(())
+    }
+
+    /// Looks up the record ids in the index's B+-tree, then fetches each tuple while holding a
+    /// shared lock on it. Tuples deleted, or whose key was updated out of the bounds, after the
+    /// lookup are left out.
+    fn range_lookup(
+        &self,
+        index_id: catalog::IndexId,
+        low: Bound<&[Field]>,
+        high: Bound<&[Field]>,
+    ) -> Result<Vec<(schema::RecordId, Tuple)>> {
+        let bounds = (Self::range_bound(low)?, Self::range_bound(high)?);
+        let (table_id, column_index, schema, rids) = {
+            let range_indexes = self.range_indexes.read().unwrap();
+            let Some(index) = range_indexes.get(&index_id) else {
+                return errinput!("Index {index_id} not found");
+            };
+            let rids = index.tree.range_scan_bounds(bounds.0, bounds.1)?;
+            (
+                index.table_id,
+                index.column_index,
+                index.schema.clone(),
+                rids,
+            )
+        };
+
+        let mut seen = HashSet::new();
+        let mut rows = Vec::new();
+        for rid in rids {
+            let rid = schema::RecordId::from(rid);
+            if !seen.insert(rid) {
+                continue;
+            }
+            let _lock = self.lock_record(rid, LockMode::Shared);
+            let tables = self.tables.read().unwrap();
+            let table_heap_lock = tables
+                .get(&table_id)
+                .ok_or_else(|| Error::InvalidInput("Table not found".to_string()))?;
+            let (metadata, tuple) = table_heap_lock.read().unwrap().get_tuple(&rid.into())?;
+            let key = tuple.field_at(column_index, &schema)?;
+            if !metadata.is_deleted() && bounds.contains(&key) {
+                rows.push((key, rid, tuple));
+            }
+        }
+        // Keys updated after the lookup may be out of order.
+        rows.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+        Ok(rows
+            .into_iter()
+            .map(|(_, rid, tuple)| (rid, tuple))
+            .collect())
+    }
+
+    /// Exports the table's schema and then its live tuples in scan order.
+    fn export_table(
+        &self,
+        table_id: catalog::TableId,
+        schema: &Schema,
+        writer: &mut dyn Write,
+    ) -> Result<usize> {
+        writer.write_all(EXPORT_MAGIC)?;
+        write_chunk(writer, &schema.to_bytes())?;
+        let mut count = 0;
+        for item in self.scan(table_id)? {
+            let (_, tuple) = item?;
+            write_chunk(writer, tuple.as_slice())?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Imports tuples one at a time with [`StorageApi::insert_tuple`], so they're added to the
+    /// table's primary key filter and range indexes.
+    fn import_table(&self, table_id: catalog::TableId, reader: &mut dyn Read) -> Result<usize> {
+        let mut magic = [0; EXPORT_MAGIC.len()];
+        match reader.read_exact(&mut magic) {
+            Ok(()) if &magic == EXPORT_MAGIC => {}
+            Ok(()) => return errdata!("Not a table export"),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return errdata!("Not a table export")
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let Some(schema) = read_chunk(reader)? else {
+            return errdata!("Table export ends before its schema");
+        };
+        Schema::from_bytes(&schema)?;
+
+        let mut count = 0;
+        while let Some(data) = read_chunk(reader)? {
+            self.insert_tuple(table_id, &Tuple::new(data.into()))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     /// Returns an iterator over all tuples in the specified table.
     fn scan(&self, table_id: catalog::TableId) -> Result<Self::ScanIterator>
     where
@@ -107,3 +940,1005 @@ impl StorageApi for StorageEngine {
         Ok(Box::new(self.scan(table_id)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ops::Bound;
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use rustdb_catalog::{
+        catalog::{AggOp, Catalog, ColumnStats, StorageApi},
+        column::Column,
+        field::{Field, NullOrder},
+        schema::Schema,
+        serde::Serde,
+        tuple::Tuple,
+        types::Type,
+    };
+    use serial_test::serial;
+
+    use super::{LockMode, StorageEngine};
+    use crate::{
+        buffer_pool::BufferPoolManager, disk::disk_manager::DiskManager, page::PAGE_SIZE,
+        record_id::RecordId, replacer::lru_k_replacer::LrukReplacer, Result,
+    };
+    use rustdb_error::Error;
+
+    fn get_storage_engine(pool_size: usize) -> StorageEngine {
+        let disk_manager = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(5));
+        let bpm = BufferPoolManager::new(pool_size, disk_manager, replacer);
+        StorageEngine::new(Arc::new(RwLock::new(bpm)))
+    }
+
+    /// Test that a batch insert spanning many pages can be scanned back in order.
+    #[test]
+    #[serial]
+    fn test_insert_tuples() -> Result<()> {
+        let engine = get_storage_engine(10);
+        engine.create_table(0, "table", &Schema::new(&[]))?;
+
+        let tuples = (0..1000u32)
+            .map(|i| Tuple::new(i.to_le_bytes().to_vec().into()))
+            .collect::<Vec<_>>();
+        let rids = engine.insert_tuples(0, &tuples)?;
+        assert_eq!(rids.len(), tuples.len());
+
+        let scanned = engine.scan(0)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(scanned.len(), tuples.len());
+        for ((rid, tuple), (expected_rid, expected_tuple)) in
+            scanned.iter().zip(rids.iter().zip(tuples.iter()))
+        {
+            assert_eq!(rid, expected_rid);
+            assert_eq!(tuple.data(), expected_tuple.data());
+        }
+
+        // Inserting into a missing table fails.
+        assert!(engine.insert_tuples(1, &tuples).is_err());
+
+        Ok(())
+    }
+
+    /// Test that record locks make readers and writers of the same tuple wait for each other,
+    /// without blocking access to other tuples.
+    #[test]
+    #[serial]
+    fn test_record_locks() -> Result<()> {
+        let engine = get_storage_engine(10);
+        engine.create_table(0, "table", &Schema::new(&[]))?;
+        let rid1 = engine.insert_tuple(0, &Tuple::new(vec![1; 8].into()))?;
+        let rid2 = engine.insert_tuple(0, &Tuple::new(vec![2; 8].into()))?;
+        let wait = || std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // A shared lock lets others read the tuple, but updates and deletes wait for it.
+        let guard = engine.lock_record(rid1, LockMode::Shared);
+        std::thread::scope(|s| -> Result<()> {
+            assert_eq!(engine.get_tuple(0, rid1)?.data().to_vec(), vec![1; 8]);
+            let updater = s.spawn(|| engine.update_tuple(0, rid1, &Tuple::new(vec![3; 8].into())));
+            engine.update_tuple(0, rid2, &Tuple::new(vec![4; 8].into()))?;
+            wait();
+            assert!(!updater.is_finished());
+            drop(guard);
+            assert_eq!(updater.join().unwrap()?, rid1);
+            Ok(())
+        })?;
+
+        // An exclusive lock makes readers wait, and they then see the tuple as it was left.
+        let guard = engine.lock_record(rid1, LockMode::Exclusive);
+        std::thread::scope(|s| -> Result<()> {
+            let reader = s.spawn(|| engine.get_tuple(0, rid1));
+            assert_eq!(engine.get_tuple(0, rid2)?.data().to_vec(), vec![4; 8]);
+            wait();
+            assert!(!reader.is_finished());
+            drop(guard);
+            assert_eq!(reader.join().unwrap()?.data().to_vec(), vec![3; 8]);
+            Ok(())
+        })?;
+
+        // The same goes for a delete waiting for a reader.
+        let guard = engine.lock_record(rid2, LockMode::Shared);
+        std::thread::scope(|s| {
+            let deleter = s.spawn(|| engine.delete_tuple(0, rid2));
+            wait();
+            assert!(!deleter.is_finished());
+            drop(guard);
+            deleter.join().unwrap()
+        })?;
+        let rids = engine
+            .scan(0)?
+            .map(|row| row.map(|(rid, _)| rid))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(rids, vec![rid1]);
+
+        Ok(())
+    }
+
+    /// Test that readers of a tuple that's being updated in a loop only ever see whole values.
+    #[test]
+    #[serial]
+    fn test_concurrent_update_and_get() -> Result<()> {
+        let engine = get_storage_engine(10);
+        engine.create_table(0, "table", &Schema::new(&[]))?;
+        // Both values are the same size, so every update happens in place.
+        let values = [vec![1; 512], vec![2; 512]];
+        let rid = engine.insert_tuple(0, &Tuple::new(values[0].clone().into()))?;
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..500 {
+                    let tuple = Tuple::new(values[i % 2].clone().into());
+                    assert_eq!(engine.update_tuple(0, rid, &tuple).unwrap(), rid);
+                }
+            });
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        let data = engine.get_tuple(0, rid).unwrap().data();
+                        assert!(
+                            values.iter().any(|value| data[..] == value[..]),
+                            "torn read"
+                        );
+                    }
+                });
+            }
+        });
+        assert_eq!(engine.get_tuple(0, rid)?.data().to_vec(), values[1]);
+
+        Ok(())
+    }
+
+    /// Test that inserts into different tables from different threads all land in their table.
+    #[test]
+    #[serial]
+    fn test_concurrent_insert_into_tables() -> Result<()> {
+        let engine = get_storage_engine(10);
+        for table_id in 0..2 {
+            engine.create_table(table_id, &format!("table{table_id}"), &Schema::new(&[]))?;
+        }
+
+        std::thread::scope(|s| {
+            for table_id in 0..2u32 {
+                let engine = &engine;
+                s.spawn(move || {
+                    for i in 0..500u32 {
+                        let tuple = Tuple::new([table_id, i].map(u32::to_le_bytes).concat().into());
+                        engine.insert_tuple(table_id, &tuple).unwrap();
+                    }
+                });
+            }
+        });
+
+        for table_id in 0..2u32 {
+            let scanned = engine
+                .scan(table_id)?
+                .map(|row| row.map(|(_, tuple)| tuple.data().to_vec()))
+                .collect::<Result<Vec<_>>>()?;
+            let expected = (0..500u32)
+                .map(|i| [table_id, i].map(u32::to_le_bytes).concat())
+                .collect::<Vec<_>>();
+            assert_eq!(scanned, expected);
+        }
+        Ok(())
+    }
+
+    /// Test that a saved catalog can be loaded after reopening the database file, with its tables
+    /// and their data intact.
+    #[test]
+    #[serial]
+    fn test_save_and_load_catalog() -> Result<()> {
+        let disk_manager = Arc::new(Mutex::new(DiskManager::new("test.db").unwrap()));
+        let replacer = Box::new(LrukReplacer::new(5));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(
+            10,
+            disk_manager.clone(),
+            replacer,
+        )));
+
+        let users = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        let orders = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("paid".to_string(), Type::Boolean),
+            Column::new("total".to_string(), Type::Float),
+        ]);
+
+        let engine = Arc::new(StorageEngine::new(bpm.clone()));
+        let mut catalog = Catalog::new(engine.clone());
+        catalog.create_table("users".to_string(), users.clone())?;
+        let orders_id = catalog
+            .create_table("orders".to_string(), orders.clone())?
+            .id();
+        let rid = engine.insert_tuple(orders_id, &Tuple::new(vec![1, 2, 3].into()))?;
+        catalog.save(&mut &*disk_manager)?;
+        bpm.write().unwrap().flush_unpinned_pages()?;
+        drop((catalog, engine, bpm, disk_manager));
+
+        // Load the catalog into a fresh storage engine over the reopened file. The disk manager is
+        // only locked to read the catalog, since reopening the tables reads their pages from it.
+        let disk_manager = Arc::new(Mutex::new(DiskManager::open("test.db")?));
+        let replacer = Box::new(LrukReplacer::new(5));
+        let bpm = Arc::new(RwLock::new(BufferPoolManager::new(
+            10,
+            disk_manager.clone(),
+            replacer,
+        )));
+        let engine = Arc::new(StorageEngine::new(bpm));
+        let mut loaded = Catalog::load(engine.clone(), &mut &*disk_manager)?;
+        assert_eq!(loaded.list_tables(), vec![(0, "users"), (1, "orders")]);
+        assert_eq!(loaded.table_with_name("users").unwrap().schema(), &users);
+        assert_eq!(loaded.table_with_id(orders_id).unwrap().schema(), &orders);
+        assert_eq!(
+            engine.get_tuple(orders_id, rid)?.data().to_vec(),
+            vec![1, 2, 3]
+        );
+
+        // New tables get fresh ids.
+        assert_eq!(loaded.create_table("items".to_string(), users)?.id(), 2);
+
+        Ok(())
+    }
+
+    /// Test that the primary key filter has no false negatives across inserts, updates, deletes,
+    /// and reopening the table, and that it rules out most absent keys.
+    #[test]
+    #[serial]
+    fn test_might_contain_key() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::with_primary_key(
+            &[
+                Column::new("id".to_string(), Type::Integer),
+                Column::new("value".to_string(), Type::Integer),
+            ],
+            &[0],
+        )?;
+        let row = |id: i32| {
+            Tuple::new(Serde::serialize(&[Field::Integer(id), Field::Integer(-id)]).into())
+        };
+        let key = |id: i32| [Field::Integer(id)];
+        engine.create_table(0, "table", &schema)?;
+
+        // Insert keys 0..1000, then delete a third of them and change the key of another third.
+        let tuples = (0..1000).map(row).collect::<Vec<_>>();
+        let rids = engine.insert_tuples(0, &tuples)?;
+        let mut live = Vec::new();
+        let mut deleted = Vec::new();
+        for (i, rid) in rids.into_iter().enumerate() {
+            let id = i as i32;
+            match i % 3 {
+                0 => {
+                    engine.delete_tuple(0, rid)?;
+                    deleted.push(id);
+                }
+                1 => {
+                    engine.update_tuple(0, rid, &row(id + 1_000_000))?;
+                    live.push(id + 1_000_000);
+                }
+                _ => live.push(id),
+            }
+        }
+
+        // Checks the filter of the table in `engine` against the expected keys.
+        let check = |engine: &StorageEngine| -> Result<()> {
+            for &id in &live {
+                assert!(
+                    engine.might_contain_key(0, &key(id))?,
+                    "false negative for {id}"
+                );
+            }
+            let removed = deleted
+                .iter()
+                .filter(|&&id| engine.might_contain_key(0, &key(id)).unwrap())
+                .count();
+            assert!(
+                removed < deleted.len() / 100,
+                "{removed} removed keys reported"
+            );
+            let absent = (2_000_000..2_010_000)
+                .filter(|&id| engine.might_contain_key(0, &key(id)).unwrap())
+                .count();
+            assert!(absent < 100, "{absent} false positives out of 10000");
+            Ok(())
+        };
+        check(&engine)?;
+
+        // Reopening the table rebuilds the filter from its tuples.
+        let first_page_id = engine.first_page_id(0)?;
+        let reopened = StorageEngine::new(engine.bpm.clone());
+        reopened.open_table(0, "table", first_page_id, &schema)?;
+        check(&reopened)?;
+
+        // Tables without a primary key have no filter.
+        engine.create_table(1, "no_key", &Schema::new(&[]))?;
+        assert!(engine.might_contain_key(1, &key(0)).is_err());
+        assert!(engine.might_contain_key(2, &key(0)).is_err());
+
+        Ok(())
+    }
+
+    /// Test that an equality scan returns exactly the matching rows and their record ids.
+    #[test]
+    #[serial]
+    fn test_scan_filter() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("color".to_string(), Type::Varchar),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        let colors = ["red", "blue", "red", "green", "red", "blue"];
+        let tuples = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                Tuple::new(Serde::serialize(&[Field::Integer(i as i32), color.into()]).into())
+            })
+            .collect::<Vec<_>>();
+        let rids = engine.insert_tuples(0, &tuples)?;
+        // Deleted tuples are never returned.
+        engine.delete_tuple(0, rids[4])?;
+
+        let rows = engine.scan_filter(0, 1, "red".into(), &schema)?;
+        let matched = rows.iter().map(|(rid, _)| *rid).collect::<Vec<_>>();
+        assert_eq!(matched, vec![rids[0], rids[2]]);
+        for (rid, tuple) in &rows {
+            assert_eq!(tuple.data(), engine.get_tuple(0, *rid)?.data());
+        }
+
+        assert_eq!(
+            engine.scan_filter(0, 0, Field::Integer(3), &schema)?.len(),
+            1
+        );
+        assert!(engine
+            .scan_filter(0, 1, "purple".into(), &schema)?
+            .is_empty());
+        assert!(engine.scan_filter(0, 2, "red".into(), &schema).is_err());
+
+        Ok(())
+    }
+
+    /// Test that range lookups return exactly the live tuples with keys in range, sorted by key.
+    #[test]
+    #[serial]
+    fn test_range_lookup() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+        let row = |id: i32| {
+            Tuple::from_fields(
+                &[Field::Integer(id), format!("#{id}").as_str().into()],
+                &schema,
+            )
+        };
+
+        // Insert keys out of order, half before and half after creating the index.
+        let keys = (0..40).map(|i| i * 7 % 40).collect::<Vec<_>>();
+        let mut rids = HashMap::new();
+        for &key in &keys[..20] {
+            rids.insert(key, engine.insert_tuple(0, &row(key)?)?);
+        }
+        engine.create_range_index(0, 0, 0, &schema)?;
+        for &key in &keys[20..] {
+            rids.insert(key, engine.insert_tuple(0, &row(key)?)?);
+        }
+        let lookup = |low: Bound<i32>, high: Bound<i32>| -> Result<Vec<i32>> {
+            let low = low.map(|key| vec![Field::Integer(key)]);
+            let high = high.map(|key| vec![Field::Integer(key)]);
+            let rows = engine.range_lookup(
+                0,
+                low.as_ref().map(Vec::as_slice),
+                high.as_ref().map(Vec::as_slice),
+            )?;
+            rows.iter()
+                .map(|(rid, tuple)| {
+                    assert_eq!(tuple.data(), engine.get_tuple(0, *rid)?.data());
+                    match tuple.field_at(0, &schema)? {
+                        Field::Integer(key) => Ok(key),
+                        field => panic!("unexpected key {field}"),
+                    }
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            lookup(Bound::Included(5), Bound::Included(10))?,
+            vec![5, 6, 7, 8, 9, 10]
+        );
+        assert_eq!(
+            lookup(Bound::Excluded(5), Bound::Excluded(10))?,
+            vec![6, 7, 8, 9]
+        );
+        assert_eq!(
+            lookup(Bound::Included(37), Bound::Unbounded)?,
+            vec![37, 38, 39]
+        );
+        assert_eq!(lookup(Bound::Unbounded, Bound::Excluded(2))?, vec![0, 1]);
+        assert!(lookup(Bound::Included(10), Bound::Included(5))?.is_empty());
+
+        // Deleted tuples are left out, and updated tuples are found by their new key only.
+        engine.delete_tuple(0, rids[&6])?;
+        engine.update_tuple(0, rids[&8], &row(100)?)?;
+        assert_eq!(
+            lookup(Bound::Included(5), Bound::Included(10))?,
+            vec![5, 7, 9, 10]
+        );
+        assert_eq!(
+            lookup(Bound::Excluded(38), Bound::Unbounded)?,
+            vec![39, 100]
+        );
+
+        // Their old entries are gone from the tree itself, rather than filtered out.
+        let tree_entries = |engine: &StorageEngine| -> Result<Vec<RecordId>> {
+            let range_indexes = engine.range_indexes.read().unwrap();
+            range_indexes[&0]
+                .tree
+                .range_scan_bounds(Bound::Unbounded, Bound::Unbounded)
+        };
+        let entries = tree_entries(&engine)?;
+        assert_eq!(entries.len(), 39);
+        assert!(!entries.contains(&rids[&6].into()));
+
+        // A tuple whose key can't be read is rejected before it reaches the table heap.
+        let num_rows = engine.scan(0)?.count();
+        assert!(engine.insert_tuple(0, &Tuple::new(vec![0].into())).is_err());
+        assert!(engine
+            .insert_tuples(0, &[row(41)?, Tuple::new(vec![0].into())])
+            .is_err());
+        assert_eq!(engine.scan(0)?.count(), num_rows);
+        assert_eq!(tree_entries(&engine)?.len(), 39);
+
+        // Keys must have one field, and the index must exist.
+        let two_fields = [Field::Integer(1), Field::Integer(2)];
+        assert!(engine
+            .range_lookup(0, Bound::Included(&two_fields), Bound::Unbounded)
+            .is_err());
+        assert!(engine
+            .range_lookup(1, Bound::Unbounded, Bound::Unbounded)
+            .is_err());
+        assert!(engine.create_range_index(0, 0, 0, &schema).is_err());
+        assert!(engine.create_range_index(1, 0, 1, &schema).is_err());
+        Ok(())
+    }
+
+    /// Test that sorted scans order tuples by integer, varchar, and NULL columns, and by integer
+    /// columns holding NULLs.
+    #[test]
+    #[serial]
+    fn test_scan_sorted() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("nothing".to_string(), Type::Null),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        let rows = [(3, "carol"), (1, "dave"), (4, "alice"), (2, "bob")];
+        let tuples = rows
+            .iter()
+            .map(|&(id, name)| {
+                let fields = [Field::Integer(id), name.into(), Field::Null];
+                Tuple::new(Serde::serialize(&fields).into())
+            })
+            .collect::<Vec<_>>();
+        let rids = engine.insert_tuples(0, &tuples)?;
+
+        // Returns the values of column `index` of the sorted rows.
+        let sorted = |index: usize, ascending: bool| -> Result<Vec<Field>> {
+            engine
+                .scan_sorted(0, index, ascending, NullOrder::First, &schema)?
+                .iter()
+                .map(|(_, tuple)| tuple.field_at(index, &schema))
+                .collect()
+        };
+        let ints = |ids: &[i32]| ids.iter().map(|&id| Field::Integer(id)).collect::<Vec<_>>();
+        let names = |names: &[&str]| names.iter().map(|&n| n.into()).collect::<Vec<Field>>();
+        assert_eq!(sorted(0, true)?, ints(&[1, 2, 3, 4]));
+        assert_eq!(sorted(0, false)?, ints(&[4, 3, 2, 1]));
+        assert_eq!(sorted(1, true)?, names(&["alice", "bob", "carol", "dave"]));
+        assert_eq!(sorted(1, false)?, names(&["dave", "carol", "bob", "alice"]));
+
+        // Each sorted row keeps its own record id.
+        let by_id = engine.scan_sorted(0, 0, true, NullOrder::First, &schema)?;
+        assert_eq!(by_id[0].0, rids[1]);
+        assert_eq!(by_id[3].0, rids[2]);
+
+        // NULLs compare equal to each other, so a column of NULLs leaves the scan order unchanged
+        // in both directions, wherever NULLs go.
+        for ascending in [true, false] {
+            for nulls in [NullOrder::First, NullOrder::Last] {
+                let order = engine.scan_sorted(0, 2, ascending, nulls, &schema)?;
+                assert_eq!(order.iter().map(|(rid, _)| *rid).collect::<Vec<_>>(), rids);
+            }
+        }
+
+        assert!(engine
+            .scan_sorted(0, 3, true, NullOrder::First, &schema)
+            .is_err());
+
+        // NULLs mixed with values of an integer column go first or last in either direction,
+        // keeping their scan order among themselves.
+        let scores = [Some(3), None, Some(1), None, Some(2)];
+        let tuples = scores
+            .iter()
+            .map(|score| {
+                let fields = [
+                    score.map_or(Field::Null, Field::Integer),
+                    "name".into(),
+                    Field::Null,
+                ];
+                Tuple::new(Serde::serialize(&fields).into())
+            })
+            .collect::<Vec<_>>();
+        engine.create_table(1, "scores", &schema)?;
+        let rids = engine.insert_tuples(1, &tuples)?;
+        let sorted = |ascending: bool, nulls: NullOrder| -> Result<Vec<_>> {
+            Ok(engine
+                .scan_sorted(1, 0, ascending, nulls, &schema)?
+                .into_iter()
+                .map(|(rid, _)| rid)
+                .collect())
+        };
+        let rids_of = |indexes: &[usize]| indexes.iter().map(|&i| rids[i]).collect::<Vec<_>>();
+        assert_eq!(sorted(true, NullOrder::First)?, rids_of(&[1, 3, 2, 4, 0]));
+        assert_eq!(sorted(true, NullOrder::Last)?, rids_of(&[2, 4, 0, 1, 3]));
+        assert_eq!(sorted(false, NullOrder::First)?, rids_of(&[1, 3, 0, 4, 2]));
+        assert_eq!(sorted(false, NullOrder::Last)?, rids_of(&[0, 4, 2, 1, 3]));
+
+        Ok(())
+    }
+
+    /// Test that scanning all tables yields each table's tuples.
+    #[test]
+    #[serial]
+    fn test_scan_all() -> Result<()> {
+        let engine = get_storage_engine(10);
+        assert!(engine.scan_all()?.is_empty());
+
+        let tuples = |table: u8| {
+            (0..3u8)
+                .map(|i| Tuple::new(vec![table, i].into()))
+                .collect::<Vec<_>>()
+        };
+        for table_id in [1, 0] {
+            engine.create_table(table_id, &format!("table{table_id}"), &Schema::new(&[]))?;
+            engine.insert_tuples(table_id, &tuples(table_id as u8))?;
+        }
+
+        let scans = engine.scan_all()?;
+        assert_eq!(scans.len(), 2);
+        for (expected_id, (table_id, iter)) in scans.into_iter().enumerate() {
+            assert_eq!(table_id, expected_id as u32);
+            let scanned = iter
+                .map(|row| row.map(|(_, tuple)| tuple.data()))
+                .collect::<Result<Vec<_>>>()?;
+            let expected = tuples(table_id as u8)
+                .iter()
+                .map(Tuple::data)
+                .collect::<Vec<_>>();
+            assert_eq!(scanned, expected);
+        }
+        Ok(())
+    }
+
+    /// Test that a table exported to a buffer imports into another table with the same tuples.
+    #[test]
+    #[serial]
+    fn test_export_and_import_table() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        engine.create_table(0, "source", &schema)?;
+        engine.create_table(1, "target", &schema)?;
+        let tuples = (0..50)
+            .map(|i| {
+                let row = [Field::Integer(i), format!("name{i}").as_str().into()];
+                Tuple::new(Serde::serialize(&row).into())
+            })
+            .collect::<Vec<_>>();
+        let rids = engine.insert_tuples(0, &tuples)?;
+        engine.delete_tuple(0, rids[0])?;
+
+        let mut export = Vec::new();
+        assert_eq!(engine.export_table(0, &schema, &mut export)?, 49);
+        assert_eq!(engine.import_table(1, &mut export.as_slice())?, 49);
+        let scanned = engine
+            .scan(1)?
+            .map(|row| row.map(|(_, tuple)| tuple.data()))
+            .collect::<Result<Vec<_>>>()?;
+        let expected = tuples[1..].iter().map(Tuple::data).collect::<Vec<_>>();
+        assert_eq!(scanned, expected);
+
+        // Truncated and foreign streams are rejected.
+        for bad in [&export[..export.len() - 1], &export[..4], b"not an export"] {
+            assert!(matches!(
+                engine.import_table(1, &mut &bad[..]),
+                Err(Error::InvalidData(_))
+            ));
+        }
+        Ok(())
+    }
+
+    /// Test that a table's size counts its pages and its live and deleted tuples.
+    #[test]
+    #[serial]
+    fn test_table_size() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[Column::new("data".to_string(), Type::Varchar)]);
+        engine.create_table(0, "table", &schema)?;
+        let size = engine.table_size(0)?;
+        assert_eq!(
+            (size.page_count, size.live_tuples, size.dead_tuples),
+            (1, 0, 0)
+        );
+
+        // Tuples of a bit over a third of a page fill a page with two of them.
+        let data = "a".repeat(PAGE_SIZE / 3);
+        let tuple = Tuple::new(Serde::serialize(&[data.as_str().into()]).into());
+        let rids = engine.insert_tuples(0, &vec![tuple; 3])?;
+        engine.delete_tuple(0, rids[0])?;
+
+        let size = engine.table_size(0)?;
+        assert_eq!(size.page_count, 2);
+        assert_eq!(size.live_tuples, 2);
+        assert_eq!(size.dead_tuples, 1);
+        assert_eq!(size.bytes_on_disk, 2 * PAGE_SIZE as u64);
+
+        assert!(engine.table_size(1).is_err());
+        Ok(())
+    }
+
+    /// Test that an external sort spilling several runs matches the in-memory sort, with NULLs
+    /// first or last in both directions.
+    #[test]
+    #[serial]
+    fn test_scan_sorted_external() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        // Repeated ids check that the sort is stable across runs, and every sixth id is NULL.
+        let tuples = (0..50)
+            .map(|i| {
+                let id = match i % 6 {
+                    0 => Field::Null,
+                    _ => Field::Integer(i * 37 % 20),
+                };
+                let fields = [id, format!("row {i}").as_str().into()];
+                Tuple::new(Serde::serialize(&fields).into())
+            })
+            .collect::<Vec<_>>();
+        let rids = engine.insert_tuples(0, &tuples)?;
+        engine.delete_tuple(0, rids[10])?;
+
+        // Collects the merged tuples of an external sort.
+        let external_sort = |index: usize, ascending: bool, nulls: NullOrder, run_size: usize| {
+            engine
+                .scan_sorted_external(0, index, ascending, nulls, &schema, run_size)?
+                .collect::<Result<Vec<_>>>()
+        };
+
+        // 49 tuples in runs of 7 make 7 runs.
+        for (index, ascending) in [(0, true), (0, false), (1, true), (1, false)] {
+            for nulls in [NullOrder::First, NullOrder::Last] {
+                let external = external_sort(index, ascending, nulls, 7)?;
+                let sorted = engine.scan_sorted(0, index, ascending, nulls, &schema)?;
+                assert_eq!(external, sorted);
+                assert_eq!(external.len(), 49);
+            }
+        }
+
+        // The NULL ids come first or last as a block, whatever the direction.
+        let null_ids = |ascending: bool, nulls: NullOrder| -> Result<Vec<bool>> {
+            external_sort(0, ascending, nulls, 7)?
+                .iter()
+                .map(|(_, tuple)| Ok(tuple.field_at(0, &schema)? == Field::Null))
+                .collect()
+        };
+        for ascending in [true, false] {
+            let first = null_ids(ascending, NullOrder::First)?;
+            assert!(first[..9].iter().all(|&null| null));
+            assert!(!first[9..].iter().any(|&null| null));
+            let last = null_ids(ascending, NullOrder::Last)?;
+            assert!(!last[..40].iter().any(|&null| null));
+            assert!(last[40..].iter().all(|&null| null));
+        }
+
+        // A single run, or one tuple per run, sorts the same way.
+        let expected = engine.scan_sorted(0, 0, true, NullOrder::First, &schema)?;
+        for run_size in [1, 100] {
+            assert_eq!(
+                external_sort(0, true, NullOrder::First, run_size)?,
+                expected
+            );
+        }
+
+        // The merge is lazy, so reading part of it yields the first tuples in order.
+        let mut merge = engine.scan_sorted_external(0, 0, true, NullOrder::First, &schema, 7)?;
+        assert_eq!(
+            merge.by_ref().take(3).collect::<Result<Vec<_>>>()?,
+            expected[..3]
+        );
+        assert_eq!(merge.count(), 46);
+
+        assert!(engine
+            .scan_sorted_external(0, 0, true, NullOrder::First, &schema, 0)
+            .is_err());
+        assert!(engine
+            .scan_sorted_external(0, 2, true, NullOrder::First, &schema, 7)
+            .is_err());
+        Ok(())
+    }
+
+    /// Test each aggregate over an integer column and a NULL column.
+    #[test]
+    #[serial]
+    fn test_aggregate() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("score".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("nothing".to_string(), Type::Null),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        let tuples = [5, 3, 9, 1]
+            .iter()
+            .map(|&score| {
+                let fields = [Field::Integer(score), "name".into(), Field::Null];
+                Tuple::new(Serde::serialize(&fields).into())
+            })
+            .collect::<Vec<_>>();
+        engine.insert_tuples(0, &tuples)?;
+
+        let aggregate = |index: usize, op: AggOp| engine.aggregate(0, index, op, &schema);
+        assert_eq!(aggregate(0, AggOp::Count)?, Field::Integer(4));
+        assert_eq!(aggregate(0, AggOp::CountAll)?, Field::Integer(4));
+        assert_eq!(aggregate(0, AggOp::Sum)?, Field::Integer(18));
+        assert_eq!(aggregate(0, AggOp::Min)?, Field::Integer(1));
+        assert_eq!(aggregate(0, AggOp::Max)?, Field::Integer(9));
+        assert_eq!(aggregate(0, AggOp::Avg)?, Field::Float(4.5));
+
+        // NULLs are only counted by CountAll, and min/max over no values are NULL.
+        assert_eq!(aggregate(2, AggOp::Count)?, Field::Integer(0));
+        assert_eq!(aggregate(2, AggOp::CountAll)?, Field::Integer(4));
+        assert_eq!(aggregate(2, AggOp::Min)?, Field::Null);
+        assert_eq!(aggregate(2, AggOp::Max)?, Field::Null);
+
+        // Summing a column scanned on its own agrees with the aggregate.
+        let sum = engine
+            .scan_column(0, 0, &schema)?
+            .try_fold(Field::Integer(0), |sum, field| {
+                field.map(|field| sum + field)
+            })?;
+        assert_eq!(sum, aggregate(0, AggOp::Sum)?);
+        let names = engine
+            .scan_column(0, 1, &schema)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(names, vec![Field::from("name"); 4]);
+        assert!(matches!(
+            engine.scan_column(0, 3, &schema),
+            Err(Error::OutOfBounds)
+        ));
+
+        // Sum and average require a numeric column; count, min and max don't.
+        for index in [1, 2] {
+            assert!(matches!(
+                aggregate(index, AggOp::Sum),
+                Err(Error::InvalidData(_))
+            ));
+            assert!(matches!(
+                aggregate(index, AggOp::Avg),
+                Err(Error::InvalidData(_))
+            ));
+        }
+        assert_eq!(aggregate(1, AggOp::Count)?, Field::Integer(4));
+        assert_eq!(aggregate(1, AggOp::Max)?, "name".into());
+
+        // Aggregates over an empty table.
+        engine.create_table(1, "empty", &schema)?;
+        assert_eq!(
+            engine.aggregate(1, 0, AggOp::Count, &schema)?,
+            Field::Integer(0)
+        );
+        assert_eq!(engine.aggregate(1, 0, AggOp::Sum, &schema)?, Field::Null);
+        assert_eq!(engine.aggregate(1, 0, AggOp::Avg, &schema)?, Field::Null);
+
+        // An integer sum that overflows is an error rather than NULL, but other aggregates over
+        // the same column still work.
+        engine.create_table(2, "large", &schema)?;
+        for score in [i32::MAX, 1] {
+            let fields = [Field::Integer(score), "name".into(), Field::Null];
+            engine.insert_tuple(2, &Tuple::new(Serde::serialize(&fields).into()))?;
+        }
+        assert_eq!(
+            engine.aggregate(2, 0, AggOp::Sum, &schema),
+            Err(Error::ArithmeticOverflow)
+        );
+        assert_eq!(
+            engine.aggregate(2, 0, AggOp::Max, &schema)?,
+            Field::Integer(i32::MAX)
+        );
+        assert_eq!(
+            engine.aggregate(2, 0, AggOp::Avg, &schema)?,
+            Field::Float((i32::MAX as f64 + 1.0) / 2.0)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_insert_unique() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::with_primary_key(
+            &[
+                Column::new("id".to_string(), Type::Integer),
+                Column::new("name".to_string(), Type::Varchar),
+            ],
+            &[0],
+        )?;
+        let row =
+            |id: i32, name: &str| Tuple::from_fields(&[Field::Integer(id), name.into()], &schema);
+        engine.create_table(0, "table", &schema)?;
+
+        // Rows with different keys are both inserted.
+        let rid1 = engine.insert_unique(0, &row(1, "a")?, &schema)?;
+        let rid2 = engine.insert_unique(0, &row(2, "a")?, &schema)?;
+        assert_eq!(
+            engine.get_tuple(0, rid1)?.to_fields(&schema)?[0],
+            Field::Integer(1)
+        );
+        assert_eq!(
+            engine.get_tuple(0, rid2)?.to_fields(&schema)?[0],
+            Field::Integer(2)
+        );
+
+        // A row with a taken key is rejected and not inserted.
+        assert!(matches!(
+            engine.insert_unique(0, &row(1, "b")?, &schema),
+            Err(Error::InvalidInput(_))
+        ));
+        assert_eq!(engine.scan_rows(0, &schema)?.len(), 2);
+
+        // Once the row holding a key is deleted, the key can be reused.
+        engine.delete_tuple(0, rid1)?;
+        engine.insert_unique(0, &row(1, "b")?, &schema)?;
+
+        // A schema without a primary key can't check for duplicates.
+        let no_key = Schema::new(&[Column::new("id".to_string(), Type::Integer)]);
+        engine.create_table(1, "no_key", &no_key)?;
+        let tuple = Tuple::from_fields(&[Field::Integer(1)], &no_key)?;
+        assert!(matches!(
+            engine.insert_unique(1, &tuple, &no_key),
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_scan_distinct() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        let rows = [(1, "a"), (2, "b"), (1, "a"), (1, "b"), (2, "b"), (3, "c")];
+        let tuples = rows
+            .iter()
+            .map(|&(id, name)| Tuple::from_fields(&[Field::Integer(id), name.into()], &schema))
+            .collect::<Result<Vec<_>>>()?;
+        let rids = engine.insert_tuples(0, &tuples)?;
+
+        // Only the first of each set of byte-identical tuples is kept, in scan order.
+        let expected = [0, 1, 3, 5]
+            .into_iter()
+            .map(|i| (rids[i], tuples[i].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(engine.scan_distinct(0)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_scan_rows() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("id".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("score".to_string(), Type::Float),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        let rows = (0..20)
+            .map(|i| {
+                vec![
+                    Field::Integer(i),
+                    Field::Varchar(format!("row {i}")),
+                    Field::Float(i as f64 / 2.0),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mut rids = Vec::new();
+        for row in &rows {
+            rids.push(engine.insert_tuple(0, &Tuple::from_fields(row, &schema)?)?);
+        }
+
+        let scanned = engine.scan_rows(0, &schema)?;
+        assert_eq!(scanned, rids.into_iter().zip(rows).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    /// Test that statistics are exact for a small table.
+    #[test]
+    #[serial]
+    fn test_analyze() -> Result<()> {
+        let engine = get_storage_engine(10);
+        let schema = Schema::new(&[
+            Column::new("score".to_string(), Type::Integer),
+            Column::new("name".to_string(), Type::Varchar),
+            Column::new("nothing".to_string(), Type::Null),
+        ]);
+        engine.create_table(0, "table", &schema)?;
+
+        let tuples = [(5, "b"), (3, "a"), (9, "c"), (3, "a")]
+            .iter()
+            .map(|&(score, name)| {
+                let fields = [Field::Integer(score), name.into(), Field::Null];
+                Tuple::new(Serde::serialize(&fields).into())
+            })
+            .collect::<Vec<_>>();
+        engine.insert_tuples(0, &tuples)?;
+
+        let stats = engine.analyze(0, &schema)?;
+        assert_eq!(stats.row_count, 4);
+        assert_eq!(
+            stats.columns,
+            vec![
+                ColumnStats {
+                    min: Field::Integer(3),
+                    max: Field::Integer(9),
+                    null_count: 0,
+                    approx_distinct: 3,
+                },
+                ColumnStats {
+                    min: "a".into(),
+                    max: "c".into(),
+                    null_count: 0,
+                    approx_distinct: 3,
+                },
+                ColumnStats {
+                    min: Field::Null,
+                    max: Field::Null,
+                    null_count: 4,
+                    approx_distinct: 0,
+                },
+            ]
+        );
+
+        // An empty table has no rows, and no bounds.
+        engine.create_table(1, "empty", &schema)?;
+        let stats = engine.analyze(1, &schema)?;
+        assert_eq!(stats.row_count, 0);
+        assert!(stats
+            .columns
+            .iter()
+            .all(|column| column.min == Field::Null && column.null_count == 0));
+
+        Ok(())
+    }
+}
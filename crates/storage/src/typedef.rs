@@ -1,2 +1,4 @@
 pub(crate) type PageId = u32;
 pub(crate) type FrameId = usize;
+pub(crate) type Lsn = u64;
+pub(crate) type TxnId = u64;
@@ -0,0 +1,380 @@
+use std::sync::{Arc, Mutex};
+
+use crate::disk::disk::Disk;
+use crate::record_id::RecordId;
+use crate::typedef::{Lsn, PageId, TxnId};
+use crate::Result;
+use rustdb_error::{errdata, errinput, Error};
+
+/// The LSN preceding that of the first log record, i.e. "no record".
+pub(crate) const INVALID_LSN: Lsn = 0;
+
+/// The page id of the first page of the log. The log's disk hands out page ids in order starting
+/// from this one.
+const FIRST_LOG_PAGE_ID: PageId = 1;
+
+/// The size of the header preceding each serialized record: the length of the rest of the
+/// record (`u32`), followed by its LSN (`u64`).
+const RECORD_HEADER_SIZE: usize = 12;
+
+/// A change to the database, as recorded in the write-ahead log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LogRecord {
+    /// A tuple was inserted at `rid`.
+    Insert { rid: RecordId, tuple_bytes: Vec<u8> },
+    /// The tuple at `rid` was deleted.
+    Delete { rid: RecordId },
+    /// The tuple at `rid` was replaced, changing its data from `before` to `after`.
+    Update {
+        rid: RecordId,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+    /// The transaction `txn` committed.
+    CommitTxn { txn: TxnId },
+    /// The transaction `txn` began.
+    Begin { txn: TxnId },
+}
+
+impl LogRecord {
+    /// Returns the record id of the tuple changed by the record, or `None` if the record doesn't
+    /// change a tuple.
+    pub(crate) fn rid(&self) -> Option<&RecordId> {
+        match self {
+            LogRecord::Insert { rid, .. }
+            | LogRecord::Delete { rid }
+            | LogRecord::Update { rid, .. } => Some(rid),
+            LogRecord::CommitTxn { .. } | LogRecord::Begin { .. } => None,
+        }
+    }
+
+    /// Serializes the record as a type tag followed by its fields. Record ids and transaction ids
+    /// are little-endian `u64`s, and byte strings are prefixed by their length as a `u32`.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            LogRecord::Insert { rid, tuple_bytes } => {
+                bytes.push(0);
+                put_rid(&mut bytes, rid);
+                put_bytes(&mut bytes, tuple_bytes);
+            }
+            LogRecord::Delete { rid } => {
+                bytes.push(1);
+                put_rid(&mut bytes, rid);
+            }
+            LogRecord::Update { rid, before, after } => {
+                bytes.push(2);
+                put_rid(&mut bytes, rid);
+                put_bytes(&mut bytes, before);
+                put_bytes(&mut bytes, after);
+            }
+            LogRecord::CommitTxn { txn } => {
+                bytes.push(3);
+                bytes.extend_from_slice(&txn.to_le_bytes());
+            }
+            LogRecord::Begin { txn } => {
+                bytes.push(4);
+                bytes.extend_from_slice(&txn.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Deserializes a record written by [`LogRecord::to_bytes`], which must span all of `bytes`.
+    fn from_bytes(mut bytes: &[u8]) -> Result<Self> {
+        let bytes = &mut bytes;
+        let record = match take(bytes, 1)?[0] {
+            0 => LogRecord::Insert {
+                rid: take_rid(bytes)?,
+                tuple_bytes: take_bytes(bytes)?,
+            },
+            1 => LogRecord::Delete {
+                rid: take_rid(bytes)?,
+            },
+            2 => LogRecord::Update {
+                rid: take_rid(bytes)?,
+                before: take_bytes(bytes)?,
+                after: take_bytes(bytes)?,
+            },
+            3 => LogRecord::CommitTxn {
+                txn: take_u64(bytes)?,
+            },
+            4 => LogRecord::Begin {
+                txn: take_u64(bytes)?,
+            },
+            tag => return errdata!("Unknown log record type {tag}"),
+        };
+        if !bytes.is_empty() {
+            return errdata!("{} trailing bytes after log record", bytes.len());
+        }
+        Ok(record)
+    }
+}
+
+/// Appends log records to a write-ahead log, and flushes them to disk on request.
+///
+/// The log is a stream of records, each made up of a header (see [`RECORD_HEADER_SIZE`]) and the
+/// serialized record, laid out across consecutive pages of its own disk. Records may span page
+/// boundaries, and the zeroed space following the last record marks the end of the log.
+///
+/// Appended records are buffered in memory until they are flushed, so only flushed records survive
+/// reopening the log.
+#[derive(Debug)]
+pub(crate) struct WalManager {
+    /// The disk holding the log. It must not be used for anything else.
+    disk: Arc<Mutex<dyn Disk>>,
+    /// The ids of the log's pages, in log order.
+    pages: Vec<PageId>,
+    /// The number of pages at the start of the log that are full and on disk.
+    full_pages: usize,
+    /// The log following the full pages, including records that haven't been flushed yet.
+    tail: Vec<u8>,
+    /// The LSN to assign to the next appended record.
+    next_lsn: Lsn,
+    /// The LSN of the last record on disk, or [`INVALID_LSN`] if there is none.
+    flushed_lsn: Lsn,
+}
+
+impl WalManager {
+    /// Opens the log stored on `disk`, which is empty if nothing has been flushed to it yet. New
+    /// records are appended after the existing ones.
+    ///
+    /// Returns [`Error::InvalidData`] if the log on disk is malformed.
+    pub(crate) fn new(disk: Arc<Mutex<dyn Disk>>) -> Result<Self> {
+        let (pages, log, page_size) = {
+            let mut disk = disk.lock()?;
+            let (pages, log) = read_log(&mut *disk)?;
+            (pages, log, disk.page_size())
+        };
+        let (records, end) = parse_records(&log)?;
+        let flushed_lsn = records.last().map_or(INVALID_LSN, |(lsn, _)| *lsn);
+
+        let full_pages = end / page_size;
+        Ok(Self {
+            disk,
+            pages,
+            full_pages,
+            tail: log[full_pages * page_size..end].to_vec(),
+            next_lsn: flushed_lsn + 1,
+            flushed_lsn,
+        })
+    }
+
+    /// Appends `record` to the log, returning its LSN. The record isn't durable until it has been
+    /// flushed (see [`WalManager::flush_to`]).
+    pub(crate) fn append(&mut self, record: &LogRecord) -> Result<Lsn> {
+        let lsn = self.next_lsn;
+        let body = record.to_bytes();
+        let length = u32::try_from(body.len() + size_of::<Lsn>())
+            .map_err(|_| Error::InvalidInput("Log record is too large".to_string()))?;
+
+        self.tail.extend_from_slice(&length.to_le_bytes());
+        self.tail.extend_from_slice(&lsn.to_le_bytes());
+        self.tail.extend_from_slice(&body);
+        self.next_lsn += 1;
+        Ok(lsn)
+    }
+
+    /// Makes every record up to and including the one with LSN `lsn` durable, by writing the
+    /// buffered records to disk. Does nothing if they already are.
+    ///
+    /// Returns [`Error::InvalidInput`] if no record with LSN `lsn` has been appended.
+    pub(crate) fn flush_to(&mut self, lsn: Lsn) -> Result<()> {
+        if lsn >= self.next_lsn {
+            return errinput!("Log record {lsn} has not been appended");
+        }
+        if lsn <= self.flushed_lsn {
+            return Ok(());
+        }
+
+        let mut disk = self.disk.lock()?;
+        let page_size = disk.page_size();
+        for (i, chunk) in self.tail.chunks(page_size).enumerate() {
+            let index = self.full_pages + i;
+            if index == self.pages.len() {
+                self.pages.push(disk.allocate_page()?);
+            }
+            disk.write(self.pages[index], chunk)?;
+        }
+
+        // Only the last, partially filled page needs to be written again by later flushes.
+        let full = self.tail.len() / page_size;
+        self.tail.drain(..full * page_size);
+        self.full_pages += full;
+        self.flushed_lsn = self.next_lsn - 1;
+        Ok(())
+    }
+
+    /// Makes every appended record durable.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.flush_to(self.next_lsn - 1)
+    }
+
+    /// Returns the LSN of the last record that is durable, or [`INVALID_LSN`] if there is none.
+    pub(crate) fn flushed_lsn(&self) -> Lsn {
+        self.flushed_lsn
+    }
+
+    /// Reads back every durable record, along with its LSN, in log order.
+    pub(crate) fn read_records(&self) -> Result<Vec<(Lsn, LogRecord)>> {
+        let (_, log) = read_log(&mut *self.disk.lock()?)?;
+        Ok(parse_records(&log)?.0)
+    }
+}
+
+/// Reads every page of the log stored on `disk`, returning their ids and concatenated contents.
+fn read_log(disk: &mut dyn Disk) -> Result<(Vec<PageId>, Vec<u8>)> {
+    let mut pages = Vec::new();
+    let mut log = Vec::new();
+    let mut page_id = FIRST_LOG_PAGE_ID;
+    while let Some(data) = disk.read(page_id)? {
+        pages.push(page_id);
+        log.extend_from_slice(&data);
+        page_id += 1;
+    }
+    Ok((pages, log))
+}
+
+/// Parses the records at the start of `log`, returning them along with the offset of the end of
+/// the last one.
+fn parse_records(log: &[u8]) -> Result<(Vec<(Lsn, LogRecord)>, usize)> {
+    let mut records: Vec<(Lsn, LogRecord)> = Vec::new();
+    let mut rest = log;
+    // A zero length, or no room for a header, marks the end of the log.
+    while rest.len() >= RECORD_HEADER_SIZE && rest[..4] != [0; 4] {
+        let length = take_u32(&mut rest)? as usize;
+        let mut record = take(&mut rest, length)?;
+        let lsn = take_u64(&mut record)?;
+        let expected = records.last().map_or(INVALID_LSN, |(lsn, _)| *lsn) + 1;
+        if lsn != expected {
+            return errdata!("Expected log record {expected}, found {lsn}");
+        }
+        records.push((lsn, LogRecord::from_bytes(record)?));
+    }
+    Ok((records, log.len() - rest.len()))
+}
+
+fn put_rid(bytes: &mut Vec<u8>, rid: &RecordId) {
+    bytes.extend_from_slice(&u64::from(rid.clone()).to_le_bytes());
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+/// Splits the first `n` bytes off of `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if bytes.len() < n {
+        return errdata!("Truncated log record");
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn take_u32(bytes: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(take(bytes, 4)?.try_into()?))
+}
+
+fn take_u64(bytes: &mut &[u8]) -> Result<u64> {
+    Ok(u64::from_le_bytes(take(bytes, 8)?.try_into()?))
+}
+
+fn take_rid(bytes: &mut &[u8]) -> Result<RecordId> {
+    Ok(RecordId::from(take_u64(bytes)?))
+}
+
+fn take_bytes(bytes: &mut &[u8]) -> Result<Vec<u8>> {
+    let len = take_u32(bytes)? as usize;
+    Ok(take(bytes, len)?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{LogRecord, WalManager, INVALID_LSN};
+    use crate::{
+        disk::{disk::Disk, memory_disk_manager::MemoryDiskManager},
+        record_id::RecordId,
+        Result,
+    };
+    use rustdb_error::Error;
+
+    fn records() -> Vec<LogRecord> {
+        vec![
+            LogRecord::Begin { txn: 7 },
+            LogRecord::Insert {
+                rid: RecordId::new(1, 0),
+                tuple_bytes: vec![1, 2, 3],
+            },
+            LogRecord::Update {
+                rid: RecordId::new(1, 0),
+                before: vec![1, 2, 3],
+                after: (0..100).collect(),
+            },
+            LogRecord::Delete {
+                rid: RecordId::new(2, 5),
+            },
+            LogRecord::CommitTxn { txn: 7 },
+        ]
+    }
+
+    #[test]
+    fn test_append_and_replay() -> Result<()> {
+        // Small pages, so that records span page boundaries.
+        let disk: Arc<Mutex<dyn Disk>> =
+            Arc::new(Mutex::new(MemoryDiskManager::with_page_size(64)));
+        let mut wal = WalManager::new(disk.clone())?;
+        assert_eq!(wal.flushed_lsn(), INVALID_LSN);
+
+        let lsns = records()
+            .iter()
+            .map(|record| wal.append(record))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(lsns, vec![1, 2, 3, 4, 5]);
+
+        // Nothing is durable until it is flushed.
+        assert!(WalManager::new(disk.clone())?.read_records()?.is_empty());
+        wal.flush_to(2)?;
+        assert_eq!(wal.flushed_lsn(), 5);
+
+        // Reopening the log replays the same records.
+        let mut reopened = WalManager::new(disk.clone())?;
+        assert_eq!(reopened.flushed_lsn(), 5);
+        let replayed = reopened.read_records()?;
+        assert_eq!(
+            replayed,
+            lsns.into_iter().zip(records()).collect::<Vec<_>>()
+        );
+
+        // Appending to the reopened log continues where it left off.
+        let commit = LogRecord::CommitTxn { txn: 8 };
+        assert_eq!(reopened.append(&commit)?, 6);
+        reopened.flush_to(6)?;
+        let replayed = WalManager::new(disk)?.read_records()?;
+        assert_eq!(replayed.len(), 6);
+        assert_eq!(replayed[5], (6, commit));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_to_unappended_lsn() -> Result<()> {
+        let disk = Arc::new(Mutex::new(MemoryDiskManager::new()));
+        let mut wal = WalManager::new(disk)?;
+        assert!(matches!(wal.flush_to(1), Err(Error::InvalidInput(_))));
+
+        let lsn = wal.append(&LogRecord::Begin { txn: 1 })?;
+        wal.flush_to(lsn)?;
+        // Flushing again is a no-op.
+        wal.flush_to(lsn)?;
+        assert_eq!(
+            wal.read_records()?,
+            vec![(lsn, LogRecord::Begin { txn: 1 })]
+        );
+
+        Ok(())
+    }
+}